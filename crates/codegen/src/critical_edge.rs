@@ -1,3 +1,4 @@
+use smallvec::SmallVec;
 use sonatina_ir::{func_cursor::FuncCursor, ControlFlowGraph};
 
 use sonatina_ir::{
@@ -6,6 +7,13 @@ use sonatina_ir::{
     Block, Function, Insn,
 };
 
+/// Splits all critical edges of `func`, i.e., edges from a block with multiple successors to a
+/// block with multiple predecessors, by inserting a block that only jumps to the original
+/// destination. `cfg` must be up to date, and is updated incrementally.
+pub fn split_critical_edges(func: &mut Function, cfg: &mut ControlFlowGraph) {
+    CriticalEdgeSplitter::new().run(func, cfg);
+}
+
 #[derive(Debug)]
 pub struct CriticalEdgeSplitter {
     critical_edges: Vec<CriticalEdge>,
@@ -44,13 +52,20 @@ impl CriticalEdgeSplitter {
     }
 
     fn add_critical_edges(&mut self, insn: Insn, func: &Function, cfg: &ControlFlowGraph) {
-        let branch_info = func.dfg.analyze_branch(insn);
-        if branch_info.dests_num() < 2 {
+        // A branch may name the same destination more than once, e.g., `br v0 block1 block1`.
+        // `split_edge` rewrites every occurrence at once, so each destination is considered once.
+        let mut dests: SmallVec<[Block; 4]> = SmallVec::new();
+        for dest in func.dfg.analyze_branch(insn).iter_dests() {
+            if !dests.contains(&dest) {
+                dests.push(dest);
+            }
+        }
+        if dests.len() < 2 {
             return;
         }
 
-        for dest in branch_info.iter_dests() {
-            if cfg.pred_num_of(dest) > 1 {
+        for dest in dests {
+            if cfg.in_degree(dest) > 1 {
                 self.critical_edges.push(CriticalEdge::new(insn, dest));
             }
         }
@@ -74,17 +89,24 @@ impl CriticalEdgeSplitter {
         func.dfg
             .rewrite_branch_dest(insn, original_dest, inserted_dest);
         self.modify_cfg(cfg, source_block, original_dest, inserted_dest);
-        self.modify_phi_blocks(func, original_dest, inserted_dest);
+        self.modify_phi_blocks(func, source_block, original_dest, inserted_dest);
     }
 
-    fn modify_phi_blocks(&self, func: &mut Function, original_dest: Block, inserted_dest: Block) {
+    /// Moves the phi args coming from `source_block` to `inserted_dest`.
+    fn modify_phi_blocks(
+        &self,
+        func: &mut Function,
+        source_block: Block,
+        original_dest: Block,
+        inserted_dest: Block,
+    ) {
         for insn in func.layout.iter_insn(original_dest) {
             if !func.dfg.is_phi(insn) {
                 continue;
             }
 
             for block in func.dfg.phi_blocks_mut(insn) {
-                if *block == original_dest {
+                if *block == source_block {
                     *block = inserted_dest;
                 }
             }
@@ -137,7 +159,7 @@ mod tests {
         builder.jump(c);
 
         builder.switch_to_block(c);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
         let mut module = builder.finish().build();
@@ -194,10 +216,10 @@ mod tests {
         builder.br(v0, e, d);
 
         builder.switch_to_block(d);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.switch_to_block(e);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
         let mut module = builder.finish().build();
@@ -260,7 +282,7 @@ mod tests {
         builder.br(phi_value, c, b);
 
         builder.switch_to_block(c);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
         let mut module = builder.finish().build();
@@ -287,6 +309,62 @@ mod tests {
     block3:
         jump block1;
 
+}
+"
+        );
+
+        let func = &mut module.funcs[func_ref];
+        let mut cfg_split = ControlFlowGraph::default();
+        cfg_split.compute(func);
+        assert_eq!(cfg, cfg_split);
+    }
+
+    #[test]
+    fn critical_edge_phi_diamond() {
+        let mut builder = test_func_builder(&[Type::I8], Type::I8);
+
+        let a = builder.append_block();
+        let b = builder.append_block();
+        let c = builder.append_block();
+
+        builder.switch_to_block(a);
+        let arg = builder.args()[0];
+        let v1 = builder.make_imm_value(1i8);
+        builder.br(arg, c, b);
+
+        builder.switch_to_block(b);
+        let v2 = builder.add(arg, v1);
+        builder.jump(c);
+
+        builder.switch_to_block(c);
+        let phi_value = builder.phi(Type::I8, &[(v1, a), (v2, b)]);
+        builder.ret(&[phi_value]);
+
+        builder.seal_all();
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+        let mut cfg = ControlFlowGraph::default();
+        cfg.compute(func);
+        split_critical_edges(func, &mut cfg);
+
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i8) -> i8 {
+    block0:
+        br v0 block3 block1;
+
+    block1:
+        v2.i8 = add v0 1.i8;
+        jump block2;
+
+    block2:
+        v3.i8 = phi (1.i8 block3) (v2 block1);
+        return v3;
+
+    block3:
+        jump block2;
+
 }
 "
         );
@@ -321,10 +399,10 @@ mod tests {
         builder.jump(b);
 
         builder.switch_to_block(d);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.switch_to_block(e);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
         let mut module = builder.finish().build();
@@ -361,6 +439,73 @@ mod tests {
     block7:
         jump block4;
 
+}
+"
+        );
+
+        let func = &mut module.funcs[func_ref];
+        let mut cfg_split = ControlFlowGraph::default();
+        cfg_split.compute(func);
+        assert_eq!(cfg, cfg_split);
+    }
+
+    #[test]
+    fn critical_edge_repeated_dest() {
+        let mut builder = test_func_builder(&[], Type::Void);
+
+        let a = builder.append_block();
+        let b = builder.append_block();
+        let c = builder.append_block();
+        let d = builder.append_block();
+
+        builder.switch_to_block(a);
+        let cond = builder.make_imm_value(true);
+        builder.br(cond, b, c);
+
+        builder.switch_to_block(b);
+        let v0 = builder.make_imm_value(0i32);
+        let v1 = builder.make_imm_value(1i32);
+        let v2 = builder.make_imm_value(2i32);
+        builder.br_table(v0, Some(c), &[(v1, d), (v2, c)]);
+
+        builder.switch_to_block(c);
+        builder.br(cond, d, d);
+
+        builder.switch_to_block(d);
+        builder.ret(&[]);
+
+        builder.seal_all();
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+        let mut cfg = ControlFlowGraph::default();
+        cfg.compute(func);
+        CriticalEdgeSplitter::new().run(func, &mut cfg);
+
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func() -> void {
+    block0:
+        br 1.i1 block1 block4;
+
+    block1:
+        br_table 0.i32 block5 (1.i32 block6) (2.i32 block5);
+
+    block2:
+        br 1.i1 block3 block3;
+
+    block3:
+        return;
+
+    block4:
+        jump block2;
+
+    block5:
+        jump block2;
+
+    block6:
+        jump block3;
+
 }
 "
         );
@@ -0,0 +1,231 @@
+//! This module contains a conversion from SSA to conventional SSA (CSSA).
+//!
+//! In CSSA, a phi insn, its result and its args can be renamed to a single variable without
+//! changing the semantics, which is required to lower phi insns to copies. The conversion
+//! isolates each phi insn by inserting copies, as the Method I described in Vugranam C.
+//! Sreedhar, Roy Dz-Ching Ju, David M. Gillies, Vatsa Santhanam.: Translating Out of Static
+//! Single Assignment Form:
+//! 1. For each incoming edge, `a' = copy a` is inserted at the end of the predecessor, and the
+//!    phi arg `a` is replaced with `a'`.
+//! 2. For each phi `v = phi ..`, `v' = copy v` is inserted after the phi insns of the block, and
+//!    all other uses of `v` are replaced with `v'`.
+//!
+//! The copies inserted at the end of a predecessor form a parallel copy. They can't clobber each
+//! other here because their results are fresh values, but once the phi resources are coalesced
+//! they must be sequenced by [`crate::parallel_copy::sequentialize`].
+//!
+//! The critical edges of the function must be split before the conversion, otherwise the copies
+//! of an edge are also executed on the other edges leaving the predecessor.
+use sonatina_ir::{
+    func_cursor::{CursorLocation, FuncCursor, InsnInserter},
+    insn::UnaryOp,
+    Block, ControlFlowGraph, Function, Insn, InsnData, Value,
+};
+
+#[derive(Debug, Default)]
+pub struct CssaConverter {
+    phis: Vec<Insn>,
+}
+
+impl CssaConverter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn run(&mut self, func: &mut Function, cfg: &ControlFlowGraph) {
+        self.clear();
+
+        let blocks: Vec<_> = func.layout.iter_block().collect();
+        for block in blocks {
+            self.phis.extend(
+                func.layout
+                    .iter_insn(block)
+                    .take_while(|insn| func.dfg.is_phi(*insn)),
+            );
+            if self.phis.is_empty() {
+                continue;
+            }
+
+            for &pred in cfg.preds_of(block) {
+                self.isolate_args(func, pred);
+            }
+            self.isolate_results(func);
+
+            self.phis.clear();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.phis.clear();
+    }
+
+    /// Inserts copies of the phi args flowing from `pred` at the end of `pred`.
+    fn isolate_args(&self, func: &mut Function, pred: Block) {
+        let last_insn = func.layout.last_insn_of(pred).unwrap();
+
+        for &phi in &self.phis {
+            let Some(idx) = func
+                .dfg
+                .phi_blocks(phi)
+                .iter()
+                .position(|block| *block == pred)
+            else {
+                continue;
+            };
+
+            let arg = func.dfg.insn_arg(phi, idx);
+            let copy = func.dfg.make_insn(InsnData::unary(UnaryOp::Copy, arg));
+            func.layout.insert_insn_before(copy, last_insn);
+            let copied = make_result(func, copy);
+            func.dfg.replace_insn_arg(phi, copied, idx);
+        }
+    }
+
+    /// Inserts copies of the phi results after the phi insns, and replaces all other uses of the
+    /// results with the copies.
+    fn isolate_results(&self, func: &mut Function) {
+        let last_phi = *self.phis.last().unwrap();
+        let mut inserter = InsnInserter::at_location(CursorLocation::At(last_phi));
+
+        for &phi in &self.phis {
+            let result = func.dfg.insn_result(phi).unwrap();
+            let users: Vec<_> = func.dfg.users(result).copied().collect();
+
+            let copy = inserter.insert_insn_data(func, InsnData::unary(UnaryOp::Copy, result));
+            let copied = make_result(func, copy);
+            inserter.set_location(CursorLocation::At(copy));
+
+            for user in users {
+                for idx in 0..func.dfg.insn_args_num(user) {
+                    if func.dfg.insn_arg(user, idx) == result {
+                        func.dfg.replace_insn_arg(user, copied, idx);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn make_result(func: &mut Function, insn: Insn) -> Value {
+    let value_data = func.dfg.make_result(insn).unwrap();
+    let value = func.dfg.make_value(value_data);
+    func.dfg.attach_result(insn, value);
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sonatina_interpreter::{EvalResult, State};
+    use sonatina_ir::{builder::test_util::*, module::FuncRef, Module, Type};
+
+    /// Builds a loop that swaps two values `n` times with a pair of phis.
+    fn build_swap_loop(n: i32) -> (Module, FuncRef) {
+        let mut builder = test_func_builder(&[], Type::I32);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+        let b3 = builder.append_block();
+
+        builder.switch_to_block(b0);
+        builder.jump(b1);
+
+        builder.switch_to_block(b1);
+        let one = builder.make_imm_value(1i32);
+        let two = builder.make_imm_value(2i32);
+        let zero = builder.make_imm_value(0i32);
+        let x = builder.phi(Type::I32, &[(one, b0)]);
+        let y = builder.phi(Type::I32, &[(two, b0)]);
+        let i = builder.phi(Type::I32, &[(zero, b0)]);
+        let n = builder.make_imm_value(n);
+        let cond = builder.slt(i, n);
+        builder.br(cond, b2, b3);
+
+        builder.switch_to_block(b2);
+        let one = builder.make_imm_value(1i32);
+        let next_i = builder.add(i, one);
+        builder.append_phi_arg(x, y, b2);
+        builder.append_phi_arg(y, x, b2);
+        builder.append_phi_arg(i, next_i, b2);
+        builder.jump(b1);
+
+        builder.switch_to_block(b3);
+        let ten = builder.make_imm_value(10i32);
+        let mul = builder.mul(x, ten);
+        let res = builder.add(mul, y);
+        builder.ret(&[res]);
+
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        (module, func_ref)
+    }
+
+    fn convert(module: &mut Module, func_ref: FuncRef) {
+        let func = &mut module.funcs[func_ref];
+        let mut cfg = ControlFlowGraph::default();
+        cfg.compute(func);
+        CssaConverter::new().run(func, &cfg);
+    }
+
+    fn interpret(module: Module, func_ref: FuncRef) -> EvalResult {
+        State::new(module, func_ref, &[]).run().unwrap()
+    }
+
+    #[test]
+    fn swap_phis() {
+        for n in 0..4 {
+            let (module, func_ref) = build_swap_loop(n);
+            let expected = interpret(module, func_ref);
+
+            let (mut module, func_ref) = build_swap_loop(n);
+            convert(&mut module, func_ref);
+            assert_eq!(interpret(module, func_ref), expected);
+        }
+
+        let (module, func_ref) = build_swap_loop(3);
+        assert_eq!(interpret(module, func_ref), EvalResult::I32(21));
+    }
+
+    #[test]
+    fn phis_are_isolated() {
+        let (mut module, func_ref) = build_swap_loop(3);
+        convert(&mut module, func_ref);
+        let func = &module.funcs[func_ref];
+
+        for block in func.layout.iter_block() {
+            for insn in func.layout.iter_insn(block) {
+                if !func.dfg.is_phi(insn) {
+                    continue;
+                }
+
+                // Each phi arg is a copy defined in the corresponding predecessor.
+                for (&arg, &pred) in func
+                    .dfg
+                    .insn_args(insn)
+                    .iter()
+                    .zip(func.dfg.phi_blocks(insn))
+                {
+                    let def = func.dfg.value_insn(arg).unwrap();
+                    assert_eq!(func.layout.insn_block(def), pred);
+                    assert!(matches!(
+                        func.dfg.insn_data(def),
+                        InsnData::Unary {
+                            code: UnaryOp::Copy,
+                            ..
+                        }
+                    ));
+                }
+
+                // The phi result is used only by its copy.
+                let result = func.dfg.insn_result(insn).unwrap();
+                assert_eq!(func.dfg.users_num(result), 1);
+                let user = func.dfg.user(result, 0);
+                assert_eq!(func.layout.insn_block(user), block);
+                assert!(!func.dfg.is_phi(user));
+            }
+        }
+    }
+}
@@ -7,7 +7,7 @@ use std::collections::BTreeSet;
 
 use cranelift_entity::{packed_option::PackedOption, SecondaryMap};
 
-use sonatina_ir::{Block, ControlFlowGraph};
+use sonatina_ir::{graphviz, Block, ControlFlowGraph};
 
 #[derive(Default, Debug)]
 pub struct DomTree {
@@ -28,7 +28,7 @@ impl DomTree {
     /// Returns the immediate dominator of the `block`.
     /// Returns None if the `block` is unreachable from the entry block, or the `block` is the entry block itself.
     pub fn idom_of(&self, block: Block) -> Option<Block> {
-        if self.rpo[0] == block {
+        if self.rpo.first() == Some(&block) {
             return None;
         }
         self.doms[block].expand()
@@ -59,8 +59,7 @@ impl DomTree {
     pub fn compute(&mut self, cfg: &ControlFlowGraph) {
         self.clear();
 
-        self.rpo = cfg.post_order().collect();
-        self.rpo.reverse();
+        self.rpo = cfg.reverse_post_order();
 
         let block_num = self.rpo.len();
 
@@ -109,7 +108,7 @@ impl DomTree {
         let mut df = DFSet::default();
 
         for &block in &self.rpo {
-            if cfg.pred_num_of(block) < 2 {
+            if cfg.in_degree(block) < 2 {
                 continue;
             }
             for pred in cfg.preds_of(block) {
@@ -175,6 +174,18 @@ impl DFSet {
     }
 }
 
+impl graphviz::DominatorTree for DomTree {
+    fn idom_of(&self, block: Block) -> Option<Block> {
+        DomTree::idom_of(self, block)
+    }
+}
+
+impl graphviz::DominanceFrontiers for DFSet {
+    fn frontiers_of(&self, block: Block) -> Vec<Block> {
+        self.frontiers(block).copied().collect()
+    }
+}
+
 #[derive(Default)]
 pub struct DominatorTreeTraversable {
     children: SecondaryMap<Block, Vec<Block>>,
@@ -189,6 +200,7 @@ impl DominatorTreeTraversable {
         }
     }
 
+    /// Returns the children of `block` in the dominator tree in RPO.
     pub fn children_of(&self, block: Block) -> &[Block] {
         &self.children[block]
     }
@@ -248,7 +260,7 @@ mod tests {
         builder.jump(merge_block);
 
         builder.switch_to_block(merge_block);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -268,6 +280,56 @@ mod tests {
         assert!(test_df(&df, merge_block, &[]));
     }
 
+    #[test]
+    fn dom_tree_children() {
+        let mut builder = test_func_builder(&[], Type::Void);
+
+        let entry_block = builder.append_block();
+        let then_block = builder.append_block();
+        let else_block = builder.append_block();
+        let merge_block = builder.append_block();
+
+        builder.switch_to_block(entry_block);
+        let v0 = builder.make_imm_value(true);
+        builder.br(v0, then_block, else_block);
+
+        builder.switch_to_block(then_block);
+        builder.jump(merge_block);
+
+        builder.switch_to_block(else_block);
+        builder.jump(merge_block);
+
+        builder.switch_to_block(merge_block);
+        builder.ret(&[]);
+
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &module.funcs[func_ref];
+
+        let (dom_tree, _) = calc_dom(func);
+        let mut traversable = DominatorTreeTraversable::default();
+        traversable.compute(&dom_tree);
+
+        let mut children = traversable.children_of(entry_block).to_vec();
+        children.sort();
+        assert_eq!(children, vec![then_block, else_block, merge_block]);
+        assert!(traversable.children_of(then_block).is_empty());
+        assert!(traversable.children_of(else_block).is_empty());
+        assert!(traversable.children_of(merge_block).is_empty());
+    }
+
+    #[test]
+    fn empty_cfg() {
+        let cfg = ControlFlowGraph::default();
+        let mut dom_tree = DomTree::default();
+        dom_tree.compute(&cfg);
+
+        assert!(dom_tree.rpo().is_empty());
+        assert_eq!(dom_tree.idom_of(Block(0)), None);
+    }
+
     #[test]
     fn unreachable_edge() {
         let mut builder = test_func_builder(&[], Type::Void);
@@ -292,7 +354,7 @@ mod tests {
         builder.jump(e);
 
         builder.switch_to_block(e);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -371,7 +433,7 @@ mod tests {
         builder.br(v0, m, b);
 
         builder.switch_to_block(m);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -435,10 +497,10 @@ mod tests {
         builder.jump(f);
 
         builder.switch_to_block(e);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.switch_to_block(f);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -461,4 +523,55 @@ mod tests {
         assert!(test_df(&df, e, &[]));
         assert!(test_df(&df, f, &[]));
     }
+
+    #[test]
+    fn render_domtree() {
+        let mut builder = test_func_builder(&[], Type::Void);
+
+        let entry_block = builder.append_block();
+        let then_block = builder.append_block();
+        let else_block = builder.append_block();
+        let merge_block = builder.append_block();
+
+        builder.switch_to_block(entry_block);
+        let v0 = builder.make_imm_value(true);
+        builder.br(v0, then_block, else_block);
+
+        builder.switch_to_block(then_block);
+        builder.jump(merge_block);
+
+        builder.switch_to_block(else_block);
+        builder.jump(merge_block);
+
+        builder.switch_to_block(merge_block);
+        builder.ret(&[]);
+
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &module.funcs[func_ref];
+        let (dom_tree, df) = calc_dom(func);
+
+        let options = graphviz::GraphvizOptions {
+            show_insts: false,
+            show_edge_labels: true,
+        };
+        let mut text = vec![];
+        graphviz::render_domtree_to(func, &dom_tree, Some(&df), &mut text, options).unwrap();
+        let text = String::from_utf8(text).unwrap();
+
+        assert!(text.starts_with("digraph test_func_domtree {"));
+        assert!(text.contains("block0 -> block1[label=\"\"];"));
+        assert!(text.contains("block0 -> block2[label=\"\"];"));
+        assert!(text.contains("block0 -> block3[label=\"\"];"));
+        assert!(text.contains("block1 -> block3[label=\"df\"][style=\"dashed\"];"));
+        assert!(text.contains("block2 -> block3[label=\"df\"][style=\"dashed\"];"));
+        assert!(text.contains("block3 (preds: 2)"));
+
+        let mut text = vec![];
+        graphviz::render_domtree_to(func, &dom_tree, None, &mut text, options).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(!text.contains("dashed"));
+    }
 }
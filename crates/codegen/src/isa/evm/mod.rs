@@ -0,0 +1,899 @@
+//! This module contains the EVM backend, which lowers a [`Function`] to EVM bytecode.
+//!
+//! The backend is intentionally simple:
+//! - Every SSA value is assigned a memory slot of a word. The result of an insn is stored to its
+//!   slot right after it's computed, and loaded onto the stack each time it's used.
+//! - Integers narrower than `i256` are kept zero extended in a word, and are sign extended only
+//!   when they are consumed by signed operations.
+//! - Each scalar in the memory occupies a whole word.
+//! - Function arguments are read from the calldata, one word per argument. A returned value is
+//!   written to the memory at the offset zero and returned by `RETURN`.
+//! - Phi insns are resolved by copies at the end of predecessors. If a block with phis is the
+//!   target of a conditional branch, the copies are placed in a trampoline block.
+//! - Divisions and remainders check the divisor, and jump to a shared `INVALID` on zero, so they
+//!   trap as the interpreter does instead of returning zero as EVM does.
+//!
+//! Calls, `gep`, checked arithmetic and global values aren't supported yet, and functions using
+//! them are rejected with a [`LoweringError`].
+mod opcode;
+
+use std::fmt;
+
+use rustc_hash::FxHashMap;
+use sonatina_ir::{
+    insn::{BinaryOp, CastOp, HaltOp, UnaryOp},
+    module::ModuleCtx,
+    types::CompoundTypeData,
+    Block, DataLocationKind, Function, Insn, InsnData, Type, Value, ValueData, U256,
+};
+use sonatina_triple::{Architecture, EvmVersion, TargetTriple, Version};
+
+use opcode::OpCode;
+
+/// The start address of the memory slots for SSA values.
+/// The memory below is reserved as a scratch space.
+const SLOT_BASE: usize = 0x80;
+
+/// The size of an EVM word in bytes.
+const WORD_SIZE: usize = 32;
+
+#[derive(Debug, Clone, Copy)]
+pub struct EvmIsa {
+    version: EvmVersion,
+}
+
+impl EvmIsa {
+    pub fn new(triple: &TargetTriple) -> Self {
+        debug_assert_eq!(triple.architecture, Architecture::Evm);
+        match triple.version {
+            Version::EvmVersion(version) => Self { version },
+        }
+    }
+}
+
+/// Compiles `func` to EVM bytecode.
+pub fn compile_function(func: &Function, isa: &EvmIsa) -> Result<Vec<u8>, LoweringError> {
+    check_supported(func, isa)?;
+    let mut lowering = Lowering::new(func);
+    lowering.lower();
+    lowering.finish()
+}
+
+/// An error returned from [`compile_function`] for IR that the backend can't lower.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoweringError {
+    /// The insn isn't supported by the backend yet, i.e., a call, a `gep` or a checked
+    /// arithmetic.
+    UnsupportedInsn(Insn),
+
+    /// The insn uses a global value, which isn't supported by the backend yet.
+    GlobalValue(Insn),
+
+    /// The return insn has more values than the scratch space below the value slots holds.
+    TooManyReturnValues(Insn),
+
+    /// The insn needs an opcode introduced after the target EVM version, e.g., a transient
+    /// storage access before Cancun.
+    UnsupportedInVersion(Insn, EvmVersion),
+
+    /// The code exceeds 64KiB, which the 2-byte jump destinations can't address.
+    CodeTooLarge,
+}
+
+impl fmt::Display for LoweringError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnsupportedInsn(insn) => {
+                write!(f, "`{insn:?}` is not supported by the EVM backend")
+            }
+            Self::GlobalValue(insn) => write!(
+                f,
+                "global value used by `{insn:?}` is not supported by the EVM backend"
+            ),
+            Self::TooManyReturnValues(insn) => {
+                write!(f, "`{insn:?}` returns more than {MAX_RETURN_VALUES} values")
+            }
+            Self::UnsupportedInVersion(insn, version) => {
+                write!(f, "`{insn:?}` is not supported by EVM `{version}`")
+            }
+            Self::CodeTooLarge => write!(f, "code size exceeds 64KiB"),
+        }
+    }
+}
+
+impl std::error::Error for LoweringError {}
+
+/// The maximum number of the returned values, which are written to the scratch space below
+/// [`SLOT_BASE`].
+const MAX_RETURN_VALUES: usize = SLOT_BASE / WORD_SIZE;
+
+/// Rejects the insns that the backend can't lower, so the lowering itself never fails.
+fn check_supported(func: &Function, isa: &EvmIsa) -> Result<(), LoweringError> {
+    let dfg = &func.dfg;
+    for block in func.layout.iter_block() {
+        for insn in func.layout.iter_insn(block) {
+            match dfg.insn_data(insn) {
+                InsnData::Call { .. } | InsnData::Gep { .. } => {
+                    return Err(LoweringError::UnsupportedInsn(insn));
+                }
+                InsnData::Binary { code, .. } if code.is_checked() => {
+                    return Err(LoweringError::UnsupportedInsn(insn));
+                }
+                InsnData::Return { args } if args.len() > MAX_RETURN_VALUES => {
+                    return Err(LoweringError::TooManyReturnValues(insn));
+                }
+                // `TLOAD` and `TSTORE` are introduced by EIP-1153 in Cancun.
+                InsnData::Load {
+                    loc: DataLocationKind::TransientStorage,
+                    ..
+                }
+                | InsnData::Store {
+                    loc: DataLocationKind::TransientStorage,
+                    ..
+                } if isa.version < EvmVersion::Cancun => {
+                    return Err(LoweringError::UnsupportedInVersion(insn, isa.version));
+                }
+                _ => {}
+            }
+
+            if dfg
+                .insn_args(insn)
+                .iter()
+                .any(|arg| matches!(dfg.value_data(*arg), ValueData::Global { .. }))
+            {
+                return Err(LoweringError::GlobalValue(insn));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Label {
+    Block(Block),
+    /// A trampoline block of the edge that contains phi copies.
+    Edge(Block, Block),
+    /// The shared block that traps by `INVALID`.
+    Trap,
+}
+
+struct Lowering<'a> {
+    func: &'a Function,
+    code: Vec<u8>,
+    slots: FxHashMap<Value, usize>,
+    alloca_addrs: FxHashMap<Insn, usize>,
+    labels: FxHashMap<Label, usize>,
+    fixups: Vec<(usize, Label)>,
+    trampolines: Vec<(Block, Block)>,
+    uses_trap: bool,
+}
+
+impl<'a> Lowering<'a> {
+    fn new(func: &'a Function) -> Self {
+        let mut lowering = Self {
+            func,
+            code: Vec::new(),
+            slots: FxHashMap::default(),
+            alloca_addrs: FxHashMap::default(),
+            labels: FxHashMap::default(),
+            fixups: Vec::new(),
+            trampolines: Vec::new(),
+            uses_trap: false,
+        };
+        lowering.assign_slots();
+        lowering
+    }
+
+    fn assign_slots(&mut self) {
+        let func = self.func;
+        let mut next_addr = SLOT_BASE;
+
+        for &arg in &func.arg_values {
+            self.slots.insert(arg, next_addr);
+            next_addr += WORD_SIZE;
+        }
+
+        for block in func.layout.iter_block() {
+            for insn in func.layout.iter_insn(block) {
+                for &result in func.dfg.insn_results(insn) {
+                    self.slots.insert(result, next_addr);
+                    next_addr += WORD_SIZE;
+                }
+            }
+        }
+
+        // Allocas are placed after the value slots.
+        for block in func.layout.iter_block() {
+            for insn in func.layout.iter_insn(block) {
+                if let InsnData::Alloca { ty } = func.dfg.insn_data(insn) {
+                    self.alloca_addrs.insert(insn, next_addr);
+                    next_addr += mem_size_of(&func.dfg.ctx, *ty).max(WORD_SIZE);
+                }
+            }
+        }
+    }
+
+    fn lower(&mut self) {
+        let func = self.func;
+
+        // Prologue: moves arguments from the calldata to their slots.
+        for (idx, &arg) in func.arg_values.iter().enumerate() {
+            self.push_usize(idx * WORD_SIZE);
+            self.emit(OpCode::CallDataLoad);
+            self.mask(func.dfg.value_ty(arg));
+            self.store_result(arg);
+        }
+
+        for block in func.layout.iter_block() {
+            self.bind_label(Label::Block(block));
+            self.emit(OpCode::JumpDest);
+            for insn in func.layout.iter_insn(block) {
+                self.lower_insn(block, insn);
+            }
+
+            for (from, to) in std::mem::take(&mut self.trampolines) {
+                self.bind_label(Label::Edge(from, to));
+                self.emit(OpCode::JumpDest);
+                self.phi_copies(from, to);
+                self.jump(Label::Block(to));
+            }
+        }
+
+        if self.uses_trap {
+            self.bind_label(Label::Trap);
+            self.emit_seq(&[OpCode::JumpDest, OpCode::Invalid]);
+        }
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, LoweringError> {
+        if self.code.len() > u16::MAX as usize {
+            return Err(LoweringError::CodeTooLarge);
+        }
+        for (pos, label) in std::mem::take(&mut self.fixups) {
+            let addr = self.labels[&label] as u16;
+            self.code[pos..pos + 2].copy_from_slice(&addr.to_be_bytes());
+        }
+        Ok(self.code)
+    }
+
+    fn lower_insn(&mut self, block: Block, insn: Insn) {
+        let func = self.func;
+        let dfg = &func.dfg;
+
+        match dfg.insn_data(insn) {
+            InsnData::Unary { code, args } => {
+                self.push_value(args[0]);
+                let ty = dfg.value_ty(args[0]);
+                match code {
+                    UnaryOp::Not => {
+                        self.emit(OpCode::Not);
+                        self.mask(ty);
+                    }
+                    UnaryOp::Neg => {
+                        self.push_usize(0);
+                        self.emit(OpCode::Sub);
+                        self.mask(ty);
+                    }
+                    UnaryOp::Copy => {}
+                }
+            }
+
+            InsnData::Binary { code, args } => self.lower_binary(*code, *args),
+
+            InsnData::Cast { code, args, ty } => {
+                self.push_value(args[0]);
+                match code {
+                    CastOp::Sext => {
+                        self.sign_extend(dfg.value_ty(args[0]));
+                        self.mask(*ty);
+                    }
+                    CastOp::Trunc => self.mask(*ty),
+                    CastOp::Zext | CastOp::BitCast => {}
+                }
+            }
+
+            // The EVM executes a transaction sequentially, so an atomic access is lowered as a
+            // plain one.
+            InsnData::Load { args, loc, .. } => {
+                self.push_value(args[0]);
+                match loc {
+                    DataLocationKind::Memory => self.emit(OpCode::Mload),
+                    DataLocationKind::Storage => {
+                        self.emit(OpCode::Sload);
+                        self.mask(dfg.insn_result_ty(insn).unwrap());
+                    }
+                    DataLocationKind::TransientStorage => {
+                        self.emit(OpCode::Tload);
+                        self.mask(dfg.insn_result_ty(insn).unwrap());
+                    }
+                }
+            }
+
+            InsnData::Store { args, loc, .. } => {
+                self.push_value(args[1]);
+                self.push_value(args[0]);
+                match loc {
+                    DataLocationKind::Memory => self.emit(OpCode::Mstore),
+                    DataLocationKind::Storage => self.emit(OpCode::Sstore),
+                    DataLocationKind::TransientStorage => self.emit(OpCode::Tstore),
+                }
+            }
+
+            InsnData::Alloca { .. } => {
+                let addr = self.alloca_addrs[&insn];
+                self.push_usize(addr);
+            }
+
+            InsnData::Jump { dests } => {
+                self.phi_copies(block, dests[0]);
+                self.jump(Label::Block(dests[0]));
+            }
+
+            InsnData::Branch { args, dests } => {
+                self.push_value(args[0]);
+                let then = self.edge_label(block, dests[0]);
+                self.push_label(then);
+                self.emit(OpCode::Jumpi);
+
+                self.phi_copies(block, dests[1]);
+                self.jump(Label::Block(dests[1]));
+            }
+
+            InsnData::BrTable {
+                args,
+                default,
+                table,
+            } => {
+                for (&value, &dest) in args[1..].iter().zip(table.iter()) {
+                    self.push_value(value);
+                    self.push_value(args[0]);
+                    self.emit(OpCode::Eq);
+                    let label = self.edge_label(block, dest);
+                    self.push_label(label);
+                    self.emit(OpCode::Jumpi);
+                }
+
+                match default {
+                    Some(dest) => {
+                        self.phi_copies(block, *dest);
+                        self.jump(Label::Block(*dest));
+                    }
+                    None => self.emit(OpCode::Invalid),
+                }
+            }
+
+            InsnData::Return { args } => {
+                if args.is_empty() {
+                    self.emit(OpCode::Stop);
+                } else {
+                    // Return values are stored in consecutive words from address zero.
+                    for (i, value) in args.iter().enumerate() {
+                        self.push_value(*value);
+                        self.push_usize(i * WORD_SIZE);
+                        self.emit(OpCode::Mstore);
+                    }
+                    self.push_usize(args.len() * WORD_SIZE);
+                    self.push_usize(0);
+                    self.emit(OpCode::Return);
+                }
+            }
+
+            InsnData::Halt { code, args } => match code {
+                HaltOp::Stop => self.emit(OpCode::Stop),
+                HaltOp::ReturnData | HaltOp::Revert => {
+                    self.push_value(args[1]);
+                    self.push_value(args[0]);
+                    self.emit(match code {
+                        HaltOp::Revert => OpCode::Revert,
+                        _ => OpCode::Return,
+                    });
+                }
+            },
+
+            // Phi results are stored by their predecessors.
+            InsnData::Phi { .. } => return,
+
+            InsnData::Call { .. } | InsnData::Gep { .. } => {
+                unreachable!("rejected by `check_supported`")
+            }
+        }
+
+        if let Some(result) = dfg.insn_result(insn) {
+            self.store_result(result);
+        }
+    }
+
+    fn lower_binary(&mut self, code: BinaryOp, args: [Value; 2]) {
+        use BinaryOp::*;
+
+        if matches!(code, Shl | Lshr | Ashr) {
+            self.lower_shift(code, args);
+            return;
+        }
+
+        let ty = self.func.dfg.value_ty(args[0]);
+        let is_signed = matches!(code, Sdiv | Srem | Slt | Sgt | Sle | Sge);
+
+        // EVM returns zero on division by zero, but the IR traps.
+        if matches!(code, Udiv | Sdiv | Urem | Srem) {
+            self.push_value(args[1]);
+            self.emit(OpCode::IsZero);
+            self.push_label(Label::Trap);
+            self.emit(OpCode::Jumpi);
+            self.uses_trap = true;
+        }
+
+        // EVM takes the first operand from the top of the stack.
+        for arg in [args[1], args[0]] {
+            self.push_value(arg);
+            if is_signed {
+                self.sign_extend(ty);
+            }
+        }
+
+        match code {
+            Add => self.emit(OpCode::Add),
+            Sub => self.emit(OpCode::Sub),
+            Mul => self.emit(OpCode::Mul),
+            Udiv => self.emit(OpCode::Div),
+            Sdiv => self.emit(OpCode::Sdiv),
+            Urem => self.emit(OpCode::Mod),
+            Srem => self.emit(OpCode::Smod),
+            Lt => self.emit(OpCode::Lt),
+            Gt => self.emit(OpCode::Gt),
+            Slt => self.emit(OpCode::Slt),
+            Sgt => self.emit(OpCode::Sgt),
+            Le => self.emit_seq(&[OpCode::Gt, OpCode::IsZero]),
+            Ge => self.emit_seq(&[OpCode::Lt, OpCode::IsZero]),
+            Sle => self.emit_seq(&[OpCode::Sgt, OpCode::IsZero]),
+            Sge => self.emit_seq(&[OpCode::Slt, OpCode::IsZero]),
+            Eq => self.emit(OpCode::Eq),
+            Ne => self.emit_seq(&[OpCode::Eq, OpCode::IsZero]),
+            And => self.emit(OpCode::And),
+            Or => self.emit(OpCode::Or),
+            Xor => self.emit(OpCode::Xor),
+            Shl | Lshr | Ashr => unreachable!(),
+            AddChecked | SubChecked | MulChecked | UaddChecked | UsubChecked | UmulChecked => {
+                unreachable!("rejected by `check_supported`")
+            }
+        }
+
+        if matches!(code, Add | Sub | Mul | Sdiv | Srem) {
+            self.mask(ty);
+        }
+    }
+
+    /// Lowers a shift. Unlike the other binary ops, EVM takes the shift amount from the top of the
+    /// stack. The amount is zero extended, so an amount that is greater than or equal to the bit
+    /// width shifts out all bits as the IR defines.
+    fn lower_shift(&mut self, code: BinaryOp, args: [Value; 2]) {
+        let ty = self.func.dfg.value_ty(args[0]);
+
+        self.push_value(args[0]);
+        if code == BinaryOp::Ashr {
+            self.sign_extend(ty);
+        }
+        self.push_value(args[1]);
+
+        match code {
+            BinaryOp::Shl => self.emit(OpCode::Shl),
+            BinaryOp::Lshr => self.emit(OpCode::Shr),
+            BinaryOp::Ashr => self.emit(OpCode::Sar),
+            _ => unreachable!(),
+        }
+
+        if matches!(code, BinaryOp::Shl | BinaryOp::Ashr) {
+            self.mask(ty);
+        }
+    }
+
+    /// Copies phi args flowing from `from` to the phi slots of `to`.
+    /// All args are pushed before any store so that the copies behave as parallel copies.
+    fn phi_copies(&mut self, from: Block, to: Block) {
+        let func = self.func;
+        let mut phi_results = Vec::new();
+
+        for insn in func.layout.iter_insn(to) {
+            if !func.dfg.is_phi(insn) {
+                break;
+            }
+            let idx = func
+                .dfg
+                .phi_blocks(insn)
+                .iter()
+                .position(|block| *block == from)
+                .unwrap();
+            self.push_value(func.dfg.insn_arg(insn, idx));
+            phi_results.push(func.dfg.insn_result(insn).unwrap());
+        }
+
+        for result in phi_results.into_iter().rev() {
+            self.store_result(result);
+        }
+    }
+
+    /// Returns the label to jump to when branching from `from` to `to`.
+    fn edge_label(&mut self, from: Block, to: Block) -> Label {
+        let has_phi = self
+            .func
+            .layout
+            .first_insn_of(to)
+            .is_some_and(|insn| self.func.dfg.is_phi(insn));
+        if !has_phi {
+            return Label::Block(to);
+        }
+
+        if !self.trampolines.contains(&(from, to)) {
+            self.trampolines.push((from, to));
+        }
+        Label::Edge(from, to)
+    }
+
+    fn push_value(&mut self, value: Value) {
+        match self.func.dfg.value_data(value) {
+            ValueData::Immediate { imm, .. } => self.push_u256(imm.as_zext_u256()),
+            // Any value is allowed for an undefined value.
+            ValueData::Undef { .. } => self.push_usize(0),
+            ValueData::Insn { .. } | ValueData::Arg { .. } => {
+                let slot = self.slots[&value];
+                self.push_usize(slot);
+                self.emit(OpCode::Mload);
+            }
+            ValueData::Global { .. } => unreachable!("rejected by `check_supported`"),
+        }
+    }
+
+    fn store_result(&mut self, value: Value) {
+        let slot = self.slots[&value];
+        self.push_usize(slot);
+        self.emit(OpCode::Mstore);
+    }
+
+    /// Clears the bits of the top of the stack beyond the width of `ty`.
+    fn mask(&mut self, ty: Type) {
+        let Some(bits) = int_bits(ty) else {
+            return;
+        };
+        if bits < 256 {
+            self.push_u256((U256::one() << bits) - 1);
+            self.emit(OpCode::And);
+        }
+    }
+
+    /// Sign extends the top of the stack from the width of `ty`.
+    fn sign_extend(&mut self, ty: Type) {
+        match int_bits(ty) {
+            Some(1) => {
+                self.push_usize(0);
+                self.emit(OpCode::Sub);
+            }
+            Some(bits) if bits < 256 && bits % 8 == 0 => {
+                self.push_usize(bits / 8 - 1);
+                self.emit(OpCode::SignExtend);
+            }
+            // `SIGNEXTEND` takes the width in bytes, so the sign bit of the other widths is moved
+            // to the top of the word and shifted back arithmetically.
+            Some(bits) if bits < 256 => {
+                self.push_usize(256 - bits);
+                self.emit(OpCode::Shl);
+                self.push_usize(256 - bits);
+                self.emit(OpCode::Sar);
+            }
+            _ => {}
+        }
+    }
+
+    fn jump(&mut self, label: Label) {
+        self.push_label(label);
+        self.emit(OpCode::Jump);
+    }
+
+    fn push_label(&mut self, label: Label) {
+        self.code.push(OpCode::Push2 as u8);
+        self.fixups.push((self.code.len(), label));
+        self.code.extend_from_slice(&[0, 0]);
+    }
+
+    fn bind_label(&mut self, label: Label) {
+        self.labels.insert(label, self.code.len());
+    }
+
+    fn push_usize(&mut self, value: usize) {
+        self.push_u256(U256::from(value));
+    }
+
+    fn push_u256(&mut self, value: U256) {
+        let mut bytes = [0; 32];
+        value.write_as_big_endian(&mut bytes);
+        // `PUSH1 0` is used for zero.
+        let len = value.bits().div_ceil(8).max(1);
+        self.code.push(OpCode::push(len));
+        self.code.extend_from_slice(&bytes[32 - len..]);
+    }
+
+    fn emit(&mut self, op: OpCode) {
+        self.code.push(op as u8);
+    }
+
+    fn emit_seq(&mut self, ops: &[OpCode]) {
+        for op in ops {
+            self.emit(*op);
+        }
+    }
+}
+
+fn int_bits(ty: Type) -> Option<usize> {
+    ty.bits().map(usize::from)
+}
+
+/// Returns the size of `ty` in the memory, where each scalar occupies a word.
+fn mem_size_of(ctx: &ModuleCtx, ty: Type) -> usize {
+    match ty {
+        Type::Compound(cmpd_ty) => ctx.with_ty_store(|s| match s.resolve_compound(cmpd_ty) {
+            CompoundTypeData::Array { elem, len } => len * mem_size_of(ctx, *elem),
+            CompoundTypeData::Ptr(_) => WORD_SIZE,
+            CompoundTypeData::Struct(data) => data
+                .fields
+                .iter()
+                .map(|field_ty| mem_size_of(ctx, *field_ty))
+                .sum(),
+            CompoundTypeData::Union(variants) => variants
+                .iter()
+                .map(|ty| mem_size_of(ctx, *ty))
+                .max()
+                .unwrap_or_default(),
+        }),
+        Type::Void => 0,
+        _ => WORD_SIZE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sonatina_ir::{
+        builder::{test_util::*, ModuleBuilder},
+        func_cursor::InsnInserter,
+        Linkage, Signature,
+    };
+
+    fn compile(module: &sonatina_ir::Module) -> Vec<u8> {
+        let func_ref = module.iter_functions().next().unwrap();
+        let isa = EvmIsa::new(module.ctx.isa.triple());
+        compile_function(&module.funcs[func_ref], &isa).unwrap()
+    }
+
+    #[test]
+    fn ret_imm() {
+        let mut builder = test_func_builder(&[], Type::I256);
+        let b0 = builder.append_block();
+
+        builder.switch_to_block(b0);
+        let one = builder.make_imm_value(1i32);
+        let one = builder.zext(one, Type::I256);
+        builder.ret(&[one]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        assert_eq!(
+            compile(&module),
+            vec![
+                0x5b, // JUMPDEST
+                0x60, 0x01, 0x60, 0x80, 0x52, // v1 = zext 1.i32
+                0x60, 0x80, 0x51, 0x60, 0x00, 0x52, // MSTORE(0, v1)
+                0x60, 0x20, 0x60, 0x00, 0xf3, // RETURN(0, 32)
+            ]
+        );
+    }
+
+    #[test]
+    fn add() {
+        let mut builder = test_func_builder(&[Type::I256, Type::I256], Type::I256);
+        let b0 = builder.append_block();
+        let (arg0, arg1) = (builder.args()[0], builder.args()[1]);
+
+        builder.switch_to_block(b0);
+        let v2 = builder.add(arg0, arg1);
+        builder.ret(&[v2]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        assert_eq!(
+            compile(&module),
+            vec![
+                0x60, 0x00, 0x35, 0x60, 0x80, 0x52, // v0 = CALLDATALOAD(0)
+                0x60, 0x20, 0x35, 0x60, 0xa0, 0x52, // v1 = CALLDATALOAD(32)
+                0x5b, // JUMPDEST
+                0x60, 0xa0, 0x51, 0x60, 0x80, 0x51, 0x01, 0x60, 0xc0,
+                0x52, // v2 = ADD(v0, v1)
+                0x60, 0xc0, 0x51, 0x60, 0x00, 0x52, // MSTORE(0, v2)
+                0x60, 0x20, 0x60, 0x00, 0xf3, // RETURN(0, 32)
+            ]
+        );
+    }
+
+    #[test]
+    fn revert() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        let b0 = builder.append_block();
+
+        builder.switch_to_block(b0);
+        let ptr = builder.make_imm_value(0i64);
+        let len = builder.make_imm_value(4i64);
+        builder.revert(ptr, len);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        assert_eq!(
+            compile(&module),
+            vec![
+                0x5b, // JUMPDEST
+                0x60, 0x04, 0x60, 0x00, 0xfd, // REVERT(0, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn branch() {
+        let mut builder = test_func_builder(&[Type::I1], Type::Void);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+        let arg = builder.args()[0];
+
+        builder.switch_to_block(b0);
+        builder.br(arg, b1, b2);
+
+        builder.switch_to_block(b1);
+        builder.ret(&[]);
+
+        builder.switch_to_block(b2);
+        builder.ret(&[]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        assert_eq!(
+            compile(&module),
+            vec![
+                0x60, 0x00, 0x35, 0x60, 0x01, 0x16, 0x60, 0x80,
+                0x52, // v0 = CALLDATALOAD(0) & 1
+                0x5b, // block0
+                0x60, 0x80, 0x51, 0x61, 0x00, 0x15, 0x57, // JUMPI(block1, v0)
+                0x61, 0x00, 0x17, 0x56, // JUMP(block2)
+                0x5b, 0x00, // block1
+                0x5b, 0x00, // block2
+            ]
+        );
+    }
+
+    #[test]
+    fn udiv_traps_on_zero() {
+        let mut builder = test_func_builder(&[Type::I256, Type::I256], Type::I256);
+        let b0 = builder.append_block();
+        let (arg0, arg1) = (builder.args()[0], builder.args()[1]);
+
+        builder.switch_to_block(b0);
+        let v2 = builder.udiv(arg0, arg1);
+        builder.ret(&[v2]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let code = compile(&module);
+
+        // The trap block is placed at the end.
+        let trap = code.len() - 2;
+        assert_eq!(&code[trap..], &[0x5b, 0xfe]);
+        // ISZERO(v1) jumps to the trap block before DIV.
+        let check = [0x15, 0x61, (trap >> 8) as u8, trap as u8, 0x57];
+        let check_pos = code.windows(check.len()).position(|w| w == check).unwrap();
+        let div_pos = code.iter().position(|&b| b == 0x04).unwrap();
+        assert!(check_pos < div_pos);
+    }
+
+    #[test]
+    fn unsupported_insn() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let callee =
+            mb.declare_function(Signature::new("callee", Linkage::Public, &[], Type::Void));
+        let caller =
+            mb.declare_function(Signature::new("caller", Linkage::Public, &[], Type::Void));
+
+        let mut builder = mb.build_function::<InsnInserter>(caller);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        builder.call(callee, &[]);
+        builder.ret(&[]);
+        builder.seal_all();
+        let module = builder.finish().build();
+
+        let func = &module.funcs[caller];
+        let call = func.layout.first_insn_of(b0).unwrap();
+        let isa = EvmIsa::new(module.ctx.isa.triple());
+        assert_eq!(
+            compile_function(func, &isa),
+            Err(LoweringError::UnsupportedInsn(call))
+        );
+    }
+
+    #[test]
+    fn too_many_return_values() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let tys = [Type::I256; 5];
+        let sig = Signature::with_ret_tys("func", Linkage::Public, &tys, &tys);
+        let func_ref = mb.declare_function(sig);
+
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let b0 = builder.append_block();
+        let args = builder.args().to_vec();
+        builder.switch_to_block(b0);
+        builder.ret(&args);
+        builder.seal_all();
+        let module = builder.finish().build();
+
+        let func = &module.funcs[func_ref];
+        let ret = func.layout.first_insn_of(b0).unwrap();
+        let isa = EvmIsa::new(module.ctx.isa.triple());
+        assert_eq!(
+            compile_function(func, &isa),
+            Err(LoweringError::TooManyReturnValues(ret))
+        );
+    }
+
+    #[test]
+    fn transient_storage_requires_cancun() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let ptr_ty = mb.ptr_type(Type::I256);
+        let sig = Signature::new("func", Linkage::Public, &[ptr_ty, Type::I256], Type::Void);
+        let func_ref = mb.declare_function(sig);
+
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let b0 = builder.append_block();
+        let [addr, value] = [builder.args()[0], builder.args()[1]];
+        builder.switch_to_block(b0);
+        builder.tstore(addr, value);
+        builder.ret(&[]);
+        builder.seal_all();
+        let module = builder.finish().build();
+
+        let func = &module.funcs[func_ref];
+        let tstore = func.layout.first_insn_of(b0).unwrap();
+        let london = EvmIsa::new(module.ctx.isa.triple());
+        assert_eq!(
+            compile_function(func, &london),
+            Err(LoweringError::UnsupportedInVersion(
+                tstore,
+                EvmVersion::London
+            ))
+        );
+
+        let cancun = EvmIsa::new(&TargetTriple::parse("evm-ethereum-cancun").unwrap());
+        let code = compile_function(func, &cancun).unwrap();
+        assert!(code.contains(&(OpCode::Tstore as u8)));
+    }
+
+    #[test]
+    fn sext_non_byte_width() {
+        for (bits, expected) in [
+            (4, vec![0x60, 0xfc, 0x1b, 0x60, 0xfc, 0x1d]), // SAR(252, SHL(252, v))
+            (24, vec![0x60, 0x02, 0x0b]),                  // SIGNEXTEND(2, v)
+        ] {
+            let mut builder = test_func_builder(&[Type::int(bits)], Type::I256);
+            let b0 = builder.append_block();
+            let arg = builder.args()[0];
+
+            builder.switch_to_block(b0);
+            let v1 = builder.sext(arg, Type::I256);
+            builder.ret(&[v1]);
+            builder.seal_all();
+
+            let module = builder.finish().build();
+            let code = compile(&module);
+            assert!(
+                code.windows(expected.len()).any(|w| w == expected),
+                "{bits}: {code:02x?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,50 @@
+/// EVM opcodes used by the backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OpCode {
+    Stop = 0x00,
+    Add = 0x01,
+    Mul = 0x02,
+    Sub = 0x03,
+    Div = 0x04,
+    Sdiv = 0x05,
+    Mod = 0x06,
+    Smod = 0x07,
+    SignExtend = 0x0b,
+    Lt = 0x10,
+    Gt = 0x11,
+    Slt = 0x12,
+    Sgt = 0x13,
+    Eq = 0x14,
+    IsZero = 0x15,
+    And = 0x16,
+    Or = 0x17,
+    Xor = 0x18,
+    Not = 0x19,
+    Shl = 0x1b,
+    Shr = 0x1c,
+    Sar = 0x1d,
+    CallDataLoad = 0x35,
+    Mload = 0x51,
+    Mstore = 0x52,
+    Sload = 0x54,
+    Sstore = 0x55,
+    Jump = 0x56,
+    Jumpi = 0x57,
+    JumpDest = 0x5b,
+    Tload = 0x5c,
+    Tstore = 0x5d,
+    Push1 = 0x60,
+    Push2 = 0x61,
+    Return = 0xf3,
+    Revert = 0xfd,
+    Invalid = 0xfe,
+}
+
+impl OpCode {
+    /// Returns `PUSHn` opcode that pushes `n` bytes.
+    pub fn push(n: usize) -> u8 {
+        debug_assert!((1..=32).contains(&n));
+        Self::Push1 as u8 + (n - 1) as u8
+    }
+}
@@ -3,7 +3,17 @@
 #![allow(clippy::needless_collect)]
 
 pub mod critical_edge;
+pub mod cssa;
 pub mod domtree;
+pub mod isa;
+pub mod liveness;
 pub mod loop_analysis;
+pub mod mem_deps;
 pub mod optim;
+pub mod parallel_copy;
+pub mod pass_manager;
 pub mod post_domtree;
+pub mod ssa_destruction;
+
+pub use critical_edge::split_critical_edges;
+pub use ssa_destruction::destruct_ssa;
@@ -0,0 +1,315 @@
+//! This module contains a liveness analysis that computes live-in and live-out sets of blocks.
+//!
+//! A phi arg is considered to be used at the end of the corresponding predecessor, so it's live
+//! out of the predecessor, but not live in the block of the phi. Likewise, a phi result is
+//! defined at the top of the block of the phi, so it's not live in the block.
+use cranelift_entity::{EntityRef, SecondaryMap};
+
+use sonatina_ir::{Block, ControlFlowGraph, Function, Insn, Value, ValueData};
+
+#[derive(Debug, Default)]
+pub struct Liveness {
+    live_ins: SecondaryMap<Block, ValueSet>,
+    live_outs: SecondaryMap<Block, ValueSet>,
+}
+
+impl Liveness {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Computes the live-in and live-out sets of the blocks reachable from the entry block.
+    pub fn compute(&mut self, func: &Function, cfg: &ControlFlowGraph) {
+        self.clear();
+
+        let mut defs: SecondaryMap<Block, ValueSet> = SecondaryMap::new();
+        // Values that are used in the block before their definitions.
+        let mut uses: SecondaryMap<Block, ValueSet> = SecondaryMap::new();
+        // Values that flow from the block to the phis of its successors.
+        let mut phi_uses: SecondaryMap<Block, ValueSet> = SecondaryMap::new();
+
+        for block in func.layout.iter_block() {
+            for insn in func.layout.iter_insn(block) {
+                if func.dfg.is_phi(insn) {
+                    let args = func.dfg.insn_args(insn);
+                    for (&arg, &pred) in args.iter().zip(func.dfg.phi_blocks(insn)) {
+                        if is_variable(func, arg) {
+                            phi_uses[pred].insert(arg);
+                        }
+                    }
+                } else {
+                    for &arg in func.dfg.insn_args(insn) {
+                        if is_variable(func, arg) && !defs[block].contains(arg) {
+                            uses[block].insert(arg);
+                        }
+                    }
+                }
+
+                for &result in func.dfg.insn_results(insn) {
+                    defs[block].insert(result);
+                }
+            }
+        }
+
+        // Visiting blocks in post order makes the backward dataflow converge quickly.
+        let blocks: Vec<_> = cfg.post_order().collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for &block in &blocks {
+                let mut live_out = phi_uses[block].clone();
+                for &succ in cfg.succs_of(block) {
+                    live_out.union_with(&self.live_ins[succ]);
+                }
+
+                let mut live_in = live_out.clone();
+                live_in.difference_with(&defs[block]);
+                live_in.union_with(&uses[block]);
+
+                if live_in != self.live_ins[block] {
+                    self.live_ins[block] = live_in;
+                    changed = true;
+                }
+                self.live_outs[block] = live_out;
+            }
+        }
+    }
+
+    /// Returns the values that are live at the top of `block`.
+    pub fn live_in(&self, block: Block) -> &ValueSet {
+        &self.live_ins[block]
+    }
+
+    /// Returns the values that are live at the bottom of `block`.
+    pub fn live_out(&self, block: Block) -> &ValueSet {
+        &self.live_outs[block]
+    }
+
+    /// Returns `true` if `value` is live immediately before `insn`, i.e., `value` is defined
+    /// before `insn` and used by `insn` or later.
+    pub fn is_live_at(&self, func: &Function, value: Value, insn: Insn) -> bool {
+        let block = func.layout.insn_block(insn);
+
+        let mut next_insn = Some(insn);
+        while let Some(insn) = next_insn {
+            if func.dfg.insn_results(insn).contains(&value) {
+                return false;
+            }
+            if !func.dfg.is_phi(insn) && func.dfg.insn_args(insn).contains(&value) {
+                return true;
+            }
+            next_insn = func.layout.next_insn_of(insn);
+        }
+
+        self.live_out(block).contains(value)
+    }
+
+    pub fn clear(&mut self) {
+        self.live_ins.clear();
+        self.live_outs.clear();
+    }
+}
+
+/// A set of values represented as a bitset keyed on the value numbers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValueSet {
+    words: Vec<u64>,
+}
+
+impl ValueSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, value: Value) -> bool {
+        let (word, bit) = Self::position(value);
+        self.words.get(word).is_some_and(|w| w & bit != 0)
+    }
+
+    /// Inserts `value`, and returns `true` if it's newly inserted.
+    pub fn insert(&mut self, value: Value) -> bool {
+        let (word, bit) = Self::position(value);
+        if self.words.len() <= word {
+            self.words.resize(word + 1, 0);
+        }
+        let inserted = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        inserted
+    }
+
+    /// Removes `value`, and returns `true` if it was in the set.
+    pub fn remove(&mut self, value: Value) -> bool {
+        let (word, bit) = Self::position(value);
+        let Some(w) = self.words.get_mut(word) else {
+            return false;
+        };
+        let removed = *w & bit != 0;
+        *w &= !bit;
+        self.normalize();
+        removed
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    /// Returns an iterator over the values in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = Value> + '_ {
+        self.words.iter().enumerate().flat_map(|(idx, &word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| Value::new(idx * 64 + bit))
+        })
+    }
+
+    /// Adds all values in `other` to the set.
+    pub fn union_with(&mut self, other: &Self) {
+        if self.words.len() < other.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (w, o) in self.words.iter_mut().zip(&other.words) {
+            *w |= o;
+        }
+        self.normalize();
+    }
+
+    /// Removes all values in `other` from the set.
+    pub fn difference_with(&mut self, other: &Self) {
+        for (w, o) in self.words.iter_mut().zip(&other.words) {
+            *w &= !o;
+        }
+        self.normalize();
+    }
+
+    /// Removes the trailing empty words so that equal sets have the same representation.
+    fn normalize(&mut self) {
+        while self.words.last() == Some(&0) {
+            self.words.pop();
+        }
+    }
+
+    fn position(value: Value) -> (usize, u64) {
+        let idx = value.index();
+        (idx / 64, 1 << (idx % 64))
+    }
+}
+
+/// Returns `true` if `value` is defined by an insn or is a function argument.
+fn is_variable(func: &Function, value: Value) -> bool {
+    matches!(
+        func.dfg.value_data(value),
+        ValueData::Insn { .. } | ValueData::Arg { .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sonatina_ir::{builder::test_util::*, Type};
+
+    fn values(set: &ValueSet) -> Vec<Value> {
+        set.iter().collect()
+    }
+
+    #[test]
+    fn value_set() {
+        let mut set = ValueSet::new();
+        assert!(set.insert(Value(3)));
+        assert!(set.insert(Value(130)));
+        assert!(!set.insert(Value(3)));
+        assert_eq!(values(&set), vec![Value(3), Value(130)]);
+
+        assert!(set.remove(Value(130)));
+        assert!(!set.remove(Value(200)));
+        assert_eq!(set.len(), 1);
+
+        let mut other = ValueSet::new();
+        other.insert(Value(3));
+        set.difference_with(&other);
+        assert!(set.is_empty());
+        assert_eq!(set, ValueSet::new());
+    }
+
+    #[test]
+    fn value_set_remove_normalizes() {
+        let mut set = ValueSet::new();
+        set.insert(Value(3));
+        set.insert(Value(130));
+        assert!(set.remove(Value(130)));
+
+        let mut expected = ValueSet::new();
+        expected.insert(Value(3));
+        assert_eq!(set, expected);
+
+        assert!(set.remove(Value(3)));
+        assert_eq!(set, ValueSet::new());
+    }
+
+    #[test]
+    fn loop_with_phis() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+        let b3 = builder.append_block();
+
+        builder.switch_to_block(b0);
+        let n = builder.args()[0];
+        let zero = builder.make_imm_value(0i32);
+        builder.jump(b1);
+
+        builder.switch_to_block(b1);
+        let i = builder.phi(Type::I32, &[(zero, b0)]);
+        let sum = builder.phi(Type::I32, &[(zero, b0)]);
+        let cond = builder.slt(i, n);
+        builder.br(cond, b2, b3);
+
+        builder.switch_to_block(b2);
+        let next_sum = builder.add(sum, i);
+        let one = builder.make_imm_value(1i32);
+        let next_i = builder.add(i, one);
+        builder.append_phi_arg(i, next_i, b2);
+        builder.append_phi_arg(sum, next_sum, b2);
+        builder.jump(b1);
+
+        builder.switch_to_block(b3);
+        builder.ret(&[sum]);
+
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &module.funcs[func_ref];
+
+        let mut cfg = ControlFlowGraph::new();
+        cfg.compute(func);
+        let mut liveness = Liveness::new();
+        liveness.compute(func, &cfg);
+
+        // Immediates are never live.
+        assert_eq!(values(liveness.live_in(b0)), vec![n]);
+        assert_eq!(values(liveness.live_out(b0)), vec![n]);
+
+        // Phi results are defined at the top of `b1`, and phi args are live out of the preds.
+        assert_eq!(values(liveness.live_in(b1)), vec![n]);
+        assert_eq!(values(liveness.live_out(b1)), vec![n, i, sum]);
+        assert_eq!(values(liveness.live_in(b2)), vec![n, i, sum]);
+        assert_eq!(values(liveness.live_out(b2)), vec![n, next_sum, next_i]);
+        assert_eq!(values(liveness.live_in(b3)), vec![sum]);
+        assert!(liveness.live_out(b3).is_empty());
+
+        let next_sum_insn = func.dfg.value_insn(next_sum).unwrap();
+        let next_i_insn = func.dfg.value_insn(next_i).unwrap();
+        assert!(liveness.is_live_at(func, sum, next_sum_insn));
+        assert!(!liveness.is_live_at(func, sum, next_i_insn));
+        assert!(liveness.is_live_at(func, next_sum, next_i_insn));
+        assert!(!liveness.is_live_at(func, next_i, next_i_insn));
+        assert!(liveness.is_live_at(func, i, next_i_insn));
+    }
+}
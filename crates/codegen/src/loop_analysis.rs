@@ -4,7 +4,7 @@ use smallvec::SmallVec;
 
 use crate::domtree::DomTree;
 
-use sonatina_ir::{Block, ControlFlowGraph};
+use sonatina_ir::{graphviz, Block, ControlFlowGraph};
 
 #[derive(Debug, Default)]
 pub struct LoopTree {
@@ -100,6 +100,23 @@ impl LoopTree {
         self.loops[lp].parent.expand()
     }
 
+    /// Returns child loops of the `lp`.
+    pub fn children_of(&self, lp: Loop) -> &[Loop] {
+        &self.loops[lp].children
+    }
+
+    /// Returns the nesting depth of the `lp`.
+    /// The depth of an outermost loop is 1.
+    pub fn loop_depth(&self, lp: Loop) -> usize {
+        let mut depth = 1;
+        let mut cur_lp = lp;
+        while let Some(parent) = self.parent_loop(cur_lp) {
+            depth += 1;
+            cur_lp = parent;
+        }
+        depth
+    }
+
     /// Returns the loop that the `block` belongs to.
     /// If the `block` belongs to multiple loops, then returns the innermost loop.
     pub fn loop_of_block(&self, block: Block) -> Option<Loop> {
@@ -163,6 +180,17 @@ impl LoopTree {
     }
 }
 
+impl graphviz::LoopNest for LoopTree {
+    fn innermost_loop_header(&self, block: Block) -> Option<Block> {
+        self.loop_of_block(block).map(|lp| self.loop_header(lp))
+    }
+
+    fn parent_loop_header(&self, header: Block) -> Option<Block> {
+        let lp = self.loop_of_block(header)?;
+        self.parent_loop(lp).map(|parent| self.loop_header(parent))
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Loop(u32);
 entity_impl!(Loop);
@@ -285,7 +313,7 @@ mod tests {
         builder.append_phi_arg(v1, v3, b2);
 
         builder.switch_to_block(b3);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -347,7 +375,7 @@ mod tests {
         builder.jump(b1);
 
         builder.switch_to_block(b6);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -386,7 +414,7 @@ mod tests {
         builder.br(arg, b1, b2);
 
         builder.switch_to_block(b2);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -456,7 +484,7 @@ mod tests {
         builder.jump(b1);
 
         builder.switch_to_block(b11);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -493,5 +521,77 @@ mod tests {
         debug_assert_eq!(lpt.loop_header(l1), b3);
         debug_assert_eq!(lpt.loop_header(l2), b4);
         debug_assert_eq!(lpt.loop_header(l3), b7);
+
+        debug_assert_eq!(lpt.loop_depth(l0), 1);
+        debug_assert_eq!(lpt.loop_depth(l1), 2);
+        debug_assert_eq!(lpt.loop_depth(l2), 3);
+        debug_assert_eq!(lpt.loop_depth(l3), 2);
+
+        let mut children_of_l0 = lpt.children_of(l0).to_vec();
+        children_of_l0.sort_by_key(|lp| lpt.loop_header(*lp));
+        debug_assert_eq!(children_of_l0, vec![l1, l3]);
+        debug_assert_eq!(lpt.children_of(l1), &[l2]);
+        debug_assert!(lpt.children_of(l2).is_empty());
+        debug_assert!(lpt.children_of(l3).is_empty());
+    }
+
+    #[test]
+    fn render_loop_clusters() {
+        let mut builder = test_func_builder(&[Type::I1], Type::Void);
+
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+        let b3 = builder.append_block();
+        let b4 = builder.append_block();
+
+        let arg = builder.args()[0];
+
+        builder.switch_to_block(b0);
+        builder.jump(b1);
+
+        builder.switch_to_block(b1);
+        builder.jump(b2);
+
+        builder.switch_to_block(b2);
+        builder.br(arg, b2, b3);
+
+        builder.switch_to_block(b3);
+        builder.br(arg, b1, b4);
+
+        builder.switch_to_block(b4);
+        builder.ret(&[]);
+
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &module.funcs[func_ref];
+        let lpt = compute_loop(func);
+
+        let options = graphviz::GraphvizOptions {
+            show_insts: false,
+            show_edge_labels: false,
+        };
+        let mut text = vec![];
+        graphviz::render_with_loops(func, &lpt, &mut text, options).unwrap();
+        let text = String::from_utf8(text).unwrap();
+
+        let clusters = "    subgraph cluster_block1 {
+        label=\"loop block1\";
+        block1;
+        block3;
+        subgraph cluster_block2 {
+            label=\"loop block2\";
+            block2;
+        }
+    }
+}
+";
+        assert!(text.starts_with("digraph test_func {"));
+        assert!(text.ends_with(clusters));
+        assert!(text.contains("block3 -> block1"));
+        assert!(!text.contains("block0;"));
+        assert!(!text.contains("block4;"));
     }
 }
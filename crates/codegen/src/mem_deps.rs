@@ -0,0 +1,253 @@
+//! This module contains a memory dependence analysis that finds, for each load, the nearest
+//! preceding insn in the same block that may write to the loaded location.
+//!
+//! This is a conservative first version. Accesses to memory, storage and transient storage never
+//! alias since they are separate address spaces. Within the same location kind, accesses through
+//! the same address value must alias, and all the other accesses may alias. A call may write to
+//! any location. An atomic load or store is ordered with all the other accesses, so it's regarded
+//! as accessing any location as well.
+use cranelift_entity::{packed_option::PackedOption, SecondaryMap};
+
+use sonatina_ir::{insn::DataLocationKind, Function, Insn, InsnData, Value};
+
+/// The result of an alias query between two memory accesses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasResult {
+    /// The accesses never touch the same location.
+    NoAlias,
+    /// The accesses may touch the same location.
+    MayAlias,
+    /// The accesses always touch the same location.
+    MustAlias,
+}
+
+#[derive(Debug, Default)]
+pub struct MemDeps {
+    accesses: SecondaryMap<Insn, Option<MemAccess>>,
+    /// Maps a load to the nearest preceding insn in the same block that may write to the loaded
+    /// location.
+    clobbering: SecondaryMap<Insn, PackedOption<Insn>>,
+}
+
+/// A memory access recorded for the alias queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MemAccess {
+    /// A load or a store of `addr` in `loc`.
+    Direct { loc: DataLocationKind, addr: Value },
+    /// A call or an atomic access, which may access any location.
+    Opaque,
+}
+
+impl MemDeps {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn compute(&mut self, func: &Function) {
+        self.clear();
+
+        for block in func.layout.iter_block() {
+            // Stores and calls seen so far in the block, in layout order.
+            let mut writes: Vec<Insn> = Vec::new();
+
+            for insn in func.layout.iter_insn(block) {
+                let insn_data = func.dfg.insn_data(insn);
+                let access = match insn_data {
+                    InsnData::Load { ordering, .. } | InsnData::Store { ordering, .. }
+                        if ordering.is_atomic() =>
+                    {
+                        MemAccess::Opaque
+                    }
+                    InsnData::Load { args, loc, .. } => MemAccess::Direct {
+                        loc: *loc,
+                        addr: args[0],
+                    },
+                    InsnData::Store { args, loc, .. } => MemAccess::Direct {
+                        loc: *loc,
+                        addr: args[0],
+                    },
+                    InsnData::Call { .. } => MemAccess::Opaque,
+                    _ => continue,
+                };
+                self.accesses[insn] = Some(access);
+
+                let is_write = insn_data.may_write_memory();
+                if !is_write {
+                    let clobber = writes
+                        .iter()
+                        .rev()
+                        .find(|&&write| self.clobbers(insn, write) != AliasResult::NoAlias);
+                    self.clobbering[insn] = clobber.copied().into();
+                }
+
+                // An atomic load doesn't write, but the later loads must not be moved across it.
+                if is_write || access == MemAccess::Opaque {
+                    writes.push(insn);
+                }
+            }
+        }
+    }
+
+    /// Returns the nearest preceding store, call or atomic load in the same block as `load` that
+    /// may write to the location read by `load` or order it, or `None` if there is no such insn in
+    /// the block before `load`.
+    pub fn clobbering_insn(&self, load: Insn) -> Option<Insn> {
+        self.clobbering[load].expand()
+    }
+
+    /// Returns whether `store` may write to the location read by `load`.
+    /// `store` may be a call or an atomic access, which may alias any location.
+    ///
+    /// # Panics
+    /// Panics if `load` or `store` is not a memory access seen by [`Self::compute`].
+    pub fn clobbers(&self, load: Insn, store: Insn) -> AliasResult {
+        let access = |insn: Insn| {
+            self.accesses[insn].unwrap_or_else(|| panic!("`{insn:?}` is not a memory access"))
+        };
+
+        match (access(load), access(store)) {
+            (
+                MemAccess::Direct {
+                    loc: load_loc,
+                    addr: load_addr,
+                },
+                MemAccess::Direct {
+                    loc: store_loc,
+                    addr: store_addr,
+                },
+            ) => {
+                if load_loc != store_loc {
+                    AliasResult::NoAlias
+                } else if load_addr == store_addr {
+                    AliasResult::MustAlias
+                } else {
+                    AliasResult::MayAlias
+                }
+            }
+            _ => AliasResult::MayAlias,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.accesses.clear();
+        self.clobbering.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sonatina_ir::{
+        builder::{test_util::build_test_isa, ModuleBuilder},
+        func_cursor::InsnInserter,
+        module::ModuleCtx,
+        Linkage, MemoryOrdering, Signature, Type,
+    };
+
+    #[test]
+    fn clobbering_insn() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let ptr_ty = mb.ptr_type(Type::I32);
+        let callee =
+            mb.declare_function(Signature::new("callee", Linkage::External, &[], Type::Void));
+        let func_ref = mb.declare_function(Signature::new(
+            "f",
+            Linkage::Public,
+            &[ptr_ty, ptr_ty],
+            Type::Void,
+        ));
+
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let [p, q] = [builder.args()[0], builder.args()[1]];
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let v = builder.make_imm_value(1i32);
+        builder.memory_store(p, v);
+        let load_p = builder.memory_load(p);
+        builder.memory_store(q, v);
+        let load_p_again = builder.memory_load(p);
+        builder.storage_store(p, v);
+        let load_q = builder.memory_load(q);
+        builder.call(callee, &[]);
+        let load_slot = builder.storage_load(p);
+        builder.ret(&[]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func = &module.funcs[func_ref];
+        let mut mem_deps = MemDeps::new();
+        mem_deps.compute(func);
+
+        let insn = |v| func.dfg.value_insn(v).unwrap();
+        let insns: Vec<_> = func.layout.iter_insn(b0).collect();
+        let [store_p, store_q, store_slot, call] = [insns[0], insns[2], insns[4], insns[6]];
+
+        assert_eq!(mem_deps.clobbering_insn(insn(load_p)), Some(store_p));
+        assert_eq!(
+            mem_deps.clobbers(insn(load_p), store_p),
+            AliasResult::MustAlias
+        );
+
+        assert_eq!(mem_deps.clobbering_insn(insn(load_p_again)), Some(store_q));
+        assert_eq!(
+            mem_deps.clobbers(insn(load_p_again), store_q),
+            AliasResult::MayAlias
+        );
+
+        // Storage never aliases memory.
+        assert_eq!(mem_deps.clobbering_insn(insn(load_q)), Some(store_q));
+        assert_eq!(
+            mem_deps.clobbers(insn(load_q), store_slot),
+            AliasResult::NoAlias
+        );
+
+        assert_eq!(mem_deps.clobbering_insn(insn(load_slot)), Some(call));
+    }
+
+    #[test]
+    fn atomic_access() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let ptr_ty = mb.ptr_type(Type::I32);
+        let func_ref = mb.declare_function(Signature::new(
+            "f",
+            Linkage::Public,
+            &[ptr_ty, ptr_ty],
+            Type::Void,
+        ));
+
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let [p, q] = [builder.args()[0], builder.args()[1]];
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let v = builder.make_imm_value(1i32);
+        builder.memory_store(p, v);
+        let acquire = builder.load_ordered(DataLocationKind::Memory, q, MemoryOrdering::Acquire);
+        let load_p = builder.memory_load(p);
+        builder.store_ordered(DataLocationKind::Storage, q, v, MemoryOrdering::Release);
+        let load_p_again = builder.memory_load(p);
+        builder.ret(&[]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func = &module.funcs[func_ref];
+        let mut mem_deps = MemDeps::new();
+        mem_deps.compute(func);
+
+        let insn = |v| func.dfg.value_insn(v).unwrap();
+        let insns: Vec<_> = func.layout.iter_insn(b0).collect();
+        let [store_p, release] = [insns[0], insns[3]];
+
+        // The accesses around an atomic access aren't reordered across it, even if they are in
+        // a different location kind.
+        assert_eq!(mem_deps.clobbering_insn(insn(acquire)), Some(store_p));
+        assert_eq!(mem_deps.clobbering_insn(insn(load_p)), Some(insn(acquire)));
+        assert_eq!(
+            mem_deps.clobbers(insn(load_p), insn(acquire)),
+            AliasResult::MayAlias
+        );
+        assert_eq!(mem_deps.clobbering_insn(insn(load_p_again)), Some(release));
+    }
+}
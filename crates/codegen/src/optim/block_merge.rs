@@ -0,0 +1,311 @@
+//! This module contains a pass that merges blocks having identical contents.
+//!
+//! Two blocks are considered identical if they have the same insn sequence, including their
+//! terminators, modulo renaming of the values defined in the blocks. For each pair of identical
+//! blocks, one of them is removed and its predecessors are redirected to the other.
+//!
+//! To keep the function in SSA form, a block is merged only if
+//! 1. It has no phi insn.
+//! 2. The values defined in it are used only in the block itself or as phi args in its
+//!    successors.
+//! 3. The phi args in the successors coming from both blocks agree with each other.
+use std::hash::{Hash, Hasher};
+
+use cranelift_entity::EntityRef;
+use rustc_hash::{FxHashMap, FxHasher};
+
+use sonatina_ir::{Block, ControlFlowGraph, Function, Insn, Value};
+
+#[derive(Debug, Default)]
+pub struct BlockMerger {
+    /// Maps a content hash to the blocks having the hash.
+    buckets: FxHashMap<u64, Vec<Block>>,
+}
+
+impl BlockMerger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn run(&mut self, func: &mut Function, cfg: &mut ControlFlowGraph) {
+        // Merging blocks may make their predecessors identical, so iterate until no change.
+        while self.run_once(func, cfg) {}
+    }
+
+    pub fn clear(&mut self) {
+        self.buckets.clear();
+    }
+
+    fn run_once(&mut self, func: &mut Function, cfg: &mut ControlFlowGraph) -> bool {
+        self.clear();
+
+        let entry = func.layout.entry_block();
+        let blocks: Vec<_> = func.layout.iter_block().collect();
+        let mut changed = false;
+
+        for block in blocks {
+            if Some(block) == entry || !is_mergeable(func, block) {
+                continue;
+            }
+
+            let hash = content_hash(func, block);
+            let bucket = self.buckets.entry(hash).or_default();
+            match bucket
+                .iter()
+                .copied()
+                .find(|&kept| is_identical(func, cfg, kept, block))
+            {
+                Some(kept) => {
+                    merge_block(func, cfg, kept, block);
+                    changed = true;
+                }
+                None => bucket.push(block),
+            }
+        }
+
+        changed
+    }
+}
+
+/// Returns `true` if the block satisfies the conditions 1 and 2 described in the module doc.
+fn is_mergeable(func: &Function, block: Block) -> bool {
+    let Some(last_insn) = func.layout.last_insn_of(block) else {
+        return false;
+    };
+    if !func.dfg.is_terminator(last_insn) {
+        return false;
+    }
+
+    for insn in func.layout.iter_insn(block) {
+        if func.dfg.is_phi(insn) {
+            return false;
+        }
+
+        let Some(result) = func.dfg.insn_result(insn) else {
+            continue;
+        };
+        for &user in func.dfg.users(result) {
+            let user_block = func.layout.insn_block(user);
+            if user_block == block {
+                continue;
+            }
+
+            let from_block = func.dfg.is_phi(user)
+                && func
+                    .dfg
+                    .insn_args(user)
+                    .iter()
+                    .zip(func.dfg.phi_blocks(user))
+                    .all(|(&arg, &from)| arg != result || from == block);
+            if !from_block {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+/// Computes the hash of the block contents.
+/// The values defined in the block are hashed by their positions in the block.
+fn content_hash(func: &Function, block: Block) -> u64 {
+    let mut hasher = FxHasher::default();
+    let mut local_values = FxHashMap::default();
+
+    for (idx, insn) in func.layout.iter_insn(block).enumerate() {
+        let mut insn_data = func.dfg.insn_data(insn).clone();
+        for arg in insn_data.args_mut() {
+            if let Some(&local) = local_values.get(arg) {
+                *arg = local;
+            }
+        }
+        insn_data.hash(&mut hasher);
+        func.dfg.insn_result_ty(insn).hash(&mut hasher);
+
+        for &result in func.dfg.insn_results(insn) {
+            local_values.insert(result, Value::new(idx));
+        }
+    }
+
+    hasher.finish()
+}
+
+/// Returns `true` if `rhs` can be merged into `lhs`.
+fn is_identical(func: &Function, cfg: &ControlFlowGraph, lhs: Block, rhs: Block) -> bool {
+    let Some(value_map) = match_insns(func, lhs, rhs) else {
+        return false;
+    };
+
+    // Phi args in the successors must agree with each other.
+    for &succ in cfg.succs_of(rhs) {
+        for phi in func.layout.iter_insn(succ) {
+            if !func.dfg.is_phi(phi) {
+                break;
+            }
+
+            let lhs_arg = phi_arg_from(func, phi, lhs);
+            let rhs_arg = phi_arg_from(func, phi, rhs).map(|arg| map_value(&value_map, arg));
+            if lhs_arg != rhs_arg {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+/// Matches insns of the two blocks, and returns a map from values defined in `rhs` to the
+/// corresponding values defined in `lhs` if the blocks are identical.
+fn match_insns(func: &Function, lhs: Block, rhs: Block) -> Option<FxHashMap<Value, Value>> {
+    let mut value_map = FxHashMap::default();
+    let mut lhs_insns = func.layout.iter_insn(lhs);
+    let mut rhs_insns = func.layout.iter_insn(rhs);
+
+    loop {
+        let (lhs_insn, rhs_insn) = match (lhs_insns.next(), rhs_insns.next()) {
+            (Some(lhs_insn), Some(rhs_insn)) => (lhs_insn, rhs_insn),
+            (None, None) => return Some(value_map),
+            _ => return None,
+        };
+
+        let mut rhs_data = func.dfg.insn_data(rhs_insn).clone();
+        for arg in rhs_data.args_mut() {
+            *arg = map_value(&value_map, *arg);
+        }
+        if func.dfg.insn_data(lhs_insn) != &rhs_data
+            || func.dfg.insn_result_ty(lhs_insn) != func.dfg.insn_result_ty(rhs_insn)
+        {
+            return None;
+        }
+
+        for (&lhs_result, &rhs_result) in func
+            .dfg
+            .insn_results(lhs_insn)
+            .iter()
+            .zip(func.dfg.insn_results(rhs_insn))
+        {
+            value_map.insert(rhs_result, lhs_result);
+        }
+    }
+}
+
+/// Removes `rhs` and redirects its predecessors to `lhs`.
+fn merge_block(func: &mut Function, cfg: &mut ControlFlowGraph, lhs: Block, rhs: Block) {
+    let preds: Vec<_> = cfg.preds_of(rhs).copied().collect();
+    for pred in preds {
+        let last_insn = func.layout.last_insn_of(pred).unwrap();
+        func.dfg.rewrite_branch_dest(last_insn, rhs, lhs);
+        cfg.remove_edge(pred, rhs);
+        cfg.add_edge(pred, lhs);
+    }
+
+    let succs: Vec<_> = cfg.succs_of(rhs).copied().collect();
+    for succ in succs {
+        let phis: Vec<_> = func
+            .layout
+            .iter_insn(succ)
+            .take_while(|insn| func.dfg.is_phi(*insn))
+            .collect();
+        for phi in phis {
+            let arg = func.dfg.remove_phi_arg(phi, rhs);
+            // The same value may still flow from `lhs`.
+            if func.dfg.insn_args(phi).contains(&arg) {
+                func.dfg.attach_user(phi);
+            }
+        }
+        cfg.remove_edge(rhs, succ);
+    }
+    cfg.exits.retain(|exit| *exit != rhs);
+
+    let insns: Vec<_> = func.layout.iter_insn(rhs).collect();
+    for insn in insns {
+        for idx in 0..func.dfg.insn_args_num(insn) {
+            let arg = func.dfg.insn_arg(insn, idx);
+            func.dfg.remove_user(arg, insn);
+        }
+        func.layout.remove_insn(insn);
+    }
+    func.layout.remove_block(rhs);
+}
+
+fn phi_arg_from(func: &Function, phi: Insn, from: Block) -> Option<Value> {
+    func.dfg
+        .phi_blocks(phi)
+        .iter()
+        .position(|&block| block == from)
+        .map(|idx| func.dfg.insn_arg(phi, idx))
+}
+
+fn map_value(value_map: &FxHashMap<Value, Value>, value: Value) -> Value {
+    value_map.get(&value).copied().unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sonatina_ir::{builder::test_util::*, Type};
+
+    #[test]
+    fn identical_tails() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+        let b3 = builder.append_block();
+
+        let arg = builder.args()[0];
+
+        builder.switch_to_block(b0);
+        let ten = builder.make_imm_value(10i32);
+        let cond = builder.slt(arg, ten);
+        builder.br(cond, b1, b2);
+
+        builder.switch_to_block(b1);
+        let one = builder.make_imm_value(1i32);
+        let v1 = builder.add(arg, one);
+        builder.jump(b3);
+
+        builder.switch_to_block(b2);
+        let one = builder.make_imm_value(1i32);
+        let v2 = builder.add(arg, one);
+        builder.jump(b3);
+
+        builder.switch_to_block(b3);
+        let v3 = builder.phi(Type::I32, &[(v1, b1), (v2, b2)]);
+        builder.ret(&[v3]);
+
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+        let mut cfg = ControlFlowGraph::default();
+        cfg.compute(func);
+        BlockMerger::new().run(func, &mut cfg);
+
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        v2.i1 = slt v0 10.i32;
+        br v2 block1 block1;
+
+    block1:
+        v4.i32 = add v0 1.i32;
+        jump block3;
+
+    block3:
+        v6.i32 = phi (v4 block1);
+        return v6;
+
+}
+"
+        );
+
+        let func = &mut module.funcs[func_ref];
+        let mut cfg_merged = ControlFlowGraph::default();
+        cfg_merged.compute(func);
+        assert_eq!(cfg, cfg_merged);
+    }
+}
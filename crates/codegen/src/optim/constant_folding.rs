@@ -1,8 +1,18 @@
+//! This module contains constant folding of insns.
 use sonatina_ir::{
-    insn::{BinaryOp, CastOp, UnaryOp},
-    DataFlowGraph, Immediate, InsnData,
+    insn::{BinaryOp, UnaryOp},
+    DataFlowGraph, Immediate, Insn, InsnData, Value,
 };
 
+/// Evaluates the `insn` if all its arguments are immediates, and returns an interned immediate
+/// value of the result.
+///
+/// Division by zero is never folded because it traps at runtime.
+pub fn fold_insn(dfg: &mut DataFlowGraph, insn: Insn) -> Option<Value> {
+    let imm = fold_constant(dfg, dfg.insn_data(insn))?;
+    Some(dfg.make_imm_value(imm))
+}
+
 pub(super) fn fold_constant(dfg: &DataFlowGraph, insn_data: &InsnData) -> Option<Immediate> {
     match insn_data {
         InsnData::Unary { code, args } => {
@@ -10,42 +20,32 @@ pub(super) fn fold_constant(dfg: &DataFlowGraph, insn_data: &InsnData) -> Option
             Some(match *code {
                 UnaryOp::Not => !arg,
                 UnaryOp::Neg => -arg,
+                UnaryOp::Copy => arg,
             })
         }
 
         InsnData::Binary { code, args } => {
+            // An undefined value can be chosen to be zero for `and` and all ones for `or`, which
+            // makes the result independent of the other argument.
+            if let Some(&undef) = args.iter().find(|arg| dfg.is_undef(**arg)) {
+                let ty = dfg.value_ty(undef);
+                return match code {
+                    BinaryOp::And => Some(Immediate::zero(ty)),
+                    BinaryOp::Or => Some(Immediate::all_one(ty)),
+                    _ => None,
+                };
+            }
+
             let lhs = dfg.value_imm(args[0])?;
             let rhs = dfg.value_imm(args[1])?;
-            Some(match *code {
-                BinaryOp::Add => lhs + rhs,
-                BinaryOp::Sub => lhs - rhs,
-                BinaryOp::Mul => lhs * rhs,
-                BinaryOp::Udiv => lhs.udiv(rhs),
-                BinaryOp::Sdiv => lhs.sdiv(rhs),
-                BinaryOp::Lt => lhs.lt(rhs),
-                BinaryOp::Gt => lhs.gt(rhs),
-                BinaryOp::Slt => lhs.slt(rhs),
-                BinaryOp::Sgt => lhs.sgt(rhs),
-                BinaryOp::Le => lhs.le(rhs),
-                BinaryOp::Ge => lhs.ge(rhs),
-                BinaryOp::Sle => lhs.sle(rhs),
-                BinaryOp::Sge => lhs.sge(rhs),
-                BinaryOp::Eq => lhs.imm_eq(rhs),
-                BinaryOp::Ne => lhs.imm_ne(rhs),
-                BinaryOp::And => lhs & rhs,
-                BinaryOp::Or => lhs | rhs,
-                BinaryOp::Xor => lhs ^ rhs,
-            })
+            // Trapping insns, e.g., division by zero or overflowing checked arithmetic, are never
+            // folded.
+            lhs.binary_op(*code, rhs)
         }
 
         InsnData::Cast { code, args, ty } => {
-            let arg = dfg.value_imm(args[0])?;
-            Some(match code {
-                CastOp::Sext => arg.sext(*ty),
-                CastOp::Zext => arg.zext(*ty),
-                CastOp::Trunc => arg.trunc(*ty),
-                CastOp::BitCast => return None,
-            })
+            // Casts with invalid widths are left to the verifier or the interpreter.
+            dfg.value_imm(args[0])?.cast(*code, *ty)
         }
 
         InsnData::Load { .. }
@@ -57,6 +57,265 @@ pub(super) fn fold_constant(dfg: &DataFlowGraph, insn_data: &InsnData) -> Option
         | InsnData::Alloca { .. }
         | InsnData::Gep { .. }
         | InsnData::Return { .. }
+        | InsnData::Halt { .. }
         | InsnData::Phi { .. } => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sonatina_interpreter::State;
+    use sonatina_ir::{builder::test_util::*, Function, Module, Type};
+
+    fn fold_all(func: &mut Function) -> Vec<Option<Immediate>> {
+        let block = func.layout.entry_block().unwrap();
+        let insns: Vec<_> = func
+            .layout
+            .iter_insn(block)
+            .filter(|insn| func.dfg.insn_result(*insn).is_some())
+            .collect();
+        insns
+            .into_iter()
+            .map(|insn| {
+                fold_insn(&mut func.dfg, insn).map(|value| func.dfg.value_imm(value).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fold_wrapping() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let max = builder.make_imm_value(i8::MAX);
+        let min = builder.make_imm_value(i8::MIN);
+        let one = builder.make_imm_value(1i8);
+        let minus_one = builder.make_imm_value(-1i8);
+        let two = builder.make_imm_value(2i8);
+        builder.add(max, one);
+        builder.sub(min, one);
+        builder.mul(max, two);
+        builder.sdiv(min, minus_one);
+        builder.udiv(minus_one, two);
+        builder.slt(minus_one, one);
+        builder.lt(minus_one, one);
+        builder.ret(&[]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+
+        assert_eq!(
+            fold_all(func),
+            vec![
+                Some(Immediate::I8(i8::MIN)),
+                Some(Immediate::I8(i8::MAX)),
+                Some(Immediate::I8(-2)),
+                Some(Immediate::I8(i8::MIN)),
+                Some(Immediate::I8(127)),
+                Some(Immediate::I1(true)),
+                Some(Immediate::I1(false)),
+            ]
+        );
+    }
+
+    #[test]
+    fn div_by_zero() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let one = builder.make_imm_value(1i32);
+        let zero = builder.make_imm_value(0i32);
+        builder.udiv(one, zero);
+        builder.sdiv(one, zero);
+        builder.ret(&[]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+
+        assert_eq!(fold_all(func), vec![None, None]);
+    }
+
+    #[test]
+    fn fold_rem() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let min = builder.make_imm_value(i8::MIN);
+        let minus_seven = builder.make_imm_value(-7i8);
+        let minus_one = builder.make_imm_value(-1i8);
+        let two = builder.make_imm_value(2i8);
+        let zero = builder.make_imm_value(0i8);
+        builder.srem(minus_seven, two);
+        builder.urem(minus_seven, two);
+        builder.srem(min, minus_one);
+        builder.urem(two, zero);
+        builder.srem(two, zero);
+        builder.ret(&[]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+
+        assert_eq!(
+            fold_all(func),
+            vec![
+                Some(Immediate::I8(-1)),
+                Some(Immediate::I8(1)),
+                Some(Immediate::I8(0)),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn fold_shift() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let minus_sixteen = builder.make_imm_value(-16i8);
+        let one = builder.make_imm_value(1i8);
+        let two = builder.make_imm_value(2i8);
+        let eight = builder.make_imm_value(8i8);
+        builder.shl(one, two);
+        builder.lshr(minus_sixteen, two);
+        builder.ashr(minus_sixteen, two);
+        builder.shl(one, eight);
+        builder.ashr(minus_sixteen, eight);
+        builder.ret(&[]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+
+        assert_eq!(
+            fold_all(func),
+            vec![
+                Some(Immediate::I8(4)),
+                Some(Immediate::I8(60)),
+                Some(Immediate::I8(-4)),
+                Some(Immediate::I8(0)),
+                Some(Immediate::I8(-1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn fold_cast() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let minus_one = builder.make_imm_value(-1i8);
+        let wide = builder.make_imm_value(0x1ff_i32);
+        builder.sext(minus_one, Type::I32);
+        builder.zext(minus_one, Type::I32);
+        builder.trunc(wide, Type::I8);
+        // Casts with invalid widths are not folded.
+        builder.sext(wide, Type::I32);
+        builder.zext(wide, Type::I8);
+        builder.trunc(minus_one, Type::I32);
+        builder.ret(&[]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+
+        assert_eq!(
+            fold_all(func),
+            vec![
+                Some(Immediate::I32(-1)),
+                Some(Immediate::I32(0xff)),
+                Some(Immediate::I8(-1)),
+                None,
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn fold_undef() {
+        let mut builder = test_func_builder(&[Type::I32], Type::Void);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg = builder.args()[0];
+        let undef = builder.make_undef(Type::I32);
+        builder.and(arg, undef);
+        builder.or(undef, arg);
+        builder.add(arg, undef);
+        builder.ret(&[]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+
+        assert_eq!(
+            fold_all(func),
+            vec![Some(Immediate::I32(0)), Some(Immediate::I32(-1)), None]
+        );
+    }
+
+    /// Builds a function computing `(1 + 2) * 3`.
+    fn build_unfolded() -> Module {
+        let mut builder = test_func_builder(&[], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let one = builder.make_imm_value(1i32);
+        let two = builder.make_imm_value(2i32);
+        let three = builder.make_imm_value(3i32);
+        let v0 = builder.add(one, two);
+        let v1 = builder.mul(v0, three);
+        builder.ret(&[v1]);
+        builder.seal_all();
+
+        builder.finish().build()
+    }
+
+    #[test]
+    fn folding_reduces_executed_insns() {
+        let run = |module: Module| {
+            let func_ref = module.iter_functions().next().unwrap();
+            let (result, stats) = State::new(module, func_ref, &[]).run_with_stats();
+            assert_eq!(result.unwrap().into_i32(), 9);
+            stats
+        };
+        let unfolded_stats = run(build_unfolded());
+
+        let mut module = build_unfolded();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+        let block = func.layout.entry_block().unwrap();
+        let insns: Vec<_> = func.layout.iter_insn(block).collect();
+        for insn in insns {
+            if let Some(value) = fold_insn(&mut func.dfg, insn) {
+                let result = func.dfg.insn_result(insn).unwrap();
+                func.dfg.change_to_alias(result, value);
+                func.layout.remove_insn(insn);
+            }
+        }
+        let folded_stats = run(module);
+
+        assert_eq!(unfolded_stats.executed_insns, 3);
+        assert_eq!(folded_stats.executed_insns, 1);
+        // `ADD` and `MUL` are saved, and only the jump to the return address is left.
+        assert_eq!(unfolded_stats.estimated_cost, 3 + 5 + 8);
+        assert_eq!(folded_stats.estimated_cost, 8);
+    }
+}
@@ -0,0 +1,61 @@
+//! This module contains local common subexpression elimination, which removes redundant insns
+//! within a single block.
+//!
+//! Unlike [`super::gvn`], no information is propagated across blocks, so this is cheap enough to
+//! be used as a pre-pass of more expensive optimizations.
+use rustc_hash::FxHashMap;
+
+use sonatina_ir::{
+    func_cursor::{CursorLocation, FuncCursor, InsnInserter},
+    Function, InsnData, Type, Value,
+};
+
+/// Replaces each pure insn that computes the same value as a preceding insn in the same block
+/// with the result of the preceding insn.
+///
+/// Insns with side effects and insns that may trap are never eliminated.
+pub fn local_cse(func: &mut Function) {
+    let mut available: FxHashMap<(InsnData, Type), Value> = FxHashMap::default();
+    let blocks: Vec<_> = func.layout.iter_block().collect();
+
+    for block in blocks {
+        available.clear();
+
+        let mut next_insn = func.layout.first_insn_of(block);
+        while let Some(insn) = next_insn {
+            next_insn = func.layout.next_insn_of(insn);
+
+            let Some(result) = func.dfg.insn_result(insn) else {
+                continue;
+            };
+            if !func.dfg.insn_is_pure(insn) {
+                continue;
+            }
+
+            let key = (
+                canonicalize(func.dfg.insn_data(insn)),
+                func.dfg.value_ty(result),
+            );
+            match available.get(&key) {
+                Some(&leader) => {
+                    func.dfg.change_to_alias(result, leader);
+                    InsnInserter::at_location(CursorLocation::At(insn)).remove_insn(func);
+                }
+                None => {
+                    available.insert(key, result);
+                }
+            }
+        }
+    }
+}
+
+/// Sorts the arguments of a commutative insn so that `add v0 v1` and `add v1 v0` are the same.
+fn canonicalize(insn_data: &InsnData) -> InsnData {
+    let mut insn_data = insn_data.clone();
+    if let InsnData::Binary { code, args } = &mut insn_data {
+        if code.is_commutative() {
+            args.sort();
+        }
+    }
+    insn_data
+}
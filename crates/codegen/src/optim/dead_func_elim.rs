@@ -0,0 +1,134 @@
+//! This module contains dead function elimination, which removes functions that are never
+//! called from the functions visible from the outside of the module.
+//!
+//! Functions with linkage other than [`Linkage::Private`] are the roots, since they may be
+//! called from the outside or refer to a definition outside. Private functions are live only if
+//! they are reachable from a root through `call` insns.
+use cranelift_entity::SecondaryMap;
+use sonatina_ir::{module::FuncRef, InsnData, Linkage, Module};
+
+/// Removes the private functions unreachable from the roots of `module`, and returns the number
+/// of the removed functions.
+///
+/// The remaining functions are renumbered by [`Module::remove_functions`], so `FuncRef`s
+/// obtained before the pass are invalidated if any function is removed.
+pub fn run(module: &mut Module) -> usize {
+    let mut live = SecondaryMap::<FuncRef, bool>::default();
+    let mut worklist: Vec<_> = module
+        .iter_functions()
+        .filter(|&func_ref| module.funcs[func_ref].sig.linkage() != Linkage::Private)
+        .collect();
+    for &root in &worklist {
+        live[root] = true;
+    }
+
+    while let Some(func_ref) = worklist.pop() {
+        let func = &module.funcs[func_ref];
+        for block in func.layout.iter_block() {
+            for insn in func.layout.iter_insn(block) {
+                if let InsnData::Call { func: callee, .. } = func.dfg.insn_data(insn) {
+                    if !live[*callee] {
+                        live[*callee] = true;
+                        worklist.push(*callee);
+                    }
+                }
+            }
+        }
+    }
+
+    let dead: Vec<_> = module
+        .iter_functions()
+        .filter(|&func_ref| !live[func_ref])
+        .collect();
+    module.remove_functions(&dead);
+    dead.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::optim::inliner::{Inliner, InlinerConfig};
+    use sonatina_ir::{
+        builder::{test_util::build_test_isa, ModuleBuilder},
+        func_cursor::InsnInserter,
+        module::ModuleCtx,
+        Signature, Type,
+    };
+
+    /// Defines `func` calling `callees` and returning nothing.
+    fn define(mb: ModuleBuilder, func: FuncRef, callees: &[FuncRef]) -> ModuleBuilder {
+        let mut builder = mb.build_function::<InsnInserter>(func);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        for &callee in callees {
+            builder.call(callee, &[]);
+        }
+        builder.ret(&[]);
+        builder.seal_all();
+        builder.finish()
+    }
+
+    #[test]
+    fn remove_unreachable_private_functions() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let mut declare =
+            |name, linkage| mb.declare_function(Signature::new(name, linkage, &[], Type::Void));
+        let ext = declare("ext", Linkage::External);
+        let dead_rec = declare("dead_rec", Linkage::Private);
+        let leaf = declare("leaf", Linkage::Private);
+        let main = declare("main", Linkage::Public);
+        let dead = declare("dead", Linkage::Private);
+        let hook = declare("hook", Linkage::Weak);
+
+        let mb = define(mb, dead_rec, &[dead_rec, leaf]);
+        let mb = define(mb, leaf, &[ext]);
+        let mb = define(mb, main, &[leaf]);
+        let mb = define(mb, dead, &[dead_rec]);
+        let mb = define(mb, hook, &[]);
+        let mut module = mb.build();
+
+        assert_eq!(run(&mut module), 2);
+
+        let names: Vec<_> = module
+            .funcs
+            .values()
+            .map(|func| func.sig.name().to_string())
+            .collect();
+        assert_eq!(names, ["ext", "leaf", "main", "hook"]);
+
+        // The call in `main` refers to the renumbered `leaf`.
+        let [leaf, main] = [FuncRef::from_u32(1), FuncRef::from_u32(2)];
+        assert!(module.funcs[main].callees.contains_key(&leaf));
+        assert_eq!(run(&mut module), 0);
+    }
+
+    #[test]
+    fn remove_inlined_function() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let mut declare =
+            |name, linkage| mb.declare_function(Signature::new(name, linkage, &[], Type::Void));
+        let leaf = declare("leaf", Linkage::Private);
+        let ext = declare("ext", Linkage::External);
+        let main = declare("main", Linkage::Public);
+
+        let mb = define(mb, leaf, &[]);
+        let mb = define(mb, main, &[leaf, ext]);
+        let mut module = mb.build();
+
+        assert_eq!(Inliner::new(InlinerConfig::default()).run(&mut module), 1);
+        // The inlined call is detached from the layout, but still refers to `leaf`.
+        assert_eq!(run(&mut module), 1);
+
+        let names: Vec<_> = module
+            .funcs
+            .values()
+            .map(|func| func.sig.name().to_string())
+            .collect();
+        assert_eq!(names, ["ext", "main"]);
+
+        let [ext, main] = [FuncRef::from_u32(0), FuncRef::from_u32(1)];
+        let callees: Vec<_> = module.funcs[main].callees.keys().copied().collect();
+        assert_eq!(callees, [ext]);
+    }
+}
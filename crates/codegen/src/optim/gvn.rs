@@ -635,7 +635,8 @@ impl GvnSolver {
             | InsnData::BrTable { .. }
             | InsnData::Alloca { .. }
             | InsnData::Gep { .. }
-            | InsnData::Return { .. } => insn_data.clone(),
+            | InsnData::Return { .. }
+            | InsnData::Halt { .. } => insn_data.clone(),
 
             InsnData::Phi { values, blocks, ty } => {
                 let edges = &self.blocks[block].in_edges;
@@ -0,0 +1,671 @@
+//! This module contains function inlining.
+//!
+//! [`inline_call`] replaces a call with a copy of the callee body:
+//! 1. The block of the call is split right after the call, and the insns following the call are
+//!    moved to a new continuation block.
+//! 2. The blocks of the callee are cloned between the two blocks. The parameters of the callee
+//!    are substituted with the call arguments, and each `return` is replaced with a jump to the
+//!    continuation block.
+//! 3. The call is replaced with a jump to the cloned entry block, and each call result is
+//!    replaced with the corresponding returned value, which is merged by a phi if the callee
+//!    returns at several places.
+//!
+//! [`Inliner`] is a module pass that decides which calls are inlined by [`InlinerConfig`].
+use rustc_hash::FxHashMap;
+use smallvec::SmallVec;
+
+use sonatina_ir::{
+    attributes, module::FuncRef, Block, CallGraph, Function, Insn, InsnData, Linkage, Module,
+    Value, ValueData,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InlinerConfig {
+    /// Callees with fewer insns than this are inlined if they are also cheaper than
+    /// `max_callee_cost`.
+    pub max_callee_insns: usize,
+    /// Callees whose insns cost less than this in total are inlined if they are also shorter
+    /// than `max_callee_insns`. The cost of an insn is estimated by the
+    /// [`CostModel`](sonatina_ir::isa::CostModel) of the target ISA.
+    pub max_callee_cost: u64,
+    /// If `true`, a callee called from only one place in the module is inlined regardless of
+    /// its size.
+    pub inline_single_caller: bool,
+}
+
+impl Default for InlinerConfig {
+    fn default() -> Self {
+        Self {
+            max_callee_insns: 32,
+            // About 32 simple arithmetic insns of EVM.
+            max_callee_cost: 100,
+            inline_single_caller: true,
+        }
+    }
+}
+
+/// Inlines calls to small or single-caller functions in a module.
+///
+/// Recursive callees, external or weak functions, and functions with the
+/// [`NOINLINE`](sonatina_ir::attributes::NOINLINE) attribute are never inlined. Callees are left
+/// intact even if all calls to them are inlined, since they may still be referenced from outside
+/// the module.
+#[derive(Debug, Default)]
+pub struct Inliner {
+    config: InlinerConfig,
+}
+
+impl Inliner {
+    pub fn new(config: InlinerConfig) -> Self {
+        Self { config }
+    }
+
+    /// Runs the inliner on all functions in `module`, and returns the number of inlined calls.
+    pub fn run(&mut self, module: &mut Module) -> usize {
+        let mut call_sites = count_call_sites(module);
+        // Inlining never makes new recursion, so the graph built up front is enough to detect
+        // recursive calls.
+        let call_graph = CallGraph::build(module);
+        let mut inlined = 0;
+
+        // Callees are visited before their callers, so a callee body is already inlined when it's
+        // cloned into its callers.
+        let order: Vec<_> = call_graph.reverse_topo_order().collect();
+        for caller in order {
+            let calls: Vec<_> = iter_calls(&module.funcs[caller]).collect();
+            for (call, callee) in calls {
+                if !self.should_inline(module, &call_graph, &call_sites, caller, callee) {
+                    continue;
+                }
+
+                let callee_func = module.funcs[callee].clone();
+                let caller_func = &mut module.funcs[caller];
+                inline_call(caller_func, call, &callee_func);
+                if iter_calls(caller_func).all(|(_, func)| func != callee) {
+                    caller_func.callees.remove(&callee);
+                }
+                inlined += 1;
+
+                // The calls in the callee body are copied into the caller.
+                *call_sites.get_mut(&callee).unwrap() -= 1;
+                for (_, func) in iter_calls(&callee_func) {
+                    *call_sites.entry(func).or_default() += 1;
+                }
+            }
+        }
+
+        inlined
+    }
+
+    fn should_inline(
+        &self,
+        module: &Module,
+        call_graph: &CallGraph,
+        call_sites: &FxHashMap<FuncRef, usize>,
+        caller: FuncRef,
+        callee: FuncRef,
+    ) -> bool {
+        let callee_func = &module.funcs[callee];
+        // A weak definition may be replaced with another one at link time, and a declaration
+        // without a body has nothing to inline.
+        if matches!(callee_func.sig.linkage(), Linkage::External | Linkage::Weak)
+            || callee_func.layout.entry_block().is_none()
+            || callee_func.attributes.contains(attributes::NOINLINE)
+            || call_graph.is_recursive(callee)
+            || call_graph.in_same_scc(caller, callee)
+        {
+            return false;
+        }
+
+        let insns: Vec<_> = callee_func
+            .layout
+            .iter_block()
+            .flat_map(|block| callee_func.layout.iter_insn(block))
+            .collect();
+        let cost_model = module.ctx.isa.cost_model();
+        let cost: u64 = insns
+            .iter()
+            .map(|insn| cost_model.insn_cost(callee_func.dfg.insn_data(*insn)))
+            .sum();
+        (insns.len() < self.config.max_callee_insns && cost < self.config.max_callee_cost)
+            || (self.config.inline_single_caller && call_sites[&callee] == 1)
+    }
+}
+
+/// Returns the calls in `func` with their callees.
+fn iter_calls(func: &Function) -> impl Iterator<Item = (Insn, FuncRef)> + '_ {
+    func.layout
+        .iter_block()
+        .flat_map(|block| func.layout.iter_insn(block))
+        .filter_map(|insn| match func.dfg.insn_data(insn) {
+            InsnData::Call { func, .. } => Some((insn, *func)),
+            _ => None,
+        })
+}
+
+fn count_call_sites(module: &Module) -> FxHashMap<FuncRef, usize> {
+    let mut call_sites = FxHashMap::default();
+    for func_ref in module.iter_functions() {
+        for (_, callee) in iter_calls(&module.funcs[func_ref]) {
+            *call_sites.entry(callee).or_default() += 1;
+        }
+    }
+    call_sites
+}
+
+/// Inlines `callee` at `call` in `caller`, and returns the continuation block, which contains the
+/// insns that followed the call.
+///
+/// `callee` must be the function called by `call`, must have a body, and must belong to the same
+/// module as `caller`.
+pub fn inline_call(caller: &mut Function, call: Insn, callee: &Function) -> Block {
+    let InsnData::Call { func, args, .. } = caller.dfg.insn_data(call) else {
+        panic!("`{call:?}` is not a call");
+    };
+    let callee_ref = *func;
+    let call_args: SmallVec<[Value; 8]> = args.clone();
+    assert_eq!(
+        call_args.len(),
+        callee.arg_values.len(),
+        "wrong number of arguments to `%{}`",
+        callee.sig.name()
+    );
+    debug_assert_eq!(caller.callees[&callee_ref].name(), callee.sig.name());
+    // All preconditions are checked before modifying the caller.
+    let Some(entry) = callee.layout.entry_block() else {
+        panic!("`%{}` has no body", callee.sig.name());
+    };
+
+    let call_block = caller.layout.insn_block(call);
+    let cont = split_block_after(caller, call);
+
+    let mut remap = Remap::new(call_args);
+    let mut anchor = call_block;
+    for block in callee.layout.iter_block() {
+        let new_block = caller.dfg.make_block();
+        caller.layout.insert_block_after(new_block, anchor);
+        remap.blocks.insert(block, new_block);
+        anchor = new_block;
+    }
+
+    // Result values are made in advance, because a phi may refer to a value defined later in
+    // the layout. They point to the call until their insns are cloned.
+    for block in callee.layout.iter_block() {
+        for insn in callee.layout.iter_insn(block) {
+            for &result in callee.dfg.insn_results(insn) {
+                let ty = callee.dfg.value_ty(result);
+                let new_result = caller.dfg.make_value(ValueData::Insn { insn: call, ty });
+                remap.values.insert(result, new_result);
+            }
+        }
+    }
+
+    let mut rets = Vec::new();
+    for block in callee.layout.iter_block() {
+        let new_block = remap.blocks[&block];
+        for insn in callee.layout.iter_insn(block) {
+            let insn_data = callee.dfg.insn_data(insn);
+            if let InsnData::Return { args } = insn_data {
+                let jump = caller.dfg.make_insn(InsnData::jump(cont));
+                caller.layout.append_insn(jump, new_block);
+                rets.push((args.clone(), new_block));
+                continue;
+            }
+
+            let mut new_data = insn_data.clone();
+            remap.map_dests(&mut new_data);
+            for idx in 0..new_data.args().len() {
+                let arg = new_data.args()[idx];
+                new_data.args_mut()[idx] = remap.map_value(caller, callee, arg);
+            }
+            let new_insn = caller.dfg.make_insn(new_data);
+            caller.layout.append_insn(new_insn, new_block);
+            // Inlined insns keep the locations in the callee.
+            if let Some(loc) = callee.dfg.insn_source_loc(insn) {
+                caller.dfg.set_insn_source_loc(new_insn, Some(loc.clone()));
+            }
+
+            let new_results: SmallVec<[Value; 2]> = callee
+                .dfg
+                .insn_results(insn)
+                .iter()
+                .map(|result| remap.values[result])
+                .collect();
+            for &new_result in &new_results {
+                let ty = caller.dfg.value_ty(new_result);
+                caller.dfg.values[new_result] = ValueData::Insn { insn: new_insn, ty };
+            }
+            caller.dfg.attach_results(new_insn, &new_results);
+        }
+    }
+
+    // Each returned value is merged separately. The phis are placed in the order of the results.
+    let results: SmallVec<[Value; 2]> = caller.dfg.insn_results(call).into();
+    let mut last_phi = None;
+    for (idx, result) in results.into_iter().enumerate() {
+        let ty = caller.dfg.value_ty(result);
+        let mut rets: Vec<_> = rets
+            .iter()
+            .filter_map(|(values, block)| {
+                let value = *values.get(idx)?;
+                Some((remap.map_value(caller, callee, value), *block))
+            })
+            .collect();
+
+        match rets.len() {
+            0 => {
+                let undef = caller.dfg.make_undef(ty);
+                caller.dfg.change_to_alias(result, undef);
+            }
+            1 => {
+                let (value, _) = rets.pop().unwrap();
+                caller.dfg.change_to_alias(result, value);
+            }
+            _ => {
+                // The phi takes over the call result, so the users of the result are kept as is.
+                let phi = caller.dfg.make_insn(InsnData::phi(ty));
+                match last_phi {
+                    Some(last_phi) => caller.layout.insert_insn_after(phi, last_phi),
+                    None => caller.layout.prepend_insn(phi, cont),
+                }
+                last_phi = Some(phi);
+                for (value, block) in rets {
+                    caller.dfg.append_phi_arg(phi, value, block);
+                }
+                caller.dfg.values[result] = ValueData::Insn { insn: phi, ty };
+                caller.dfg.attach_result(phi, result);
+            }
+        }
+    }
+
+    for idx in 0..caller.dfg.insn_args_num(call) {
+        let arg = caller.dfg.insn_arg(call, idx);
+        caller.dfg.remove_user(arg, call);
+    }
+    caller.layout.remove_insn(call);
+
+    let jump = caller.dfg.make_insn(InsnData::jump(remap.blocks[&entry]));
+    caller.layout.append_insn(jump, call_block);
+
+    for (func_ref, sig) in &callee.callees {
+        caller
+            .callees
+            .entry(*func_ref)
+            .or_insert_with(|| sig.clone());
+    }
+
+    cont
+}
+
+/// Moves the insns following `insn` to a new block inserted after the block of `insn`, and
+/// returns the new block.
+fn split_block_after(func: &mut Function, insn: Insn) -> Block {
+    let block = func.layout.insn_block(insn);
+    let new_block = func.dfg.make_block();
+    func.layout.insert_block_after(new_block, block);
+
+    while let Some(next) = func.layout.next_insn_of(insn) {
+        func.layout.remove_insn(next);
+        func.layout.append_insn(next, new_block);
+    }
+
+    // The successors are now reached from the new block.
+    let Some(last_insn) = func.layout.last_insn_of(new_block) else {
+        return new_block;
+    };
+    let dests: Vec<_> = func.dfg.analyze_branch(last_insn).iter_dests().collect();
+    for dest in dests {
+        let phis: Vec<_> = func
+            .layout
+            .iter_insn(dest)
+            .take_while(|insn| func.dfg.is_phi(*insn))
+            .collect();
+        for phi in phis {
+            for phi_block in func.dfg.phi_blocks_mut(phi) {
+                if *phi_block == block {
+                    *phi_block = new_block;
+                }
+            }
+        }
+    }
+
+    new_block
+}
+
+/// Maps the blocks and values of the callee to the ones of the caller.
+struct Remap {
+    args: SmallVec<[Value; 8]>,
+    blocks: FxHashMap<Block, Block>,
+    values: FxHashMap<Value, Value>,
+}
+
+impl Remap {
+    fn new(args: SmallVec<[Value; 8]>) -> Self {
+        Self {
+            args,
+            blocks: FxHashMap::default(),
+            values: FxHashMap::default(),
+        }
+    }
+
+    fn map_value(&mut self, caller: &mut Function, callee: &Function, value: Value) -> Value {
+        if let Some(&mapped) = self.values.get(&value) {
+            return mapped;
+        }
+
+        let mapped = match *callee.dfg.value_data(value) {
+            ValueData::Insn { .. } => unreachable!("results of insns are made in advance"),
+            ValueData::Arg { idx, .. } => self.args[idx],
+            ValueData::Immediate { imm, .. } => caller.dfg.make_imm_value(imm),
+            ValueData::Global { gv, .. } => caller
+                .dfg
+                .make_global_value(gv)
+                .unwrap_or_else(|err| panic!("{err}")),
+            ValueData::Undef { ty } => caller.dfg.make_undef(ty),
+        };
+        self.values.insert(value, mapped);
+        mapped
+    }
+
+    fn map_dests(&self, insn_data: &mut InsnData) {
+        let map = |block: &mut Block| *block = self.blocks[block];
+        match insn_data {
+            InsnData::Jump { dests } => dests.iter_mut().for_each(map),
+            InsnData::Branch { dests, .. } => dests.iter_mut().for_each(map),
+            InsnData::BrTable { default, table, .. } => {
+                default.iter_mut().for_each(map);
+                table.iter_mut().for_each(map);
+            }
+            InsnData::Phi { blocks, .. } => blocks.iter_mut().for_each(map),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sonatina_ir::{
+        builder::{test_util::*, ModuleBuilder},
+        func_cursor::InsnInserter,
+        module::ModuleCtx,
+        AttrValue, Signature, Type,
+    };
+
+    /// Builds a module with a leaf function `%add_one(x) = x + 1`, and `%test_func(x) =
+    /// add_one(x) * 2`.
+    fn build_leaf_call() -> (Module, FuncRef, FuncRef) {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let sig = Signature::new("add_one", Linkage::Private, &[Type::I32], Type::I32);
+        let callee = mb.declare_function(sig);
+        let sig = Signature::new("test_func", Linkage::Public, &[Type::I32], Type::I32);
+        let caller = mb.declare_function(sig);
+
+        let mut builder = mb.build_function::<InsnInserter>(callee);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg = builder.args()[0];
+        let one = builder.make_imm_value(1i32);
+        let v = builder.add(arg, one);
+        builder.ret(&[v]);
+        builder.seal_all();
+        let mb = builder.finish();
+
+        let mut builder = mb.build_function::<InsnInserter>(caller);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg = builder.args()[0];
+        let v = builder.call(callee, &[arg]).unwrap();
+        let two = builder.make_imm_value(2i32);
+        let v = builder.mul(v, two);
+        builder.ret(&[v]);
+        builder.seal_all();
+
+        (builder.finish().build(), caller, callee)
+    }
+
+    fn first_call(func: &Function) -> Insn {
+        func.layout
+            .iter_block()
+            .flat_map(|block| func.layout.iter_insn(block))
+            .find(|insn| matches!(func.dfg.insn_data(*insn), InsnData::Call { .. }))
+            .unwrap()
+    }
+
+    #[test]
+    fn inline_leaf() {
+        let (mut module, caller, callee) = build_leaf_call();
+        let callee_func = module.funcs[callee].clone();
+        let func = &mut module.funcs[caller];
+        let call = first_call(func);
+        inline_call(func, call, &callee_func);
+
+        assert_eq!(
+            dump_func(&module, caller),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        jump block2;
+
+    block2:
+        v4.i32 = add v0 1.i32;
+        jump block1;
+
+    block1:
+        v3.i32 = mul v4 2.i32;
+        return v3;
+
+}
+"
+        );
+    }
+
+    #[test]
+    fn inline_multiple_returns() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let sig = Signature::new("abs", Linkage::Private, &[Type::I32], Type::I32);
+        let callee = mb.declare_function(sig);
+        let sig = Signature::new("test_func", Linkage::Public, &[Type::I32], Type::I32);
+        let caller = mb.declare_function(sig);
+
+        let mut builder = mb.build_function::<InsnInserter>(callee);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg = builder.args()[0];
+        let zero = builder.make_imm_value(0i32);
+        let is_neg = builder.slt(arg, zero);
+        builder.br(is_neg, b1, b2);
+        builder.switch_to_block(b1);
+        let neg = builder.neg(arg);
+        builder.ret(&[neg]);
+        builder.switch_to_block(b2);
+        builder.ret(&[arg]);
+        builder.seal_all();
+        let mb = builder.finish();
+
+        let mut builder = mb.build_function::<InsnInserter>(caller);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg = builder.args()[0];
+        let v = builder.call(callee, &[arg]).unwrap();
+        builder.ret(&[v]);
+        builder.seal_all();
+        let mut module = builder.finish().build();
+
+        let callee_func = module.funcs[callee].clone();
+        let func = &mut module.funcs[caller];
+        let call = first_call(func);
+        inline_call(func, call, &callee_func);
+
+        assert_eq!(
+            dump_func(&module, caller),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        jump block2;
+
+    block2:
+        v2.i1 = slt v0 0.i32;
+        br v2 block3 block4;
+
+    block3:
+        v3.i32 = neg v0;
+        jump block1;
+
+    block4:
+        jump block1;
+
+    block1:
+        v1.i32 = phi (v3 block3) (v0 block4);
+        return v1;
+
+}
+"
+        );
+    }
+
+    /// Builds `%test_func` and `%other_func`, both of which call `%callee`.
+    fn build_two_callers(callee_insns: usize, recursive: bool) -> (Module, FuncRef, FuncRef) {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let sig = Signature::new("callee", Linkage::Private, &[Type::I32], Type::I32);
+        let callee = mb.declare_function(sig);
+        let sig = Signature::new("test_func", Linkage::Public, &[Type::I32], Type::I32);
+        let caller = mb.declare_function(sig);
+        let sig = Signature::new("other_func", Linkage::Public, &[Type::I32], Type::I32);
+        let other = mb.declare_function(sig);
+
+        let mut builder = mb.build_function::<InsnInserter>(callee);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let mut v = builder.args()[0];
+        if recursive {
+            v = builder.call(callee, &[v]).unwrap();
+        }
+        let one = builder.make_imm_value(1i32);
+        for _ in 0..callee_insns {
+            v = builder.add(v, one);
+        }
+        builder.ret(&[v]);
+        builder.seal_all();
+        let mut mb = builder.finish();
+
+        for func_ref in [caller, other] {
+            let mut builder = mb.build_function::<InsnInserter>(func_ref);
+            let b0 = builder.append_block();
+            builder.switch_to_block(b0);
+            let arg = builder.args()[0];
+            let v = builder.call(callee, &[arg]).unwrap();
+            builder.ret(&[v]);
+            builder.seal_all();
+            mb = builder.finish();
+        }
+
+        (mb.build(), caller, callee)
+    }
+
+    #[test]
+    fn inliner_keeps_callee() {
+        let (mut module, caller, callee) = build_two_callers(1, false);
+        let callee_dump = dump_func(&module, callee);
+
+        let mut inliner = Inliner::new(InlinerConfig::default());
+        assert_eq!(inliner.run(&mut module), 2);
+
+        assert_eq!(
+            dump_func(&module, caller),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        jump block2;
+
+    block2:
+        v2.i32 = add v0 1.i32;
+        jump block1;
+
+    block1:
+        return v2;
+
+}
+"
+        );
+        assert!(!module.funcs[caller].callees.contains_key(&callee));
+        assert_eq!(dump_func(&module, callee), callee_dump);
+    }
+
+    #[test]
+    fn inliner_cost_limit() {
+        let config = InlinerConfig {
+            max_callee_insns: usize::MAX,
+            max_callee_cost: 20,
+            inline_single_caller: true,
+        };
+
+        // `add` * 3 + `return` costs 3 * 3 + 8 = 17, which is below the limit.
+        let (mut module, ..) = build_two_callers(3, false);
+        assert_eq!(Inliner::new(config).run(&mut module), 2);
+
+        // The callee is too large, and it's called from two places.
+        let (mut module, caller, callee) = build_two_callers(4, false);
+        assert_eq!(Inliner::new(config).run(&mut module), 0);
+        assert!(module.funcs[caller].callees.contains_key(&callee));
+    }
+
+    #[test]
+    fn inliner_insn_limit() {
+        let config = InlinerConfig {
+            max_callee_insns: 4,
+            max_callee_cost: u64::MAX,
+            inline_single_caller: true,
+        };
+
+        // `add` * 2 + `return` is 3 insns, which is below the limit.
+        let (mut module, ..) = build_two_callers(2, false);
+        assert_eq!(Inliner::new(config).run(&mut module), 2);
+
+        let (mut module, caller, callee) = build_two_callers(3, false);
+        assert_eq!(Inliner::new(config).run(&mut module), 0);
+        assert!(module.funcs[caller].callees.contains_key(&callee));
+    }
+
+    #[test]
+    fn inliner_skips_noinline_callee() {
+        let (mut module, caller, callee) = build_two_callers(1, false);
+        module.funcs[callee].set_attr(attributes::NOINLINE, AttrValue::Flag);
+
+        assert_eq!(Inliner::new(InlinerConfig::default()).run(&mut module), 0);
+        assert!(module.funcs[caller].callees.contains_key(&callee));
+    }
+
+    #[test]
+    fn inliner_skips_declaration() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let sig = Signature::new("decl", Linkage::Private, &[Type::I32], Type::I32);
+        let callee = mb.declare_function(sig);
+        let sig = Signature::new("test_func", Linkage::Public, &[Type::I32], Type::I32);
+        let caller = mb.declare_function(sig);
+
+        let mut builder = mb.build_function::<InsnInserter>(caller);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg = builder.args()[0];
+        let v = builder.call(callee, &[arg]).unwrap();
+        builder.ret(&[v]);
+        builder.seal_all();
+        let mut module = builder.finish().build();
+        let caller_dump = dump_func(&module, caller);
+
+        assert_eq!(Inliner::new(InlinerConfig::default()).run(&mut module), 0);
+        assert_eq!(dump_func(&module, caller), caller_dump);
+    }
+
+    #[test]
+    fn inliner_skips_recursive_callee() {
+        let (mut module, ..) = build_two_callers(1, true);
+        let config = InlinerConfig {
+            max_callee_insns: usize::MAX,
+            max_callee_cost: u64::MAX,
+            inline_single_caller: true,
+        };
+        assert_eq!(Inliner::new(config).run(&mut module), 0);
+    }
+}
@@ -0,0 +1,163 @@
+//! This module contains a pass that threads jumps through conditional branches.
+//!
+//! If a block `pred` jumps to a block whose `br` condition is a phi, and the phi arg flowing from
+//! `pred` is a constant, the destination of the `br` taken from `pred` is known. So `pred` can
+//! jump to the destination directly, skipping the intermediate block.
+//!
+//! When the intermediate block has insns other than its phis and the `br`, they are duplicated
+//! into a new block placed on the threaded edge. The values defined in the intermediate block
+//! must be used only in the block itself or as phi args in its successors, so that the
+//! duplication keeps the function in SSA form.
+use rustc_hash::FxHashMap;
+use smallvec::SmallVec;
+
+use sonatina_ir::{insn::InsnData, Block, ControlFlowGraph, Function, Insn, Value};
+
+/// Thread the jumps in `func` whose destinations are known.
+/// Each block is threaded at most once, so that the pass terminates even on infinite loops.
+pub fn run(func: &mut Function, cfg: &mut ControlFlowGraph) {
+    let blocks: Vec<_> = func.layout.iter_block().collect();
+    for pred in blocks {
+        if let Some((block, dest)) = find_threadable(func, pred) {
+            thread(func, cfg, pred, block, dest);
+        }
+    }
+}
+
+/// Returns the block `pred` jumps to and the destination taken from `pred` if the jump can be
+/// threaded.
+fn find_threadable(func: &Function, pred: Block) -> Option<(Block, Block)> {
+    let jump = func.layout.last_insn_of(pred)?;
+    let InsnData::Jump { dests: [block] } = *func.dfg.insn_data(jump) else {
+        return None;
+    };
+    let br = func.layout.last_insn_of(block)?;
+    let InsnData::Branch {
+        args: [cond],
+        dests,
+    } = *func.dfg.insn_data(br)
+    else {
+        return None;
+    };
+
+    let phi = func.dfg.value_insn(cond)?;
+    if !func.dfg.is_phi(phi) || func.layout.insn_block(phi) != block {
+        return None;
+    }
+    let imm = func.dfg.value_imm(phi_arg_from(func, phi, pred)?)?;
+    let dest = if imm.is_zero() { dests[1] } else { dests[0] };
+
+    if block == pred || dest == block || !is_local(func, block) {
+        return None;
+    }
+    Some((block, dest))
+}
+
+/// Redirects `pred` to `dest` skipping `block`.
+fn thread(func: &mut Function, cfg: &mut ControlFlowGraph, pred: Block, block: Block, dest: Block) {
+    // Maps the values defined in `block` to the values seen from the threaded edge.
+    let mut value_map = FxHashMap::default();
+    let phis = phis_of(func, block);
+    for &phi in &phis {
+        let result = func.dfg.insn_result(phi).unwrap();
+        value_map.insert(result, phi_arg_from(func, phi, pred).unwrap());
+    }
+
+    let body: Vec<_> = func
+        .layout
+        .iter_insn(block)
+        .skip(phis.len())
+        .filter(|&insn| !func.dfg.is_terminator(insn))
+        .collect();
+    let dest_phis = phis_of(func, dest);
+    let conflicts = !dest_phis.is_empty() && cfg.preds_of(dest).any(|&p| p == pred);
+
+    // The block flowing into `dest` on the threaded edge.
+    let from = if body.is_empty() && !conflicts {
+        pred
+    } else {
+        let dup = func.dfg.make_block();
+        func.layout.insert_block_after(dup, block);
+        for insn in body {
+            let mut data = func.dfg.insn_data(insn).clone();
+            for arg in data.args_mut() {
+                *arg = map_value(&value_map, *arg);
+            }
+            let new_insn = func.dfg.make_insn(data);
+            func.layout.append_insn(new_insn, dup);
+
+            let new_results: SmallVec<[Value; 2]> = func
+                .dfg
+                .make_results(new_insn)
+                .into_iter()
+                .map(|value| func.dfg.make_value(value))
+                .collect();
+            func.dfg.attach_results(new_insn, &new_results);
+            for (&result, &new_result) in func.dfg.insn_results(insn).iter().zip(&new_results) {
+                value_map.insert(result, new_result);
+            }
+        }
+        let jump = func.dfg.make_insn(InsnData::jump(dest));
+        func.layout.append_insn(jump, dup);
+        dup
+    };
+
+    for phi in dest_phis {
+        let arg = phi_arg_from(func, phi, block).unwrap();
+        func.dfg
+            .append_phi_arg(phi, map_value(&value_map, arg), from);
+    }
+    for phi in phis {
+        func.dfg.remove_phi_arg(phi, pred);
+        // The same value may still flow from another block.
+        func.dfg.attach_user(phi);
+    }
+
+    let jump = func.layout.last_insn_of(pred).unwrap();
+    let new_dest = if from == pred { dest } else { from };
+    func.dfg.rewrite_branch_dest(jump, block, new_dest);
+
+    cfg.recompute_block(func, pred);
+    if from != pred {
+        cfg.recompute_block(func, from);
+    }
+}
+
+/// Returns `true` if the values defined in `block` are used only in the block itself or as phi
+/// args flowing from the block.
+fn is_local(func: &Function, block: Block) -> bool {
+    func.layout.iter_insn(block).all(|insn| {
+        let Some(result) = func.dfg.insn_result(insn) else {
+            return true;
+        };
+        func.dfg.users(result).all(|&user| {
+            func.layout.insn_block(user) == block
+                || func.dfg.is_phi(user)
+                    && func
+                        .dfg
+                        .insn_args(user)
+                        .iter()
+                        .zip(func.dfg.phi_blocks(user))
+                        .all(|(&arg, &from)| arg != result || from == block)
+        })
+    })
+}
+
+fn phis_of(func: &Function, block: Block) -> Vec<Insn> {
+    func.layout
+        .iter_insn(block)
+        .take_while(|&insn| func.dfg.is_phi(insn))
+        .collect()
+}
+
+fn phi_arg_from(func: &Function, phi: Insn, from: Block) -> Option<Value> {
+    func.dfg
+        .phi_blocks(phi)
+        .iter()
+        .position(|&block| block == from)
+        .map(|idx| func.dfg.insn_arg(phi, idx))
+}
+
+fn map_value(value_map: &FxHashMap<Value, Value>, value: Value) -> Value {
+    value_map.get(&value).copied().unwrap_or(value)
+}
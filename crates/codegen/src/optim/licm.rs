@@ -24,8 +24,12 @@ impl LicmSolver {
         self.invariants.clear();
     }
 
-    /// Run loop invariant code motion ont the function.
-    /// This method also modifies `cfg` and `lpt` htt
+    /// Run loop invariant code motion on the function.
+    /// This method also modifies `cfg` and `lpt` when a preheader is inserted.
+    ///
+    /// Only pure insns (see [`InsnData::is_pure`]) are hoisted. So insns that may trap, e.g.,
+    /// divisions, are never speculated, and loads are never moved out of the loop even if their
+    /// addresses are invariant.
     pub fn run(&mut self, func: &mut Function, cfg: &mut ControlFlowGraph, lpt: &mut LoopTree) {
         for lp in lpt.loops() {
             self.collect_invaliants(func, cfg, lpt, lp);
@@ -55,8 +59,8 @@ impl LicmSolver {
             for insn in func.layout.iter_insn(block) {
                 if self.is_invariant(func, &loop_var, insn) {
                     self.invariants.push(insn);
-                } else if let Some(result) = func.dfg.insn_result(insn) {
-                    loop_var.insert(result);
+                } else {
+                    loop_var.extend(func.dfg.insn_results(insn).iter().copied());
                 }
             }
         }
@@ -79,10 +83,7 @@ impl LicmSolver {
 
     /// Returns `true` if the `insn` is safe to hoist.
     fn is_safe_to_hoist(&self, func: &Function, insn: Insn) -> bool {
-        !(func.dfg.has_side_effect(insn)
-            || func.dfg.is_branch(insn)
-            || func.dfg.may_trap(insn)
-            || func.dfg.is_phi(insn))
+        func.dfg.insn_is_pure(insn) && !func.dfg.is_phi(insn)
     }
 
     /// Returns preheader of the loop.
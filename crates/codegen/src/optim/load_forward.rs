@@ -0,0 +1,55 @@
+//! This module contains store-to-load forwarding, which replaces a memory load with the value
+//! stored by a preceding store to the same address in the same block.
+//!
+//! The forwarding relies on [`MemDeps`], so the address equality is tested by value identity, and
+//! a load is forwarded only if no store or call in between may clobber the loaded location.
+//! Atomic loads are never forwarded since the location may be written concurrently.
+use sonatina_ir::{
+    func_cursor::{CursorLocation, FuncCursor, InsnInserter},
+    insn::{DataLocationKind, InsnData, MemoryOrdering},
+    Function,
+};
+
+use crate::mem_deps::{AliasResult, MemDeps};
+
+/// Forward stored values to memory loads in `func`, and remove the forwarded loads.
+pub fn run(func: &mut Function) {
+    let mut mem_deps = MemDeps::new();
+    mem_deps.compute(func);
+
+    let mut forwarded = Vec::new();
+    for block in func.layout.iter_block() {
+        for insn in func.layout.iter_insn(block) {
+            let InsnData::Load {
+                loc: DataLocationKind::Memory,
+                ordering: MemoryOrdering::NotAtomic,
+                ..
+            } = func.dfg.insn_data(insn)
+            else {
+                continue;
+            };
+            let Some(store) = mem_deps.clobbering_insn(insn) else {
+                continue;
+            };
+            if !matches!(func.dfg.insn_data(store), InsnData::Store { .. })
+                || mem_deps.clobbers(insn, store) != AliasResult::MustAlias
+            {
+                continue;
+            }
+
+            let stored = func.dfg.insn_arg(store, 1);
+            if func.dfg.insn_result_ty(insn) == Some(func.dfg.value_ty(stored)) {
+                forwarded.push((insn, store));
+            }
+        }
+    }
+
+    for (load, store) in forwarded {
+        // The stored value is looked up here since it may be the result of a load that has
+        // already been forwarded.
+        let stored = func.dfg.insn_arg(store, 1);
+        let result = func.dfg.insn_result(load).unwrap();
+        func.dfg.change_to_alias(result, stored);
+        InsnInserter::at_location(CursorLocation::At(load)).remove_insn(func);
+    }
+}
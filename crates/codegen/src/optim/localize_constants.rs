@@ -0,0 +1,111 @@
+//! This module contains a pass that materializes immediates at each use site.
+//!
+//! Immediates are interned in [`DataFlowGraph`](sonatina_ir::DataFlowGraph), so a single
+//! immediate value is shared by all its users. Some backends prefer to have a distinct value
+//! for each use of an immediate, e.g., to allocate a register per use site. This pass replaces
+//! each immediate operand with the result of a `copy` insn placed right before the user.
+//!
+//! An immediate used by a phi is materialized at the end of the corresponding predecessor.
+use sonatina_ir::{insn::UnaryOp, Function, Insn, InsnData, Value};
+
+#[derive(Debug, Default)]
+pub struct ConstantLocalizer {}
+
+impl ConstantLocalizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn run(&mut self, func: &mut Function) {
+        let insns: Vec<_> = func
+            .layout
+            .iter_block()
+            .flat_map(|block| func.layout.iter_insn(block))
+            .collect();
+
+        for insn in insns {
+            for idx in 0..func.dfg.insn_args_num(insn) {
+                let arg = func.dfg.insn_arg(insn, idx);
+                if !func.dfg.is_imm(arg) {
+                    continue;
+                }
+
+                let insert_before = if func.dfg.is_phi(insn) {
+                    let pred = func.dfg.phi_blocks(insn)[idx];
+                    func.layout.last_insn_of(pred).unwrap()
+                } else {
+                    insn
+                };
+
+                let copy = self.materialize(func, arg, insert_before);
+                func.dfg.replace_insn_arg(insn, copy, idx);
+            }
+        }
+    }
+
+    fn materialize(&self, func: &mut Function, imm: Value, insert_before: Insn) -> Value {
+        let copy = func.dfg.make_insn(InsnData::Unary {
+            code: UnaryOp::Copy,
+            args: [imm],
+        });
+        func.layout.insert_insn_before(copy, insert_before);
+
+        let result = func.dfg.make_result(copy).unwrap();
+        let result = func.dfg.make_value(result);
+        func.dfg.attach_result(copy, result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sonatina_ir::{builder::test_util::*, Type};
+
+    #[test]
+    fn shared_immediate() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+
+        let arg = builder.args()[0];
+
+        builder.switch_to_block(b0);
+        let one = builder.make_imm_value(1i32);
+        let v1 = builder.add(arg, one);
+        let v2 = builder.mul(v1, one);
+        builder.jump(b1);
+
+        builder.switch_to_block(b1);
+        let v3 = builder.phi(Type::I32, &[(one, b0)]);
+        let v4 = builder.add(v2, v3);
+        builder.ret(&[v4]);
+
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        ConstantLocalizer::new().run(&mut module.funcs[func_ref]);
+
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        v6.i32 = copy 1.i32;
+        v2.i32 = add v0 v6;
+        v7.i32 = copy 1.i32;
+        v3.i32 = mul v2 v7;
+        v8.i32 = copy 1.i32;
+        jump block1;
+
+    block1:
+        v4.i32 = phi (v8 block0);
+        v5.i32 = add v3 v4;
+        return v5;
+
+}
+"
+        );
+    }
+}
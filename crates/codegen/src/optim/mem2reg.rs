@@ -0,0 +1,257 @@
+//! This module contains a solver for `mem2reg`, which promotes stack slots to SSA values.
+//!
+//! An alloca whose address never escapes is accessed only by direct loads and stores, so the
+//! value in the slot can be tracked as an SSA value instead. Phis are placed at the iterated
+//! dominance frontier of the blocks storing to the slot, then loads are replaced with the
+//! reaching values while walking the dominator tree.
+//!
+//! ref: Cytron et al., "Efficiently Computing Static Single Assignment Form and the Control
+//! Dependence Graph".
+use cranelift_entity::SecondaryMap;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::domtree::{DomTree, DominatorTreeTraversable};
+
+use sonatina_ir::{
+    func_cursor::{CursorLocation, FuncCursor, InsnInserter},
+    insn::{DataLocationKind, InsnData},
+    Block, ControlFlowGraph, Function, Insn, Type, Value,
+};
+
+#[derive(Default)]
+pub struct Mem2RegSolver {
+    /// Promotable allocas.
+    slots: Vec<Slot>,
+    /// Maps the address of a promotable alloca to the index of `slots`.
+    slot_of: FxHashMap<Value, usize>,
+    /// Phis inserted in each block, together with the index of the slot they merge.
+    phis: SecondaryMap<Block, Vec<(Insn, usize)>>,
+    domtree_traversable: DominatorTreeTraversable,
+}
+
+#[derive(Debug)]
+struct Slot {
+    alloca: Insn,
+    ty: Type,
+    /// Loads and stores accessing the slot.
+    accesses: Vec<Insn>,
+}
+
+impl Mem2RegSolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.slot_of.clear();
+        self.phis.clear();
+        self.domtree_traversable.clear();
+    }
+
+    /// Promote non-escaping allocas in the function to SSA values.
+    /// The CFG is left unchanged, so `cfg` and `domtree` are still valid after the pass.
+    ///
+    /// Nothing is promoted if the entry block has predecessors, since a phi placed in the entry
+    /// would have no arg for the function entry.
+    pub fn run(&mut self, func: &mut Function, cfg: &ControlFlowGraph, domtree: &DomTree) {
+        self.clear();
+        if cfg.entry_pred().is_some() {
+            return;
+        }
+
+        self.collect_slots(func);
+        if self.slots.is_empty() {
+            return;
+        }
+
+        self.insert_phis(func, cfg, domtree);
+        self.rename(func, cfg, domtree);
+        self.remove_accesses(func);
+        self.remove_dead_phis(func);
+    }
+
+    /// Collect allocas that can be promoted, i.e., allocas whose address doesn't escape and whose
+    /// loads and stores all access the slot with the allocated type.
+    fn collect_slots(&mut self, func: &Function) {
+        for block in func.layout.iter_block() {
+            for insn in func.layout.iter_insn(block) {
+                let InsnData::Alloca { ty } = *func.dfg.insn_data(insn) else {
+                    continue;
+                };
+                if func.dfg.alloca_escapes(insn) {
+                    continue;
+                }
+
+                let addr = func.dfg.insn_result(insn).unwrap();
+                let accesses: Vec<_> = func.dfg.users(addr).copied().collect();
+                let is_typed_access = |&access: &Insn| match func.dfg.insn_data(access) {
+                    InsnData::Load { .. } => func.dfg.insn_result_ty(access) == Some(ty),
+                    InsnData::Store { args, .. } => func.dfg.value_ty(args[1]) == ty,
+                    _ => unreachable!(),
+                };
+                if !accesses.iter().all(is_typed_access) {
+                    continue;
+                }
+
+                self.slot_of.insert(addr, self.slots.len());
+                self.slots.push(Slot {
+                    alloca: insn,
+                    ty,
+                    accesses,
+                });
+            }
+        }
+    }
+
+    /// Insert phis at the iterated dominance frontier of the blocks storing to each slot.
+    fn insert_phis(&mut self, func: &mut Function, cfg: &ControlFlowGraph, domtree: &DomTree) {
+        let df = domtree.compute_df(cfg);
+
+        for (idx, slot) in self.slots.iter().enumerate() {
+            let mut worklist: Vec<_> = slot
+                .accesses
+                .iter()
+                .filter(|&&access| matches!(func.dfg.insn_data(access), InsnData::Store { .. }))
+                .map(|&store| func.layout.insn_block(store))
+                .collect();
+            let mut has_phi = FxHashSet::default();
+
+            while let Some(block) = worklist.pop() {
+                for &frontier in df.frontiers(block) {
+                    if !has_phi.insert(frontier) {
+                        continue;
+                    }
+
+                    let mut cursor = InsnInserter::at_location(CursorLocation::BlockTop(frontier));
+                    let phi = cursor.prepend_insn_data(func, InsnData::phi(slot.ty));
+                    let result = cursor.make_result(func, phi).unwrap();
+                    cursor.attach_result(func, phi, result);
+                    self.phis[frontier].push((phi, idx));
+
+                    worklist.push(frontier);
+                }
+            }
+        }
+    }
+
+    /// Replace loads with the values reaching them while walking the dominator tree in preorder,
+    /// and fill the args of the inserted phis.
+    fn rename(&mut self, func: &mut Function, cfg: &ControlFlowGraph, domtree: &DomTree) {
+        let Some(entry) = func.layout.entry_block() else {
+            return;
+        };
+        self.domtree_traversable.compute(domtree);
+
+        // The slot is undefined until the first store.
+        let initial = self
+            .slots
+            .iter()
+            .map(|slot| func.dfg.make_undef(slot.ty))
+            .collect::<Vec<_>>();
+
+        let mut stack = vec![(entry, initial)];
+        while let Some((block, mut reaching)) = stack.pop() {
+            self.rename_block(func, cfg, block, &mut reaching);
+
+            for &child in self.domtree_traversable.children_of(block).iter().rev() {
+                stack.push((child, reaching.clone()));
+            }
+        }
+    }
+
+    fn rename_block(
+        &self,
+        func: &mut Function,
+        cfg: &ControlFlowGraph,
+        block: Block,
+        reaching: &mut [Value],
+    ) {
+        for &(phi, idx) in &self.phis[block] {
+            reaching[idx] = func.dfg.insn_result(phi).unwrap();
+        }
+
+        let insns: Vec<_> = func.layout.iter_insn(block).collect();
+        for insn in insns {
+            match *func.dfg.insn_data(insn) {
+                InsnData::Load {
+                    args: [addr],
+                    loc: DataLocationKind::Memory,
+                    ..
+                } => {
+                    if let Some(&idx) = self.slot_of.get(&addr) {
+                        let result = func.dfg.insn_result(insn).unwrap();
+                        func.dfg.change_to_alias(result, reaching[idx]);
+                    }
+                }
+                InsnData::Store {
+                    args: [addr, value],
+                    loc: DataLocationKind::Memory,
+                    ..
+                } => {
+                    if let Some(&idx) = self.slot_of.get(&addr) {
+                        reaching[idx] = value;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut succs: Vec<_> = cfg.succs_of(block).copied().collect();
+        succs.sort_unstable();
+        succs.dedup();
+        for succ in succs {
+            for &(phi, idx) in &self.phis[succ] {
+                func.dfg.append_phi_arg(phi, reaching[idx], block);
+            }
+        }
+    }
+
+    /// Remove the promoted allocas and their loads and stores.
+    fn remove_accesses(&self, func: &mut Function) {
+        for slot in &self.slots {
+            for &access in &slot.accesses {
+                // Loads in unreachable blocks are not visited while renaming.
+                if let Some(result) = func.dfg.insn_result(access) {
+                    if func.dfg.users_num(result) != 0 {
+                        let undef = func.dfg.make_undef(slot.ty);
+                        func.dfg.change_to_alias(result, undef);
+                    }
+                }
+                InsnInserter::at_location(CursorLocation::At(access)).remove_insn(func);
+            }
+            InsnInserter::at_location(CursorLocation::At(slot.alloca)).remove_insn(func);
+        }
+    }
+
+    /// Remove inserted phis that are not used other than by themselves.
+    fn remove_dead_phis(&self, func: &mut Function) {
+        let mut inserted: FxHashSet<_> = self
+            .phis
+            .values()
+            .flat_map(|phis| phis.iter().map(|&(phi, _)| phi))
+            .collect();
+        let mut worklist: Vec<_> = inserted.iter().copied().collect();
+
+        while let Some(phi) = worklist.pop() {
+            if !inserted.contains(&phi) {
+                continue;
+            }
+            let result = func.dfg.insn_result(phi).unwrap();
+            if func.dfg.users(result).any(|&user| user != phi) {
+                continue;
+            }
+
+            // The args may become dead once the phi is removed.
+            for &arg in func.dfg.insn_args(phi) {
+                if let Some(arg_insn) = func.dfg.value_insn(arg) {
+                    if arg_insn != phi && inserted.contains(&arg_insn) {
+                        worklist.push(arg_insn);
+                    }
+                }
+            }
+            InsnInserter::at_location(CursorLocation::At(phi)).remove_insn(func);
+            inserted.remove(&phi);
+        }
+    }
+}
@@ -1,8 +1,18 @@
 pub mod adce;
+pub mod block_merge;
+pub mod constant_folding;
+pub mod cse;
+pub mod dead_func_elim;
 pub mod gvn;
+pub mod inliner;
 pub mod insn_simplify;
+pub mod jump_threading;
 pub mod licm;
+pub mod load_forward;
+pub mod localize_constants;
+pub mod mem2reg;
+pub mod peephole;
 pub mod sccp;
+pub mod simplify_cfg;
 
-mod constant_folding;
 mod simplify_impl;
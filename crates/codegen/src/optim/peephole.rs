@@ -0,0 +1,175 @@
+//! This module contains a peephole simplification pass built on the insn rewrite driver.
+//!
+//! Algebraic identities, e.g., `add x 0 => x` and `xor x x => 0`, come from the rule library
+//! shared with [`super::insn_simplify`]. Unlike [`super::insn_simplify`], this pass doesn't
+//! keep a worklist; it sweeps the whole function until no insn is simplified.
+use sonatina_ir::{
+    visitor::{rewrite_func, InsnRewriter, RewriteAction},
+    DataFlowGraph, Function, Insn,
+};
+
+use super::simplify_impl::{simplify_insn, SimplifyResult};
+
+/// Simplifies insns of `func` with algebraic identities until no insn is simplified.
+///
+/// Returns `true` if `func` is changed.
+pub fn simplify(func: &mut Function) -> bool {
+    let mut changed = false;
+    while rewrite_func(func, &mut Peephole) {
+        changed = true;
+    }
+    changed
+}
+
+/// An [`InsnRewriter`] that applies algebraic identities to each insn.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Peephole;
+
+impl Peephole {
+    pub fn run(&mut self, func: &mut Function) {
+        simplify(func);
+    }
+}
+
+impl InsnRewriter for Peephole {
+    fn rewrite(&mut self, dfg: &mut DataFlowGraph, insn: Insn) -> RewriteAction {
+        match simplify_insn(dfg, insn) {
+            Some(SimplifyResult::Value(value)) => {
+                if let Some(result) = dfg.insn_result(insn) {
+                    dfg.change_to_alias(result, value);
+                }
+                RewriteAction::Remove
+            }
+
+            // Keeping an insn that is simplified to itself guarantees termination of the sweep.
+            Some(SimplifyResult::Insn(data)) if data != *dfg.insn_data(insn) => {
+                RewriteAction::Replace(data)
+            }
+
+            _ => RewriteAction::Keep,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sonatina_ir::{
+        builder::{test_util::*, FunctionBuilder},
+        func_cursor::InsnInserter,
+        Immediate, Type, Value,
+    };
+
+    /// Builds a function returning the value made by `f` from the argument `x`, and returns the
+    /// argument and the returned value after simplification.
+    fn simplify_ret(
+        f: impl FnOnce(&mut FunctionBuilder<InsnInserter>, Value) -> Value,
+    ) -> (Function, Value, Value) {
+        let ret_ty = Type::I32;
+        let mut builder = test_func_builder(&[Type::I32], ret_ty);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let x = builder.args()[0];
+        let v = f(&mut builder, x);
+        if builder.type_of(v) == ret_ty {
+            builder.ret(&[v]);
+        } else {
+            let v = builder.zext(v, ret_ty);
+            builder.ret(&[v]);
+        }
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let mut func = module.funcs[func_ref].clone();
+        simplify(&mut func);
+
+        let block = func.layout.entry_block().unwrap();
+        let ret = func.layout.last_insn_of(block).unwrap();
+        let ret_value = func.dfg.insn_arg(ret, 0);
+        (func, x, ret_value)
+    }
+
+    fn assert_imm(func: &Function, value: Value, imm: impl Into<Immediate>) {
+        assert_eq!(func.dfg.value_imm(value), Some(imm.into()));
+    }
+
+    #[test]
+    fn add_zero() {
+        let (_, x, ret) = simplify_ret(|b, x| {
+            let zero = b.make_imm_value(0i32);
+            b.add(x, zero)
+        });
+        assert_eq!(ret, x);
+    }
+
+    #[test]
+    fn mul_one() {
+        let (_, x, ret) = simplify_ret(|b, x| {
+            let one = b.make_imm_value(1i32);
+            b.mul(x, one)
+        });
+        assert_eq!(ret, x);
+    }
+
+    #[test]
+    fn mul_zero() {
+        let (func, _, ret) = simplify_ret(|b, x| {
+            let zero = b.make_imm_value(0i32);
+            b.mul(x, zero)
+        });
+        assert_imm(&func, ret, 0i32);
+    }
+
+    #[test]
+    fn sub_self() {
+        let (func, _, ret) = simplify_ret(|b, x| b.sub(x, x));
+        assert_imm(&func, ret, 0i32);
+    }
+
+    #[test]
+    fn and_self() {
+        let (_, x, ret) = simplify_ret(|b, x| b.and(x, x));
+        assert_eq!(ret, x);
+    }
+
+    #[test]
+    fn or_zero() {
+        let (_, x, ret) = simplify_ret(|b, x| {
+            let zero = b.make_imm_value(0i32);
+            b.or(x, zero)
+        });
+        assert_eq!(ret, x);
+    }
+
+    #[test]
+    fn xor_self() {
+        let (func, _, ret) = simplify_ret(|b, x| b.xor(x, x));
+        assert_imm(&func, ret, 0i32);
+    }
+
+    #[test]
+    fn eq_self() {
+        let (func, _, ret) = simplify_ret(|b, x| b.eq(x, x));
+
+        // `zext` of the folded comparison is left as is.
+        let ret_insn = func.dfg.value_insn(ret).unwrap();
+        let cmp = func.dfg.insn_arg(ret_insn, 0);
+        assert_imm(&func, cmp, true);
+    }
+
+    #[test]
+    fn chained_identities() {
+        // `(x - x) + x` is simplified to `0 + x` in the first sweep, and then to `x`.
+        let (func, x, ret) = simplify_ret(|b, x| {
+            let v = b.sub(x, x);
+            b.add(v, x)
+        });
+        assert_eq!(ret, x);
+
+        let block = func.layout.entry_block().unwrap();
+        assert_eq!(func.layout.iter_insn(block).count(), 1);
+    }
+}
@@ -176,43 +176,20 @@ impl SccpSolver {
                 match *code {
                     UnaryOp::Not => arg_cell.not(),
                     UnaryOp::Neg => arg_cell.neg(),
+                    UnaryOp::Copy => arg_cell,
                 }
             }
 
             InsnData::Binary { code, args } => {
                 let lhs = self.lattice[args[0]];
                 let rhs = self.lattice[args[1]];
-                match *code {
-                    BinaryOp::Add => lhs.add(rhs),
-                    BinaryOp::Sub => lhs.sub(rhs),
-                    BinaryOp::Mul => lhs.mul(rhs),
-                    BinaryOp::Udiv => lhs.udiv(rhs),
-                    BinaryOp::Sdiv => lhs.sdiv(rhs),
-                    BinaryOp::Lt => lhs.lt(rhs),
-                    BinaryOp::Gt => lhs.gt(rhs),
-                    BinaryOp::Slt => lhs.slt(rhs),
-                    BinaryOp::Sgt => lhs.sgt(rhs),
-                    BinaryOp::Le => lhs.le(rhs),
-                    BinaryOp::Ge => lhs.ge(rhs),
-                    BinaryOp::Sle => lhs.sle(rhs),
-                    BinaryOp::Sge => lhs.sge(rhs),
-                    BinaryOp::Eq => lhs.eq(rhs),
-                    BinaryOp::Ne => lhs.ne(rhs),
-                    BinaryOp::And => lhs.and(rhs),
-                    BinaryOp::Or => lhs.or(rhs),
-                    BinaryOp::Xor => lhs.xor(rhs),
-                }
+                lhs.binary_op(*code, rhs)
             }
 
-            InsnData::Cast { code, args, ty } => {
-                let arg_cell = self.lattice[args[0]];
-                match code {
-                    CastOp::Sext => arg_cell.sext(*ty),
-                    CastOp::Zext => arg_cell.zext(*ty),
-                    CastOp::Trunc => arg_cell.trunc(*ty),
-                    CastOp::BitCast => LatticeCell::Top,
-                }
-            }
+            InsnData::Cast { code, args, ty } => match code {
+                CastOp::BitCast => LatticeCell::Top,
+                _ => self.lattice[args[0]].cast(*code, *ty),
+            },
 
             InsnData::Load { .. } => LatticeCell::Top,
 
@@ -274,11 +251,14 @@ impl SccpSolver {
 
                 let mut contains_top = false;
                 for (value, dest) in args[1..].iter().zip(table.iter()) {
-                    if self.lattice[*value] == v_cell {
+                    let value_cell = self.lattice[*value];
+                    if value_cell.is_top() {
+                        contains_top = true;
+                    } else if value_cell == v_cell && !contains_top {
+                        // The destination is determined only if all preceding table values
+                        // are known not to match.
                         self.flow_work.push(FlowEdge::new(insn, *dest));
                         return;
-                    } else if v_cell.is_top() {
-                        contains_top = true;
                     }
                 }
 
@@ -297,7 +277,7 @@ impl SccpSolver {
 
             InsnData::Alloca { .. } | InsnData::Gep { .. } => LatticeCell::Top,
 
-            InsnData::Store { .. } | InsnData::Return { .. } => {
+            InsnData::Store { .. } | InsnData::Return { .. } | InsnData::Halt { .. } => {
                 // No insn result. Do nothing.
                 return;
             }
@@ -348,10 +328,7 @@ impl SccpSolver {
     }
 
     fn fold_insns(&mut self, func: &mut Function, cfg: &ControlFlowGraph) {
-        let mut rpo: Vec<_> = cfg.post_order().collect();
-        rpo.reverse();
-
-        for block in rpo {
+        for block in cfg.reverse_post_order() {
             let mut next_insn = func.layout.first_insn_of(block);
             while let Some(insn) = next_insn {
                 next_insn = func.layout.next_insn_of(insn);
@@ -508,13 +485,15 @@ impl LatticeCell {
         }
     }
 
+    /// Applies `f` to the constants of the cells. The result is `Top` if `f` fails, e.g., the insn
+    /// traps on overflow.
     fn apply_binop<F>(self, rhs: Self, f: F) -> Self
     where
-        F: FnOnce(Immediate, Immediate) -> Immediate,
+        F: FnOnce(Immediate, Immediate) -> Option<Immediate>,
     {
         match (self, rhs) {
             (Self::Top, _) | (_, Self::Top) => Self::Top,
-            (Self::Const(lhs), Self::Const(rhs)) => Self::Const(f(lhs, rhs)),
+            (Self::Const(lhs), Self::Const(rhs)) => f(lhs, rhs).map_or(Self::Top, Self::Const),
             (Self::Bot, _) | (_, Self::Bot) => Self::Bot,
         }
     }
@@ -527,88 +506,16 @@ impl LatticeCell {
         self.apply_unop(ops::Neg::neg)
     }
 
-    fn add(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, ops::Add::add)
-    }
-
-    fn sub(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, ops::Sub::sub)
-    }
-
-    fn mul(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, ops::Mul::mul)
-    }
-
-    fn udiv(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, Immediate::udiv)
-    }
-
-    fn sdiv(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, Immediate::sdiv)
-    }
-
-    fn lt(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, Immediate::lt)
-    }
-
-    fn gt(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, Immediate::gt)
-    }
-
-    fn slt(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, Immediate::slt)
-    }
-
-    fn sgt(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, Immediate::sgt)
+    fn binary_op(self, code: BinaryOp, rhs: Self) -> Self {
+        self.apply_binop(rhs, |lhs, rhs| lhs.binary_op(code, rhs))
     }
 
-    fn le(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, Immediate::le)
-    }
-
-    fn ge(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, Immediate::ge)
-    }
-
-    fn sle(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, Immediate::sle)
-    }
-
-    fn sge(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, Immediate::sge)
-    }
-
-    fn eq(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, Immediate::imm_eq)
-    }
-
-    fn ne(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, Immediate::imm_ne)
-    }
-
-    fn and(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, ops::BitAnd::bitand)
-    }
-
-    fn or(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, ops::BitOr::bitor)
-    }
-
-    fn xor(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, ops::BitXor::bitxor)
-    }
-
-    fn sext(self, ty: Type) -> Self {
-        self.apply_unop(|val| Immediate::sext(val, ty))
-    }
-
-    fn zext(self, ty: Type) -> Self {
-        self.apply_unop(|val| Immediate::zext(val, ty))
-    }
-
-    fn trunc(self, ty: Type) -> Self {
-        self.apply_unop(|val| Immediate::trunc(val, ty))
+    /// The result is `Top` if the widths are invalid for the cast.
+    fn cast(self, code: CastOp, ty: Type) -> Self {
+        match self {
+            Self::Const(arg) => arg.cast(code, ty).map_or(Self::Top, Self::Const),
+            _ => self,
+        }
     }
 }
 
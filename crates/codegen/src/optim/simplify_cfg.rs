@@ -0,0 +1,231 @@
+//! This module contains a pass that cleans up the CFG. The pass
+//! 1. Turns a `br` whose destinations are the same block into a `jump`.
+//! 2. Removes a block containing only a `jump` by redirecting its predecessors to the
+//!    destination of the jump.
+//! 3. Merges a block into its predecessor if the block is the single successor of the
+//!    predecessor and the predecessor is the single predecessor of the block.
+//!
+//! The CFG is kept up to date while simplifying.
+use std::collections::BTreeSet;
+
+use sonatina_ir::{insn::InsnData, Block, ControlFlowGraph, Function, Insn};
+
+/// Simplify the CFG of `func` until no more simplification is possible.
+pub fn run(func: &mut Function, cfg: &mut ControlFlowGraph) {
+    while run_once(func, cfg) {}
+}
+
+fn run_once(func: &mut Function, cfg: &mut ControlFlowGraph) -> bool {
+    let mut changed = false;
+
+    let blocks: Vec<_> = func.layout.iter_block().collect();
+    for block in blocks {
+        // The block may be removed while simplifying the previous blocks.
+        if !func.layout.is_block_inserted(block) {
+            continue;
+        }
+
+        changed |= fold_branch(func, block);
+        changed |= remove_empty_block(func, cfg, block) || merge_into_pred(func, cfg, block);
+    }
+
+    changed
+}
+
+/// Turns the `br` of `block` into a `jump` if both destinations are the same.
+fn fold_branch(func: &mut Function, block: Block) -> bool {
+    let Some(last_insn) = func.layout.last_insn_of(block) else {
+        return false;
+    };
+    let InsnData::Branch { dests, .. } = func.dfg.insn_data(last_insn) else {
+        return false;
+    };
+    if dests[0] != dests[1] {
+        return false;
+    }
+
+    // The CFG doesn't distinguish the two edges, so it's still valid.
+    let dest = dests[0];
+    func.dfg.replace_insn(last_insn, InsnData::jump(dest));
+    true
+}
+
+/// Removes `block` if it contains only a `jump`, and redirects its predecessors to the
+/// destination.
+///
+/// The phis in the destination receive the args flowing from `block` from each predecessor
+/// instead. So if a predecessor already jumps to the destination, the phi args may conflict,
+/// and the block is kept.
+fn remove_empty_block(func: &mut Function, cfg: &mut ControlFlowGraph, block: Block) -> bool {
+    if Some(block) == func.layout.entry_block() {
+        return false;
+    }
+    let Some(jump) = func.layout.first_insn_of(block) else {
+        return false;
+    };
+    let InsnData::Jump { dests: [dest] } = *func.dfg.insn_data(jump) else {
+        return false;
+    };
+    if dest == block {
+        return false;
+    }
+
+    let preds: Vec<_> = cfg.preds_of(block).copied().collect();
+    let phis = phis_of(func, dest);
+    if !phis.is_empty()
+        && preds
+            .iter()
+            .any(|pred| cfg.preds_of(dest).any(|p| p == pred))
+    {
+        return false;
+    }
+
+    for phi in phis {
+        let arg = func.dfg.remove_phi_arg(phi, block);
+        for &pred in &preds {
+            func.dfg.append_phi_arg(phi, arg, pred);
+        }
+        // The same value may still flow from another block.
+        func.dfg.attach_user(phi);
+    }
+
+    for &pred in &preds {
+        let last_insn = func.layout.last_insn_of(pred).unwrap();
+        func.dfg.rewrite_branch_dest(last_insn, block, dest);
+    }
+
+    func.layout.remove_insn(jump);
+    func.layout.remove_block(block);
+    cfg.recompute_block(func, block);
+    for pred in preds {
+        cfg.recompute_block(func, pred);
+    }
+
+    true
+}
+
+/// Merges `block` into its predecessor if `block` is the single successor of the predecessor and
+/// the predecessor is the single predecessor of `block`.
+fn merge_into_pred(func: &mut Function, cfg: &mut ControlFlowGraph, block: Block) -> bool {
+    if Some(block) == func.layout.entry_block() || cfg.pred_num_of(block) != 1 {
+        return false;
+    }
+    let pred = *cfg.preds_of(block).next().unwrap();
+    if pred == block {
+        return false;
+    }
+    let pred_last = func.layout.last_insn_of(pred).unwrap();
+    if !matches!(func.dfg.insn_data(pred_last), InsnData::Jump { .. }) {
+        return false;
+    }
+
+    // Each phi has the only arg flowing from `pred`.
+    for phi in phis_of(func, block) {
+        let arg = func.dfg.insn_arg(phi, 0);
+        func.dfg.remove_user(arg, phi);
+        let result = func.dfg.insn_result(phi).unwrap();
+        func.dfg.change_to_alias(result, arg);
+        func.layout.remove_insn(phi);
+    }
+
+    func.layout.remove_insn(pred_last);
+    let insns: Vec<_> = func.layout.iter_insn(block).collect();
+    for insn in insns {
+        func.layout.remove_insn(insn);
+        func.layout.append_insn(insn, pred);
+    }
+
+    // Values now flow into the successors from `pred`.
+    let succs: BTreeSet<_> = cfg.succs_of(block).copied().collect();
+    for succ in succs {
+        for phi in phis_of(func, succ) {
+            for from in func.dfg.phi_blocks_mut(phi) {
+                if *from == block {
+                    *from = pred;
+                }
+            }
+        }
+    }
+
+    func.layout.remove_block(block);
+    cfg.recompute_block(func, block);
+    cfg.recompute_block(func, pred);
+
+    true
+}
+
+fn phis_of(func: &Function, block: Block) -> Vec<Insn> {
+    func.layout
+        .iter_insn(block)
+        .take_while(|&insn| func.dfg.is_phi(insn))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sonatina_ir::{builder::test_util::*, Type};
+
+    #[test]
+    fn simplify_diamond() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+        let b3 = builder.append_block();
+        let b4 = builder.append_block();
+
+        let arg = builder.args()[0];
+
+        builder.switch_to_block(b0);
+        let ten = builder.make_imm_value(10i32);
+        let cond = builder.slt(arg, ten);
+        builder.br(cond, b1, b1);
+
+        builder.switch_to_block(b1);
+        builder.br(cond, b2, b3);
+
+        builder.switch_to_block(b2);
+        builder.jump(b3);
+
+        builder.switch_to_block(b3);
+        let one = builder.make_imm_value(1i32);
+        let v = builder.phi(Type::I32, &[(one, b2), (ten, b1)]);
+        builder.jump(b4);
+
+        builder.switch_to_block(b4);
+        let sum = builder.add(v, arg);
+        builder.ret(&[sum]);
+
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+        let mut cfg = ControlFlowGraph::default();
+        cfg.compute(func);
+        run(func, &mut cfg);
+        cfg.debug_check_consistent(func);
+
+        // `block2` is kept since `block1` also jumps to `block3` having a phi.
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        v2.i1 = slt v0 10.i32;
+        br v2 block2 block3;
+
+    block2:
+        jump block3;
+
+    block3:
+        v4.i32 = phi (1.i32 block2) (10.i32 block0);
+        v5.i32 = add v4 v0;
+        return v5;
+
+}
+"
+        );
+    }
+}
@@ -5,7 +5,7 @@ use smallvec::SmallVec;
 use cranelift_entity::{entity_impl, PrimaryMap, SecondaryMap};
 
 use sonatina_ir::{
-    insn::{BinaryOp, CastOp, DataLocationKind, UnaryOp},
+    insn::{BinaryOp, CastOp, DataLocationKind, HaltOp, MemoryOrdering, UnaryOp},
     module::FuncRef,
     Block, DataFlowGraph, Immediate, Insn, InsnData, Type, Value,
 };
@@ -88,6 +88,7 @@ type BlockArray2 = [Block; 2];
 
 type BlockList = SmallVec<[Block; 8]>;
 type ArgList = SmallVec<[ExprValue; 8]>;
+type TypeList = SmallVec<[Type; 2]>;
 type BrTableDefaultDest = Option<Block>;
 
 /// An opaque reference to [`ExprData`]
@@ -120,17 +121,19 @@ pub enum ExprData {
     Load {
         args: ArgArray1,
         loc: DataLocationKind,
+        ordering: MemoryOrdering,
     },
 
     /// Store a value to memory or storage.
     Store {
         args: ArgArray2,
         loc: DataLocationKind,
+        ordering: MemoryOrdering,
     },
     Call {
         func: FuncRef,
         args: ArgList,
-        ret_ty: Type,
+        ret_tys: TypeList,
     },
 
     /// Unconditional jump operations.
@@ -157,7 +160,13 @@ pub enum ExprData {
 
     /// Return.
     Return {
-        args: Option<Value>,
+        args: ArgList,
+    },
+
+    /// Halt.
+    Halt {
+        code: HaltOp,
+        args: ArgList,
     },
 
     Gep {
@@ -191,20 +200,34 @@ impl ExprData {
                 ty: *ty,
             },
 
-            InsnData::Load { args, loc } => Self::Load {
+            InsnData::Load {
+                args,
+                loc,
+                ordering,
+            } => Self::Load {
                 args: [args[0].into()],
                 loc: *loc,
+                ordering: *ordering,
             },
 
-            InsnData::Store { args, loc } => Self::Store {
+            InsnData::Store {
+                args,
+                loc,
+                ordering,
+            } => Self::Store {
                 args: [args[0].into(), args[1].into()],
                 loc: *loc,
+                ordering: *ordering,
             },
 
-            InsnData::Call { func, args, ret_ty } => Self::Call {
+            InsnData::Call {
+                func,
+                args,
+                ret_tys,
+            } => Self::Call {
                 func: *func,
                 args: args.iter().copied().map(Into::into).collect(),
-                ret_ty: *ret_ty,
+                ret_tys: ret_tys.clone(),
             },
 
             InsnData::Jump { dests } => Self::Jump { dests: *dests },
@@ -230,7 +253,14 @@ impl ExprData {
                 args: args.iter().copied().map(Into::into).collect(),
             },
 
-            InsnData::Return { args } => Self::Return { args: *args },
+            InsnData::Return { args } => Self::Return {
+                args: args.iter().copied().map(Into::into).collect(),
+            },
+
+            InsnData::Halt { code, args } => Self::Halt {
+                code: *code,
+                args: args.iter().copied().map(Into::into).collect(),
+            },
 
             InsnData::Phi { values, blocks, ty } => Self::Phi {
                 values: values.iter().copied().map(Into::into).collect(),
@@ -258,23 +288,37 @@ impl ExprData {
                 ty: *ty,
             },
 
-            Self::Load { args, loc } => InsnData::Load {
+            Self::Load {
+                args,
+                loc,
+                ordering,
+            } => InsnData::Load {
                 args: [args[0].as_value()?],
                 loc: *loc,
+                ordering: *ordering,
             },
 
-            Self::Store { args, loc } => InsnData::Store {
+            Self::Store {
+                args,
+                loc,
+                ordering,
+            } => InsnData::Store {
                 args: [args[0].as_value()?, args[1].as_value()?],
                 loc: *loc,
+                ordering: *ordering,
             },
 
-            Self::Call { func, args, ret_ty } => InsnData::Call {
+            Self::Call {
+                func,
+                args,
+                ret_tys,
+            } => InsnData::Call {
                 func: *func,
                 args: args
                     .iter()
                     .map(|val| val.as_value())
                     .collect::<Option<_>>()?,
-                ret_ty: *ret_ty,
+                ret_tys: ret_tys.clone(),
             },
 
             Self::Jump { dests } => InsnData::Jump { dests: *dests },
@@ -306,7 +350,20 @@ impl ExprData {
                     .collect::<Option<_>>()?,
             },
 
-            Self::Return { args } => InsnData::Return { args: *args },
+            Self::Return { args } => InsnData::Return {
+                args: args
+                    .iter()
+                    .map(|val| val.as_value())
+                    .collect::<Option<_>>()?,
+            },
+
+            Self::Halt { code, args } => InsnData::Halt {
+                code: *code,
+                args: args
+                    .iter()
+                    .map(|val| val.as_value())
+                    .collect::<Option<_>>()?,
+            },
 
             Self::Phi { values, blocks, ty } => InsnData::Phi {
                 values: values
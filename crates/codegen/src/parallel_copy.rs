@@ -0,0 +1,161 @@
+//! This module contains a sequentialization of parallel copies.
+//!
+//! A parallel copy reads all its sources before writing any destination, which is the semantics
+//! of the phi insns of a block along an incoming edge. To emit it as a sequence of ordinary
+//! copies, a copy must not overwrite a location that is still read by a later copy. Cycles such
+//! as a swap are broken by saving one of the locations into a temporary.
+//!
+//! The algorithm is based on Benoit Boissinot, Alain Darte, Fabrice Rastello, Benoit Dupont de
+//! Dinechin, Christophe Guillon.: Revisiting Out-of-SSA Translation for Correctness, Code
+//! Quality, and Efficiency: <https://hal.inria.fr/inria-00349925v1/document>
+use std::hash::Hash;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// Sequentializes the parallel copy `copies`.
+///
+/// Each copy is a `(dst, src)` pair, and all `dst`s must be distinct. `temp` must be a location
+/// that appears in none of the copies; it is used to break cycles.
+///
+/// Returns the sequence of `(dst, src)` copies that has the same effect as the parallel copy.
+pub fn sequentialize<L>(copies: &[(L, L)], temp: L) -> Vec<(L, L)>
+where
+    L: Copy + Eq + Hash,
+{
+    // `loc` maps a source to the location where its original value currently resides.
+    let mut loc: FxHashMap<L, L> = FxHashMap::default();
+    // `pred` maps a destination to its source.
+    let mut pred: FxHashMap<L, L> = FxHashMap::default();
+    let mut todo = Vec::new();
+
+    for &(dst, src) in copies {
+        debug_assert!(dst != temp && src != temp);
+        if dst == src {
+            continue;
+        }
+        debug_assert!(!pred.contains_key(&dst), "destinations must be distinct");
+        loc.insert(src, src);
+        pred.insert(dst, src);
+        todo.push(dst);
+    }
+
+    let mut seq = Sequentializer {
+        loc,
+        pred,
+        ready: Vec::new(),
+        written: FxHashSet::default(),
+        seq: Vec::with_capacity(todo.len()),
+    };
+
+    // A destination that is not read by any copy can be written immediately.
+    for &dst in &todo {
+        if !seq.loc.contains_key(&dst) {
+            seq.ready.push(dst);
+        }
+    }
+
+    while let Some(dst) = todo.pop() {
+        seq.emit_ready();
+
+        // If `dst` is still not written, it's in a cycle; break it by saving `dst` to `temp`.
+        if !seq.written.contains(&dst) {
+            seq.seq.push((temp, dst));
+            seq.loc.insert(dst, temp);
+            seq.ready.push(dst);
+        }
+    }
+    seq.emit_ready();
+
+    seq.seq
+}
+
+struct Sequentializer<L> {
+    loc: FxHashMap<L, L>,
+    pred: FxHashMap<L, L>,
+    /// Destinations that can be overwritten.
+    ready: Vec<L>,
+    written: FxHashSet<L>,
+    seq: Vec<(L, L)>,
+}
+
+impl<L> Sequentializer<L>
+where
+    L: Copy + Eq + Hash,
+{
+    fn emit_ready(&mut self) {
+        while let Some(dst) = self.ready.pop() {
+            let src = self.pred[&dst];
+            let cur = self.loc[&src];
+            self.seq.push((dst, cur));
+            self.written.insert(dst);
+            self.loc.insert(src, dst);
+
+            // The original value of `src` has been saved, so `src` can be overwritten now.
+            if src == cur && self.pred.contains_key(&src) {
+                self.ready.push(src);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMP: u32 = u32::MAX;
+
+    /// Runs `seq` on the registers initialized to their own numbers, and returns the result.
+    fn exec(seq: &[(u32, u32)]) -> FxHashMap<u32, u32> {
+        let mut regs = FxHashMap::default();
+        for &(dst, src) in seq {
+            let value = regs.get(&src).copied().unwrap_or(src);
+            regs.insert(dst, value);
+        }
+        regs.remove(&TEMP);
+        regs
+    }
+
+    fn check(copies: &[(u32, u32)]) -> Vec<(u32, u32)> {
+        let seq = sequentialize(copies, TEMP);
+        let regs = exec(&seq);
+        for &(dst, src) in copies {
+            assert_eq!(regs.get(&dst).copied().unwrap_or(dst), src);
+        }
+        seq
+    }
+
+    #[test]
+    fn chain() {
+        let seq = check(&[(1, 0), (2, 1), (3, 2)]);
+        assert_eq!(seq, vec![(3, 2), (2, 1), (1, 0)]);
+    }
+
+    #[test]
+    fn swap() {
+        let seq = check(&[(0, 1), (1, 0)]);
+        assert_eq!(seq.len(), 3);
+        assert!(seq.iter().any(|&(dst, _)| dst == TEMP));
+    }
+
+    #[test]
+    fn rotation() {
+        let seq = check(&[(0, 1), (1, 2), (2, 0)]);
+        assert_eq!(seq.len(), 4);
+    }
+
+    #[test]
+    fn cycle_with_tail() {
+        check(&[(0, 1), (1, 0), (2, 0), (3, 1)]);
+    }
+
+    #[test]
+    fn fan_out() {
+        let seq = check(&[(1, 0), (2, 0), (0, 3)]);
+        assert!(seq.iter().all(|&(dst, _)| dst != TEMP));
+    }
+
+    #[test]
+    fn self_copy() {
+        assert!(check(&[(0, 0)]).is_empty());
+    }
+}
@@ -0,0 +1,470 @@
+//! This module contains a pass manager that runs a pipeline of function passes over a module.
+//!
+//! The manager caches the analyses shared between passes, i.e., CFG, dominator tree and loop tree,
+//! and recomputes them lazily when a pass requests an analysis that a previous pass invalidated.
+use std::ops::BitOr;
+
+use sonatina_ir::{module::ModuleCtx, Block, ControlFlowGraph, Function, Insn, InsnData, Module};
+
+use crate::{
+    domtree::DomTree,
+    loop_analysis::LoopTree,
+    optim::{
+        adce::AdceSolver, block_merge::BlockMerger, gvn::GvnSolver,
+        insn_simplify::InsnSimplifySolver, licm::LicmSolver, localize_constants::ConstantLocalizer,
+        mem2reg::Mem2RegSolver, peephole::Peephole, sccp::SccpSolver,
+    },
+};
+
+/// A pass that transforms a single function.
+pub trait FunctionPass {
+    fn name(&self) -> &str;
+
+    fn run(&mut self, func: &mut Function, ctx: &ModuleCtx, analyses: &mut AnalysisCache);
+
+    /// Returns the analyses that are no longer valid after the pass is run.
+    /// A pass that keeps an analysis up to date must not include it in the result.
+    fn invalidated_analyses(&self) -> Analyses {
+        Analyses::ALL
+    }
+}
+
+/// A set of analyses cached in [`AnalysisCache`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Analyses(u8);
+
+impl Analyses {
+    pub const NONE: Self = Self(0);
+    pub const CFG: Self = Self(1 << 0);
+    pub const DOMTREE: Self = Self(1 << 1);
+    pub const LOOP_TREE: Self = Self(1 << 2);
+    pub const ALL: Self = Self(Self::CFG.0 | Self::DOMTREE.0 | Self::LOOP_TREE.0);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for Analyses {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Holds the analyses of a function, and computes them on demand.
+#[derive(Debug, Default)]
+pub struct AnalysisCache {
+    cfg: ControlFlowGraph,
+    domtree: DomTree,
+    lpt: LoopTree,
+    valid: Analyses,
+}
+
+impl AnalysisCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cfg(&mut self, func: &Function) -> &mut ControlFlowGraph {
+        self.ensure(func, Analyses::CFG);
+        &mut self.cfg
+    }
+
+    pub fn cfg_and_domtree(&mut self, func: &Function) -> (&mut ControlFlowGraph, &mut DomTree) {
+        self.ensure(func, Analyses::CFG | Analyses::DOMTREE);
+        (&mut self.cfg, &mut self.domtree)
+    }
+
+    pub fn cfg_and_loop_tree(&mut self, func: &Function) -> (&mut ControlFlowGraph, &mut LoopTree) {
+        self.ensure(func, Analyses::ALL);
+        (&mut self.cfg, &mut self.lpt)
+    }
+
+    /// Returns the analyses that are up to date.
+    pub fn valid(&self) -> Analyses {
+        self.valid
+    }
+
+    /// Marks `analyses` as invalid.
+    /// The dominator tree and the loop tree depend on the CFG, and the loop tree depends on the
+    /// dominator tree, so invalidating an analysis also invalidates the analyses derived from it.
+    pub fn invalidate(&mut self, analyses: Analyses) {
+        let mut analyses = analyses;
+        if analyses.contains(Analyses::CFG) {
+            analyses = analyses | Analyses::DOMTREE;
+        }
+        if analyses.contains(Analyses::DOMTREE) {
+            analyses = analyses | Analyses::LOOP_TREE;
+        }
+        self.valid = Analyses(self.valid.0 & !analyses.0);
+    }
+
+    pub fn clear(&mut self) {
+        self.cfg.clear();
+        self.domtree.clear();
+        self.lpt.clear();
+        self.valid = Analyses::NONE;
+    }
+
+    fn ensure(&mut self, func: &Function, analyses: Analyses) {
+        if !self.valid.contains(Analyses::CFG) {
+            self.cfg.compute(func);
+            self.valid = self.valid | Analyses::CFG;
+        }
+
+        let needs_domtree =
+            analyses.contains(Analyses::DOMTREE) || analyses.contains(Analyses::LOOP_TREE);
+        if needs_domtree && !self.valid.contains(Analyses::DOMTREE) {
+            self.domtree.compute(&self.cfg);
+            self.valid = self.valid | Analyses::DOMTREE;
+        }
+
+        if analyses.contains(Analyses::LOOP_TREE) && !self.valid.contains(Analyses::LOOP_TREE) {
+            self.lpt.compute(&self.cfg, &self.domtree);
+            self.valid = self.valid | Analyses::LOOP_TREE;
+        }
+    }
+}
+
+/// Runs function passes in the order they are added.
+///
+/// The whole pipeline is repeated on each function until it no longer changes the function, or
+/// the number of iterations reaches the limit set by [`PassManager::set_max_iterations`].
+pub struct PassManager {
+    passes: Vec<Box<dyn FunctionPass>>,
+    max_iterations: usize,
+    analyses: AnalysisCache,
+}
+
+impl PassManager {
+    pub const DEFAULT_MAX_ITERATIONS: usize = 8;
+
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            max_iterations: Self::DEFAULT_MAX_ITERATIONS,
+            analyses: AnalysisCache::new(),
+        }
+    }
+
+    pub fn add_pass(&mut self, pass: Box<dyn FunctionPass>) -> &mut Self {
+        self.passes.push(pass);
+        self
+    }
+
+    /// Sets the maximum number of times the pipeline is run on a function.
+    /// `1` means each pass is run exactly once.
+    pub fn set_max_iterations(&mut self, max_iterations: usize) -> &mut Self {
+        debug_assert!(max_iterations > 0);
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Returns the names of the passes in the order they are run.
+    pub fn pass_names(&self) -> impl Iterator<Item = &str> {
+        self.passes.iter().map(|pass| pass.name())
+    }
+
+    /// Runs the pipeline on all functions in the module.
+    /// Functions are processed sequentially in the order of `FuncRef`, because the analysis cache
+    /// and the passes are shared between them.
+    pub fn run(&mut self, module: &mut Module) {
+        let Module { funcs, ctx } = module;
+        for func in funcs.values_mut() {
+            self.run_on_function(func, ctx);
+        }
+    }
+
+    /// Runs the pipeline on `func`, and returns the number of iterations it took.
+    pub fn run_on_function(&mut self, func: &mut Function, ctx: &ModuleCtx) -> usize {
+        self.analyses.clear();
+
+        let mut snapshot = LayoutSnapshot::take(func);
+        for iteration in 1..=self.max_iterations {
+            for pass in &mut self.passes {
+                pass.run(func, ctx, &mut self.analyses);
+                self.analyses.invalidate(pass.invalidated_analyses());
+            }
+
+            let new_snapshot = LayoutSnapshot::take(func);
+            if new_snapshot == snapshot {
+                return iteration;
+            }
+            snapshot = new_snapshot;
+        }
+
+        self.max_iterations
+    }
+}
+
+impl Default for PassManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A copy of the blocks and insns in the function layout, which is compared with the one taken
+/// after a pipeline iteration to detect whether the iteration changed the function.
+#[derive(PartialEq, Eq)]
+struct LayoutSnapshot(Vec<(Block, Vec<(Insn, InsnData)>)>);
+
+impl LayoutSnapshot {
+    fn take(func: &Function) -> Self {
+        let blocks = func
+            .layout
+            .iter_block()
+            .map(|block| {
+                let insns = func
+                    .layout
+                    .iter_insn(block)
+                    .map(|insn| (insn, func.dfg.insn_data(insn).clone()))
+                    .collect();
+                (block, insns)
+            })
+            .collect();
+        Self(blocks)
+    }
+}
+
+impl FunctionPass for AdceSolver {
+    fn name(&self) -> &str {
+        "adce"
+    }
+
+    fn run(&mut self, func: &mut Function, _ctx: &ModuleCtx, _analyses: &mut AnalysisCache) {
+        AdceSolver::run(self, func);
+    }
+}
+
+impl FunctionPass for SccpSolver {
+    fn name(&self) -> &str {
+        "sccp"
+    }
+
+    fn run(&mut self, func: &mut Function, _ctx: &ModuleCtx, analyses: &mut AnalysisCache) {
+        SccpSolver::run(self, func, analyses.cfg(func));
+    }
+
+    fn invalidated_analyses(&self) -> Analyses {
+        Analyses::DOMTREE
+    }
+}
+
+impl FunctionPass for GvnSolver {
+    fn name(&self) -> &str {
+        "gvn"
+    }
+
+    fn run(&mut self, func: &mut Function, _ctx: &ModuleCtx, analyses: &mut AnalysisCache) {
+        let (cfg, domtree) = analyses.cfg_and_domtree(func);
+        GvnSolver::run(self, func, cfg, domtree);
+    }
+
+    fn invalidated_analyses(&self) -> Analyses {
+        Analyses::LOOP_TREE
+    }
+}
+
+impl FunctionPass for Mem2RegSolver {
+    fn name(&self) -> &str {
+        "mem2reg"
+    }
+
+    fn run(&mut self, func: &mut Function, _ctx: &ModuleCtx, analyses: &mut AnalysisCache) {
+        let (cfg, domtree) = analyses.cfg_and_domtree(func);
+        Mem2RegSolver::run(self, func, cfg, domtree);
+    }
+
+    fn invalidated_analyses(&self) -> Analyses {
+        Analyses::NONE
+    }
+}
+
+impl FunctionPass for LicmSolver {
+    fn name(&self) -> &str {
+        "licm"
+    }
+
+    fn run(&mut self, func: &mut Function, _ctx: &ModuleCtx, analyses: &mut AnalysisCache) {
+        let (cfg, lpt) = analyses.cfg_and_loop_tree(func);
+        LicmSolver::run(self, func, cfg, lpt);
+    }
+
+    fn invalidated_analyses(&self) -> Analyses {
+        // Inserting a preheader changes the dominator tree.
+        Analyses::DOMTREE
+    }
+}
+
+impl FunctionPass for BlockMerger {
+    fn name(&self) -> &str {
+        "block_merge"
+    }
+
+    fn run(&mut self, func: &mut Function, _ctx: &ModuleCtx, analyses: &mut AnalysisCache) {
+        BlockMerger::run(self, func, analyses.cfg(func));
+    }
+
+    fn invalidated_analyses(&self) -> Analyses {
+        Analyses::DOMTREE
+    }
+}
+
+impl FunctionPass for InsnSimplifySolver {
+    fn name(&self) -> &str {
+        "insn_simplify"
+    }
+
+    fn run(&mut self, func: &mut Function, _ctx: &ModuleCtx, _analyses: &mut AnalysisCache) {
+        InsnSimplifySolver::run(self, func);
+    }
+}
+
+impl FunctionPass for Peephole {
+    fn name(&self) -> &str {
+        "peephole"
+    }
+
+    fn run(&mut self, func: &mut Function, _ctx: &ModuleCtx, _analyses: &mut AnalysisCache) {
+        Peephole::run(self, func);
+    }
+}
+
+impl FunctionPass for ConstantLocalizer {
+    fn name(&self) -> &str {
+        "localize_constants"
+    }
+
+    fn run(&mut self, func: &mut Function, _ctx: &ModuleCtx, _analyses: &mut AnalysisCache) {
+        ConstantLocalizer::run(self, func);
+    }
+
+    fn invalidated_analyses(&self) -> Analyses {
+        Analyses::NONE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+
+    use sonatina_ir::{
+        builder::test_util::*,
+        func_cursor::{CursorLocation, FuncCursor, InsnInserter},
+        Type,
+    };
+
+    /// Removes at most one unused insn per run.
+    struct RemoveOneDeadInsn {
+        runs: Rc<Cell<usize>>,
+    }
+
+    impl FunctionPass for RemoveOneDeadInsn {
+        fn name(&self) -> &str {
+            "remove_one_dead_insn"
+        }
+
+        fn run(&mut self, func: &mut Function, _ctx: &ModuleCtx, analyses: &mut AnalysisCache) {
+            self.runs.set(self.runs.get() + 1);
+            // The CFG must be recomputed in each iteration because it's invalidated below.
+            analyses.cfg(func);
+
+            let dead = func.layout.iter_block().find_map(|block| {
+                func.layout.iter_insn(block).find(|insn| {
+                    !func.dfg.has_side_effect(*insn)
+                        && func
+                            .dfg
+                            .insn_result(*insn)
+                            .is_some_and(|result| func.dfg.users_num(result) == 0)
+                })
+            });
+            if let Some(insn) = dead {
+                InsnInserter::at_location(CursorLocation::At(insn)).remove_insn(func);
+            }
+        }
+    }
+
+    #[test]
+    fn iterate_to_fixpoint() {
+        let mut builder = test_func_builder(&[Type::I32], Type::Void);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg = builder.args()[0];
+        let v1 = builder.add(arg, arg);
+        let v2 = builder.mul(v1, arg);
+        builder.sub(v2, arg);
+        builder.ret(&[]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+
+        let runs = Rc::new(Cell::new(0));
+        let mut pm = PassManager::new();
+        pm.add_pass(Box::new(RemoveOneDeadInsn { runs: runs.clone() }));
+        assert_eq!(
+            pm.pass_names().collect::<Vec<_>>(),
+            ["remove_one_dead_insn"]
+        );
+
+        let Module { funcs, ctx } = &mut module;
+        // Three iterations remove the insns, and the last one confirms nothing changes.
+        assert_eq!(pm.run_on_function(&mut funcs[func_ref], ctx), 4);
+        assert_eq!(runs.get(), 4);
+        assert!(!pm.analyses.valid().contains(Analyses::CFG));
+
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i32) -> void {
+    block0:
+        return;
+
+}
+"
+        );
+    }
+
+    #[test]
+    fn max_iterations() {
+        let mut builder = test_func_builder(&[Type::I32], Type::Void);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg = builder.args()[0];
+        let v1 = builder.add(arg, arg);
+        builder.mul(v1, arg);
+        builder.ret(&[]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+
+        let runs = Rc::new(Cell::new(0));
+        let mut pm = PassManager::new();
+        pm.add_pass(Box::new(RemoveOneDeadInsn { runs: runs.clone() }))
+            .set_max_iterations(1);
+        pm.run(&mut module);
+        assert_eq!(runs.get(), 1);
+    }
+
+    #[test]
+    fn invalidate_dependent_analyses() {
+        let mut cache = AnalysisCache::new();
+        cache.valid = Analyses::ALL;
+
+        cache.invalidate(Analyses::DOMTREE);
+        assert_eq!(cache.valid(), Analyses::CFG);
+
+        cache.valid = Analyses::ALL;
+        cache.invalidate(Analyses::CFG);
+        assert!(cache.valid().is_empty());
+    }
+}
@@ -193,7 +193,7 @@ mod tests {
         builder.switch_to_block(merge_block);
         let v3 = builder.phi(Type::I64, &[(v1, then_block), (v2, else_block)]);
         builder.add(v3, arg0);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -247,16 +247,16 @@ mod tests {
         builder.br(v0, b, c);
 
         builder.switch_to_block(b);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.switch_to_block(c);
         builder.br(v0, d, e);
 
         builder.switch_to_block(d);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.switch_to_block(e);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -314,7 +314,7 @@ mod tests {
         builder.br(v0, a, h);
 
         builder.switch_to_block(h);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -0,0 +1,250 @@
+//! This module contains a translation out of SSA form.
+//!
+//! Each phi insn of a block is replaced with copies to the phi result, which are inserted at the
+//! bottom of each predecessor. After the translation, the result of a phi insn is defined by
+//! several copies, i.e., it's no longer an SSA value but a variable.
+//!
+//! The copies of an incoming edge form a parallel copy; they are sequenced by
+//! [`crate::parallel_copy::sequentialize`] so that a phi result read by another phi of the same
+//! block isn't overwritten before it's read (the swap problem). The lost-copy problem is avoided
+//! by requiring the critical edges of the function to be split in advance, see
+//! [`crate::critical_edge::split_critical_edges`].
+use rustc_hash::FxHashMap;
+use sonatina_ir::{
+    insn::UnaryOp, Block, ControlFlowGraph, Function, Insn, InsnData, Type, Value, ValueData,
+};
+
+use crate::parallel_copy::sequentialize;
+
+/// Replaces all phi insns of `func` with copies inserted at the bottom of the predecessors.
+///
+/// The critical edges of `func` must be split, and `cfg` must be up to date.
+pub fn destruct_ssa(func: &mut Function, cfg: &ControlFlowGraph) {
+    let blocks: Vec<_> = func.layout.iter_block().collect();
+    for block in blocks {
+        let phis: Vec<_> = func
+            .layout
+            .iter_insn(block)
+            .take_while(|insn| func.dfg.is_phi(*insn))
+            .collect();
+        if phis.is_empty() {
+            continue;
+        }
+
+        let mut preds: Vec<_> = cfg.preds_of(block).copied().collect();
+        preds.dedup();
+        for pred in preds {
+            debug_assert!(
+                cfg.out_degree(pred) == 1 || cfg.in_degree(block) == 1,
+                "critical edge from `{pred}` to `{block}` must be split"
+            );
+            insert_copies(func, &phis, pred);
+        }
+
+        for phi in phis {
+            remove_phi(func, phi);
+        }
+    }
+}
+
+/// A location of a parallel copy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Loc {
+    Value(Value),
+    /// A temporary to break a cycle of copies. A fresh value is made each time it's written.
+    Temp,
+}
+
+/// Inserts the copies of the phi args flowing from `pred` before the terminator of `pred`.
+fn insert_copies(func: &mut Function, phis: &[Insn], pred: Block) {
+    // Copies can form a cycle only among locations of the same type, so each type is sequenced
+    // separately with its own temporary.
+    let mut copies: FxHashMap<Type, Vec<(Loc, Loc)>> = FxHashMap::default();
+    let mut types = Vec::new();
+    for &phi in phis {
+        let Some(idx) = func
+            .dfg
+            .phi_blocks(phi)
+            .iter()
+            .position(|block| *block == pred)
+        else {
+            continue;
+        };
+
+        let result = func.dfg.insn_result(phi).unwrap();
+        let arg = func.dfg.insn_arg(phi, idx);
+        let ty = func.dfg.value_ty(result);
+        if !copies.contains_key(&ty) {
+            types.push(ty);
+        }
+        copies
+            .entry(ty)
+            .or_default()
+            .push((Loc::Value(result), Loc::Value(arg)));
+    }
+
+    let last_insn = func.layout.last_insn_of(pred).unwrap();
+    for ty in types {
+        let mut temp = None;
+        for (dst, src) in sequentialize(&copies[&ty], Loc::Temp) {
+            let src = match src {
+                Loc::Value(value) => value,
+                Loc::Temp => temp.unwrap(),
+            };
+            let copy = func.dfg.make_insn(InsnData::unary(UnaryOp::Copy, src));
+            func.layout.insert_insn_before(copy, last_insn);
+
+            let dst = match dst {
+                Loc::Value(value) => value,
+                Loc::Temp => {
+                    let value = func.dfg.make_value(ValueData::Insn { insn: copy, ty });
+                    temp = Some(value);
+                    value
+                }
+            };
+            func.dfg.values[dst] = ValueData::Insn { insn: copy, ty };
+            func.dfg.attach_result(copy, dst);
+        }
+    }
+}
+
+fn remove_phi(func: &mut Function, phi: Insn) {
+    for idx in 0..func.dfg.insn_args_num(phi) {
+        let arg = func.dfg.insn_arg(phi, idx);
+        func.dfg.remove_user(arg, phi);
+    }
+    func.layout.remove_insn(phi);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sonatina_interpreter::{EvalResult, State};
+    use sonatina_ir::{builder::test_util::*, module::FuncRef, Module};
+
+    use crate::critical_edge::split_critical_edges;
+
+    #[test]
+    fn diamond() {
+        let mut builder = test_func_builder(&[Type::I1], Type::I32);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+
+        builder.switch_to_block(b0);
+        let cond = builder.args()[0];
+        let one = builder.make_imm_value(1i32);
+        builder.br(cond, b2, b1);
+
+        builder.switch_to_block(b1);
+        let two = builder.make_imm_value(2i32);
+        builder.jump(b2);
+
+        builder.switch_to_block(b2);
+        let phi = builder.phi(Type::I32, &[(one, b0), (two, b1)]);
+        builder.ret(&[phi]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+        let mut cfg = ControlFlowGraph::default();
+        cfg.compute(func);
+        split_critical_edges(func, &mut cfg);
+        destruct_ssa(func, &cfg);
+
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i1) -> i32 {
+    block0:
+        br v0 block3 block1;
+
+    block1:
+        v3.i32 = copy 2.i32;
+        jump block2;
+
+    block2:
+        return v3;
+
+    block3:
+        v3.i32 = copy 1.i32;
+        jump block2;
+
+}
+"
+        );
+    }
+
+    /// Builds a loop that swaps two values `n` times with a pair of phis.
+    fn build_swap_loop(n: i32) -> (Module, FuncRef) {
+        let mut builder = test_func_builder(&[], Type::I32);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+        let b3 = builder.append_block();
+
+        builder.switch_to_block(b0);
+        builder.jump(b1);
+
+        builder.switch_to_block(b1);
+        let one = builder.make_imm_value(1i32);
+        let two = builder.make_imm_value(2i32);
+        let zero = builder.make_imm_value(0i32);
+        let x = builder.phi(Type::I32, &[(one, b0)]);
+        let y = builder.phi(Type::I32, &[(two, b0)]);
+        let i = builder.phi(Type::I32, &[(zero, b0)]);
+        let n = builder.make_imm_value(n);
+        let cond = builder.slt(i, n);
+        builder.br(cond, b2, b3);
+
+        builder.switch_to_block(b2);
+        let next_i = builder.add(i, one);
+        builder.append_phi_arg(x, y, b2);
+        builder.append_phi_arg(y, x, b2);
+        builder.append_phi_arg(i, next_i, b2);
+        builder.jump(b1);
+
+        builder.switch_to_block(b3);
+        let ten = builder.make_imm_value(10i32);
+        let mul = builder.mul(x, ten);
+        let res = builder.add(mul, y);
+        builder.ret(&[res]);
+
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        (module, func_ref)
+    }
+
+    fn interpret(module: Module, func_ref: FuncRef) -> EvalResult {
+        State::new(module, func_ref, &[]).run().unwrap()
+    }
+
+    #[test]
+    fn swap_phis() {
+        for n in 0..4 {
+            let (module, func_ref) = build_swap_loop(n);
+            let expected = interpret(module, func_ref);
+
+            let (mut module, func_ref) = build_swap_loop(n);
+            let func = &mut module.funcs[func_ref];
+            let mut cfg = ControlFlowGraph::default();
+            cfg.compute(func);
+            destruct_ssa(func, &cfg);
+
+            let func = &module.funcs[func_ref];
+            for block in func.layout.iter_block() {
+                assert!(func
+                    .layout
+                    .iter_insn(block)
+                    .all(|insn| !func.dfg.is_phi(insn)));
+            }
+            assert_eq!(interpret(module, func_ref), expected);
+        }
+
+        let (module, func_ref) = build_swap_loop(3);
+        assert_eq!(interpret(module, func_ref), EvalResult::I32(21));
+    }
+}
@@ -0,0 +1,20 @@
+use std::path::{Path, PathBuf};
+
+use sonatina_codegen::optim::cse::local_cse;
+
+use sonatina_ir::Function;
+
+use super::{FuncTransform, FIXTURE_ROOT};
+
+#[derive(Default)]
+pub struct LocalCseTransform {}
+
+impl FuncTransform for LocalCseTransform {
+    fn transform(&mut self, func: &mut Function) {
+        local_cse(func);
+    }
+
+    fn test_root(&self) -> PathBuf {
+        Path::new(FIXTURE_ROOT).join("cse")
+    }
+}
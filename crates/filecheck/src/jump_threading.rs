@@ -0,0 +1,23 @@
+use std::path::{Path, PathBuf};
+
+use sonatina_codegen::optim::jump_threading;
+
+use sonatina_ir::{ControlFlowGraph, Function};
+
+use super::{FuncTransform, FIXTURE_ROOT};
+
+#[derive(Default)]
+pub struct JumpThreadingTransform {
+    cfg: ControlFlowGraph,
+}
+
+impl FuncTransform for JumpThreadingTransform {
+    fn transform(&mut self, func: &mut Function) {
+        self.cfg.compute(func);
+        jump_threading::run(func, &mut self.cfg);
+    }
+
+    fn test_root(&self) -> PathBuf {
+        Path::new(FIXTURE_ROOT).join("jump_threading")
+    }
+}
@@ -1,8 +1,14 @@
 pub mod adce;
+pub mod cse;
 pub mod gvn;
+pub mod inliner;
 pub mod insn_simplify;
+pub mod jump_threading;
 pub mod licm;
+pub mod load_forward;
+pub mod mem2reg;
 pub mod sccp;
+pub mod ssa_destruction;
 
 use std::{
     fs,
@@ -11,7 +17,7 @@ use std::{
     time,
 };
 
-use sonatina_ir::{ir_writer::FuncWriter, module::FuncRef, Function};
+use sonatina_ir::{ir_writer::FuncWriter, module::FuncRef, Function, Module};
 
 use sonatina_parser::{parse_module, ParsedModule};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
@@ -22,6 +28,12 @@ pub(crate) const FIXTURE_ROOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixt
 pub trait FuncTransform {
     fn transform(&mut self, func: &mut Function);
 
+    /// Transforms the whole module before the functions are checked. A module pass overrides
+    /// this to see all functions at once.
+    fn transform_module(&mut self, module: &mut Module) {
+        module.for_each_function(|_, func| self.transform(func));
+    }
+
     fn test_root(&self) -> PathBuf;
 }
 
@@ -131,23 +143,20 @@ impl<'a> FileChecker<'a> {
             Err(msg) => return vec![FileCheckResult::new(self.file_path.to_owned(), Err(msg))],
         };
 
+        self.transformer.transform_module(&mut parsed_module.module);
+
         let module = &parsed_module.module;
 
         module
             .iter_functions()
-            .map(|func_ref| self.check_func(&mut parsed_module, func_ref))
+            .map(|func_ref| self.check_func(&parsed_module, func_ref))
             .collect()
     }
 
-    fn check_func(
-        &mut self,
-        parsed_module: &mut ParsedModule,
-        func_ref: FuncRef,
-    ) -> FileCheckResult {
-        let func = &mut parsed_module.module.funcs[func_ref];
+    fn check_func(&self, parsed_module: &ParsedModule, func_ref: FuncRef) -> FileCheckResult {
+        let func = &parsed_module.module.funcs[func_ref];
         let comments = &parsed_module.debug.func_comments[func_ref];
 
-        self.transformer.transform(func);
         let func_ir = FuncWriter::new(func_ref, func, Some(&parsed_module.debug))
             .dump_string()
             .unwrap();
@@ -0,0 +1,20 @@
+use std::path::{Path, PathBuf};
+
+use sonatina_codegen::optim::load_forward;
+
+use sonatina_ir::Function;
+
+use super::{FuncTransform, FIXTURE_ROOT};
+
+#[derive(Default)]
+pub struct LoadForwardTransform {}
+
+impl FuncTransform for LoadForwardTransform {
+    fn transform(&mut self, func: &mut Function) {
+        load_forward::run(func);
+    }
+
+    fn test_root(&self) -> PathBuf {
+        Path::new(FIXTURE_ROOT).join("load_forward")
+    }
+}
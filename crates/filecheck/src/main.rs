@@ -1,6 +1,8 @@
 use sonatina_filecheck::{
-    adce::AdceTransform, gvn::GvnTransform, insn_simplify::InsnSimplifyTransform,
-    licm::LicmTransformer, sccp::SccpTransform, FileCheckRunner,
+    adce::AdceTransform, cse::LocalCseTransform, gvn::GvnTransform, inliner::InlinerTransform,
+    insn_simplify::InsnSimplifyTransform, jump_threading::JumpThreadingTransform,
+    licm::LicmTransformer, load_forward::LoadForwardTransform, mem2reg::Mem2RegTransform,
+    sccp::SccpTransform, ssa_destruction::SsaDestructionTransform, FileCheckRunner,
 };
 
 fn main() {
@@ -13,12 +15,30 @@ fn main() {
     runner.attach_transformer(InsnSimplifyTransform::default());
     runner.run();
 
+    runner.attach_transformer(LocalCseTransform::default());
+    runner.run();
+
     runner.attach_transformer(GvnTransform::default());
     runner.run();
 
     runner.attach_transformer(LicmTransformer::default());
     runner.run();
 
+    runner.attach_transformer(LoadForwardTransform::default());
+    runner.run();
+
+    runner.attach_transformer(Mem2RegTransform::default());
+    runner.run();
+
+    runner.attach_transformer(JumpThreadingTransform::default());
+    runner.run();
+
+    runner.attach_transformer(SsaDestructionTransform::default());
+    runner.run();
+
+    runner.attach_transformer(InlinerTransform::default());
+    runner.run();
+
     runner.print_results();
     if !runner.is_ok() {
         std::process::exit(101);
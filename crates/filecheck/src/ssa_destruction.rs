@@ -0,0 +1,24 @@
+use std::path::{Path, PathBuf};
+
+use sonatina_codegen::{critical_edge::split_critical_edges, ssa_destruction::destruct_ssa};
+
+use sonatina_ir::{ControlFlowGraph, Function};
+
+use super::{FuncTransform, FIXTURE_ROOT};
+
+#[derive(Default)]
+pub struct SsaDestructionTransform {
+    cfg: ControlFlowGraph,
+}
+
+impl FuncTransform for SsaDestructionTransform {
+    fn transform(&mut self, func: &mut Function) {
+        self.cfg.compute(func);
+        split_critical_edges(func, &mut self.cfg);
+        destruct_ssa(func, &self.cfg);
+    }
+
+    fn test_root(&self) -> PathBuf {
+        Path::new(FIXTURE_ROOT).join("ssa_destruction")
+    }
+}
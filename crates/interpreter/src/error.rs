@@ -0,0 +1,73 @@
+use std::fmt;
+
+use sonatina_ir::{Insn, U256};
+
+/// An error that stops the interpretation.
+/// Each variant holds the insn where the interpretation stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterpretError {
+    /// The depth of the call stack exceeds the limit.
+    StackOverflow(Insn),
+
+    /// The gas is exhausted before executing the insn.
+    OutOfGas { insn: Insn, consumed: u64 },
+
+    /// The insn accesses memory out of bounds at `addr`.
+    MemoryFault { insn: Insn, addr: U256 },
+
+    /// The checked arithmetic insn overflows the bit width of its operands.
+    Overflow(Insn),
+
+    /// The division or remainder insn divides by zero.
+    DivByZero(Insn),
+
+    /// The control reaches a point that has no defined continuation, e.g., a phi has no arg for
+    /// the incoming edge.
+    Unreachable(Insn),
+
+    /// A `br_table` without a default destination matches none of its cases.
+    NoMatchingCase(Insn),
+
+    /// The operands of the insn don't match the types it expects, e.g., a call passes the wrong
+    /// number of arguments to the callee.
+    TypeMismatch(Insn),
+
+    /// The `gep` insn indexes a struct or a union out of its fields, or indexes into a
+    /// non-aggregate type.
+    IndexOutOfBounds(Insn),
+}
+
+impl InterpretError {
+    /// Returns the insn where the interpretation stopped.
+    pub fn insn(&self) -> Insn {
+        match self {
+            Self::StackOverflow(insn)
+            | Self::Overflow(insn)
+            | Self::DivByZero(insn)
+            | Self::Unreachable(insn)
+            | Self::NoMatchingCase(insn)
+            | Self::TypeMismatch(insn)
+            | Self::IndexOutOfBounds(insn)
+            | Self::OutOfGas { insn, .. }
+            | Self::MemoryFault { insn, .. } => *insn,
+        }
+    }
+}
+
+impl fmt::Display for InterpretError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::StackOverflow(_) => write!(f, "call stack overflow"),
+            Self::OutOfGas { consumed, .. } => write!(f, "out of gas: {consumed} consumed"),
+            Self::MemoryFault { addr, .. } => write!(f, "memory fault at address {addr:#x}"),
+            Self::Overflow(_) => write!(f, "arithmetic overflow"),
+            Self::DivByZero(_) => write!(f, "division by zero"),
+            Self::Unreachable(_) => write!(f, "unreachable code is reached"),
+            Self::NoMatchingCase(_) => write!(f, "no matching case in `br_table` without default"),
+            Self::TypeMismatch(_) => write!(f, "type mismatch"),
+            Self::IndexOutOfBounds(_) => write!(f, "`gep` index out of bounds"),
+        }
+    }
+}
+
+impl std::error::Error for InterpretError {}
@@ -1,14 +1,14 @@
 use cranelift_entity::{packed_option::PackedOption, SecondaryMap};
 
-use sonatina_ir::{module::ModuleCtx, DataFlowGraph, Type, Value, I256};
+use sonatina_ir::{DataFlowGraph, Value, I256};
 
-use crate::{types, EvalValue, ProgramCounter};
+use crate::{EvalValue, ProgramCounter};
 
 #[derive(Default)]
 pub struct Frame {
     pub ret_addr: PackedOption<ProgramCounter>,
     local_values: SecondaryMap<Value, EvalValue>, // 256-bit register
-    alloca_region: Vec<u8>,                       // big endian
+    stack_base: usize,
 }
 
 impl Frame {
@@ -20,6 +20,16 @@ impl Frame {
         self.ret_addr = ret_addr.into();
     }
 
+    /// Sets the size of the memory at the frame entry.
+    /// The memory beyond it is released when the frame returns.
+    pub fn set_stack_base(&mut self, stack_base: usize) {
+        self.stack_base = stack_base;
+    }
+
+    pub fn stack_base(&self) -> usize {
+        self.stack_base
+    }
+
     pub fn load_args(&mut self, args: &[Value], arg_literals: impl Iterator<Item = I256>) {
         for (v, literal_value) in args.iter().zip(arg_literals) {
             self.local_values[*v] = EvalValue::from_i256(literal_value)
@@ -28,53 +38,27 @@ impl Frame {
 
     pub fn load(&mut self, v: Value, dfg: &DataFlowGraph) -> I256 {
         if !self.is_assigned(v) {
-            if let Some(gv) = dfg.value_gv(v) {
-                dfg.ctx.with_gv_store(|s| {
-                    if !s.is_const(gv) {
-                        todo!()
-                    }
-                })
-            }
-            let i256 = dfg.value_imm(v).unwrap().as_i256();
+            debug_assert!(
+                dfg.value_gv(v).is_none(),
+                "global values must be mapped to their addresses"
+            );
+            // An undefined value is deterministically read as zero.
+            let i256 = if dfg.is_undef(v) {
+                I256::zero()
+            } else {
+                dfg.value_imm(v).unwrap().as_i256()
+            };
             self.local_values[v] = EvalValue::from_i256(i256);
         }
         self.local_values[v].i256()
     }
 
+    /// Maps `v` to `literal`. A value may be mapped more than once, e.g., when its definition is
+    /// executed again in a loop, or when it's defined by several copies out of SSA.
     pub fn map(&mut self, literal: I256, v: Value) {
-        debug_assert!(!self.is_assigned(v));
         self.local_values[v] = EvalValue::from_i256(literal)
     }
 
-    pub fn alloca(&mut self, ctx: &ModuleCtx, ty: Type, v: Value) {
-        debug_assert!(!self.is_assigned(v));
-
-        let addr = self.alloca_region.len();
-
-        let size = types::size_of_ty_data(ctx, ty);
-        self.alloca_region.resize(addr + size, 0);
-        self.local_values[v] = EvalValue::from_usize(addr);
-    }
-
-    pub fn ldr(&mut self, ctx: &ModuleCtx, addr: I256, v: Value, ty: Type) {
-        let addr = addr.to_u256().as_usize();
-        debug_assert!(addr < self.alloca_region.len());
-
-        let size = types::size_of_ty_data(ctx, ty);
-        let literal_b = &self.alloca_region[addr..addr + size];
-        let Some(data) = EvalValue::deserialize(ctx, ty, literal_b) else {
-            return;
-        };
-        self.map(data.i256(), v);
-    }
-
-    pub fn str(&mut self, ctx: &ModuleCtx, addr: I256, data: I256, ty: Type) {
-        let addr = addr.to_u256().as_usize();
-        let size = types::size_of_ty_data(ctx, ty);
-        let reg_value = EvalValue::from_i256(data);
-        reg_value.serialize(ctx, ty, &mut self.alloca_region[addr..size]);
-    }
-
     pub fn is_assigned(&self, v: Value) -> bool {
         for (local_v, local) in self.local_values.iter() {
             if v == local_v {
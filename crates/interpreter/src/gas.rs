@@ -0,0 +1,30 @@
+use sonatina_ir::InsnData;
+
+/// A meter that charges gas for each insn before it's executed.
+pub trait GasMeter {
+    /// Charges gas for the `insn`.
+    /// Returns `Err(OutOfGas)` if there isn't enough gas to execute the `insn`, then the
+    /// interpretation stops.
+    fn charge(&mut self, insn: &InsnData) -> Result<(), OutOfGas>;
+
+    /// Returns the gas consumed so far.
+    fn consumed(&self) -> u64;
+}
+
+/// An error returned from [`GasMeter::charge`] when the gas is exhausted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfGas;
+
+/// A [`GasMeter`] that charges nothing. This is the default meter of the interpreter.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ZeroGasMeter;
+
+impl GasMeter for ZeroGasMeter {
+    fn charge(&mut self, _insn: &InsnData) -> Result<(), OutOfGas> {
+        Ok(())
+    }
+
+    fn consumed(&self) -> u64 {
+        0
+    }
+}
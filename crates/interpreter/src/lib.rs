@@ -1,10 +1,18 @@
+pub mod error;
 pub mod frame;
+pub mod gas;
+pub mod memory;
+pub mod observer;
 pub mod pc;
 pub mod state;
 pub mod types;
 pub mod value;
 
+pub use error::InterpretError;
 pub use frame::Frame;
+pub use gas::{GasMeter, OutOfGas, ZeroGasMeter};
+pub use memory::Memory;
+pub use observer::{Observer, PrintTracer};
 pub use pc::ProgramCounter;
-pub use state::State;
+pub use state::{ExecStats, OverflowPolicy, State};
 pub use value::{EvalResult, EvalValue};
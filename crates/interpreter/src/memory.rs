@@ -0,0 +1,133 @@
+use sonatina_ir::{
+    global_variable::ConstantValue, module::ModuleCtx, Type, TypeLayout, I256, U256,
+};
+
+use crate::{types, EvalValue};
+
+/// A linear memory shared by all frames.
+///
+/// `alloca` bumps the memory like a stack; each frame remembers the size of the memory at its
+/// entry so that the region allocated by the frame is released on return.
+/// Multi-byte values are laid out in the byte order of the target ISA.
+#[derive(Default)]
+pub struct Memory {
+    data: Vec<u8>,
+}
+
+/// Indicates that a memory access is out of bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfBounds;
+
+impl Memory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Releases the memory beyond `len`.
+    pub fn truncate(&mut self, len: usize) {
+        self.data.truncate(len);
+    }
+
+    /// Allocates a region for `ty` and returns its address.
+    /// The address is rounded up to the natural alignment of `ty`, so the same sequence of
+    /// allocations always yields the same addresses.
+    pub fn alloca(&mut self, ctx: &ModuleCtx, ty: Type) -> usize {
+        let align = types::align_of_ty_data(ctx, ty);
+        let addr = self.data.len().next_multiple_of(align);
+
+        let size = types::size_of_ty_data(ctx, ty);
+        self.data.resize(addr + size, 0);
+        addr
+    }
+
+    pub fn load(&self, ctx: &ModuleCtx, addr: I256, ty: Type) -> Result<I256, OutOfBounds> {
+        let size = types::size_of_ty_data(ctx, ty);
+        let addr = self.check_bounds(addr, size)?;
+
+        let bytes = &self.data[addr..addr + size];
+        EvalValue::deserialize(ctx, ty, bytes)
+            .map(|data| data.i256())
+            .ok_or(OutOfBounds)
+    }
+
+    pub fn store(
+        &mut self,
+        ctx: &ModuleCtx,
+        addr: I256,
+        data: I256,
+        ty: Type,
+    ) -> Result<(), OutOfBounds> {
+        let size = types::size_of_ty_data(ctx, ty);
+        let addr = self.check_bounds(addr, size)?;
+
+        let bytes = &mut self.data[addr..addr + size];
+        EvalValue::from_i256(data).serialize(ctx, ty, bytes);
+        Ok(())
+    }
+
+    /// Returns `len` bytes from `addr`.
+    pub fn read(&self, addr: I256, len: U256) -> Result<&[u8], OutOfBounds> {
+        if len > U256::from(self.data.len()) {
+            return Err(OutOfBounds);
+        }
+        let len = len.as_usize();
+        let addr = self.check_bounds(addr, len)?;
+        Ok(&self.data[addr..addr + len])
+    }
+
+    /// Writes the initializer `data` of a global variable of `ty` at `addr`.
+    /// Elements of aggregates are placed at the offsets given by [`TypeLayout`].
+    pub fn store_constant(
+        &mut self,
+        ctx: &ModuleCtx,
+        addr: usize,
+        data: &ConstantValue,
+        ty: Type,
+    ) -> Result<(), OutOfBounds> {
+        match data {
+            ConstantValue::Immediate(imm) => self.store(ctx, addr.into(), imm.as_i256(), ty),
+            ConstantValue::Array(elems) => {
+                let (elem_ty, _) = ctx.with_ty_store(|s| s.array_def(ty)).unwrap();
+                let elem_size = types::size_of_ty_data(ctx, elem_ty);
+                for (idx, elem) in elems.iter().enumerate() {
+                    self.store_constant(ctx, addr + idx * elem_size, elem, elem_ty)?;
+                }
+                Ok(())
+            }
+            ConstantValue::Struct(fields) => {
+                let field_tys = ctx.with_ty_store(|s| s.struct_fields(ty).unwrap().to_vec());
+                let layouts = TypeLayout::struct_layout(ty, ctx).unwrap();
+                for ((field, field_ty), layout) in fields.iter().zip(field_tys).zip(layouts) {
+                    self.store_constant(ctx, addr + layout.offset, field, field_ty)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn check_bounds(&self, addr: I256, size: usize) -> Result<usize, OutOfBounds> {
+        if addr.is_negative() {
+            return Err(OutOfBounds);
+        }
+
+        let addr = addr.to_u256();
+        if addr > U256::from(self.data.len()) {
+            return Err(OutOfBounds);
+        }
+
+        let addr = addr.as_usize();
+        if addr + size > self.data.len() {
+            Err(OutOfBounds)
+        } else {
+            Ok(addr)
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! This module contains observers that are called after each insn is executed, which are
+//! useful to build execution traces, coverage, or step debuggers on top of the interpreter.
+use std::io;
+
+use sonatina_ir::{ir_writer::FuncWriter, module::FuncRef, Function, Immediate, Insn};
+
+/// An observer that is called after each insn is executed.
+///
+/// Values of pointer types are passed as `i256` immediates.
+pub trait Observer {
+    /// Observes `insn` of `func` with the values of its operands and its result.
+    /// The operand of a phi insn is the value flowing from the executed edge, and the result of
+    /// a call insn is `None` since the callee hasn't returned yet.
+    fn observe(
+        &mut self,
+        func_ref: FuncRef,
+        func: &Function,
+        insn: Insn,
+        args: &[Immediate],
+        result: Option<Immediate>,
+    );
+}
+
+impl<F> Observer for F
+where
+    F: FnMut(Insn, &[Immediate], Option<Immediate>),
+{
+    fn observe(
+        &mut self,
+        _func_ref: FuncRef,
+        _func: &Function,
+        insn: Insn,
+        args: &[Immediate],
+        result: Option<Immediate>,
+    ) {
+        self(insn, args, result)
+    }
+}
+
+/// An [`Observer`] that writes each executed insn in the textual IR form followed by its
+/// operands and result, e.g., `v2.i32 = add v0 v1;  # 1.i32, 2.i32 => 3.i32`.
+pub struct PrintTracer<W> {
+    w: W,
+}
+
+impl<W> PrintTracer<W>
+where
+    W: io::Write,
+{
+    pub fn new(w: W) -> Self {
+        Self { w }
+    }
+}
+
+impl<W> Observer for PrintTracer<W>
+where
+    W: io::Write,
+{
+    fn observe(
+        &mut self,
+        func_ref: FuncRef,
+        func: &Function,
+        insn: Insn,
+        args: &[Immediate],
+        result: Option<Immediate>,
+    ) {
+        let imm_string = |imm: &Immediate| format!("{imm}.{}", imm.ty().to_string(&func.dfg));
+
+        let insn = FuncWriter::new(func_ref, func, None)
+            .insn_string(insn)
+            .unwrap();
+        let mut comment = args.iter().map(imm_string).collect::<Vec<_>>().join(", ");
+        if let Some(result) = result {
+            comment = format!("{comment} => {}", imm_string(&result))
+                .trim_start()
+                .to_string();
+        }
+        let res = if comment.is_empty() {
+            writeln!(self.w, "{insn}")
+        } else {
+            writeln!(self.w, "{insn}  # {comment}")
+        };
+        // Tracing is a debugging aid, so a write error shouldn't stop the interpretation.
+        let _ = res;
+    }
+}
@@ -1,18 +1,108 @@
-use std::ops::{Add, BitAnd, BitOr, BitXor, Mul, Neg, Not, Sub};
+use std::ops::{Neg, Not};
 
+use cranelift_entity::packed_option::ReservedValue;
+use rustc_hash::FxHashMap;
 use sonatina_ir::{
-    insn::{BinaryOp, CastOp, UnaryOp},
+    insn::{BinaryOp, CastOp, HaltOp, UnaryOp},
     module::FuncRef,
-    Block, DataLocationKind, Immediate, InsnData, Module, Value,
+    Block, DataLocationKind, GlobalVariable, Immediate, Insn, InsnData, Module, SourceLoc, Type,
+    Value, I256, U256,
 };
 
-use crate::{types, EvalResult, Frame, ProgramCounter};
+use crate::{
+    types, EvalResult, Frame, GasMeter, InterpretError, Memory, Observer, ProgramCounter,
+    ZeroGasMeter,
+};
+
+/// The default maximum depth of the call stack, which is the same as the EVM call depth limit.
+pub const DEFAULT_CALL_DEPTH_LIMIT: usize = 1024;
+
+/// How the interpreter handles an overflow of `add`, `sub` and `mul`.
+///
+/// The overflow is detected in the signed interpretation of the operands, i.e., the same as
+/// `add_checked`, `sub_checked` and `mul_checked`. The checked and the other insns are not
+/// affected by the policy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The result wraps around in the width of the type.
+    #[default]
+    Wrap,
+    /// The interpretation stops with [`InterpretError::Overflow`].
+    Trap,
+    /// The result is clamped to the minimum or the maximum value of the type.
+    Saturate,
+}
+
+impl OverflowPolicy {
+    /// Evaluates the binary insn under the policy.
+    /// Returns `None` if the insn traps or `binary_op` of the operands fails.
+    fn eval(self, code: BinaryOp, lhs: Immediate, rhs: Immediate) -> Option<Immediate> {
+        let checked = match code {
+            BinaryOp::Add => BinaryOp::AddChecked,
+            BinaryOp::Sub => BinaryOp::SubChecked,
+            BinaryOp::Mul => BinaryOp::MulChecked,
+            _ => return lhs.binary_op(code, rhs),
+        };
+
+        match self {
+            Self::Wrap => lhs.binary_op(code, rhs),
+            Self::Trap => lhs.binary_op(checked, rhs),
+            Self::Saturate => lhs.binary_op(checked, rhs).or_else(|| {
+                let ty = lhs.ty();
+                let one = Immediate::one(ty);
+                let max = Immediate::all_one(ty).lshr(one);
+                let is_upward = match code {
+                    BinaryOp::Add => !rhs.is_negative(),
+                    BinaryOp::Sub => rhs.is_negative(),
+                    _ => lhs.is_negative() == rhs.is_negative(),
+                };
+                if is_upward {
+                    Some(max)
+                } else {
+                    // The minimum value is the maximum value plus one in wrapping arithmetic.
+                    max.binary_op(BinaryOp::Add, one)
+                }
+            }),
+        }
+    }
+}
+
+/// Execution statistics of the interpretation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExecStats {
+    /// The number of executed insns.
+    pub executed_insns: u64,
+    /// The estimated execution cost, i.e., the sum of the costs of the executed insns given by
+    /// the cost model of the target ISA.
+    pub estimated_cost: u64,
+    /// The number of executed insns that used an undefined value, which is read as zero.
+    /// A non-zero count means the result may depend on the choice of the undefined values.
+    pub undef_uses: u64,
+}
 
 pub struct State {
     module: Module,
     frames: Vec<Frame>,
+    memory: Memory,
+    /// Addresses of the global variables materialized in the memory.
+    globals: FxHashMap<GlobalVariable, usize>,
+    storage: FxHashMap<U256, U256>,
+    /// The storage before the execution, which is restored by `revert`.
+    initial_storage: FxHashMap<U256, U256>,
+    /// The transient storage, which is empty at the start of each interpretation since it's
+    /// discarded at the end of each transaction.
+    transient_storage: FxHashMap<U256, U256>,
     pc: ProgramCounter,
     prev_block: Option<Block>,
+    /// The values of the phis at the head of the current block.
+    phi_values: FxHashMap<Insn, I256>,
+    call_depth_limit: usize,
+    overflow_policy: OverflowPolicy,
+    gas_meter: Box<dyn GasMeter>,
+    executed_insns: u64,
+    estimated_cost: u64,
+    undef_uses: u64,
+    observer: Option<Box<dyn Observer>>,
 }
 
 impl State {
@@ -20,7 +110,30 @@ impl State {
         let func = &module.funcs[entry_func];
         let pc = ProgramCounter::new(entry_func, &func.layout);
 
+        // Global variables are placed at the bottom of the memory in a deterministic order, and
+        // live as long as the interpretation.
+        // The global variable store is released before computing the layouts of their types.
+        let mut memory = Memory::new();
+        let gvs: Vec<_> = module.ctx.with_gv_store(|s| {
+            s.ordered_iter()
+                .map(|(gv, gv_data)| (gv, gv_data.clone()))
+                .collect()
+        });
+        let globals: FxHashMap<_, _> = gvs
+            .into_iter()
+            .map(|(gv, gv_data)| {
+                let addr = memory.alloca(&module.ctx, gv_data.ty);
+                if let Some(data) = &gv_data.data {
+                    memory
+                        .store_constant(&module.ctx, addr, data, gv_data.ty)
+                        .unwrap();
+                }
+                (gv, addr)
+            })
+            .collect();
+
         let mut entry_frame = Frame::new();
+        entry_frame.set_stack_base(memory.len());
         debug_assert!(func.arg_values.len() == args.len());
         for arg in args {
             entry_frame.load(*arg, &func.dfg);
@@ -30,20 +143,190 @@ impl State {
         Self {
             module,
             frames,
+            memory,
+            globals,
+            storage: FxHashMap::default(),
+            initial_storage: FxHashMap::default(),
+            transient_storage: FxHashMap::default(),
             pc,
             prev_block: None,
+            phi_values: FxHashMap::default(),
+            call_depth_limit: DEFAULT_CALL_DEPTH_LIMIT,
+            overflow_policy: OverflowPolicy::default(),
+            gas_meter: Box::new(ZeroGasMeter),
+            executed_insns: 0,
+            estimated_cost: 0,
+            undef_uses: 0,
+            observer: None,
         }
     }
 
-    pub fn run(mut self) -> EvalResult {
+    /// Sets the gas meter that is charged before each insn is executed.
+    /// If the meter runs out of gas, the interpretation stops with
+    /// [`InterpretError::OutOfGas`].
+    pub fn with_gas_meter(mut self, gas_meter: Box<dyn GasMeter>) -> Self {
+        self.gas_meter = gas_meter;
+        self
+    }
+
+    /// Returns the gas consumed so far.
+    pub fn consumed_gas(&self) -> u64 {
+        self.gas_meter.consumed()
+    }
+
+    /// Seeds the storage before the execution.
+    /// Slots that are not in `storage` are read as zero.
+    pub fn with_storage(mut self, storage: FxHashMap<U256, U256>) -> Self {
+        self.initial_storage = storage.clone();
+        self.storage = storage;
+        self
+    }
+
+    /// Returns the storage so far.
+    pub fn storage(&self) -> &FxHashMap<U256, U256> {
+        &self.storage
+    }
+
+    /// Returns the transient storage so far.
+    pub fn transient_storage(&self) -> &FxHashMap<U256, U256> {
+        &self.transient_storage
+    }
+
+    /// Sets the maximum depth of the call stack.
+    /// A call exceeding the limit results in [`InterpretError::StackOverflow`].
+    pub fn with_call_depth_limit(mut self, limit: usize) -> Self {
+        self.call_depth_limit = limit;
+        self
+    }
+
+    /// Sets how an overflow of `add`, `sub` and `mul` is handled.
+    /// The default is [`OverflowPolicy::Wrap`].
+    pub fn with_overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.overflow_policy = policy;
+        self
+    }
+
+    /// Sets the observer that is called after each insn is executed.
+    /// Without an observer, the interpreter doesn't collect operands and results at all.
+    pub fn with_observer(mut self, observer: Box<dyn Observer>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    pub fn run(mut self) -> Result<EvalResult, InterpretError> {
         loop {
-            if let Some(arg) = self.step() {
-                return arg;
+            if let Some(arg) = self.step()? {
+                return Ok(arg);
+            }
+        }
+    }
+
+    /// Similar to [`Self::run`], but also returns the execution statistics.
+    pub fn run_with_stats(mut self) -> (Result<EvalResult, InterpretError>, ExecStats) {
+        let result = loop {
+            match self.step() {
+                Ok(Some(arg)) => break Ok(arg),
+                Ok(None) => {}
+                Err(err) => break Err(err),
+            }
+        };
+
+        (result, self.stats())
+    }
+
+    /// Similar to [`Self::run`], but also returns the storage after the execution.
+    pub fn run_with_storage(
+        mut self,
+    ) -> (Result<EvalResult, InterpretError>, FxHashMap<U256, U256>) {
+        let result = loop {
+            match self.step() {
+                Ok(Some(arg)) => break Ok(arg),
+                Ok(None) => {}
+                Err(err) => break Err(err),
             }
+        };
+
+        (result, self.storage)
+    }
+
+    /// Returns the execution statistics so far.
+    pub fn stats(&self) -> ExecStats {
+        ExecStats {
+            executed_insns: self.executed_insns,
+            estimated_cost: self.estimated_cost,
+            undef_uses: self.undef_uses,
+        }
+    }
+
+    /// Returns the source location of the insn to be executed next. After [`Self::step`] fails,
+    /// this is the location of the insn where the interpretation stopped.
+    pub fn source_loc(&self) -> Option<&SourceLoc> {
+        if self.pc.is_reserved_value() {
+            return None;
+        }
+        self.module.funcs[self.pc.func_ref]
+            .dfg
+            .insn_source_loc(self.pc.insn)
+    }
+
+    pub fn step(&mut self) -> Result<Option<EvalResult>, InterpretError> {
+        if self.observer.is_none() {
+            return self.exec_insn();
+        }
+
+        let func_ref = self.pc.func_ref;
+        let insn = self.pc.insn;
+        let args = self.observed_args();
+        let eval_result = self.exec_insn()?;
+        let result = self.observed_result(func_ref, insn);
+
+        let func = &self.module.funcs[func_ref];
+        if let Some(observer) = &mut self.observer {
+            observer.observe(func_ref, func, insn, &args, result);
+        }
+        Ok(eval_result)
+    }
+
+    /// Returns the values of the operands of the insn to be executed.
+    fn observed_args(&mut self) -> Vec<Immediate> {
+        let frame = self.frames.last_mut().unwrap();
+        let dfg = &self.module.funcs[self.pc.func_ref].dfg;
+
+        let args = match dfg.insn_data(self.pc.insn) {
+            InsnData::Phi { values, blocks, .. } => values
+                .iter()
+                .zip(blocks.iter())
+                .find(|(_, block)| Some(**block) == self.prev_block)
+                .map(|(value, _)| std::slice::from_ref(value))
+                .unwrap_or_default(),
+            insn_data => insn_data.args(),
+        };
+
+        args.iter()
+            .map(|&arg| {
+                let data = match dfg.value_gv(arg) {
+                    Some(gv) => self.globals[&gv].into(),
+                    None => frame.load(arg, dfg),
+                };
+                to_imm(data, dfg.value_ty(arg))
+            })
+            .collect()
+    }
+
+    /// Returns the result of the executed `insn`.
+    fn observed_result(&mut self, func_ref: FuncRef, insn: Insn) -> Option<Immediate> {
+        let dfg = &self.module.funcs[func_ref].dfg;
+        if matches!(dfg.insn_data(insn), InsnData::Call { .. }) {
+            return None;
         }
+
+        let result = dfg.insn_result(insn)?;
+        let frame = self.frames.last_mut()?;
+        Some(to_imm(frame.load(result, dfg), dfg.value_ty(result)))
     }
 
-    pub fn step(&mut self) -> Option<EvalResult> {
+    fn exec_insn(&mut self) -> Result<Option<EvalResult>, InterpretError> {
+        let call_depth = self.frames.len();
         let frame = self.frames.last_mut().unwrap();
         let insn = self.pc.insn;
         let ctx = &self.module.ctx;
@@ -54,14 +337,44 @@ impl State {
 
         let insn_data = dfg.insn_data(insn);
 
+        if self.gas_meter.charge(insn_data).is_err() {
+            return Err(InterpretError::OutOfGas {
+                insn,
+                consumed: self.gas_meter.consumed(),
+            });
+        }
+        self.executed_insns += 1;
+        self.estimated_cost += ctx.isa.cost_model().insn_cost(insn_data);
+
+        let uses_undef = match insn_data {
+            InsnData::Phi { values, blocks, .. } => values
+                .iter()
+                .zip(blocks.iter())
+                .any(|(v, block)| Some(*block) == self.prev_block && dfg.is_undef(*v)),
+            _ => insn_data.args().iter().any(|arg| dfg.is_undef(*arg)),
+        };
+        if uses_undef {
+            self.undef_uses += 1;
+        }
+
+        // A global value is the address of the global variable.
+        for arg in insn_data.args() {
+            if let Some(gv) = dfg.value_gv(*arg) {
+                if !frame.is_assigned(*arg) {
+                    frame.map(self.globals[&gv].into(), *arg);
+                }
+            }
+        }
+
         use InsnData::*;
-        match insn_data {
+        let result = match insn_data {
             Unary { code, args } => {
                 let arg = frame.load(args[0], dfg);
                 use UnaryOp::*;
                 let result = match code {
                     Not => arg.not(),
                     Neg => arg.neg(),
+                    Copy => arg,
                 };
 
                 let v = dfg.insn_result(insn).unwrap();
@@ -71,30 +384,28 @@ impl State {
                 None
             }
             Binary { code, args } => {
-                let lhs: Immediate = frame.load(args[0], dfg).into();
-                let rhs: Immediate = frame.load(args[1], dfg).into();
-                use BinaryOp::*;
-                let result = match code {
-                    Add => lhs.add(rhs),
-                    Sub => lhs.sub(rhs),
-                    Mul => lhs.mul(rhs),
-                    Udiv => lhs.udiv(rhs),
-                    Sdiv => lhs.sdiv(rhs),
-                    Lt => lhs.lt(rhs),
-                    Gt => lhs.gt(rhs),
-                    Slt => lhs.slt(rhs),
-                    Sgt => lhs.sgt(rhs),
-                    Le => lhs.le(rhs),
-                    Ge => lhs.ge(rhs),
-                    Sle => lhs.sle(rhs),
-                    Sge => lhs.sge(rhs),
-                    Eq => lhs.imm_eq(rhs),
-                    Ne => lhs.imm_ne(rhs),
-                    And => lhs.bitand(rhs),
-                    Or => lhs.bitor(rhs),
-                    Xor => lhs.bitxor(rhs),
+                if dfg.value_ty(args[0]) != dfg.value_ty(args[1]) {
+                    return Err(InterpretError::TypeMismatch(insn));
+                }
+                // Operands are interpreted at their exact widths, so that the result wraps around
+                // in the width of the type.
+                let ty = dfg.value_ty(args[0]);
+                let lhs = to_imm(frame.load(args[0], dfg), ty);
+                let rhs = to_imm(frame.load(args[1], dfg), ty);
+                if matches!(
+                    code,
+                    BinaryOp::Udiv | BinaryOp::Sdiv | BinaryOp::Urem | BinaryOp::Srem
+                ) && rhs.is_zero()
+                {
+                    return Err(InterpretError::DivByZero(insn));
                 }
-                .as_i256();
+                // The types and the divisor are checked above, so the only failure left is an
+                // overflow of checked arithmetic or an overflow trapped by the policy.
+                let result = self
+                    .overflow_policy
+                    .eval(*code, lhs, rhs)
+                    .ok_or(InterpretError::Overflow(insn))?
+                    .as_i256();
 
                 let v = dfg.insn_result(insn).unwrap();
                 frame.map(result, v);
@@ -102,12 +413,28 @@ impl State {
                 self.pc.next_insn(layout);
                 None
             }
-            Cast { code, args, .. } => {
+            Cast { code, args, ty } => {
+                let arg_ty = dfg.value_ty(args[0]);
+                // `Immediate` panics on a cast that doesn't change the width as expected.
+                let is_valid_width = match (arg_ty.bits(), ty.bits()) {
+                    (Some(from), Some(to)) => match code {
+                        CastOp::Sext | CastOp::Zext => from < to,
+                        CastOp::Trunc => from > to,
+                        CastOp::BitCast => true,
+                    },
+                    _ => *code == CastOp::BitCast,
+                };
+                if !is_valid_width {
+                    return Err(InterpretError::TypeMismatch(insn));
+                }
+
                 let arg = frame.load(args[0], dfg);
-                use CastOp::*;
+                let arg_imm = || to_imm(arg, arg_ty);
                 let result = match code {
-                    Zext => arg.neg(),
-                    Sext | Trunc | BitCast => arg,
+                    CastOp::Sext => arg_imm().sext(*ty).as_i256(),
+                    CastOp::Zext => arg_imm().zext(*ty).as_i256(),
+                    CastOp::Trunc => arg_imm().trunc(*ty).as_i256(),
+                    CastOp::BitCast => arg,
                 };
 
                 let v = dfg.insn_result(insn).unwrap();
@@ -116,48 +443,103 @@ impl State {
                 self.pc.next_insn(layout);
                 None
             }
-            Load { args, loc } => {
+            // The interpretation is sequential, so the ordering of an atomic access has no effect.
+            Load { args, loc, .. } => {
                 use DataLocationKind::*;
                 match loc {
                     Memory => {
                         let addr = frame.load(args[0], dfg);
                         let v = dfg.insn_result(insn).unwrap();
                         let ty = dfg.insn_result_ty(insn).unwrap();
-                        frame.ldr(ctx, addr, v, ty);
+                        let data = self.memory.load(ctx, addr, ty).map_err(|_| {
+                            InterpretError::MemoryFault {
+                                insn,
+                                addr: addr.to_u256(),
+                            }
+                        })?;
+                        frame.map(data, v);
+                    }
+                    Storage => {
+                        let key = frame.load(args[0], dfg).to_u256();
+                        let v = dfg.insn_result(insn).unwrap();
+                        let ty = dfg.insn_result_ty(insn).unwrap();
+                        let slot = self.storage.get(&key).copied().unwrap_or_default();
+                        frame.map(from_storage_slot(slot, ty), v);
+                    }
+                    TransientStorage => {
+                        let key = frame.load(args[0], dfg).to_u256();
+                        let v = dfg.insn_result(insn).unwrap();
+                        let ty = dfg.insn_result_ty(insn).unwrap();
+                        let slot = self
+                            .transient_storage
+                            .get(&key)
+                            .copied()
+                            .unwrap_or_default();
+                        frame.map(from_storage_slot(slot, ty), v);
                     }
-                    Storage => todo!(),
                 }
 
                 self.pc.next_insn(layout);
                 None
             }
-            Store { args, loc } => {
+            Store { args, loc, .. } => {
                 use DataLocationKind::*;
                 match loc {
                     Memory => {
                         let addr = frame.load(args[0], dfg);
                         let data = frame.load(args[1], dfg);
                         let ty = dfg.value_ty(args[1]);
-                        frame.str(ctx, addr, data, ty);
+                        self.memory.store(ctx, addr, data, ty).map_err(|_| {
+                            InterpretError::MemoryFault {
+                                insn,
+                                addr: addr.to_u256(),
+                            }
+                        })?;
+                    }
+                    Storage => {
+                        let key = frame.load(args[0], dfg).to_u256();
+                        let data = frame.load(args[1], dfg);
+                        let ty = dfg.value_ty(args[1]);
+                        self.storage.insert(key, to_storage_slot(data, ty));
+                    }
+                    TransientStorage => {
+                        let key = frame.load(args[0], dfg).to_u256();
+                        let data = frame.load(args[1], dfg);
+                        let ty = dfg.value_ty(args[1]);
+                        self.transient_storage
+                            .insert(key, to_storage_slot(data, ty));
                     }
-                    Storage => todo!(),
                 }
 
                 self.pc.next_insn(layout);
                 None
             }
             Call { func, args, .. } => {
+                if call_depth >= self.call_depth_limit {
+                    return Err(InterpretError::StackOverflow(insn));
+                }
+
+                let callee = &self.module.funcs[*func];
+                if callee.arg_values.len() != args.len()
+                    || callee
+                        .arg_values
+                        .iter()
+                        .zip(args.iter())
+                        .any(|(param, arg)| callee.dfg.value_ty(*param) != dfg.value_ty(*arg))
+                {
+                    return Err(InterpretError::TypeMismatch(insn));
+                }
+
                 let arg_literals = args.iter().map(|arg| frame.load(*arg, dfg));
 
                 // Function prologue
 
                 let ret_addr = self.pc;
 
-                let callee = &self.module.funcs[*func];
                 let mut new_frame = Frame::new();
-                debug_assert!(callee.arg_values.len() == args.len());
                 new_frame.load_args(&callee.arg_values, arg_literals);
                 new_frame.set_ret_addr(ret_addr);
+                new_frame.set_stack_base(self.memory.len());
                 self.frames.push(new_frame);
 
                 self.pc.call(*func, &callee.layout);
@@ -172,7 +554,8 @@ impl State {
             }
             Branch { args, dests } => {
                 let arg = frame.load(args[0], dfg);
-                let idx = arg.not().to_u256().as_usize();
+                // `dests[0]` is taken when the condition is non-zero.
+                let idx = usize::from(arg.is_zero());
 
                 let block = layout.insn_block(insn);
                 self.prev_block = Some(block);
@@ -193,23 +576,26 @@ impl State {
                     let arg = frame.load(*arg, dfg);
                     if cond == arg {
                         self.pc.branch_to(table[idx], layout);
-                        return None;
+                        return Ok(None);
                     }
                 }
-                if let Some(block) = *default {
-                    self.pc.branch_to(block, layout);
+                match *default {
+                    Some(block) => self.pc.branch_to(block, layout),
+                    None => return Err(InterpretError::NoMatchingCase(insn)),
                 }
                 None
             }
             Alloca { ty } => {
                 let v = dfg.insn_result(insn).unwrap();
-                frame.alloca(ctx, *ty, v);
+                let addr = self.memory.alloca(ctx, *ty);
+                frame.map(addr.into(), v);
 
                 self.pc.next_insn(layout);
                 None
             }
             Return { args } => {
                 let mut frame = self.frames.pop().unwrap(); // pop returning frame
+                self.memory.truncate(frame.stack_base());
 
                 match self.frames.last_mut() {
                     Some(caller_frame) => {
@@ -218,32 +604,76 @@ impl State {
                         self.pc.resume_frame_at(frame.ret_addr.unwrap());
 
                         let caller = &self.module.funcs[self.pc.func_ref];
-                        if let Some(arg) = *args {
-                            let arg_literal = frame.load(arg, dfg);
-                            let v = caller.dfg.insn_result(self.pc.insn).unwrap();
-                            caller_frame.map(arg_literal, v);
+                        let call = self.pc.insn;
+                        let results = caller.dfg.insn_results(call);
+                        if args.len() != results.len()
+                            || args.iter().zip(results).any(|(arg, result)| {
+                                dfg.value_ty(*arg) != caller.dfg.value_ty(*result)
+                            })
+                        {
+                            return Err(InterpretError::TypeMismatch(insn));
+                        }
+                        for (arg, result) in args.iter().zip(results) {
+                            let arg_literal = frame.load(*arg, dfg);
+                            caller_frame.map(arg_literal, *result);
                         }
 
                         self.pc.next_insn(&caller.layout);
                         None
                     }
                     None => {
-                        let Some(arg) = *args else {
-                            return Some(EvalResult::Void);
-                        };
-                        let arg_literal = frame.load(arg, dfg);
-                        let ty = dfg.value_ty(arg);
-                        Some(EvalResult::from_i256(ctx, arg_literal, ty))
+                        let mut results: Vec<_> = args
+                            .iter()
+                            .map(|&arg| {
+                                let arg_literal = frame.load(arg, dfg);
+                                let ty = dfg.value_ty(arg);
+                                EvalResult::from_i256(ctx, arg_literal, ty)
+                            })
+                            .collect();
+                        Some(match results.len() {
+                            0 => EvalResult::Void,
+                            1 => results.pop().unwrap(),
+                            _ => EvalResult::Tuple(results),
+                        })
                     }
                 }
             }
+            Halt { code, args } => {
+                let data = match code {
+                    HaltOp::Stop => Vec::new(),
+                    HaltOp::ReturnData | HaltOp::Revert => {
+                        let addr = frame.load(args[0], dfg);
+                        let len = frame.load(args[1], dfg).to_u256();
+                        self.memory
+                            .read(addr, len)
+                            .map_err(|_| InterpretError::MemoryFault {
+                                insn,
+                                addr: addr.to_u256(),
+                            })?
+                            .to_vec()
+                    }
+                };
+
+                // Halting ends the execution of all the frames.
+                self.frames.clear();
+                Some(match code {
+                    HaltOp::Stop => EvalResult::Stop,
+                    HaltOp::ReturnData => EvalResult::ReturnData(data),
+                    HaltOp::Revert => {
+                        self.storage = self.initial_storage.clone();
+                        self.transient_storage.clear();
+                        EvalResult::Revert(data)
+                    }
+                })
+            }
             Gep { args } => {
                 let mut arg_literals = args.iter().map(|arg| frame.load(*arg, dfg));
                 let base_addr = arg_literals.next().unwrap();
                 let ty = dfg.value_ty(args[0]);
-                debug_assert!(ctx.with_ty_store(|s| s.is_ptr(ty)));
+                debug_assert!(ty.is_pointer(ctx));
 
-                let elem_ptr = types::gep(ctx, base_addr, ty, arg_literals);
+                let elem_ptr = types::gep(ctx, base_addr, ty, arg_literals)
+                    .ok_or(InterpretError::IndexOutOfBounds(insn))?;
 
                 let v = dfg.insn_result(insn).unwrap();
                 frame.map(elem_ptr, v);
@@ -251,20 +681,68 @@ impl State {
                 self.pc.next_insn(layout);
                 None
             }
-            Phi { values, blocks, .. } => {
-                let prev_block = self.prev_block.unwrap();
-                for (v, block) in values.iter().zip(blocks.iter()) {
-                    if prev_block == *block {
-                        let lit = frame.load(*v, dfg);
-                        let v = dfg.insn_result(insn).unwrap();
-                        frame.map(lit, v);
-                        break;
+            Phi { .. } => {
+                // The phis at the head of a block read their args simultaneously, so all of them
+                // are evaluated on entering the first one.
+                if layout
+                    .prev_insn_of(insn)
+                    .is_none_or(|prev| !dfg.is_phi(prev))
+                {
+                    self.phi_values.clear();
+                    let mut next = Some(insn);
+                    while let Some(phi) = next.filter(|next| dfg.is_phi(*next)) {
+                        let Phi { values, blocks, .. } = dfg.insn_data(phi) else {
+                            unreachable!();
+                        };
+                        let Some((v, _)) = values
+                            .iter()
+                            .zip(blocks.iter())
+                            .find(|(_, block)| Some(**block) == self.prev_block)
+                        else {
+                            return Err(InterpretError::Unreachable(phi));
+                        };
+                        self.phi_values.insert(phi, frame.load(*v, dfg));
+                        next = layout.next_insn_of(phi);
                     }
                 }
+
+                let lit = self.phi_values[&insn];
+                let v = dfg.insn_result(insn).unwrap();
+                frame.map(lit, v);
                 self.pc.next_insn(layout);
                 None
             }
-        }
+        };
+
+        Ok(result)
+    }
+}
+
+/// Converts `data` to an immediate of `ty`. Pointers are converted to `i256` immediates.
+fn to_imm(data: I256, ty: Type) -> Immediate {
+    if ty.is_integral() {
+        Immediate::from_i256(data, ty)
+    } else {
+        Immediate::I256(data)
+    }
+}
+
+/// Converts `data` to a storage slot by zero extending it from `ty`.
+fn to_storage_slot(data: I256, ty: Type) -> U256 {
+    if ty.is_integral() {
+        Immediate::from_i256(data, ty).as_zext_u256()
+    } else {
+        data.to_u256()
+    }
+}
+
+/// Reads a storage slot as a value of `ty`. The slot is truncated to the width of `ty`.
+fn from_storage_slot(slot: U256, ty: Type) -> I256 {
+    let data = I256::from_u256(slot);
+    if ty.is_integral() {
+        Immediate::from_i256(data, ty).as_i256()
+    } else {
+        data
     }
 }
 
@@ -306,7 +784,7 @@ mod test {
 
         let state = parse_module_make_state(input);
 
-        let result = state.run();
+        let result = state.run().unwrap();
 
         assert_eq!(result.into_i32(), 1i32);
     }
@@ -328,217 +806,1260 @@ mod test {
 
         let state = parse_module_make_state(input);
 
-        let result = state.run();
+        let result = state.run().unwrap();
 
         assert_eq!(result.into_i16(), -3i16);
     }
 
-    #[test]
-    fn cast_sext() {
-        let input = "
+    fn eval_binary(op: &str, lhs: &str, rhs: &str, ty: &str) -> EvalResult {
+        let input = format!(
+            "
         target = \"evm-ethereum-london\"
 
-        func private %test() -> i16 {
+        func private %test() -> {ty} {{
             block0:
-                v0.i16 = sext -128.i8;
+                v0.{ty} = {op} {lhs}.{ty} {rhs}.{ty};
                 return v0;
-        }
-        ";
+        }}
+        "
+        );
 
-        let state = parse_module_make_state(input);
+        parse_module_make_state(&input).run().unwrap()
+    }
 
-        let result = state.run();
+    #[test]
+    fn sdiv_rounding() {
+        // Signed division truncates toward zero.
+        assert_eq!(eval_binary("sdiv", "-7", "2", "i32").into_i32(), -3);
+        assert_eq!(eval_binary("sdiv", "7", "-2", "i32").into_i32(), -3);
+        assert_eq!(eval_binary("sdiv", "-7", "-2", "i32").into_i32(), 3);
+        assert_eq!(eval_binary("sdiv", "7", "2", "i32").into_i32(), 3);
+    }
 
-        assert_eq!(result.into_i16(), -128i16);
+    #[test]
+    fn udiv_rounding() {
+        assert_eq!(eval_binary("udiv", "7", "2", "i32").into_i32(), 3);
+        // `-7.i16` is `65529` as unsigned.
+        assert_eq!(eval_binary("udiv", "-7", "2", "i16").into_i16(), 32764);
     }
 
     #[test]
-    fn cast_zext() {
-        let input = "
+    fn sdiv_min_by_minus_one() {
+        // The minimum value divided by `-1` wraps around to the minimum value.
+        assert_eq!(eval_binary("sdiv", "-128", "-1", "i8").into_i8(), i8::MIN);
+
+        let min = "-57896044618658097711785492504343953926634992332820282019728792003956564819968";
+        let result = eval_binary("sdiv", min, "-1", "i256").into_i256();
+        assert!(result.is_minimum());
+    }
+
+    #[test]
+    fn srem_sign() {
+        // The sign of the signed remainder follows the dividend.
+        assert_eq!(eval_binary("srem", "-7", "2", "i32").into_i32(), -1);
+        assert_eq!(eval_binary("srem", "7", "-2", "i32").into_i32(), 1);
+        assert_eq!(eval_binary("srem", "-7", "-2", "i32").into_i32(), -1);
+        assert_eq!(eval_binary("srem", "7", "2", "i32").into_i32(), 1);
+        assert_eq!(eval_binary("srem", "-8", "2", "i32").into_i32(), 0);
+    }
+
+    #[test]
+    fn urem() {
+        assert_eq!(eval_binary("urem", "7", "2", "i32").into_i32(), 1);
+        // `-7.i16` is `65529` as unsigned.
+        assert_eq!(eval_binary("urem", "-7", "10", "i16").into_i16(), 9);
+    }
+
+    #[test]
+    fn srem_min_by_minus_one() {
+        assert_eq!(eval_binary("srem", "-128", "-1", "i8").into_i8(), 0);
+
+        let min = "-57896044618658097711785492504343953926634992332820282019728792003956564819968";
+        let result = eval_binary("srem", min, "-1", "i256").into_i256();
+        assert!(result.is_zero());
+    }
+
+    #[test]
+    fn div_by_zero() {
+        for op in ["udiv", "sdiv", "urem", "srem"] {
+            let input = format!(
+                "
         target = \"evm-ethereum-london\"
 
-        func private %test() -> i16 {
+        func private %test() -> i32 {{
             block0:
-                v0.i16 = zext -128.i8;
+                v0.i32 = {op} 1.i32 0.i32;
                 return v0;
+        }}
+        "
+            );
+
+            let module = parse_module(&input);
+            let func_ref = module.iter_functions().next().unwrap();
+            let insn = module.funcs[func_ref]
+                .layout
+                .first_insn_of(Block(0))
+                .unwrap();
+
+            let state = State::new(module, func_ref, &[]);
+            assert_eq!(state.run(), Err(InterpretError::DivByZero(insn)));
         }
-        ";
+    }
 
-        let state = parse_module_make_state(input);
+    #[test]
+    fn shift() {
+        assert_eq!(eval_binary("shl", "3", "4", "i32").into_i32(), 48);
+        assert_eq!(eval_binary("shl", "1", "7", "i8").into_i8(), i8::MIN);
+        // `-16.i8` is `0xf0`.
+        assert_eq!(eval_binary("lshr", "-16", "2", "i8").into_i8(), 0x3c);
+        assert_eq!(eval_binary("ashr", "-16", "2", "i8").into_i8(), -4);
+        assert_eq!(eval_binary("ashr", "16", "2", "i8").into_i8(), 4);
+    }
 
-        let elem_ptr = state.run();
+    #[test]
+    fn shift_out_of_range() {
+        // The amount is treated as unsigned, and an amount that is greater than or equal to the
+        // bit width shifts out all bits.
+        assert_eq!(eval_binary("shl", "1", "32", "i32").into_i32(), 0);
+        assert_eq!(eval_binary("lshr", "-1", "-1", "i32").into_i32(), 0);
+        assert_eq!(eval_binary("ashr", "-5", "100", "i32").into_i32(), -1);
+        assert_eq!(eval_binary("ashr", "5", "100", "i32").into_i32(), 0);
+
+        let result = eval_binary("shl", "1", "256", "i256").into_i256();
+        assert!(result.is_zero());
+        let result = eval_binary("ashr", "-1", "255", "i256").into_i256();
+        assert_eq!(result, I256::all_one());
+    }
 
-        assert_eq!(elem_ptr.into_i16(), 128i16);
+    fn eval_i24(op: BinaryOp, lhs: i32, rhs: i32) -> I256 {
+        use sonatina_ir::builder::test_util::test_func_builder;
+
+        let i24 = Type::int(24);
+        let mut builder = test_func_builder(&[], i24);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let lhs = builder.make_imm_value(Immediate::from_i256(lhs.into(), i24));
+        let rhs = builder.make_imm_value(Immediate::from_i256(rhs.into(), i24));
+        let result = builder.binary_op(op, lhs, rhs);
+        builder.ret(&[result]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        State::new(module, func_ref, &[]).run().unwrap().into_int()
     }
 
     #[test]
-    fn load_store() {
+    fn arbitrary_width_int() {
+        // `0x7fffff` is the maximum value of `i24`.
+        assert_eq!(
+            eval_i24(BinaryOp::Add, 0x7f_ffff, 1),
+            I256::from(-0x80_0000)
+        );
+        assert_eq!(eval_i24(BinaryOp::Add, -1, 1), I256::zero());
+        assert_eq!(
+            eval_i24(BinaryOp::Mul, 0x40_0000, 2),
+            I256::from(-0x80_0000)
+        );
+        // `-1.i24` is `0xffffff` as unsigned.
+        assert_eq!(eval_i24(BinaryOp::Udiv, -1, 2), I256::from(0x7f_ffff));
+    }
+
+    #[test]
+    fn binary_checked_arithmetic() {
         let input = "
         target = \"evm-ethereum-london\"
 
-        func private %test() -> i32 {
+        func private %test() -> i8 {
             block0:
-                v0.*i32 = alloca i32;
-                store @memory v0 1.i32;
-                v1.i32 = load @memory v0;
-                return v1;
+                v0.i8 = add 127.i8 1.i8;
+                v1.i8 = add_checked v0 1.i8;
+                v2.i8 = mul_checked 8.i8 -16.i8;
+                return v2;
         }
         ";
 
         let state = parse_module_make_state(input);
 
-        let data = state.run();
+        let result = state.run().unwrap();
 
-        assert_eq!(data.into_i32(), 1i32);
+        assert_eq!(result.into_i8(), -128i8);
     }
 
     #[test]
-    fn call() {
+    fn binary_checked_arithmetic_trap() {
         let input = "
         target = \"evm-ethereum-london\"
 
-        func public %test_callee(v0.i8) -> i8 {
+        func private %test() -> i8 {
             block0:
-                v1.i8 = mul v0 1.i8;
+                v0.i8 = add 127.i8 1.i8;
+                v1.i8 = add_checked 127.i8 1.i8;
                 return v1;
         }
-
-        func public %test() -> i8 {
-            block0:
-                v0.i8 = call %test_callee 0.i8;
-                return v0;
-        }
         ";
 
         let module = parse_module(input);
-        let func_ref = module.iter_functions().nth(1).unwrap();
+        let func_ref = module.iter_functions().next().unwrap();
+        let layout = &module.funcs[func_ref].layout;
+        let insn = layout.next_insn_of(layout.first_insn_of(Block(0)).unwrap());
 
         let state = State::new(module, func_ref, &[]);
-
-        let data = state.run();
-
-        assert_eq!(data.into_i8(), 0i8);
+        assert_eq!(state.run(), Err(InterpretError::Overflow(insn.unwrap())));
     }
 
-    #[test]
-    fn jump() {
-        let input = "
+    fn eval_checked(op: &str, lhs: &str, rhs: &str, ty: &str) -> Option<EvalResult> {
+        let input = format!(
+            "
         target = \"evm-ethereum-london\"
 
-        func private %test() -> i1 {
+        func private %test() -> {ty} {{
             block0:
-                jump block2;
-            block1:
-                return 1.i1;
-            block2:
-                return 0.i1;
+                v0.{ty} = {op} {lhs}.{ty} {rhs}.{ty};
+                return v0;
+        }}
+        "
+        );
+
+        match parse_module_make_state(&input).run() {
+            Ok(result) => Some(result),
+            Err(InterpretError::Overflow(_)) => None,
+            Err(err) => panic!("unexpected error: {err}"),
         }
-        ";
+    }
 
-        let state = parse_module_make_state(input);
+    #[test]
+    fn signed_checked_arithmetic() {
+        let eval = |op, lhs, rhs| eval_checked(op, lhs, rhs, "i8").map(EvalResult::into_i8);
+        assert_eq!(eval("add_checked", "-128", "127"), Some(-1));
+        assert_eq!(eval("add_checked", "-128", "-1"), None);
+        assert_eq!(eval("sub_checked", "-1", "127"), Some(-128));
+        assert_eq!(eval("sub_checked", "-2", "127"), None);
+        assert_eq!(eval("sub_checked", "0", "-128"), None);
+        assert_eq!(eval("mul_checked", "-1", "-128"), None);
+        assert_eq!(eval("mul_checked", "-16", "8"), Some(-128));
+    }
 
-        let boolean = state.run();
+    #[test]
+    fn unsigned_checked_arithmetic() {
+        // `-1.i8` is `255` as unsigned.
+        let eval = |op, lhs, rhs| eval_checked(op, lhs, rhs, "i8").map(EvalResult::into_i8);
+        assert_eq!(eval("uadd_checked", "-2", "1"), Some(-1));
+        assert_eq!(eval("uadd_checked", "-1", "1"), None);
+        assert_eq!(eval("usub_checked", "-1", "-2"), Some(1));
+        assert_eq!(eval("usub_checked", "0", "1"), None);
+        assert_eq!(eval("umul_checked", "127", "2"), Some(-2));
+        assert_eq!(eval("umul_checked", "-128", "2"), None);
+
+        let max = "-1";
+        let result = eval_checked("uadd_checked", max, "1", "i256");
+        assert!(result.is_none());
+        let result = eval_checked("umul_checked", max, "1", "i256").unwrap();
+        assert_eq!(result.into_i256(), I256::all_one());
+    }
 
-        assert!(!boolean.into_bool())
+    #[test]
+    fn overflow_policy() {
+        let eval = |policy, op: &str, lhs: &str, rhs: &str| {
+            let input = format!(
+                "
+            target = \"evm-ethereum-london\"
+
+            func private %test() -> i8 {{
+                block0:
+                    v0.i8 = {op} {lhs}.i8 {rhs}.i8;
+                    return v0;
+            }}
+            "
+            );
+            match parse_module_make_state(&input)
+                .with_overflow_policy(policy)
+                .run()
+            {
+                Ok(result) => Some(result.into_i8()),
+                Err(InterpretError::Overflow(_)) => None,
+                Err(err) => panic!("unexpected error: {err}"),
+            }
+        };
+
+        use OverflowPolicy::*;
+        assert_eq!(eval(Wrap, "add", "127", "1"), Some(-128));
+        assert_eq!(eval(Trap, "add", "127", "1"), None);
+        assert_eq!(eval(Trap, "add", "126", "1"), Some(127));
+        assert_eq!(eval(Trap, "sub", "-128", "1"), None);
+        assert_eq!(eval(Trap, "mul", "-128", "-1"), None);
+
+        assert_eq!(eval(Saturate, "add", "127", "1"), Some(127));
+        assert_eq!(eval(Saturate, "add", "-128", "-1"), Some(-128));
+        assert_eq!(eval(Saturate, "sub", "-128", "1"), Some(-128));
+        assert_eq!(eval(Saturate, "sub", "0", "-128"), Some(127));
+        assert_eq!(eval(Saturate, "mul", "-128", "-1"), Some(127));
+        assert_eq!(eval(Saturate, "mul", "64", "-3"), Some(-128));
+        assert_eq!(eval(Saturate, "mul", "5", "-3"), Some(-15));
+
+        // Other insns are not affected by the policy.
+        assert_eq!(eval(Trap, "shl", "64", "1"), Some(-128));
+        assert_eq!(eval(Saturate, "add_checked", "127", "1"), None);
     }
 
     #[test]
-    fn branch() {
+    fn cast_sext() {
         let input = "
         target = \"evm-ethereum-london\"
 
-        func private %test() -> i8 {
+        func private %test() -> i16 {
             block0:
-                br 1.i1 block1 block2;
-            block1:
-                return 1.i8;
-            block2:
-                return 2.i8;
+                v0.i16 = sext -128.i8;
+                return v0;
         }
         ";
 
         let state = parse_module_make_state(input);
 
-        let result = state.run();
+        let result = state.run().unwrap();
 
-        assert_eq!(result.into_i8(), 1i8);
+        assert_eq!(result.into_i16(), -128i16);
     }
 
     #[test]
-    fn br_table() {
+    fn cast_zext() {
         let input = "
         target = \"evm-ethereum-london\"
 
-        func private %test() -> i64 {
+        func private %test() -> i16 {
             block0:
-                br_table 1.i64 (0.i64 block1) (1.i64 block2);
-            block1:
-                return 1.i64;
-            block2:
-                return 2.i64;
-            block3:
-                return 3.i64;
+                v0.i16 = zext -128.i8;
+                return v0;
         }
         ";
 
         let state = parse_module_make_state(input);
 
-        let result = state.run();
+        let elem_ptr = state.run().unwrap();
 
-        assert_eq!(result.into_i64(), 2i64);
+        assert_eq!(elem_ptr.into_i16(), 128i16);
     }
 
     #[test]
-    fn phi() {
+    fn load_store() {
         let input = "
         target = \"evm-ethereum-london\"
 
-        func private %test() -> i8 {
+        func private %test() -> i32 {
             block0:
-                jump block1;
-            block1:
-                jump block2;
-            block2:
-                v0.i8 = phi (1.i8 block0) (-1.i8 block1);
-                return v0;
+                v0.*i32 = alloca i32;
+                store @memory v0 1.i32;
+                v1.i32 = load @memory v0;
+                return v1;
         }
         ";
 
         let state = parse_module_make_state(input);
 
-        let result = state.run();
+        let data = state.run().unwrap();
 
-        assert_eq!(result.into_i8(), -1i8);
+        assert_eq!(data.into_i32(), 1i32);
     }
 
     #[test]
-    fn gep() {
+    fn alloca_addr_deterministic() {
         let input = "
         target = \"evm-ethereum-london\"
 
-        type %s1 = {i32, i64, i1};
-
-        func private %test() -> *i1 {
+        func private %test() -> *i32 {
             block0:
-                v0.*%s1 = alloca %s1;
-                v1.*i1 = gep v0 2.i8;
+                v0.*i8 = alloca i8;
+                v1.*i32 = alloca i32;
                 return v1;
         }
         ";
 
-        let state = parse_module_make_state(input);
-
-        let elem_ptr = state.run();
+        let first = parse_module_make_state(input).run().unwrap().into_usize();
+        let second = parse_module_make_state(input).run().unwrap().into_usize();
 
-        assert_eq!(elem_ptr.into_usize(), 12usize);
+        assert_eq!(first, second);
+        assert_eq!(first, 4usize);
     }
 
     #[test]
-    fn ret_void() {
+    fn memory_shared_across_frames() {
         let input = "
         target = \"evm-ethereum-london\"
 
-        type %s1 = {i32, i64, i1};
+        func public %store_one(v0.*i32) -> i32 {
+            block0:
+                v1.*i8 = alloca i8;
+                store @memory v0 1.i32;
+                return 0.i32;
+        }
+
+        func public %test() -> i32 {
+            block0:
+                v0.*i32 = alloca i32;
+                v5.i32 = call %store_one v0;
+                v1.*i32 = alloca i32;
+                store @memory v1 2.i32;
+                v2.i32 = load @memory v0;
+                v3.i32 = load @memory v1;
+                v4.i32 = add v2 v3;
+                return v4;
+        }
+        ";
+
+        let module = parse_module(input);
+        let func_ref = module.iter_functions().nth(1).unwrap();
+
+        let state = State::new(module, func_ref, &[]);
+
+        assert_eq!(state.run().unwrap().into_i32(), 3i32);
+    }
+
+    #[test]
+    fn storage() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> i32 {
+            block0:
+                v0.i256 = load @storage 1.i256;
+                v1.i256 = load @storage 2.i256;
+                v2.i256 = add v0 v1;
+                store @storage 3.i256 v2;
+                store @storage 4.i256 -1.i8;
+                v3.i32 = trunc v2;
+                return v3;
+        }
+        ";
+
+        let mut storage = FxHashMap::default();
+        storage.insert(U256::from(1), U256::from(10));
+
+        let state = parse_module_make_state(input).with_storage(storage);
+        let (result, storage) = state.run_with_storage();
+
+        // Uninitialized slots are read as zero.
+        assert_eq!(result.unwrap().into_i32(), 10i32);
+        assert_eq!(storage.len(), 3);
+        assert_eq!(storage[&U256::from(1)], U256::from(10));
+        assert_eq!(storage[&U256::from(3)], U256::from(10));
+        assert_eq!(storage[&U256::from(4)], U256::from(0xff));
+    }
+
+    #[test]
+    fn transient_storage() {
+        let input = "
+        target = \"evm-ethereum-cancun\"
+
+        func private %test() -> i32 {
+            block0:
+                store @transient 1.i256 -1.i8;
+                v0.i256 = load @transient 1.i256;
+                v1.i256 = load @transient 2.i256;
+                v2.i256 = add v0 v1;
+                v3.i32 = trunc v2;
+                return v3;
+        }
+        ";
+
+        let state = parse_module_make_state(input);
+        assert!(state.transient_storage().is_empty());
+        let (result, storage) = state.run_with_storage();
+
+        // The slot is zero extended as the storage, and the persistent storage is untouched.
+        assert_eq!(result.unwrap().into_i32(), 0xff);
+        assert!(storage.is_empty());
+    }
+
+    #[test]
+    fn return_data_from_callee() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> i32 {
+            block0:
+                v0.*i32 = alloca i32;
+                store @memory v0 258.i32;
+                call %halt v0;
+                return 0.i32;
+        }
+
+        func private %halt(v0.*i32) -> void {
+            block0:
+                return_data v0 4.i256;
+        }
+        ";
+
+        let state = parse_module_make_state(input);
+
+        // The halt ends the execution without returning to the caller.
+        assert_eq!(state.run().unwrap().into_return_data(), vec![0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn revert() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> void {
+            block0:
+                store @storage 1.i256 5.i256;
+                revert 0.i256 0.i256;
+        }
+        ";
+
+        let mut storage = FxHashMap::default();
+        storage.insert(U256::from(2), U256::from(7));
+
+        let state = parse_module_make_state(input).with_storage(storage.clone());
+        let (result, after) = state.run_with_storage();
+
+        // State changes are discarded.
+        assert_eq!(result.unwrap().into_revert(), Vec::<u8>::new());
+        assert_eq!(after, storage);
+    }
+
+    #[test]
+    fn union_reinterpret() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> i8 {
+            block0:
+                v0.*union{i32, [i8; 8]} = alloca union{i32, [i8; 8]};
+                v1.*i32 = gep v0 0.i256;
+                store @memory v1 16909060.i32;
+                v2.*[i8; 8] = gep v0 1.i256;
+                v3.*i8 = gep v2 3.i256;
+                v4.i8 = load @memory v3;
+                return v4;
+        }
+        ";
+
+        let module = parse_module(input);
+        let ctx = &module.ctx;
+        let arr = ctx.with_ty_store_mut(|s| s.make_array(Type::I8, 8));
+        let union = ctx.with_ty_store_mut(|s| s.make_union(&[Type::I32, arr]));
+        assert_eq!(types::size_of_ty_data(ctx, union), 8);
+        assert_eq!(types::align_of_ty_data(ctx, union), 4);
+
+        let func_ref = module.iter_functions().next().unwrap();
+        let state = State::new(module, func_ref, &[]);
+
+        // `16909060` is `0x01020304`, and the memory is big endian.
+        assert_eq!(state.run().unwrap().into_i8(), 4i8);
+    }
+
+    #[test]
+    fn aggregate_global() {
+        use sonatina_ir::{
+            builder::{test_util::build_test_isa, ModuleBuilder},
+            func_cursor::InsnInserter,
+            global_variable::ConstantValue,
+            module::ModuleCtx,
+            GlobalVariableData, Linkage, Signature,
+        };
+
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+
+        // `{i8, [i16; 2]}` is laid out as `i8, pad, i16, i16`.
+        let arr = mb.declare_array_type(Type::I16, 2);
+        let st = mb.declare_struct_type("s", &[Type::I8, arr], false);
+        let data = ConstantValue::make_struct(vec![
+            ConstantValue::make_imm(7i8),
+            ConstantValue::make_array(vec![
+                ConstantValue::make_imm(258i16),
+                ConstantValue::make_imm(-3i16),
+            ]),
+        ]);
+        let gv = mb.make_global(GlobalVariableData::constant(
+            "table".to_string(),
+            st,
+            Linkage::Private,
+            data,
+        ));
+
+        let sig = Signature::new("test", Linkage::Public, &[], Type::I16);
+        let func_ref = mb.declare_function(sig);
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let table = builder.make_global_value(gv);
+        let zero = builder.make_imm_value(I256::zero());
+        let one = builder.make_imm_value(I256::one());
+        let elem0 = builder.gep(&[table, one, zero]).unwrap();
+        let elem1 = builder.gep(&[table, one, one]).unwrap();
+        let v0 = builder.memory_load(elem0);
+        let v1 = builder.memory_load(elem1);
+        let v2 = builder.add(v0, v1);
+        builder.ret(&[v2]);
+        builder.seal_all();
+        let module = builder.finish().build();
+
+        // The globals are also visible as raw bytes in the big endian memory.
+        let state = State::new(module, func_ref, &[]);
+        assert_eq!(state.memory.len(), 6);
+        assert_eq!(
+            (0..6usize)
+                .map(|addr| state.memory.load(&state.module.ctx, addr.into(), Type::I8))
+                .map(|byte| byte.unwrap().trunc_to_i8())
+                .collect::<Vec<_>>(),
+            vec![7, 0, 1, 2, -1, -3]
+        );
+        assert_eq!(state.run().unwrap().into_i16(), 255i16);
+    }
+
+    #[test]
+    fn global_addr_deterministic() {
+        use sonatina_ir::{
+            builder::{test_util::build_test_isa, ModuleBuilder},
+            func_cursor::InsnInserter,
+            module::ModuleCtx,
+            GlobalVariableData, Linkage, Signature,
+        };
+
+        // Globals are placed in the order of their symbols regardless of the creation order.
+        let make_state = |symbols: &[(&str, Type)]| {
+            let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+            for (symbol, ty) in symbols {
+                mb.make_global(GlobalVariableData::new(
+                    symbol.to_string(),
+                    *ty,
+                    Linkage::Private,
+                    false,
+                    None,
+                ));
+            }
+            let sig = Signature::new("test", Linkage::Public, &[], Type::Void);
+            let func_ref = mb.declare_function(sig);
+            let mut builder = mb.build_function::<InsnInserter>(func_ref);
+            let b0 = builder.append_block();
+            builder.switch_to_block(b0);
+            builder.ret(&[]);
+            builder.seal_all();
+            State::new(builder.finish().build(), func_ref, &[])
+        };
+        // Addresses of the globals in the order of their symbols.
+        let addrs = |state: &State| -> Vec<usize> {
+            state
+                .module
+                .ctx
+                .with_gv_store(|s| s.ordered_iter().map(|(gv, _)| state.globals[&gv]).collect())
+        };
+
+        let first = make_state(&[("a", Type::I8), ("b", Type::I32), ("c", Type::I16)]);
+        let second = make_state(&[("c", Type::I16), ("b", Type::I32), ("a", Type::I8)]);
+
+        // Each global is aligned to its natural alignment.
+        assert_eq!(addrs(&first), vec![0, 4, 8]);
+        assert_eq!(addrs(&first), addrs(&second));
+        assert_eq!(first.memory.len(), 10);
+    }
+
+    #[test]
+    fn error_source_loc() {
+        use sonatina_ir::{
+            builder::{test_util::build_test_isa, ModuleBuilder},
+            func_cursor::InsnInserter,
+            module::ModuleCtx,
+            Linkage, Signature,
+        };
+
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let sig = Signature::new("test", Linkage::Public, &[], Type::I32);
+        let func_ref = mb.declare_function(sig);
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let one = builder.make_imm_value(1i32);
+        let zero = builder.make_imm_value(0i32);
+        let v0 = builder.add(one, one);
+        let loc = SourceLoc::new("main.fe", 4, 12);
+        builder.set_source_loc(loc.clone());
+        let v1 = builder.udiv(v0, zero);
+        builder.ret(&[v1]);
+        builder.seal_all();
+        let module = builder.finish().build();
+
+        let mut state = State::new(module, func_ref, &[]);
+        assert_eq!(state.source_loc(), None);
+        let err = loop {
+            if let Err(err) = state.step() {
+                break err;
+            }
+        };
+        assert!(matches!(err, InterpretError::DivByZero(_)));
+        assert_eq!(state.source_loc(), Some(&loc));
+    }
+
+    #[test]
+    fn br_table_no_matching_case() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> i32 {
+            block0:
+                br_table 3.i32 (1.i32 block1) (2.i32 block1);
+
+            block1:
+                return 1.i32;
+        }
+        ";
+
+        let module = parse_module(input);
+        let func_ref = module.iter_functions().next().unwrap();
+        let br_table = module.funcs[func_ref]
+            .layout
+            .first_insn_of(Block(0))
+            .unwrap();
+
+        let state = State::new(module, func_ref, &[]);
+        assert_eq!(state.run(), Err(InterpretError::NoMatchingCase(br_table)));
+    }
+
+    #[test]
+    fn phi_unreachable() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> i32 {
+            block0:
+                jump block2;
+
+            block1:
+                jump block2;
+
+            block2:
+                v0.i32 = phi (1.i32 block1);
+                return v0;
+        }
+        ";
+
+        let module = parse_module(input);
+        let func_ref = module.iter_functions().next().unwrap();
+        let phi = module.funcs[func_ref]
+            .layout
+            .first_insn_of(Block(2))
+            .unwrap();
+
+        let state = State::new(module, func_ref, &[]);
+        assert_eq!(state.run(), Err(InterpretError::Unreachable(phi)));
+    }
+
+    #[test]
+    fn call_type_mismatch() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %callee(v0.i32) -> i32 {
+            block0:
+                return v0;
+        }
+
+        func private %test() -> i32 {
+            block0:
+                v0.i32 = call %callee 1.i32;
+                return v0;
+        }
+        ";
+
+        let mut module = parse_module(input);
+        let func_ref = module.iter_functions().nth(1).unwrap();
+        let func = &mut module.funcs[func_ref];
+        let call = func.layout.first_insn_of(Block(0)).unwrap();
+
+        // The builder rejects the mismatch, so the arg is replaced after building.
+        let arg = func.dfg.make_imm_value(1i64);
+        func.dfg.replace_insn_arg(call, arg, 0);
+
+        let state = State::new(module, func_ref, &[]);
+        assert_eq!(state.run(), Err(InterpretError::TypeMismatch(call)));
+    }
+
+    #[test]
+    fn memory_fault() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> i32 {
+            block0:
+                v0.*i8 = alloca i8;
+                v1.*i32 = bitcast v0;
+                v2.i32 = load @memory v1;
+                return v2;
+        }
+        ";
+
+        let module = parse_module(input);
+        let func_ref = module.iter_functions().next().unwrap();
+        let layout = &module.funcs[func_ref].layout;
+        let load = layout.last_insn_of(Block(0)).unwrap();
+        let load = layout.prev_insn_of(load).unwrap();
+
+        let state = State::new(module, func_ref, &[]);
+
+        assert_eq!(
+            state.run(),
+            Err(InterpretError::MemoryFault {
+                insn: load,
+                addr: 0.into(),
+            })
+        );
+    }
+
+    #[test]
+    fn call() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func public %test_callee(v0.i8) -> i8 {
+            block0:
+                v1.i8 = mul v0 1.i8;
+                return v1;
+        }
+
+        func public %test() -> i8 {
+            block0:
+                v0.i8 = call %test_callee 0.i8;
+                return v0;
+        }
+        ";
+
+        let module = parse_module(input);
+        let func_ref = module.iter_functions().nth(1).unwrap();
+
+        let state = State::new(module, func_ref, &[]);
+
+        let data = state.run().unwrap();
+
+        assert_eq!(data.into_i8(), 0i8);
+    }
+
+    #[test]
+    fn recursive_call() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func public %factorial(v0.i32) -> i32 {
+            block0:
+                v1.i1 = slt v0 2.i32;
+                br v1 block1 block2;
+            block1:
+                return 1.i32;
+            block2:
+                v2.i32 = sub v0 1.i32;
+                v3.i32 = call %factorial v2;
+                v4.i32 = mul v0 v3;
+                return v4;
+        }
+
+        func public %test() -> i32 {
+            block0:
+                v0.i32 = call %factorial 5.i32;
+                return v0;
+        }
+        ";
+
+        let module = parse_module(input);
+        let func_ref = module.iter_functions().nth(1).unwrap();
+
+        let state = State::new(module, func_ref, &[]);
+
+        let data = state.run().unwrap();
+
+        assert_eq!(data.into_i32(), 120i32);
+    }
+
+    #[test]
+    fn stack_overflow() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func public %test() -> i32 {
+            block0:
+                v0.i32 = call %test;
+                return v0;
+        }
+        ";
+
+        let module = parse_module(input);
+        let func_ref = module.iter_functions().next().unwrap();
+        let insn = module.funcs[func_ref]
+            .layout
+            .first_insn_of(Block(0))
+            .unwrap();
+
+        let state = State::new(module, func_ref, &[]).with_call_depth_limit(8);
+
+        assert_eq!(state.run(), Err(InterpretError::StackOverflow(insn)));
+    }
+
+    #[test]
+    fn out_of_gas() {
+        struct InsnCounter {
+            limit: u64,
+            consumed: u64,
+        }
+
+        impl GasMeter for InsnCounter {
+            fn charge(&mut self, _insn: &InsnData) -> Result<(), crate::OutOfGas> {
+                if self.consumed == self.limit {
+                    return Err(crate::OutOfGas);
+                }
+                self.consumed += 1;
+                Ok(())
+            }
+
+            fn consumed(&self) -> u64 {
+                self.consumed
+            }
+        }
+
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> i32 {
+            block0:
+                v0.i32 = add 1.i32 2.i32;
+                v1.i32 = add v0 3.i32;
+                v2.i32 = add v1 4.i32;
+                return v2;
+        }
+        ";
+
+        let module = parse_module(input);
+        let func_ref = module.iter_functions().next().unwrap();
+        let layout = &module.funcs[func_ref].layout;
+        let halted_insn = layout.last_insn_of(Block(0)).unwrap();
+
+        let meter = InsnCounter {
+            limit: 3,
+            consumed: 0,
+        };
+        let state = State::new(module, func_ref, &[]).with_gas_meter(Box::new(meter));
+
+        assert_eq!(
+            state.run(),
+            Err(InterpretError::OutOfGas {
+                insn: halted_insn,
+                consumed: 3
+            })
+        );
+    }
+
+    #[test]
+    fn undef() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> i32 {
+            block0:
+                v0.i32 = add undef.i32 1.i32;
+                v1.i32 = mul v0 2.i32;
+                return v1;
+        }
+        ";
+
+        let (result, stats) = parse_module_make_state(input).run_with_stats();
+        assert_eq!(result.unwrap().into_i32(), 2);
+        assert_eq!(stats.undef_uses, 1);
+    }
+
+    #[test]
+    fn multiple_return_values() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> (i32, i64) {
+            block0:
+                v0.i32 = add 1.i32 2.i32;
+                return v0 -1.i64;
+        }
+        ";
+
+        let result = parse_module_make_state(input).run().unwrap();
+        assert_eq!(
+            result.into_tuple(),
+            vec![EvalResult::I32(3), EvalResult::I64(-1)]
+        );
+    }
+
+    #[test]
+    fn jump() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> i1 {
+            block0:
+                jump block2;
+            block1:
+                return 1.i1;
+            block2:
+                return 0.i1;
+        }
+        ";
+
+        let state = parse_module_make_state(input);
+
+        let boolean = state.run().unwrap();
+
+        assert!(!boolean.into_bool())
+    }
+
+    #[test]
+    fn branch() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> i8 {
+            block0:
+                br 1.i1 block1 block2;
+            block1:
+                return 1.i8;
+            block2:
+                return 2.i8;
+        }
+        ";
+
+        let state = parse_module_make_state(input);
+
+        let result = state.run().unwrap();
+
+        assert_eq!(result.into_i8(), 1i8);
+    }
+
+    #[test]
+    fn br_table() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> i64 {
+            block0:
+                br_table 1.i64 (0.i64 block1) (1.i64 block2);
+            block1:
+                return 1.i64;
+            block2:
+                return 2.i64;
+            block3:
+                return 3.i64;
+        }
+        ";
+
+        let state = parse_module_make_state(input);
+
+        let result = state.run().unwrap();
+
+        assert_eq!(result.into_i64(), 2i64);
+    }
+
+    #[test]
+    fn phi() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> i8 {
+            block0:
+                jump block1;
+            block1:
+                jump block2;
+            block2:
+                v0.i8 = phi (1.i8 block0) (-1.i8 block1);
+                return v0;
+        }
+        ";
+
+        let state = parse_module_make_state(input);
+
+        let result = state.run().unwrap();
+
+        assert_eq!(result.into_i8(), -1i8);
+    }
+
+    #[test]
+    fn gep() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        type %s1 = {i32, i64, i1};
+
+        func private %test() -> *i1 {
+            block0:
+                v0.*%s1 = alloca %s1;
+                v1.*i1 = gep v0 2.i8;
+                return v1;
+        }
+        ";
+
+        let state = parse_module_make_state(input);
+
+        let elem_ptr = state.run().unwrap();
+
+        // The `i64` field is aligned to 8 bytes, so the `i1` field starts at 16.
+        assert_eq!(elem_ptr.into_usize(), 16usize);
+    }
+
+    #[test]
+    fn cast_width_mismatch() {
+        for (ty, cast) in [
+            ("i32", "sext 1.i32"),
+            ("i32", "zext 1.i32"),
+            ("i32", "trunc 1.i32"),
+            ("i64", "trunc 1.i32"),
+        ] {
+            let input = format!(
+                "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> void {{
+            block0:
+                v0.{ty} = {cast};
+                return;
+        }}
+        "
+            );
+
+            let module = parse_module(&input);
+            let func_ref = module.iter_functions().next().unwrap();
+            let insn = module.funcs[func_ref]
+                .layout
+                .first_insn_of(Block(0))
+                .unwrap();
+
+            let state = State::new(module, func_ref, &[]);
+            assert_eq!(
+                state.run(),
+                Err(InterpretError::TypeMismatch(insn)),
+                "{ty} = {cast}"
+            );
+        }
+    }
+
+    #[test]
+    fn gep_index_out_of_bounds() {
+        use sonatina_ir::{
+            builder::test_util::test_func_builder,
+            func_cursor::{CursorLocation, FuncCursor},
+            ValueData,
+        };
+
+        // Struct fields out of range, an index into a scalar, an array element out of range,
+        // and negative and huge indices. The parser rejects them since it can't infer the result
+        // type of the gep, so the gep is inserted with the result type given directly.
+        let indices: [&[Immediate]; 6] = [
+            &[Immediate::I8(3)],
+            &[Immediate::I8(0), Immediate::I8(0)],
+            &[Immediate::I8(3), Immediate::I8(2)],
+            &[Immediate::I8(0), Immediate::I8(-1)],
+            &[Immediate::I8(-1)],
+            &[
+                Immediate::I8(3),
+                Immediate::I128(100_000_000_000_000_000_000),
+            ],
+        ];
+        for index in indices {
+            let mut builder = test_func_builder(&[], Type::Void);
+            let b0 = builder.append_block();
+            builder.switch_to_block(b0);
+
+            let arr = builder.declare_array_type(Type::I8, 2);
+            let s1 =
+                builder.declare_struct_type("s1", &[Type::I32, Type::I64, Type::I1, arr], false);
+            let ptr_i1 = builder.ptr_type(Type::I1);
+            let base = builder.alloca(s1);
+
+            let mut args = vec![base];
+            args.extend(index.iter().map(|imm| builder.make_imm_value(*imm)));
+            let gep = builder
+                .cursor
+                .insert_insn_data(&mut builder.func, InsnData::Gep { args: args.into() });
+            let result = builder.func.dfg.make_value(ValueData::Insn {
+                insn: gep,
+                ty: ptr_i1,
+            });
+            builder.func.dfg.attach_result(gep, result);
+            builder.cursor.set_location(CursorLocation::At(gep));
+            builder.ret(&[]);
+            builder.seal_all();
+
+            let module = builder.finish().build();
+            let func_ref = module.iter_functions().next().unwrap();
+            let state = State::new(module, func_ref, &[]);
+            assert_eq!(state.run(), Err(InterpretError::IndexOutOfBounds(gep)));
+        }
+    }
+
+    #[test]
+    fn call_multi_return() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> i64 {
+            block0:
+                v0.i32, v1.i64 = call %pair 3.i32;
+                v2.i64 = sext v0;
+                v3.i64 = add v1 v2;
+                return v3;
+        }
+
+        func private %pair(v0.i32) -> (i32, i64) {
+            block0:
+                v1.i32 = add v0 1.i32;
+                return v1 -10.i64;
+        }
+        ";
+
+        let result = parse_module_make_state(input).run().unwrap();
+        assert_eq!(result.into_i64(), -6i64);
+    }
+
+    #[test]
+    fn call_result_count_mismatch() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        func private %single() -> i32 {
+            block0:
+                return 1.i32;
+        }
+
+        func private %pair() -> (i32, i64) {
+            block0:
+                return 1.i32 2.i64;
+        }
+
+        func private %test() -> i32 {
+            block0:
+                v0.i32 = call %single;
+                return v0;
+        }
+        ";
+
+        let mut module = parse_module(input);
+        let mut funcs = module.iter_functions();
+        let (pair, test) = (funcs.nth(1).unwrap(), funcs.next().unwrap());
+
+        // Neither the builder nor the parser makes such a call, so retarget the call directly.
+        let func = &mut module.funcs[test];
+        let call = func.layout.first_insn_of(Block(0)).unwrap();
+        func.dfg.replace_insn(
+            call,
+            InsnData::Call {
+                func: pair,
+                args: Default::default(),
+                ret_tys: [Type::I32].as_slice().into(),
+            },
+        );
+        let ret = module.funcs[pair].layout.last_insn_of(Block(0)).unwrap();
+
+        let state = State::new(module, test, &[]);
+        assert_eq!(state.run(), Err(InterpretError::TypeMismatch(ret)));
+    }
+
+    #[test]
+    fn ret_void() {
+        let input = "
+        target = \"evm-ethereum-london\"
+
+        type %s1 = {i32, i64, i1};
 
         func private %test() -> void {
             block0:
@@ -548,12 +2069,11 @@ mod test {
 
         let state = parse_module_make_state(input);
 
-        let arg = state.run();
+        let arg = state.run().unwrap();
 
         arg.into_void();
     }
 
-    #[cfg(target_arch = "aarch64")]
     #[test]
     fn gep_ptr_ty() {
         let input = "
@@ -569,9 +2089,10 @@ mod test {
 
         let state = parse_module_make_state(input);
 
-        let elem_ptr = state.run();
+        let elem_ptr = state.run().unwrap();
 
-        assert_eq!(elem_ptr.into_usize(), 16usize);
+        // Pointers have the size of the pointer type of the target, which is `i256` on EVM.
+        assert_eq!(elem_ptr.into_usize(), 64usize);
     }
 
     #[test]
@@ -591,8 +2112,98 @@ mod test {
 
         let state = parse_module_make_state(input);
 
-        let elem_ptr = state.run();
+        let elem_ptr = state.run().unwrap();
 
         assert_eq!(elem_ptr.into_usize(), 11usize);
     }
+
+    const TRACED_INPUT: &str = "
+        target = \"evm-ethereum-london\"
+
+        func private %test() -> i32 {
+            block0:
+                v0.i32 = add 1.i32 2.i32;
+                jump block1;
+
+            block1:
+                v1.i32 = phi (v0 block0);
+                v2.i32 = mul v1 3.i32;
+                return v2;
+        }
+        ";
+
+    #[test]
+    fn observer() {
+        use std::{cell::RefCell, rc::Rc};
+
+        let trace = Rc::new(RefCell::new(Vec::new()));
+        let observer = {
+            let trace = trace.clone();
+            move |insn: Insn, args: &[Immediate], result: Option<Immediate>| {
+                trace.borrow_mut().push((insn, args.to_vec(), result))
+            }
+        };
+
+        let state = parse_module_make_state(TRACED_INPUT).with_observer(Box::new(observer));
+        assert_eq!(state.run().unwrap().into_i32(), 9);
+
+        let trace: Vec<_> = trace
+            .borrow()
+            .iter()
+            .map(|(_, args, result)| (args.clone(), *result))
+            .collect();
+        assert_eq!(
+            trace,
+            vec![
+                (vec![1i32.into(), 2i32.into()], Some(3i32.into())),
+                (vec![], None),
+                (vec![3i32.into()], Some(3i32.into())),
+                (vec![3i32.into(), 3i32.into()], Some(9i32.into())),
+                (vec![9i32.into()], None),
+            ]
+        );
+    }
+
+    #[test]
+    fn print_tracer() {
+        use std::{cell::RefCell, io, rc::Rc};
+
+        #[derive(Clone, Default)]
+        struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+        impl io::Write for SharedBuf {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buf = SharedBuf::default();
+        let tracer = crate::PrintTracer::new(buf.clone());
+        let state = parse_module_make_state(TRACED_INPUT).with_observer(Box::new(tracer));
+        state.run().unwrap();
+
+        let trace = String::from_utf8(buf.0.take()).unwrap();
+        let comments: Vec<_> = trace
+            .lines()
+            .map(|line| line.split_once("  # ").map(|(_, comment)| comment))
+            .collect();
+        assert_eq!(
+            comments,
+            vec![
+                Some("1.i32, 2.i32 => 3.i32"),
+                None,
+                Some("3.i32 => 3.i32"),
+                Some("3.i32, 3.i32 => 9.i32"),
+                Some("9.i32"),
+            ]
+        );
+
+        let lines: Vec<_> = trace.lines().collect();
+        assert!(lines[0].ends_with(" = add 1.i32 2.i32;  # 1.i32, 2.i32 => 3.i32"));
+        assert_eq!(lines[1], "jump block1;");
+    }
 }
@@ -1,32 +1,16 @@
-use std::mem;
-
 use sonatina_ir::{
     module::ModuleCtx,
     types::{CompoundType, CompoundTypeData},
-    Type, I256,
+    Type, TypeLayout, I256,
 };
 
 pub fn size_of_ty_data(ctx: &ModuleCtx, ty: Type) -> usize {
-    match ty {
-        Type::I1 => mem::size_of::<bool>(),
-        Type::I8 => mem::size_of::<i8>(),
-        Type::I16 => mem::size_of::<i16>(),
-        Type::I32 => mem::size_of::<i32>(),
-        Type::I64 => mem::size_of::<i64>(),
-        Type::I128 => mem::size_of::<i128>(),
-        Type::I256 => 32,
-        Type::Compound(cmpd_ty) => {
-            use CompoundTypeData::*;
-            ctx.with_ty_store(|s| match s.resolve_compound(cmpd_ty) {
-                Array { len, elem } => len * size_of_ty_data(ctx, *elem),
-                Ptr(_) => mem::size_of::<usize>(),
-                Struct(data) => data.fields.iter().fold(0usize, |acc, field_ty| {
-                    acc + size_of_ty_data(ctx, *field_ty)
-                }),
-            })
-        }
-        Type::Void => mem::size_of::<()>(),
-    }
+    TypeLayout::of(ty, ctx).size
+}
+
+/// Returns the natural alignment of the type in bytes.
+pub fn align_of_ty_data(ctx: &ModuleCtx, ty: Type) -> usize {
+    TypeLayout::of(ty, ctx).align
 }
 
 fn to_cmpd_ty(ty: Type) -> Option<CompoundType> {
@@ -36,34 +20,50 @@ fn to_cmpd_ty(ty: Type) -> Option<CompoundType> {
     }
 }
 
+/// Computes the address of the element of `ptr_ty` indexed by `args` from `base_addr`.
+///
+/// Returns `None` if an index is negative or out of the elements of an array, or the fields of
+/// a struct or a union, if there are more indices than the nesting depth of the pointee type,
+/// or if the address overflows.
 pub fn gep(
     ctx: &ModuleCtx,
     base_addr: I256,
     ptr_ty: Type,
     args: impl Iterator<Item = I256>,
-) -> I256 {
+) -> Option<I256> {
     let pointee_ty = ctx.with_ty_store(|s| s.deref(ptr_ty)).unwrap();
-    debug_assert!(!pointee_ty.is_integral() && !ctx.with_ty_store(|s| s.is_ptr(pointee_ty)));
+    debug_assert!(!pointee_ty.is_integral() && !pointee_ty.is_pointer(ctx));
     let mut cmpd_ty = to_cmpd_ty(pointee_ty);
 
     let mut offset = 0usize;
 
     for arg in args {
-        let index = arg.to_u256().as_usize();
+        // A negative index is a huge `U256`, so it's rejected as well.
+        let index = usize::try_from(arg.to_u256()).ok()?;
+        let ty = Type::Compound(cmpd_ty?);
         let cmpd_ty_data = ctx.with_ty_store(|s| s.resolve_compound(cmpd_ty.unwrap()).clone());
         match cmpd_ty_data {
-            CompoundTypeData::Array { elem, .. } => {
-                offset += index * size_of_ty_data(ctx, elem);
+            CompoundTypeData::Array { elem, len } => {
+                if index >= len {
+                    return None;
+                }
+                let elem_offset = index.checked_mul(size_of_ty_data(ctx, elem))?;
+                offset = offset.checked_add(elem_offset)?;
                 cmpd_ty = to_cmpd_ty(elem);
             }
             CompoundTypeData::Struct(data) => {
-                for ty in &data.fields[..index] {
-                    offset += size_of_ty_data(ctx, *ty);
-                }
+                let field_offset = TypeLayout::field_offset(ty, index, ctx).ok()?;
+                offset = offset.checked_add(field_offset)?;
                 cmpd_ty = to_cmpd_ty(data.fields[index]);
             }
-            _ => unreachable!(),
+            CompoundTypeData::Union(variants) => {
+                // All variants are located at the start of the union.
+                cmpd_ty = to_cmpd_ty(*variants.get(index)?);
+            }
+            CompoundTypeData::Ptr(_) => return None,
         }
     }
-    (base_addr.to_u256().as_usize() + offset).into()
+
+    let base_addr = usize::try_from(base_addr.to_u256()).ok()?;
+    Some(base_addr.checked_add(offset)?.into())
 }
@@ -1,7 +1,4 @@
-use std::mem;
-
-use byteorder::{BigEndian, WriteBytesExt};
-use sonatina_ir::{module::ModuleCtx, Type, I256, U256};
+use sonatina_ir::{isa::Endian, module::ModuleCtx, Type, TypeLayout, I256, U256};
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
 pub enum EvalValue {
@@ -26,55 +23,56 @@ impl EvalValue {
         i256
     }
 
+    /// Reads a value of `ty` from `b` in the byte order of the target ISA.
+    /// Integral values are sign extended, and pointers are zero extended.
     pub fn deserialize(ctx: &ModuleCtx, ty: Type, b: &[u8]) -> Option<Self> {
-        macro_rules! from_be_bytes {
-            ($integral:ty) => {
-                <$integral>::from_be_bytes(b.try_into().unwrap()).into()
-            };
-        }
-
+        let endian = ctx.endian();
         Some(Self::Literal(match ty {
             Type::I1 => (b[0] & 0b1).into(),
-            Type::I8 => from_be_bytes!(i8),
-            Type::I16 => from_be_bytes!(i16),
-            Type::I32 => from_be_bytes!(i32),
-            Type::I64 => from_be_bytes!(i64),
-            Type::I128 => from_be_bytes!(i128),
-            Type::I256 => I256::from_u256(U256::from_big_endian(b)),
+            Type::I8 | Type::I16 | Type::I32 | Type::I64 | Type::I128 | Type::I256 => {
+                match endian {
+                    Endian::Big => I256::from_be_slice(b),
+                    Endian::Little => I256::from_le_slice(b),
+                }
+            }
+            Type::Int(width) => {
+                let i256 = match endian {
+                    Endian::Big => I256::from_be_slice(b),
+                    Endian::Little => I256::from_le_slice(b),
+                };
+                i256.trunc_to_bits(width.bits() as usize)
+            }
             Type::Compound(ty) => {
                 debug_assert!(ctx.with_ty_store(|s| s.resolve_compound(ty).is_ptr()));
-                debug_assert!(b.len() == mem::size_of::<usize>());
-                from_be_bytes!(usize)
+                debug_assert_eq!(b.len(), TypeLayout::of(Type::Compound(ty), ctx).size);
+                let addr = match endian {
+                    Endian::Big => U256::from_big_endian(b),
+                    Endian::Little => U256::from_little_endian(b),
+                };
+                I256::from_u256(addr)
             }
             Type::Void => return None,
         }))
     }
 
-    pub fn serialize(&self, ctx: &ModuleCtx, ty: Type, mut buff: &mut [u8]) {
-        macro_rules! to_be_bytes {
-            ($bytes:expr) => {{
-                let data = self.i256().trunc_to_i128();
-                buff.write_int128::<BigEndian>(data, $bytes).unwrap()
-            }};
+    /// Writes the value as `ty` to `buff` in the byte order of the target ISA.
+    /// The value is truncated to the length of `buff`.
+    pub fn serialize(&self, ctx: &ModuleCtx, ty: Type, buff: &mut [u8]) {
+        if ty == Type::Void {
+            return;
+        }
+        if let Type::Compound(ty) = ty {
+            debug_assert!(ctx.with_ty_store(|s| s.resolve_compound(ty).is_ptr()));
         }
 
-        match ty {
-            Type::I1 => to_be_bytes!(1),
-            Type::I8 => to_be_bytes!(1),
-            Type::I16 => to_be_bytes!(2),
-            Type::I32 => to_be_bytes!(4),
-            Type::I64 => to_be_bytes!(8),
-            Type::I128 => to_be_bytes!(16),
-            Type::I256 => self.i256().to_u256().write_as_big_endian(buff),
-            Type::Compound(ty) => {
-                debug_assert!(ctx.with_ty_store(|s| s.resolve_compound(ty).is_ptr()));
-                to_be_bytes!(mem::size_of::<usize>());
-            }
-            Type::Void => (),
+        match ctx.endian() {
+            Endian::Big => self.i256().write_be_bytes(buff),
+            Endian::Little => self.i256().write_le_bytes(buff),
         }
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EvalResult {
     I1(bool),
     I8(i8),
@@ -83,8 +81,21 @@ pub enum EvalResult {
     I64(i64),
     I128(i128),
     I256(I256),
+    /// A value of an arbitrary width integer type, sign extended from `bits` bits.
+    Int {
+        bits: u16,
+        value: I256,
+    },
     Void,
     Addr(usize),
+    /// The return values of a function returning multiple values.
+    Tuple(Vec<EvalResult>),
+    /// The execution is halted by `stop`.
+    Stop,
+    /// The execution is halted by `return_data` with the output data.
+    ReturnData(Vec<u8>),
+    /// The execution is halted by `revert` with the output data.
+    Revert(Vec<u8>),
 }
 
 impl EvalResult {
@@ -98,8 +109,12 @@ impl EvalResult {
             Type::I64 => I64(i256.trunc_to_i64()),
             Type::I128 => I128(i256.trunc_to_i128()),
             Type::I256 => I256(i256),
+            Type::Int(width) => Int {
+                bits: width.bits(),
+                value: i256.trunc_to_bits(width.bits() as usize),
+            },
             Type::Compound(_) => {
-                debug_assert!(ctx.with_ty_store(|s| s.is_ptr(ty)));
+                debug_assert!(ty.is_pointer(ctx));
                 Addr(i256.to_u256().as_usize())
             }
             _ => unreachable!(),
@@ -155,6 +170,13 @@ impl EvalResult {
         i256
     }
 
+    pub fn into_int(self) -> I256 {
+        let Self::Int { value, .. } = self else {
+            panic!("not an arbitrary width integer")
+        };
+        value
+    }
+
     pub fn into_void(self) {
         let Self::Void = self else {
             panic!("not a void")
@@ -167,4 +189,25 @@ impl EvalResult {
         };
         usize
     }
+
+    pub fn into_tuple(self) -> Vec<EvalResult> {
+        let Self::Tuple(values) = self else {
+            panic!("not a tuple")
+        };
+        values
+    }
+
+    pub fn into_return_data(self) -> Vec<u8> {
+        let Self::ReturnData(data) = self else {
+            panic!("not a return data")
+        };
+        data
+    }
+
+    pub fn into_revert(self) -> Vec<u8> {
+        let Self::Revert(data) = self else {
+            panic!("not a revert")
+        };
+        data
+    }
 }
@@ -0,0 +1,96 @@
+//! This module contains function attributes, which attach information to a function for passes
+//! and frontends, e.g., optimization hints.
+use std::{collections::BTreeMap, fmt};
+
+/// The attribute that prevents the function from being inlined into its callers.
+pub const NOINLINE: &str = "noinline";
+
+/// A value of a function attribute.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AttrValue {
+    /// An attribute whose presence is the information, e.g., [`NOINLINE`].
+    Flag,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+}
+
+impl AttrValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Str(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AttrValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Flag => Ok(()),
+            Self::Bool(value) => write!(f, "{value}"),
+            Self::Int(value) => write!(f, "{value}"),
+            Self::Str(value) => write!(f, "{value:?}"),
+        }
+    }
+}
+
+/// Attributes of a function keyed by name. The attributes are iterated in the order of the
+/// names, so the output depending on them is deterministic.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AttrMap {
+    attrs: BTreeMap<String, AttrValue>,
+}
+
+impl AttrMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the attribute `name` to `value`, and returns the previous value if any.
+    pub fn insert(&mut self, name: impl Into<String>, value: AttrValue) -> Option<AttrValue> {
+        self.attrs.insert(name.into(), value)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AttrValue> {
+        self.attrs.get(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<AttrValue> {
+        self.attrs.remove(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.attrs.contains_key(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &AttrValue)> {
+        self.attrs
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.attrs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.attrs.is_empty()
+    }
+}
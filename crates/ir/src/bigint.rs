@@ -2,6 +2,84 @@ use std::{cmp, fmt, hash, ops};
 
 pub type U256 = primitive_types::U256;
 
+/// Arithmetic helpers for [`U256`] that `primitive_types` doesn't provide.
+///
+/// `checked_add`, `checked_mul`, `overflowing_add`, `leading_zeros` and `bit` are inherent
+/// methods of [`U256`].
+pub trait U256Ext: Sized {
+    /// Addition that wraps around at `2^256`.
+    fn wrapping_add(self, rhs: Self) -> Self;
+
+    /// Subtraction that wraps around at `2^256`.
+    fn wrapping_sub(self, rhs: Self) -> Self;
+
+    /// Multiplication that wraps around at `2^256`.
+    fn wrapping_mul(self, rhs: Self) -> Self;
+
+    /// Returns the quotient and the remainder of the division.
+    ///
+    /// # Panics
+    /// Panics if `rhs` is zero.
+    fn div_rem(self, rhs: Self) -> (Self, Self);
+
+    /// Returns the number of ones in the binary representation.
+    fn count_ones(self) -> u32;
+
+    /// Creates a value from its representation as a byte array in big endian.
+    fn from_be_bytes(bytes: [u8; 32]) -> Self;
+
+    /// Creates a value from its representation as a byte array in little endian.
+    fn from_le_bytes(bytes: [u8; 32]) -> Self;
+
+    /// Returns the representation as a byte array in big endian.
+    fn to_be_bytes(self) -> [u8; 32];
+
+    /// Returns the representation as a byte array in little endian.
+    fn to_le_bytes(self) -> [u8; 32];
+}
+
+impl U256Ext for U256 {
+    fn wrapping_add(self, rhs: Self) -> Self {
+        self.overflowing_add(rhs).0
+    }
+
+    fn wrapping_sub(self, rhs: Self) -> Self {
+        self.overflowing_sub(rhs).0
+    }
+
+    fn wrapping_mul(self, rhs: Self) -> Self {
+        self.overflowing_mul(rhs).0
+    }
+
+    fn div_rem(self, rhs: Self) -> (Self, Self) {
+        self.div_mod(rhs)
+    }
+
+    fn count_ones(self) -> u32 {
+        self.0.iter().map(|limb| limb.count_ones()).sum()
+    }
+
+    fn from_be_bytes(bytes: [u8; 32]) -> Self {
+        Self::from_big_endian(&bytes)
+    }
+
+    fn from_le_bytes(bytes: [u8; 32]) -> Self {
+        Self::from_little_endian(&bytes)
+    }
+
+    fn to_be_bytes(self) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        self.write_as_big_endian(&mut bytes);
+        bytes
+    }
+
+    fn to_le_bytes(self) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        self.write_as_little_endian(&mut bytes);
+        bytes
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct I256 {
     is_negative: bool,
@@ -16,21 +94,96 @@ const I256_MASK: U256 = primitive_types::U256([
 ]);
 
 impl I256 {
+    /// Two's complement addition that wraps around on overflow.
+    /// The flag is set if the addition of the bit patterns as unsigned integers carries out.
     pub fn overflowing_add(self, rhs: Self) -> (Self, bool) {
         let (val, flag) = self.to_u256().overflowing_add(rhs.to_u256());
         (Self::from_u256(val), flag)
     }
 
+    /// Two's complement subtraction that wraps around on overflow.
+    /// The flag is set if the subtraction of the bit patterns as unsigned integers borrows.
     pub fn overflowing_sub(self, rhs: Self) -> (Self, bool) {
         let (val, flag) = self.to_u256().overflowing_sub(rhs.to_u256());
         (Self::from_u256(val), flag)
     }
 
+    /// Two's complement multiplication that wraps around on overflow.
+    /// The flag is set if the multiplication of the bit patterns as unsigned integers overflows.
     pub fn overflowing_mul(self, rhs: Self) -> (Self, bool) {
         let (val, flag) = self.to_u256().overflowing_mul(rhs.to_u256());
         (Self::from_u256(val), flag)
     }
 
+    /// Similar to [`Self::overflowing_add`], but the flag is set if the signed result
+    /// overflows.
+    pub fn overflowing_add_signed(self, rhs: Self) -> (Self, bool) {
+        let (res, _) = self.overflowing_add(rhs);
+        let overflow = self.is_negative == rhs.is_negative && res.is_negative != self.is_negative;
+        (res, overflow)
+    }
+
+    /// Similar to [`Self::overflowing_sub`], but the flag is set if the signed result
+    /// overflows.
+    pub fn overflowing_sub_signed(self, rhs: Self) -> (Self, bool) {
+        let (res, _) = self.overflowing_sub(rhs);
+        let overflow = self.is_negative != rhs.is_negative && res.is_negative != self.is_negative;
+        (res, overflow)
+    }
+
+    /// Similar to [`Self::overflowing_mul`], but the flag is set if the signed result
+    /// overflows.
+    pub fn overflowing_mul_signed(self, rhs: Self) -> (Self, bool) {
+        match self.checked_mul(rhs) {
+            Some(res) => (res, false),
+            None => (self.overflowing_mul(rhs).0, true),
+        }
+    }
+
+    pub fn wrapping_add(self, rhs: Self) -> Self {
+        self.overflowing_add(rhs).0
+    }
+
+    pub fn wrapping_sub(self, rhs: Self) -> Self {
+        self.overflowing_sub(rhs).0
+    }
+
+    pub fn wrapping_mul(self, rhs: Self) -> Self {
+        self.overflowing_mul(rhs).0
+    }
+
+    /// Signed addition. Returns `None` if overflow occurs.
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        let (res, overflow) = self.overflowing_add_signed(rhs);
+        (!overflow).then_some(res)
+    }
+
+    /// Signed subtraction. Returns `None` if overflow occurs.
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        let (res, overflow) = self.overflowing_sub_signed(rhs);
+        (!overflow).then_some(res)
+    }
+
+    /// Signed multiplication. Returns `None` if overflow occurs.
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        let (abs, overflow) = self.abs.overflowing_mul(rhs.abs);
+        if overflow {
+            return None;
+        }
+
+        if self.is_negative != rhs.is_negative && !abs.is_zero() {
+            // The absolute value of the minimum is `I256_MASK + 1`.
+            (abs <= I256_MASK + U256::one()).then(|| Self::make_negative(abs))
+        } else {
+            (abs <= I256_MASK).then(|| Self::make_positive(abs))
+        }
+    }
+
+    /// Signed division that truncates toward zero.
+    /// `MIN / -1` wraps around to `MIN`, and the overflow flag is set.
+    ///
+    /// # Panics
+    /// Panics if `rhs` is zero.
     pub fn overflowing_div(self, rhs: I256) -> (I256, bool) {
         if rhs.is_zero() {
             panic!("attempt to divide by zero");
@@ -48,6 +201,122 @@ impl I256 {
         }
     }
 
+    /// Signed remainder whose sign follows the dividend, e.g., `-7 % 2 == -1`.
+    /// `MIN % -1` is defined as `0`, and the overflow flag is set.
+    ///
+    /// # Panics
+    /// Panics if `rhs` is zero.
+    pub fn overflowing_rem(self, rhs: I256) -> (I256, bool) {
+        if rhs.is_zero() {
+            panic!("attempt to calculate the remainder with a divisor of zero");
+        }
+
+        if self.is_minimum() && rhs.is_negative && rhs.abs == U256::one() {
+            return (I256::zero(), true);
+        }
+
+        let rem_abs = self.abs % rhs.abs;
+
+        if self.is_negative && !rem_abs.is_zero() {
+            (I256::make_negative(rem_abs), false)
+        } else {
+            (I256::make_positive(rem_abs), false)
+        }
+    }
+
+    /// Returns the quotient and the remainder of the signed division, which are the results of
+    /// [`Self::overflowing_div`] and [`Self::overflowing_rem`].
+    ///
+    /// # Panics
+    /// Panics if `rhs` is zero.
+    pub fn div_rem(self, rhs: I256) -> (I256, I256) {
+        (self.overflowing_div(rhs).0, self.overflowing_rem(rhs).0)
+    }
+
+    /// Returns the number of ones in the two's complement representation.
+    pub fn count_ones(self) -> u32 {
+        self.to_u256().count_ones()
+    }
+
+    /// Returns the number of leading zeros in the two's complement representation, which is
+    /// always `0` for negative values.
+    pub fn leading_zeros(self) -> u32 {
+        self.to_u256().leading_zeros()
+    }
+
+    /// Creates a value from its two's complement representation as a byte array in big endian.
+    pub fn from_be_bytes(bytes: [u8; 32]) -> Self {
+        Self::from_u256(U256::from_be_bytes(bytes))
+    }
+
+    /// Creates a value from its two's complement representation as a byte array in little
+    /// endian.
+    pub fn from_le_bytes(bytes: [u8; 32]) -> Self {
+        Self::from_u256(U256::from_le_bytes(bytes))
+    }
+
+    /// Returns the two's complement representation as a byte array in big endian.
+    pub fn to_be_bytes(self) -> [u8; 32] {
+        self.to_u256().to_be_bytes()
+    }
+
+    /// Returns the two's complement representation as a byte array in little endian.
+    pub fn to_le_bytes(self) -> [u8; 32] {
+        self.to_u256().to_le_bytes()
+    }
+
+    /// Reads a two's complement value of `bytes.len()` bytes in big endian, and sign extends it.
+    ///
+    /// # Panics
+    /// Panics if `bytes` is longer than 32 bytes.
+    pub fn from_be_slice(bytes: &[u8]) -> Self {
+        assert!(bytes.len() <= 32, "too many bytes for I256");
+        let mut buf = [sign_fill(bytes.first()); 32];
+        buf[32 - bytes.len()..].copy_from_slice(bytes);
+        Self::from_be_bytes(buf)
+    }
+
+    /// Reads a two's complement value of `bytes.len()` bytes in little endian, and sign extends
+    /// it.
+    ///
+    /// # Panics
+    /// Panics if `bytes` is longer than 32 bytes.
+    pub fn from_le_slice(bytes: &[u8]) -> Self {
+        assert!(bytes.len() <= 32, "too many bytes for I256");
+        let mut buf = [sign_fill(bytes.last()); 32];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        Self::from_le_bytes(buf)
+    }
+
+    /// Writes the value to `buf` in big endian, truncating it to `buf.len()` bytes.
+    ///
+    /// # Panics
+    /// Panics if `buf` is longer than 32 bytes.
+    pub fn write_be_bytes(self, buf: &mut [u8]) {
+        assert!(buf.len() <= 32, "too many bytes for I256");
+        let len = buf.len();
+        buf.copy_from_slice(&self.to_be_bytes()[32 - len..]);
+    }
+
+    /// Writes the value to `buf` in little endian, truncating it to `buf.len()` bytes.
+    ///
+    /// # Panics
+    /// Panics if `buf` is longer than 32 bytes.
+    pub fn write_le_bytes(self, buf: &mut [u8]) {
+        assert!(buf.len() <= 32, "too many bytes for I256");
+        let len = buf.len();
+        buf.copy_from_slice(&self.to_le_bytes()[..len]);
+    }
+
+    /// Returns the `n`-th bit of the two's complement representation.
+    ///
+    /// # Panics
+    /// Panics if `n` is greater than or equal to `256`.
+    pub fn bit(self, n: usize) -> bool {
+        assert!(n < 256, "bit index out of range");
+        self.to_u256().bit(n)
+    }
+
     pub fn zero() -> Self {
         Self::from_u256(U256::zero())
     }
@@ -99,6 +368,22 @@ impl I256 {
         self.to_u256().low_u128() as i128
     }
 
+    /// Truncates the value to the low `bits` bits, and sign extends it back to 256 bits.
+    pub fn trunc_to_bits(self, bits: usize) -> Self {
+        debug_assert!(bits > 0);
+        if bits >= 256 {
+            return self;
+        }
+
+        let mask = (U256::one() << bits) - U256::one();
+        let low = self.to_u256() & mask;
+        if low.bit(bits - 1) {
+            Self::from_u256(low | !mask)
+        } else {
+            Self::from_u256(low)
+        }
+    }
+
     pub fn is_positive(&self) -> bool {
         !self.is_negative && !self.is_zero()
     }
@@ -230,6 +515,14 @@ impl From<bool> for I256 {
     }
 }
 
+/// Returns the byte that sign extends a value whose most significant byte is `msb`.
+fn sign_fill(msb: Option<&u8>) -> u8 {
+    match msb {
+        Some(msb) if msb & 0x80 != 0 => 0xff,
+        _ => 0,
+    }
+}
+
 macro_rules! impl_from {
     ($ty:ty, signed) => {
         impl From<$ty> for I256 {
@@ -277,3 +570,215 @@ impl_from!(u32, unsigned);
 impl_from!(u64, unsigned);
 impl_from!(u128, unsigned);
 impl_from!(usize, unsigned);
+
+/// Serialized as the 32 bytes of the two's complement representation in big endian.
+#[cfg(feature = "serde")]
+impl serde::Serialize for I256 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_u256().to_be_bytes().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for I256 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = <[u8; 32]>::deserialize(deserializer)?;
+        Ok(Self::from_u256(U256::from_be_bytes(bytes)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u256(hex: &str) -> U256 {
+        U256::from_str_radix(hex, 16).unwrap()
+    }
+
+    fn min() -> I256 {
+        I256::from_u256(U256::one() << 255)
+    }
+
+    fn max() -> I256 {
+        I256::from_u256(I256_MASK)
+    }
+
+    #[test]
+    fn u256_wrapping() {
+        assert_eq!(U256::MAX.wrapping_add(2.into()), U256::one());
+        assert_eq!(U256::zero().wrapping_sub(U256::one()), U256::MAX);
+        assert_eq!(
+            u256("8000000000000000000000000000000000000000000000000000000000000001")
+                .wrapping_mul(4.into()),
+            U256::from(4)
+        );
+    }
+
+    #[test]
+    fn u256_div_rem() {
+        let lhs = u256("fedcba9876543210fedcba9876543210fedcba9876543210fedcba9876543210");
+        let (quot, rem) = lhs.div_rem(u256("100000000000000000000000000000000"));
+        assert_eq!(quot, u256("fedcba9876543210fedcba9876543210"));
+        assert_eq!(rem, u256("fedcba9876543210fedcba9876543210"));
+    }
+
+    #[test]
+    fn u256_bits() {
+        assert_eq!(U256::MAX.count_ones(), 256);
+        assert_eq!(u256("f0f0").count_ones(), 8);
+        assert_eq!(U256::one().leading_zeros(), 255);
+        assert!(u256("8000000000000000000000000000000000000000000000000000000000000000").bit(255));
+    }
+
+    #[test]
+    fn i256_overflowing() {
+        assert_eq!(max().overflowing_add(I256::one()), (min(), false));
+        assert_eq!(min().overflowing_sub(I256::one()), (max(), false));
+        assert_eq!(
+            I256::all_one().overflowing_add(I256::one()),
+            (I256::zero(), true)
+        );
+        assert_eq!(
+            I256::zero().overflowing_sub(I256::one()),
+            (I256::all_one(), true)
+        );
+        assert_eq!(min().overflowing_mul(I256::all_one()), (min(), true));
+        assert_eq!(max().overflowing_mul(I256::all_one()), (-max(), true));
+        assert_eq!(
+            I256::from(2).overflowing_mul(I256::from(3)),
+            (I256::from(6), false)
+        );
+    }
+
+    #[test]
+    fn i256_overflowing_signed() {
+        assert_eq!(max().overflowing_add_signed(I256::one()), (min(), true));
+        assert_eq!(min().overflowing_sub_signed(I256::one()), (max(), true));
+        assert_eq!(
+            I256::all_one().overflowing_add_signed(I256::one()),
+            (I256::zero(), false)
+        );
+        assert_eq!(
+            I256::zero().overflowing_sub_signed(I256::one()),
+            (I256::all_one(), false)
+        );
+        assert_eq!(min().overflowing_mul_signed(I256::all_one()), (min(), true));
+        assert_eq!(
+            max().overflowing_mul_signed(I256::all_one()),
+            (-max(), false)
+        );
+    }
+
+    #[test]
+    fn i256_checked() {
+        assert_eq!(max().checked_add(I256::one()), None);
+        assert_eq!(min().checked_add(max()), Some(I256::all_one()));
+        assert_eq!(min().checked_mul(I256::from(2)), None);
+        assert_eq!(
+            I256::from(-2).checked_mul(I256::from(1i128 << 100)),
+            Some(I256::from(-(1i128 << 101)))
+        );
+    }
+
+    #[test]
+    fn i256_wrapping() {
+        assert_eq!(max().wrapping_add(I256::one()), min());
+        assert_eq!(min().wrapping_sub(I256::one()), max());
+        assert_eq!(max().wrapping_mul(I256::from(2)), I256::from(-2));
+    }
+
+    #[test]
+    fn i256_div_rem() {
+        assert_eq!(
+            I256::from(-7).div_rem(I256::from(2)),
+            (I256::from(-3), I256::from(-1))
+        );
+        assert_eq!(
+            I256::from(7).div_rem(I256::from(-2)),
+            (I256::from(-3), I256::from(1))
+        );
+        assert_eq!(min().div_rem(I256::all_one()), (min(), I256::zero()));
+    }
+
+    #[test]
+    fn i256_bits() {
+        assert_eq!(I256::all_one().count_ones(), 256);
+        assert_eq!(min().count_ones(), 1);
+        assert_eq!(I256::all_one().leading_zeros(), 0);
+        assert_eq!(max().leading_zeros(), 1);
+        assert!(I256::from(-2).bit(255));
+        assert!(!I256::from(-2).bit(0));
+    }
+
+    #[test]
+    fn u256_bytes_roundtrip() {
+        for val in [U256::zero(), U256::one(), U256::MAX, U256::one() << 255] {
+            assert_eq!(U256::from_be_bytes(val.to_be_bytes()), val);
+            assert_eq!(U256::from_le_bytes(val.to_le_bytes()), val);
+        }
+
+        let val = u256("0102");
+        assert_eq!(val.to_be_bytes()[30..], [1u8, 2]);
+        assert_eq!(val.to_le_bytes()[..2], [2u8, 1]);
+    }
+
+    #[test]
+    fn i256_bytes_roundtrip() {
+        for val in [
+            I256::zero(),
+            I256::all_one(),
+            min(),
+            max(),
+            I256::from(-129),
+        ] {
+            assert_eq!(I256::from_be_bytes(val.to_be_bytes()), val);
+            assert_eq!(I256::from_le_bytes(val.to_le_bytes()), val);
+
+            let mut buf = [0; 32];
+            val.write_be_bytes(&mut buf);
+            assert_eq!(I256::from_be_slice(&buf), val);
+            val.write_le_bytes(&mut buf);
+            assert_eq!(I256::from_le_slice(&buf), val);
+        }
+        assert_eq!(I256::all_one().to_be_bytes(), [0xffu8; 32]);
+    }
+
+    #[test]
+    fn i256_bytes_sign_extension() {
+        // The sign boundary of `i8`.
+        assert_eq!(I256::from_be_slice(&[0x7f]), I256::from(127));
+        assert_eq!(I256::from_be_slice(&[0x80]), I256::from(-128));
+        assert_eq!(I256::from_be_slice(&[0xff, 0x7f]), I256::from(-129));
+        assert_eq!(I256::from_le_slice(&[0x7f, 0xff]), I256::from(-129));
+        assert_eq!(I256::from_be_slice(&[0x00, 0x80]), I256::from(128));
+        assert_eq!(I256::from_be_slice(&[]), I256::zero());
+    }
+
+    #[test]
+    fn i256_bytes_truncation() {
+        let mut buf = [0; 2];
+        I256::from(-129).write_be_bytes(&mut buf);
+        assert_eq!(buf, [0xff, 0x7f]);
+        I256::from(-129).write_le_bytes(&mut buf);
+        assert_eq!(buf, [0x7f, 0xff]);
+
+        // `0x12345` is truncated to `0x2345`.
+        I256::from(0x12345).write_be_bytes(&mut buf);
+        assert_eq!(buf, [0x23, 0x45]);
+    }
+
+    #[test]
+    fn i256_trunc_to_bits() {
+        assert_eq!(
+            I256::from(0x7f_ffff).trunc_to_bits(24),
+            I256::from(0x7f_ffff)
+        );
+        assert_eq!(
+            I256::from(0x80_0000).trunc_to_bits(24),
+            I256::from(-0x80_0000)
+        );
+        assert_eq!(I256::from(0x1_000_001).trunc_to_bits(24), I256::one());
+        assert_eq!(I256::from(-1).trunc_to_bits(24), I256::from(-1));
+        assert_eq!(max().trunc_to_bits(256), max());
+    }
+}
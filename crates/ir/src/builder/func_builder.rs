@@ -1,14 +1,16 @@
-use smallvec::SmallVec;
+use cranelift_entity::SecondaryMap;
+use smallvec::{smallvec, SmallVec};
 
 use crate::{
+    dfg::InvalidImmediate,
     func_cursor::{CursorLocation, FuncCursor},
-    insn::{BinaryOp, CastOp, DataLocationKind, InsnData, UnaryOp},
+    insn::{BinaryOp, CastOp, DataLocationKind, HaltOp, InsnData, MemoryOrdering, UnaryOp},
     module::FuncRef,
-    Block, Function, GlobalVariable, Immediate, Type, Value,
+    Block, Function, GlobalVariable, Immediate, Insn, SourceLoc, Type, Value,
 };
 
 use super::{
-    ssa::{SsaBuilder, Variable},
+    ssa::{IncompletePreds, SsaBuilder, UndefinedVariable, Variable},
     ModuleBuilder,
 };
 
@@ -18,6 +20,10 @@ pub struct FunctionBuilder<C> {
     func_ref: FuncRef,
     pub cursor: C,
     ssa_builder: SsaBuilder,
+    /// Parameters of blocks, each of which is the result of a phi insn at the top of the block.
+    block_params: SecondaryMap<Block, Vec<Value>>,
+    /// The source location stamped on the inserted insns.
+    source_loc: Option<SourceLoc>,
 }
 
 macro_rules! impl_binary_insn {
@@ -48,6 +54,8 @@ where
             func_ref,
             cursor,
             ssa_builder: SsaBuilder::new(),
+            block_params: SecondaryMap::default(),
+            source_loc: None,
         }
     }
 
@@ -84,6 +92,156 @@ where
         block
     }
 
+    /// Makes a new block and inserts it right after `anchor` in the layout.
+    pub fn insert_block_after(&mut self, anchor: Block) -> Block {
+        assert!(
+            self.func.layout.is_block_inserted(anchor),
+            "`{anchor}` is not inserted in the layout"
+        );
+        let block = self.cursor.make_block(&mut self.func);
+        self.func.layout.insert_block_after(block, anchor);
+        block
+    }
+
+    /// Makes a new block and inserts it right before `anchor` in the layout.
+    /// If `anchor` is the entry block, the new block becomes the entry block.
+    pub fn insert_block_before(&mut self, anchor: Block) -> Block {
+        assert!(
+            self.func.layout.is_block_inserted(anchor),
+            "`{anchor}` is not inserted in the layout"
+        );
+        let block = self.cursor.make_block(&mut self.func);
+        self.func.layout.insert_block_before(block, anchor);
+        block
+    }
+
+    /// Appends a parameter of `ty` to `block`, and returns the value of the parameter.
+    ///
+    /// Block parameters are an alternative to writing phi insns by hand: each predecessor passes
+    /// the arguments with [`Self::jump_with_args`], [`Self::br_with_args`] or
+    /// [`Self::br_table_with_args`]. The parameter is lowered to a phi insn at the
+    /// top of `block` as it's made, so the function is in the plain phi form that all passes
+    /// expect.
+    pub fn append_block_param(&mut self, block: Block, ty: Type) -> Value {
+        let func = &mut self.func;
+        let insn = func.dfg.make_insn(InsnData::phi(ty));
+        let first_non_phi = func
+            .layout
+            .iter_insn(block)
+            .find(|insn| !func.dfg.is_phi(*insn));
+        match first_non_phi {
+            Some(before) => func.layout.insert_insn_before(insn, before),
+            None => func.layout.append_insn(insn, block),
+        }
+
+        let result = self.cursor.make_result(func, insn).unwrap();
+        self.cursor.attach_result(func, insn, result);
+        self.stamp_source_loc(insn);
+        self.block_params[block].push(result);
+        result
+    }
+
+    /// Returns the parameters of `block` in the order they're appended.
+    pub fn block_params(&self, block: Block) -> &[Value] {
+        &self.block_params[block]
+    }
+
+    /// Jumps to `dest` passing `args` to the parameters of `dest`.
+    pub fn jump_with_args(&mut self, dest: Block, args: &[Value]) {
+        self.pass_block_args(&[(dest, args)]);
+        self.insert_jump(dest);
+    }
+
+    /// Branches to `then` or `else_` passing the arguments to the parameters of the destination.
+    ///
+    /// # Panics
+    /// Panics if `then` and `else_` are the same block but the arguments differ, since a phi
+    /// can't tell the two edges from the same predecessor apart.
+    pub fn br_with_args(
+        &mut self,
+        cond: Value,
+        (then, then_args): (Block, &[Value]),
+        (else_, else_args): (Block, &[Value]),
+    ) {
+        self.pass_block_args(&[(then, then_args), (else_, else_args)]);
+        self.insert_br(cond, then, else_);
+    }
+
+    /// Similar to [`Self::br_table`], but passes the arguments to the parameters of each
+    /// destination.
+    ///
+    /// # Panics
+    /// Panics if the same block is passed different arguments, since a phi can't tell the edges
+    /// from the same predecessor apart.
+    pub fn br_table_with_args(
+        &mut self,
+        cond: Value,
+        default: Option<(Block, &[Value])>,
+        table: &[(Value, Block, &[Value])],
+    ) {
+        let dests: Vec<_> = default
+            .into_iter()
+            .chain(table.iter().map(|&(_, block, args)| (block, args)))
+            .collect();
+        self.pass_block_args(&dests);
+
+        let table: Vec<_> = table
+            .iter()
+            .map(|&(value, block, _)| (value, block))
+            .collect();
+        self.insert_br_table(cond, default.map(|(block, _)| block), &table);
+    }
+
+    /// Appends the arguments to the phis of the destination parameters for the edges from the
+    /// current block. A destination appearing more than once gets the arguments only once.
+    fn pass_block_args(&mut self, dests: &[(Block, &[Value])]) {
+        let pred = self.cursor.block(&self.func).unwrap();
+        for (i, &(dest, args)) in dests.iter().enumerate() {
+            if let Some(&(_, prev_args)) = dests[..i].iter().find(|(prev, _)| *prev == dest) {
+                assert_eq!(
+                    prev_args, args,
+                    "different arguments are passed to `{dest}` from `{pred}`"
+                );
+                continue;
+            }
+
+            let params = &self.block_params[dest];
+            assert_eq!(
+                params.len(),
+                args.len(),
+                "wrong number of arguments to `{dest}`"
+            );
+            for (param, arg) in params.clone().into_iter().zip(args) {
+                assert_eq!(
+                    self.func.dfg.value_ty(param),
+                    self.func.dfg.value_ty(*arg),
+                    "argument type mismatch in branch to `{dest}`"
+                );
+                self.append_phi_arg(param, *arg, pred);
+            }
+        }
+    }
+
+    /// Asserts that `dest` has no parameters, which a branch without arguments can't feed.
+    fn assert_no_block_params(&self, dest: Block) {
+        assert!(
+            self.block_params[dest].is_empty(),
+            "`{dest}` has parameters; pass the arguments with a `*_with_args` branch"
+        );
+    }
+
+    /// Sets the source location of the insns inserted after this call, until the location is
+    /// set again or cleared by [`Self::clear_source_loc`].
+    /// The phis inserted while sealing blocks have no location.
+    pub fn set_source_loc(&mut self, loc: SourceLoc) {
+        self.source_loc = Some(loc);
+    }
+
+    /// Stops stamping a source location on the inserted insns.
+    pub fn clear_source_loc(&mut self) {
+        self.source_loc = None;
+    }
+
     pub fn switch_to_block(&mut self, block: Block) {
         self.cursor.set_location(CursorLocation::BlockBottom(block));
     }
@@ -95,9 +253,58 @@ where
         self.func.dfg.make_imm_value(imm)
     }
 
+    /// Returns an immediate value of `ty` made from `bytes` in the byte order of the target ISA.
+    /// See [`crate::DataFlowGraph::make_imm_value_typed`].
+    pub fn make_imm_value_typed(
+        &mut self,
+        bytes: &[u8],
+        ty: Type,
+    ) -> Result<Value, InvalidImmediate> {
+        self.func.dfg.make_imm_value_typed(bytes, ty)
+    }
+
+    /// Returns an immediate value of `ty` made from `val`. `val` is truncated or sign extended to
+    /// the width of `ty`, e.g., `iconst(Type::I8, 255)` is `-1.i8`.
+    ///
+    /// # Panics
+    /// Panics if `ty` is not integral.
+    pub fn iconst(&mut self, ty: Type, val: i64) -> Value {
+        assert!(ty.is_integral(), "immediate of `{ty:?}` is not integral");
+        self.make_imm_value(Immediate::from_i256(val.into(), ty))
+    }
+
+    /// Returns the `i1` immediate value `1`.
+    pub fn true_(&mut self) -> Value {
+        self.make_imm_value(true)
+    }
+
+    /// Returns the `i1` immediate value `0`.
+    pub fn false_(&mut self) -> Value {
+        self.make_imm_value(false)
+    }
+
+    /// Returns the zero immediate value of `ty`.
+    /// Immediates are integral, so this fails for the other types, e.g., aggregate types.
+    pub fn zero(&mut self, ty: Type) -> Result<Value, InvalidImmediate> {
+        if !ty.is_integral() {
+            return Err(InvalidImmediate::NotIntegral(ty));
+        }
+        Ok(self.make_imm_value(Immediate::zero(ty)))
+    }
+
+    /// Returns an undefined value of `ty`.
+    pub fn make_undef(&mut self, ty: Type) -> Value {
+        self.func.dfg.make_undef(ty)
+    }
+
     /// Return pointer value to the global variable.
+    /// # Panics
+    /// Panics if `gv` is not defined in the module.
     pub fn make_global_value(&mut self, gv: GlobalVariable) -> Value {
-        self.func.dfg.make_global_value(gv)
+        self.func
+            .dfg
+            .make_global_value(gv)
+            .unwrap_or_else(|err| panic!("{err}"))
     }
 
     pub fn ptr_type(&mut self, ty: Type) -> Type {
@@ -129,6 +336,10 @@ where
         self.unary_op(UnaryOp::Neg, lhs)
     }
 
+    pub fn copy(&mut self, lhs: Value) -> Value {
+        self.unary_op(UnaryOp::Copy, lhs)
+    }
+
     pub fn binary_op(&mut self, op: BinaryOp, lhs: Value, rhs: Value) -> Value {
         let insn_data = InsnData::Binary {
             code: op,
@@ -142,6 +353,8 @@ where
     impl_binary_insn!(mul, BinaryOp::Mul);
     impl_binary_insn!(udiv, BinaryOp::Udiv);
     impl_binary_insn!(sdiv, BinaryOp::Sdiv);
+    impl_binary_insn!(urem, BinaryOp::Urem);
+    impl_binary_insn!(srem, BinaryOp::Srem);
     impl_binary_insn!(lt, BinaryOp::Lt);
     impl_binary_insn!(gt, BinaryOp::Gt);
     impl_binary_insn!(slt, BinaryOp::Slt);
@@ -154,6 +367,16 @@ where
     impl_binary_insn!(ne, BinaryOp::Ne);
     impl_binary_insn!(and, BinaryOp::And);
     impl_binary_insn!(or, BinaryOp::Or);
+    impl_binary_insn!(xor, BinaryOp::Xor);
+    impl_binary_insn!(shl, BinaryOp::Shl);
+    impl_binary_insn!(lshr, BinaryOp::Lshr);
+    impl_binary_insn!(ashr, BinaryOp::Ashr);
+    impl_binary_insn!(add_checked, BinaryOp::AddChecked);
+    impl_binary_insn!(sub_checked, BinaryOp::SubChecked);
+    impl_binary_insn!(mul_checked, BinaryOp::MulChecked);
+    impl_binary_insn!(uadd_checked, BinaryOp::UaddChecked);
+    impl_binary_insn!(usub_checked, BinaryOp::UsubChecked);
+    impl_binary_insn!(umul_checked, BinaryOp::UmulChecked);
 
     pub fn cast_op(&mut self, op: CastOp, value: Value, ty: Type) -> Value {
         let insn_data = InsnData::Cast {
@@ -170,14 +393,54 @@ where
     impl_cast_insn!(bitcast, CastOp::BitCast);
 
     pub fn load(&mut self, loc: DataLocationKind, addr: Value) -> Value {
-        let insn_data = InsnData::Load { args: [addr], loc };
-        self.insert_insn(insn_data).unwrap()
+        self.load_ordered(loc, addr, MemoryOrdering::NotAtomic)
     }
 
     pub fn store(&mut self, loc: DataLocationKind, addr: Value, data: Value) {
+        self.store_ordered(loc, addr, data, MemoryOrdering::NotAtomic)
+    }
+
+    /// Build load instruction with the memory `ordering`.
+    ///
+    /// # Panics
+    /// Panics if `ordering` is [`MemoryOrdering::Release`], which is only valid for a store.
+    pub fn load_ordered(
+        &mut self,
+        loc: DataLocationKind,
+        addr: Value,
+        ordering: MemoryOrdering,
+    ) -> Value {
+        assert!(
+            ordering.is_valid_for_load(),
+            "`{ordering:?}` is not a valid ordering of a load"
+        );
+        let insn_data = InsnData::Load {
+            args: [addr],
+            loc,
+            ordering,
+        };
+        self.insert_insn(insn_data).unwrap()
+    }
+
+    /// Build store instruction with the memory `ordering`.
+    ///
+    /// # Panics
+    /// Panics if `ordering` is [`MemoryOrdering::Acquire`], which is only valid for a load.
+    pub fn store_ordered(
+        &mut self,
+        loc: DataLocationKind,
+        addr: Value,
+        data: Value,
+        ordering: MemoryOrdering,
+    ) {
+        assert!(
+            ordering.is_valid_for_store(),
+            "`{ordering:?}` is not a valid ordering of a store"
+        );
         let insn_data = InsnData::Store {
             args: [addr, data],
             loc,
+            ordering,
         };
         self.insert_insn(insn_data);
     }
@@ -202,14 +465,32 @@ where
         self.store(DataLocationKind::Storage, addr, data)
     }
 
+    /// Build transient storage load instruction.
+    pub fn tload(&mut self, addr: Value) -> Value {
+        self.load(DataLocationKind::TransientStorage, addr)
+    }
+
+    /// Build transient storage store instruction.
+    pub fn tstore(&mut self, addr: Value, data: Value) {
+        self.store(DataLocationKind::TransientStorage, addr, data)
+    }
+
     /// Build alloca instruction.
     pub fn alloca(&mut self, ty: Type) -> Value {
         let insn_data = InsnData::Alloca { ty };
         self.insert_insn(insn_data).unwrap()
     }
 
+    /// Build jump instruction.
+    ///
+    /// # Panics
+    /// Panics if `dest` has parameters; use [`Self::jump_with_args`] instead.
     pub fn jump(&mut self, dest: Block) {
-        debug_assert!(!self.ssa_builder.is_sealed(dest));
+        self.assert_no_block_params(dest);
+        self.insert_jump(dest);
+    }
+
+    fn insert_jump(&mut self, dest: Block) {
         let insn_data = InsnData::Jump { dests: [dest] };
 
         let pred = self.cursor.block(&self.func);
@@ -217,15 +498,27 @@ where
         self.insert_insn(insn_data);
     }
 
+    /// Build br_table instruction. See [`InsnData::BrTable`] for the semantics without `default`.
+    ///
+    /// # Panics
+    /// Panics if a destination has parameters; use [`Self::br_table_with_args`] instead.
+    /// In debug builds, panics if the type of a case value differs from the type of `cond`.
     pub fn br_table(&mut self, cond: Value, default: Option<Block>, table: &[(Value, Block)]) {
-        if cfg!(debug_assertions) {
-            if let Some(default) = default {
-                debug_assert!(!self.ssa_builder.is_sealed(default))
-            }
+        for dest in default.iter().chain(table.iter().map(|(_, block)| block)) {
+            self.assert_no_block_params(*dest);
+        }
+        self.insert_br_table(cond, default, table);
+    }
 
-            for (_, dest) in table {
-                debug_assert!(!self.ssa_builder.is_sealed(*dest));
-            }
+    fn insert_br_table(&mut self, cond: Value, default: Option<Block>, table: &[(Value, Block)]) {
+        let cond_ty = self.func.dfg.value_ty(cond);
+        for &(value, block) in table {
+            debug_assert!(
+                self.func.dfg.value_ty(value) == cond_ty,
+                "case (v{}, {block}) has type `{:?}`, but the cond has type `{cond_ty:?}`",
+                value.as_u32(),
+                self.func.dfg.value_ty(value)
+            );
         }
 
         let mut args = SmallVec::new();
@@ -252,10 +545,17 @@ where
         self.insert_insn(insn_data);
     }
 
+    /// Build branch instruction.
+    ///
+    /// # Panics
+    /// Panics if `then` or `else_` has parameters; use [`Self::br_with_args`] instead.
     pub fn br(&mut self, cond: Value, then: Block, else_: Block) {
-        debug_assert!(!self.ssa_builder.is_sealed(then));
-        debug_assert!(!self.ssa_builder.is_sealed(else_));
+        self.assert_no_block_params(then);
+        self.assert_no_block_params(else_);
+        self.insert_br(cond, then, else_);
+    }
 
+    fn insert_br(&mut self, cond: Value, then: Block, else_: Block) {
         let insn_data = InsnData::Branch {
             args: [cond],
             dests: [then, else_],
@@ -267,19 +567,112 @@ where
         self.insert_insn(insn_data);
     }
 
+    /// Insert a call to `func`, and returns its result if the callee returns a value.
+    ///
+    /// # Panics
+    /// Panics if `args` doesn't match the arguments of the callee signature, or if the callee
+    /// returns multiple values. Use [`Self::call_multi`] to receive them.
     pub fn call(&mut self, func: FuncRef, args: &[Value]) -> Option<Value> {
+        let sig = self.module_builder.get_sig(func);
+        assert!(
+            sig.ret_tys().len() <= 1,
+            "`%{}` returns multiple values, use `call_multi` to receive them",
+            sig.name()
+        );
+        self.call_multi(func, args).pop()
+    }
+
+    /// Insert a call to `func`, and returns its results, one for each return type of the callee.
+    ///
+    /// # Panics
+    /// Panics if `args` doesn't match the arguments of the callee signature.
+    pub fn call_multi(&mut self, func: FuncRef, args: &[Value]) -> SmallVec<[Value; 2]> {
         let sig = self.module_builder.get_sig(func).clone();
+        assert_eq!(
+            args.len(),
+            sig.args().len(),
+            "wrong number of arguments to `%{}`",
+            sig.name()
+        );
+        for (arg, &expected_ty) in args.iter().zip(sig.args()) {
+            assert_eq!(
+                self.func.dfg.value_ty(*arg),
+                expected_ty,
+                "argument type mismatch in call to `%{}`",
+                sig.name()
+            );
+        }
+
         let insn_data = InsnData::Call {
             func,
             args: args.into(),
-            ret_ty: sig.ret_ty(),
+            ret_tys: sig.ret_tys().into(),
         };
         self.func.callees.insert(func, sig);
-        self.insert_insn(insn_data)
+
+        let insn = self.cursor.insert_insn_data(&mut self.func, insn_data);
+        let results: SmallVec<[Value; 2]> = self
+            .func
+            .dfg
+            .make_results(insn)
+            .into_iter()
+            .map(|value_data| self.func.dfg.make_value(value_data))
+            .collect();
+        self.func.dfg.attach_results(insn, &results);
+        self.stamp_source_loc(insn);
+        self.cursor.set_location(CursorLocation::At(insn));
+        results
+    }
+
+    /// Build return instruction.
+    /// The number and the types of `args` must match the return types of the function.
+    pub fn ret(&mut self, args: &[Value]) {
+        let ret_tys = self.func.sig.ret_tys();
+        assert_eq!(
+            args.len(),
+            ret_tys.len(),
+            "wrong number of return values in `%{}`",
+            self.func.sig.name()
+        );
+        for (arg, &expected_ty) in args.iter().zip(ret_tys) {
+            assert_eq!(
+                self.func.dfg.value_ty(*arg),
+                expected_ty,
+                "return value type mismatch in `%{}`",
+                self.func.sig.name()
+            );
+        }
+
+        let insn_data = InsnData::Return { args: args.into() };
+        self.insert_insn(insn_data);
     }
 
-    pub fn ret(&mut self, args: Option<Value>) {
-        let insn_data = InsnData::Return { args };
+    /// Build stop instruction, which halts the execution successfully without output data.
+    pub fn stop(&mut self) {
+        let insn_data = InsnData::Halt {
+            code: HaltOp::Stop,
+            args: SmallVec::new(),
+        };
+        self.insert_insn(insn_data);
+    }
+
+    /// Build return_data instruction, which halts the execution successfully with the output
+    /// data of `len` bytes at `ptr` in memory.
+    pub fn return_data(&mut self, ptr: Value, len: Value) {
+        let insn_data = InsnData::Halt {
+            code: HaltOp::ReturnData,
+            args: smallvec![ptr, len],
+        };
+        self.insert_insn(insn_data);
+    }
+
+    /// Build revert instruction, which halts the execution and reverts the state changes, with
+    /// the output data of `len` bytes at `ptr` in memory.
+    pub fn revert(&mut self, ptr: Value, len: Value) {
+        let insn_data = InsnData::Halt {
+            code: HaltOp::Revert,
+            args: smallvec![ptr, len],
+        };
         self.insert_insn(insn_data);
     }
 
@@ -288,7 +681,14 @@ where
         self.insert_insn(insn_data)
     }
 
+    /// Build phi instruction.
+    ///
+    /// # Panics
+    /// In debug builds, panics if the type of an incoming value differs from `ty`.
     pub fn phi(&mut self, ty: Type, args: &[(Value, Block)]) -> Value {
+        for &(value, block) in args {
+            self.debug_check_phi_arg(ty, value, block);
+        }
         let insn_data = InsnData::Phi {
             values: args.iter().map(|(val, _)| *val).collect(),
             blocks: args.iter().map(|(_, block)| *block).collect(),
@@ -307,14 +707,29 @@ where
             self.func.dfg.is_phi(insn),
             "value must be the result of phi function"
         );
+        self.debug_check_phi_arg(self.func.dfg.value_ty(phi_value), value, block);
         self.func.dfg.append_phi_arg(insn, value, block);
     }
 
+    fn debug_check_phi_arg(&self, ty: Type, value: Value, block: Block) {
+        debug_assert!(
+            self.func.dfg.value_ty(value) == ty,
+            "phi arg (v{}, {block}) has type `{:?}`, but the phi has type `{ty:?}`",
+            value.as_u32(),
+            self.func.dfg.value_ty(value)
+        );
+    }
+
     pub fn declare_var(&mut self, ty: Type) -> Variable {
         self.ssa_builder.declare_var(ty)
     }
 
-    pub fn use_var(&mut self, var: Variable) -> Value {
+    /// Returns the value of `var` in the current block.
+    ///
+    /// Returns an error if no definition of `var` reaches the current block. If the block isn't
+    /// sealed yet, the lookup is deferred and the error is reported by
+    /// [`Self::undefined_vars`] once the block is sealed.
+    pub fn use_var(&mut self, var: Variable) -> Result<Value, UndefinedVariable> {
         let block = self.cursor.block(&self.func).unwrap();
         self.ssa_builder.use_var(&mut self.func, var, block)
     }
@@ -331,6 +746,13 @@ where
         self.ssa_builder.seal_block(&mut self.func, block);
     }
 
+    /// Seals the current block, or returns an error if a branch to the block was inserted without
+    /// going through the builder.
+    pub fn try_seal_block(&mut self) -> Result<(), IncompletePreds> {
+        let block = self.cursor.block(&self.func).unwrap();
+        self.ssa_builder.try_seal_block(&mut self.func, block)
+    }
+
     pub fn seal_all(&mut self) {
         self.ssa_builder.seal_all(&mut self.func);
     }
@@ -339,6 +761,11 @@ where
         self.ssa_builder.is_sealed(block)
     }
 
+    /// Returns the undefined variables found while sealing blocks.
+    pub fn undefined_vars(&self) -> &[UndefinedVariable] {
+        self.ssa_builder.undefined_vars()
+    }
+
     pub fn type_of(&self, value: Value) -> Type {
         self.func.dfg.value_ty(value)
     }
@@ -365,15 +792,139 @@ where
         if let Some(result) = result {
             self.cursor.attach_result(&mut self.func, insn, result);
         }
+        self.stamp_source_loc(insn);
         self.cursor.set_location(CursorLocation::At(insn));
         result
     }
+
+    fn stamp_source_loc(&mut self, insn: Insn) {
+        if let Some(loc) = &self.source_loc {
+            self.func.dfg.set_insn_source_loc(insn, Some(loc.clone()));
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{super::test_util::*, *};
 
+    use crate::{
+        func_cursor::InsnInserter, global_variable::ConstantValue, module::ModuleCtx,
+        GlobalVariableData, Linkage, Signature, U256,
+    };
+
+    #[test]
+    fn imm_helpers() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let v0 = builder.iconst(Type::I32, 7);
+        assert_eq!(v0, builder.make_imm_value(7i32));
+        let v1 = builder.iconst(Type::I8, 255);
+        assert_eq!(v1, builder.make_imm_value(-1i8));
+        let v2 = builder.iconst(Type::int(24), -1);
+        assert_eq!(builder.func.dfg.value_ty(v2), Type::int(24));
+
+        let t = builder.true_();
+        assert_eq!(t, builder.make_imm_value(true));
+        let f = builder.false_();
+        assert_eq!(f, builder.make_imm_value(false));
+
+        let zero = builder.zero(Type::I256).unwrap();
+        assert_eq!(zero, builder.make_imm_value(U256::zero()));
+        let arr = builder
+            .module_builder
+            .ctx
+            .with_ty_store_mut(|s| s.make_array(Type::I8, 4));
+        assert_eq!(builder.zero(arr), Err(InvalidImmediate::NotIntegral(arr)));
+        assert_eq!(
+            builder.zero(Type::Void),
+            Err(InvalidImmediate::NotIntegral(Type::Void))
+        );
+
+        builder.ret(&[]);
+        builder.seal_all();
+    }
+
+    #[test]
+    #[should_panic(expected = "phi arg (v1, block0) has type `Compound")]
+    #[cfg(debug_assertions)]
+    fn phi_global_address_mismatch() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let gv = builder
+            .module_builder
+            .make_global(GlobalVariableData::constant(
+                "ZERO".to_string(),
+                Type::I32,
+                Linkage::Private,
+                ConstantValue::make_imm(0),
+            ));
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+
+        // A global value is the address of the global, so it must be loaded to get an `i32`.
+        builder.switch_to_block(b0);
+        let zero = builder.make_global_value(gv);
+        builder.jump(b1);
+
+        builder.switch_to_block(b1);
+        builder.phi(Type::I32, &[(zero, b0)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "phi arg (v1, block1) has type `I8`")]
+    #[cfg(debug_assertions)]
+    fn phi_type_mismatch() {
+        let mut builder = test_func_builder(&[Type::I32, Type::I8], Type::I32);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+        let [v0, v1] = [builder.args()[0], builder.args()[1]];
+
+        builder.switch_to_block(b0);
+        builder.jump(b2);
+        builder.switch_to_block(b1);
+        builder.jump(b2);
+
+        builder.switch_to_block(b2);
+        builder.phi(Type::I32, &[(v0, b0), (v1, b1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "phi arg (v1, block1) has type `I8`")]
+    #[cfg(debug_assertions)]
+    fn append_phi_arg_type_mismatch() {
+        let mut builder = test_func_builder(&[Type::I32, Type::I8], Type::I32);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+        let [v0, v1] = [builder.args()[0], builder.args()[1]];
+
+        builder.switch_to_block(b0);
+        builder.jump(b2);
+        builder.switch_to_block(b1);
+        builder.jump(b2);
+
+        builder.switch_to_block(b2);
+        let phi = builder.phi(Type::I32, &[(v0, b0)]);
+        builder.append_phi_arg(phi, v1, b1);
+    }
+
+    #[test]
+    #[should_panic(expected = "case (v1, block1) has type `I8`")]
+    #[cfg(debug_assertions)]
+    fn br_table_case_type_mismatch() {
+        let mut builder = test_func_builder(&[Type::I32], Type::Void);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let cond = builder.args()[0];
+
+        builder.switch_to_block(b0);
+        let case = builder.make_imm_value(1i8);
+        builder.br_table(cond, None, &[(case, b1)]);
+    }
+
     #[test]
     fn entry_block() {
         let mut builder = test_func_builder(&[], Type::Void);
@@ -384,7 +935,7 @@ mod tests {
         let v1 = builder.make_imm_value(2i8);
         let v2 = builder.add(v0, v1);
         builder.sub(v2, v0);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -414,7 +965,7 @@ mod tests {
         assert_eq!(args.len(), 2);
         let v3 = builder.sext(arg0, Type::I64);
         builder.mul(v3, arg1);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -441,7 +992,7 @@ mod tests {
 
         builder.switch_to_block(entry_block);
         let v0 = builder.make_imm_value(1i32);
-        builder.ret(Some(v0));
+        builder.ret(&[v0]);
         builder.seal_all();
 
         let module = builder.finish().build();
@@ -482,7 +1033,7 @@ mod tests {
         builder.switch_to_block(merge_block);
         let v3 = builder.phi(Type::I64, &[(v1, then_block), (v2, else_block)]);
         builder.add(v3, arg0);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -509,4 +1060,450 @@ mod tests {
 "
         );
     }
+
+    #[test]
+    fn block_params() {
+        let mut builder = test_func_builder(&[], Type::I32);
+        let entry = builder.append_block();
+        let header = builder.append_block();
+        let body = builder.append_block();
+        let exit = builder.append_block();
+        let i = builder.append_block_param(header, Type::I32);
+        assert_eq!(builder.block_params(header), &[i]);
+
+        builder.switch_to_block(entry);
+        let zero = builder.make_imm_value(0i32);
+        builder.jump_with_args(header, &[zero]);
+
+        builder.switch_to_block(header);
+        let ten = builder.make_imm_value(10i32);
+        let cond = builder.lt(i, ten);
+        builder.br(cond, body, exit);
+
+        builder.switch_to_block(body);
+        let one = builder.make_imm_value(1i32);
+        let next = builder.add(i, one);
+        builder.jump_with_args(header, &[next]);
+
+        builder.switch_to_block(exit);
+        builder.ret(&[i]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func() -> i32 {
+    block0:
+        jump block1;
+
+    block1:
+        v0.i32 = phi (0.i32 block0) (v5 block2);
+        v3.i1 = lt v0 10.i32;
+        br v3 block2 block3;
+
+    block2:
+        v5.i32 = add v0 1.i32;
+        jump block1;
+
+    block3:
+        return v0;
+
+}
+"
+        );
+    }
+
+    #[test]
+    fn block_param_after_insns() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let arg = builder.args()[0];
+
+        // A param appended after the block is filled is still placed at the block top.
+        builder.switch_to_block(b1);
+        let v1 = builder.add(arg, arg);
+        let v2 = builder.append_block_param(b1, Type::I32);
+        let v3 = builder.add(v1, v2);
+        builder.ret(&[v3]);
+
+        builder.switch_to_block(b0);
+        builder.jump_with_args(b1, &[arg]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        jump block1;
+
+    block1:
+        v2.i32 = phi (v0 block0);
+        v1.i32 = add v0 v0;
+        v3.i32 = add v1 v2;
+        return v3;
+
+}
+"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "wrong number of arguments to `block1`")]
+    fn block_args_arity_mismatch() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        builder.append_block_param(b1, Type::I32);
+
+        builder.switch_to_block(b0);
+        builder.jump_with_args(b1, &[]);
+    }
+
+    #[test]
+    fn branch_with_args() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+        let b3 = builder.append_block();
+        let arg = builder.args()[0];
+        let p1 = builder.append_block_param(b1, Type::I32);
+        let p2 = builder.append_block_param(b2, Type::I32);
+        let p3 = builder.append_block_param(b3, Type::I32);
+
+        builder.switch_to_block(b0);
+        let zero = builder.make_imm_value(0i32);
+        let cond = builder.eq(arg, zero);
+        builder.br_with_args(cond, (b1, &[zero]), (b2, &[arg]));
+
+        builder.switch_to_block(b1);
+        let one = builder.make_imm_value(1i32);
+        builder.br_table_with_args(
+            p1,
+            Some((b3, &[p1])),
+            &[(zero, b2, &[one]), (one, b3, &[p1])],
+        );
+
+        builder.switch_to_block(b2);
+        builder.jump_with_args(b3, &[p2]);
+
+        builder.switch_to_block(b3);
+        builder.ret(&[p3]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        v5.i1 = eq v0 0.i32;
+        br v5 block1 block2;
+
+    block1:
+        v1.i32 = phi (0.i32 block0);
+        br_table v1 block3 (0.i32 block2) (1.i32 block3);
+
+    block2:
+        v2.i32 = phi (v0 block0) (1.i32 block1);
+        jump block3;
+
+    block3:
+        v3.i32 = phi (v1 block1) (v2 block2);
+        return v3;
+
+}
+"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "`block1` has parameters")]
+    fn br_to_block_with_params() {
+        let mut builder = test_func_builder(&[Type::I1], Type::Void);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+        builder.append_block_param(b1, Type::I32);
+
+        builder.switch_to_block(b0);
+        let cond = builder.args()[0];
+        builder.br(cond, b1, b2);
+    }
+
+    #[test]
+    #[should_panic(expected = "different arguments are passed to `block1` from `block0`")]
+    fn br_with_conflicting_args() {
+        let mut builder = test_func_builder(&[Type::I1], Type::Void);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        builder.append_block_param(b1, Type::I32);
+
+        builder.switch_to_block(b0);
+        let cond = builder.args()[0];
+        let zero = builder.make_imm_value(0i32);
+        let one = builder.make_imm_value(1i32);
+        builder.br_with_args(cond, (b1, &[zero]), (b1, &[one]));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "`%callee` returns multiple values, use `call_multi` to receive them"
+    )]
+    fn call_multi_return() {
+        let ctx = ModuleCtx::new(build_test_isa());
+        let mut mb = ModuleBuilder::new(ctx);
+        let sig = Signature::with_ret_tys("callee", Linkage::Private, &[], &[Type::I32, Type::I64]);
+        let callee = mb.declare_function(sig);
+        let sig = Signature::new("test_func", Linkage::Public, &[], Type::Void);
+        let func_ref = mb.declare_function(sig);
+
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        builder.call(callee, &[]);
+    }
+
+    #[test]
+    fn call_multi() {
+        let ctx = ModuleCtx::new(build_test_isa());
+        let mut mb = ModuleBuilder::new(ctx);
+        let sig = Signature::with_ret_tys(
+            "callee",
+            Linkage::Private,
+            &[Type::I32],
+            &[Type::I32, Type::I64],
+        );
+        let callee = mb.declare_function(sig);
+        let sig = Signature::with_ret_tys(
+            "test_func",
+            Linkage::Public,
+            &[Type::I32],
+            &[Type::I64, Type::I32],
+        );
+        let func_ref = mb.declare_function(sig);
+
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg0 = builder.args()[0];
+        let results = builder.call_multi(callee, &[arg0]);
+        assert_eq!(results.len(), 2);
+        builder.ret(&[results[1], results[0]]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i32) -> (i64, i32) {
+    block0:
+        v1.i32, v2.i64 = call %callee v0;
+        return v2 v1;
+
+}
+"
+        );
+    }
+
+    fn call_test_module_builder() -> (ModuleBuilder, FuncRef) {
+        let ctx = ModuleCtx::new(build_test_isa());
+        let mut mb = ModuleBuilder::new(ctx);
+        let sig = Signature::new(
+            "callee",
+            Linkage::Private,
+            &[Type::I32, Type::I64],
+            Type::I32,
+        );
+        let callee = mb.declare_function(sig);
+        (mb, callee)
+    }
+
+    #[test]
+    fn call() {
+        let (mut mb, callee) = call_test_module_builder();
+        let sig = Signature::new("test_func", Linkage::Public, &[Type::I32], Type::I32);
+        let func_ref = mb.declare_function(sig);
+
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg0 = builder.args()[0];
+        let v1 = builder.make_imm_value(1i64);
+        let v2 = builder.call(callee, &[arg0, v1]).unwrap();
+        builder.ret(&[v2]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        v2.i32 = call %callee v0 1.i64;
+        return v2;
+
+}
+"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "wrong number of arguments to `%callee`")]
+    fn call_arity_mismatch() {
+        let (mut mb, callee) = call_test_module_builder();
+        let sig = Signature::new("test_func", Linkage::Public, &[Type::I32], Type::I32);
+        let func_ref = mb.declare_function(sig);
+
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg0 = builder.args()[0];
+        builder.call(callee, &[arg0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "argument type mismatch in call to `%callee`")]
+    fn call_type_mismatch() {
+        let (mut mb, callee) = call_test_module_builder();
+        let sig = Signature::new("test_func", Linkage::Public, &[Type::I32], Type::I32);
+        let func_ref = mb.declare_function(sig);
+
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg0 = builder.args()[0];
+        builder.call(callee, &[arg0, arg0]);
+    }
+
+    #[test]
+    fn ret_multiple_values() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let sig = Signature::with_ret_tys(
+            "test_func",
+            Linkage::Public,
+            &[Type::I32],
+            &[Type::I32, Type::I64],
+        );
+        let func_ref = mb.declare_function(sig);
+
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg0 = builder.args()[0];
+        let v1 = builder.make_imm_value(1i64);
+        builder.ret(&[arg0, v1]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i32) -> (i32, i64) {
+    block0:
+        return v0 1.i64;
+
+}
+"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "wrong number of return values in `%test_func`")]
+    fn ret_arity_mismatch() {
+        let mut builder = test_func_builder(&[Type::I32], Type::Void);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg0 = builder.args()[0];
+        builder.ret(&[arg0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "return value type mismatch in `%test_func`")]
+    fn ret_type_mismatch() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I64);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg0 = builder.args()[0];
+        builder.ret(&[arg0]);
+    }
+
+    #[test]
+    fn insert_block_after_before() {
+        let mut builder = test_func_builder(&[], Type::Void);
+
+        let b0 = builder.append_block();
+        let b3 = builder.append_block();
+        let b1 = builder.insert_block_after(b0);
+        let b2 = builder.insert_block_before(b3);
+        assert!(!builder.is_sealed(b1));
+        assert!(!builder.is_sealed(b2));
+
+        for (block, dest) in [(b0, b1), (b1, b2), (b2, b3)] {
+            builder.switch_to_block(block);
+            builder.jump(dest);
+        }
+        builder.switch_to_block(b3);
+        builder.ret(&[]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func() -> void {
+    block0:
+        jump block2;
+
+    block2:
+        jump block3;
+
+    block3:
+        jump block1;
+
+    block1:
+        return;
+
+}
+"
+        );
+    }
+
+    #[test]
+    fn insert_block_before_entry() {
+        let mut builder = test_func_builder(&[], Type::Void);
+
+        let b0 = builder.append_block();
+        let b1 = builder.insert_block_before(b0);
+        assert_eq!(builder.func.layout.entry_block(), Some(b1));
+    }
+
+    #[test]
+    fn source_loc() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg = builder.args()[0];
+
+        let v0 = builder.add(arg, arg);
+        let loc = SourceLoc::new("main.fe", 2, 9);
+        builder.set_source_loc(loc.clone());
+        let v1 = builder.mul(v0, arg);
+        let v2 = builder.sub(v1, arg);
+        builder.clear_source_loc();
+        builder.ret(&[v2]);
+        builder.seal_all();
+
+        let func = &builder.func;
+        let insn_loc = |value| {
+            func.dfg
+                .insn_source_loc(func.dfg.value_insn(value).unwrap())
+        };
+        assert_eq!(insn_loc(v0), None);
+        assert_eq!(insn_loc(v1), Some(&loc));
+        assert_eq!(insn_loc(v2), Some(&loc));
+        let ret = func.layout.last_insn_of(b0).unwrap();
+        assert_eq!(func.dfg.insn_source_loc(ret), None);
+    }
 }
@@ -5,7 +5,7 @@ mod ssa;
 pub use func_builder::FunctionBuilder;
 pub use module_builder::ModuleBuilder;
 
-pub use ssa::Variable;
+pub use ssa::{IncompletePreds, UndefinedVariable, Variable};
 
 pub mod test_util {
     use super::*;
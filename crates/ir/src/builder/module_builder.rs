@@ -45,7 +45,8 @@ impl ModuleBuilder {
     }
 
     pub fn make_global(&self, global: GlobalVariableData) -> GlobalVariable {
-        self.ctx.with_gv_store_mut(|s| s.make_gv(global))
+        self.ctx
+            .with_ty_store(|ty_store| self.ctx.with_gv_store_mut(|s| s.make_gv(global, ty_store)))
     }
 
     pub fn global_by_name(&self, name: &str) -> Option<GlobalVariable> {
@@ -65,6 +66,10 @@ impl ModuleBuilder {
         self.ctx.with_ty_store_mut(|s| s.make_array(elem, len))
     }
 
+    pub fn declare_union_type(&mut self, variants: &[Type]) -> Type {
+        self.ctx.with_ty_store_mut(|s| s.make_union(variants))
+    }
+
     pub fn ptr_type(&mut self, ty: Type) -> Type {
         self.ctx.with_ty_store_mut(|s| s.make_ptr(ty))
     }
@@ -1,6 +1,8 @@
 //! SSA construction algorithm here is based on [`Simple and Efficient Construction of Static
 //! Single Assignment Form`](https://link.springer.com/chapter/10.1007/978-3-642-37051-9_6).
 
+use std::fmt;
+
 use cranelift_entity::{packed_option::PackedOption, PrimaryMap, SecondaryMap, SparseSet};
 use smallvec::SmallVec;
 
@@ -9,10 +11,51 @@ use crate::{
     Block, Function, Insn, InsnData, Type, Value,
 };
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Variable(u32);
 cranelift_entity::entity_impl!(Variable);
 
+/// An error reported when a variable is read in a block that no definition of it reaches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UndefinedVariable {
+    pub var: Variable,
+    /// The block in which the variable is read.
+    pub block: Block,
+}
+
+impl fmt::Display for UndefinedVariable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "variable `{:?}` is used in `{}` before any definition",
+            self.var, self.block
+        )
+    }
+}
+
+impl std::error::Error for UndefinedVariable {}
+
+/// An error reported when a block is sealed before all of its predecessors are appended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncompletePreds {
+    pub block: Block,
+    /// Predecessors found in the layout but not appended to the builder.
+    pub missing: Vec<Block>,
+}
+
+impl fmt::Display for IncompletePreds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}` is sealed before its predecessors", self.block)?;
+        for (i, pred) in self.missing.iter().enumerate() {
+            let delim = if i == 0 { " " } else { ", " };
+            write!(f, "{delim}`{pred}`")?;
+        }
+        write!(f, " are appended")
+    }
+}
+
+impl std::error::Error for IncompletePreds {}
+
 pub struct VariableData {
     ty: Type,
 }
@@ -25,6 +68,9 @@ pub(super) struct SsaBuilder {
 
     /// Records trivial phis.
     trivial_phis: SparseSet<Insn>,
+
+    /// Records undefined variables found while resolving deferred uses on sealing.
+    undefined_vars: Vec<UndefinedVariable>,
 }
 
 impl SsaBuilder {
@@ -33,6 +79,7 @@ impl SsaBuilder {
             blocks: SecondaryMap::default(),
             vars: PrimaryMap::default(),
             trivial_phis: SparseSet::new(),
+            undefined_vars: Vec::new(),
         }
     }
     pub(super) fn declare_var(&mut self, ty: Type) -> Variable {
@@ -43,12 +90,22 @@ impl SsaBuilder {
         self.blocks[block].def_var(var, value);
     }
 
-    pub(super) fn use_var(&mut self, func: &mut Function, var: Variable, block: Block) -> Value {
-        if let Some(value) = self.blocks[block].use_var_local(var) {
-            value
-        } else {
-            self.use_var_recursive(func, var, block)
-        }
+    /// Returns the value of `var` reaching `block`.
+    ///
+    /// If `block` isn't sealed yet, the lookup is deferred and an error, if any, is recorded when
+    /// the block is sealed. See [`Self::undefined_vars`].
+    pub(super) fn use_var(
+        &mut self,
+        func: &mut Function,
+        var: Variable,
+        block: Block,
+    ) -> Result<Value, UndefinedVariable> {
+        self.lookup_var(func, var, block)
+            .ok_or(UndefinedVariable { var, block })
+    }
+
+    pub(super) fn undefined_vars(&self) -> &[UndefinedVariable] {
+        &self.undefined_vars
     }
 
     pub(super) fn var_ty(&mut self, var: Variable) -> Type {
@@ -56,16 +113,46 @@ impl SsaBuilder {
     }
 
     pub(super) fn append_pred(&mut self, block: Block, pred: Block) {
+        debug_assert!(
+            !self.is_sealed(block),
+            "predecessor `{pred}` is added to `{block}` after sealing"
+        );
         self.blocks[block].append_pred(pred);
     }
 
     pub(super) fn seal_block(&mut self, func: &mut Function, block: Block) {
+        if cfg!(debug_assertions) && !self.is_sealed(block) {
+            if let Err(err) = self.verify_preds(func, block) {
+                panic!("{err}");
+            }
+        }
+
+        self.seal_block_unchecked(func, block);
+    }
+
+    /// Seals `block` after verifying that all predecessors found in the layout have been appended.
+    pub(super) fn try_seal_block(
+        &mut self,
+        func: &mut Function,
+        block: Block,
+    ) -> Result<(), IncompletePreds> {
+        if !self.is_sealed(block) {
+            self.verify_preds(func, block)?;
+        }
+
+        self.seal_block_unchecked(func, block);
+        Ok(())
+    }
+
+    fn seal_block_unchecked(&mut self, func: &mut Function, block: Block) {
         if self.is_sealed(block) {
             return;
         }
 
         for (var, phi) in self.blocks[block].take_incomplete_phis() {
-            self.add_phi_args(func, var, phi);
+            if !self.add_phi_args(func, var, phi) {
+                self.undefined_vars.push(UndefinedVariable { var, block });
+            }
         }
 
         self.blocks[block].seal();
@@ -83,45 +170,114 @@ impl SsaBuilder {
         self.blocks[block].is_sealed()
     }
 
-    fn use_var_recursive(&mut self, func: &mut Function, var: Variable, block: Block) -> Value {
+    /// Compares the appended predecessors of `block` with the branches targeting it in the layout.
+    fn verify_preds(&self, func: &Function, block: Block) -> Result<(), IncompletePreds> {
+        let mut appended = self.blocks[block].preds().to_vec();
+        let mut missing = Vec::new();
+
+        for pred in func.layout.iter_block() {
+            let Some(last_insn) = func.layout.last_insn_of(pred) else {
+                continue;
+            };
+            for dest in func.dfg.analyze_branch(last_insn).iter_dests() {
+                if dest != block {
+                    continue;
+                }
+                match appended.iter().position(|appended| *appended == pred) {
+                    Some(idx) => {
+                        appended.swap_remove(idx);
+                    }
+                    None => missing.push(pred),
+                }
+            }
+        }
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(IncompletePreds { block, missing })
+        }
+    }
+
+    fn lookup_var(&mut self, func: &mut Function, var: Variable, block: Block) -> Option<Value> {
+        if let Some(value) = self.blocks[block].use_var_local(var) {
+            Some(value)
+        } else {
+            self.lookup_var_recursive(func, var, block)
+        }
+    }
+
+    fn lookup_var_recursive(
+        &mut self,
+        func: &mut Function,
+        var: Variable,
+        block: Block,
+    ) -> Option<Value> {
         if !self.is_sealed(block) {
             let (insn, value) = self.prepend_phi(func, var, block);
             self.blocks[block].push_incomplete_phi(var, insn);
             self.def_var(var, value, block);
-            return value;
+            return Some(value);
         }
 
         match *self.blocks[block].preds() {
-            [pred] => self.use_var(func, var, pred),
+            [] => None,
+            [pred] => self.lookup_var(func, var, pred),
             _ => {
                 let (phi_insn, phi_value) = self.prepend_phi(func, var, block);
                 // Break potential cycles by defining operandless phi.
                 self.def_var(var, phi_value, block);
-                self.add_phi_args(func, var, phi_insn);
-                phi_value
+                if self.add_phi_args(func, var, phi_insn) {
+                    Some(phi_value)
+                } else {
+                    // Undo the phi and the definition, so a later lookup fails again instead of
+                    // finding the phi. Values already referring to the phi become undefined.
+                    if !self.trivial_phis.contains_key(phi_insn) {
+                        let undef = func.dfg.make_undef(self.var_ty(var));
+                        self.replace_phi(func, phi_insn, undef);
+                    }
+                    self.blocks[block].undef_var(var);
+                    None
+                }
             }
         }
     }
 
-    fn add_phi_args(&mut self, func: &mut Function, var: Variable, phi: Insn) {
+    /// Fills the arguments of `phi` with the values of `var` reaching from the predecessors.
+    /// A predecessor that no definition reaches contributes an undefined value.
+    ///
+    /// Returns `false` if `var` is undefined in any predecessor.
+    fn add_phi_args(&mut self, func: &mut Function, var: Variable, phi: Insn) -> bool {
         let block = func.layout.insn_block(phi);
         let preds = std::mem::take(&mut self.blocks[block].preds);
 
-        for pred in &preds {
-            let value = self.use_var(func, var, *pred);
-            func.dfg.append_phi_arg(phi, value, *pred);
+        let mut is_defined = !preds.is_empty();
+        for &pred in &preds {
+            let value = match self.lookup_var(func, var, pred) {
+                Some(value) => value,
+                None => {
+                    is_defined = false;
+                    func.dfg.make_undef(self.var_ty(var))
+                }
+            };
+            func.dfg.append_phi_arg(phi, value, pred);
         }
         self.blocks[block].preds = preds;
 
-        self.remove_trivial_phi(func, phi);
+        if func.dfg.insn_args(phi).is_empty() {
+            // The block is unreachable, so the phi is replaced with an undefined value.
+            let undef = func.dfg.make_undef(self.var_ty(var));
+            self.replace_phi(func, phi, undef);
+        } else {
+            self.remove_trivial_phi(func, phi);
+        }
+
+        is_defined
     }
 
     fn remove_trivial_phi(&mut self, func: &mut Function, phi: Insn) {
-        let phi_value = func.dfg.insn_result(phi).unwrap();
         let phi_args = func.dfg.insn_args(phi);
-        if phi_args.is_empty() {
-            panic!("variable is undefined or used in unreachable block");
-        }
+        debug_assert!(!phi_args.is_empty());
 
         let first = phi_args[0];
 
@@ -129,7 +285,12 @@ impl SsaBuilder {
             return;
         }
 
-        func.dfg.change_to_alias(phi_value, first);
+        self.replace_phi(func, phi, first);
+    }
+
+    fn replace_phi(&mut self, func: &mut Function, phi: Insn, value: Value) {
+        let phi_value = func.dfg.insn_result(phi).unwrap();
+        func.dfg.change_to_alias(phi_value, value);
         self.trivial_phis.insert(phi);
         InsnInserter::at_location(CursorLocation::At(phi)).remove_insn(func);
 
@@ -178,6 +339,10 @@ impl SsaBlockData {
         self.defs[var] = value.into();
     }
 
+    fn undef_var(&mut self, var: Variable) {
+        self.defs[var] = None.into();
+    }
+
     fn use_var_local(&self, var: Variable) -> Option<Value> {
         self.defs[var].expand()
     }
@@ -209,7 +374,10 @@ impl SsaBlockData {
 
 #[cfg(test)]
 mod tests {
-    use super::{super::test_util::*, *};
+    use super::{
+        super::{test_util::*, FunctionBuilder},
+        *,
+    };
 
     #[test]
     fn use_var_local() {
@@ -221,9 +389,9 @@ mod tests {
         builder.switch_to_block(entry_block);
         let v0 = builder.make_imm_value(1i32);
         builder.def_var(var, v0);
-        let v1 = builder.use_var(var);
+        let v1 = builder.use_var(var).unwrap();
         builder.add(v1, v0);
-        builder.ret(None);
+        builder.ret(&[]);
         builder.seal_block();
 
         let module = builder.finish().build();
@@ -270,8 +438,8 @@ mod tests {
         builder.seal_block();
 
         builder.switch_to_block(b3);
-        builder.use_var(var);
-        builder.ret(None);
+        builder.use_var(var).unwrap();
+        builder.ret(&[]);
         builder.seal_block();
 
         let module = builder.finish().build();
@@ -347,10 +515,10 @@ mod tests {
         builder.jump(b7);
 
         builder.switch_to_block(b7);
-        let v_var0 = builder.use_var(var0);
-        let v_var1 = builder.use_var(var1);
+        let v_var0 = builder.use_var(var0).unwrap();
+        let v_var1 = builder.use_var(var1).unwrap();
         builder.add(v_var0, v_var1);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -422,9 +590,9 @@ mod tests {
         builder.seal_block();
 
         builder.switch_to_block(b3);
-        let val = builder.use_var(var);
+        let val = builder.use_var(var).unwrap();
         builder.add(val, val);
-        builder.ret(None);
+        builder.ret(&[]);
         builder.seal_block();
 
         let module = builder.finish().build();
@@ -497,9 +665,9 @@ mod tests {
         builder.seal_block();
 
         builder.switch_to_block(b6);
-        let val = builder.use_var(var);
+        let val = builder.use_var(var).unwrap();
         builder.add(val, val);
-        builder.ret(None);
+        builder.ret(&[]);
         builder.seal_block();
 
         let module = builder.finish().build();
@@ -576,9 +744,9 @@ mod tests {
         builder.switch_to_block(b1);
 
         builder.switch_to_block(b6);
-        let val = builder.use_var(var);
+        let val = builder.use_var(var).unwrap();
         builder.add(val, val);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
 
@@ -655,8 +823,8 @@ mod tests {
         builder.jump(b4);
 
         builder.switch_to_block(b4);
-        let ret = builder.use_var(var);
-        builder.ret(ret.into());
+        let ret = builder.use_var(var).unwrap();
+        builder.ret(&[ret]);
 
         builder.seal_all();
 
@@ -688,19 +856,38 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn undef_use() {
         let mut builder = test_func_builder(&[], Type::Void);
 
         let var = builder.declare_var(Type::I32);
         let b1 = builder.append_block();
         builder.switch_to_block(b1);
-        builder.use_var(var);
         builder.seal_block();
+
+        assert_eq!(
+            builder.use_var(var),
+            Err(UndefinedVariable { var, block: b1 })
+        );
+    }
+
+    #[test]
+    fn undef_use_deferred() {
+        let mut builder = test_func_builder(&[], Type::Void);
+
+        let var = builder.declare_var(Type::I32);
+        let b1 = builder.append_block();
+        builder.switch_to_block(b1);
+        assert!(builder.use_var(var).is_ok());
+        assert!(builder.undefined_vars().is_empty());
+        builder.seal_block();
+
+        assert_eq!(
+            builder.undefined_vars(),
+            &[UndefinedVariable { var, block: b1 }]
+        );
     }
 
     #[test]
-    #[should_panic]
     fn unreachable_use() {
         let mut builder = test_func_builder(&[], Type::Void);
 
@@ -711,10 +898,140 @@ mod tests {
         builder.switch_to_block(b1);
         let imm = builder.make_imm_value(1i32);
         builder.def_var(var, imm);
+        builder.ret(&[]);
         builder.seal_block();
 
         builder.switch_to_block(b2);
-        builder.use_var(var);
+        builder.use_var(var).unwrap();
+        builder.ret(&[]);
         builder.seal_block();
+
+        assert_eq!(
+            builder.undefined_vars(),
+            &[UndefinedVariable { var, block: b2 }]
+        );
+    }
+
+    /// Builds `block0` branching to `block1` and `block2`, both of which jump to `block3`, and
+    /// defines `var` only in `block1`.
+    fn build_partially_defined(builder: &mut FunctionBuilder<InsnInserter>) -> (Variable, Block) {
+        let var = builder.declare_var(Type::I32);
+
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+        let b3 = builder.append_block();
+
+        builder.switch_to_block(b0);
+        let imm = builder.make_imm_value(1i32);
+        builder.br(imm, b1, b2);
+        builder.seal_block();
+
+        builder.switch_to_block(b1);
+        let imm = builder.make_imm_value(2i32);
+        builder.def_var(var, imm);
+        builder.jump(b3);
+        builder.seal_block();
+
+        builder.switch_to_block(b2);
+        builder.jump(b3);
+        builder.seal_block();
+
+        builder.switch_to_block(b3);
+        (var, b3)
+    }
+
+    #[test]
+    fn use_var_defined_on_one_branch() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        let (var, b3) = build_partially_defined(&mut builder);
+        builder.seal_block();
+
+        assert_eq!(
+            builder.use_var(var),
+            Err(UndefinedVariable { var, block: b3 })
+        );
+        // The failed lookup leaves neither a phi nor a definition behind.
+        assert_eq!(
+            builder.use_var(var),
+            Err(UndefinedVariable { var, block: b3 })
+        );
+        assert!(builder.func.layout.first_insn_of(b3).is_none());
+        assert!(builder.undefined_vars().is_empty());
+    }
+
+    #[test]
+    fn use_var_defined_on_one_branch_deferred() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        let (var, b3) = build_partially_defined(&mut builder);
+
+        let value = builder.use_var(var).unwrap();
+        builder.ret(&[]);
+        assert!(builder.undefined_vars().is_empty());
+        builder.seal_block();
+
+        assert_eq!(
+            builder.undefined_vars(),
+            &[UndefinedVariable { var, block: b3 }]
+        );
+        assert!(builder
+            .func
+            .dfg
+            .is_phi(builder.func.dfg.value_insn(value).unwrap()));
+    }
+
+    /// Builds `block0` jumping to `block1` without appending `block0` to the predecessors of
+    /// `block1`.
+    fn build_unrecorded_jump(builder: &mut FunctionBuilder<InsnInserter>) -> (Block, Block) {
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+
+        builder.switch_to_block(b0);
+        builder
+            .cursor
+            .insert_insn_data(&mut builder.func, InsnData::Jump { dests: [b1] });
+        builder.seal_block();
+
+        builder.switch_to_block(b1);
+        (b0, b1)
+    }
+
+    #[test]
+    fn try_seal_block() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        let (b0, b1) = build_unrecorded_jump(&mut builder);
+
+        assert_eq!(
+            builder.try_seal_block(),
+            Err(IncompletePreds {
+                block: b1,
+                missing: vec![b0]
+            })
+        );
+        assert!(!builder.is_sealed(b1));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "`block1` is sealed before its predecessors `block0` are appended")]
+    fn seal_block_with_incomplete_preds() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        build_unrecorded_jump(&mut builder);
+        builder.seal_block();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "predecessor `block0` is added to `block1` after sealing")]
+    fn pred_after_seal() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+
+        builder.switch_to_block(b1);
+        builder.seal_block();
+
+        builder.switch_to_block(b0);
+        builder.jump(b1);
     }
 }
@@ -0,0 +1,230 @@
+//! This module contains the call graph of a module, which is the interprocedural counterpart to
+//! the CFG.
+//!
+//! Edges are found by scanning the `call` insns of each function, so the graph reflects the
+//! function bodies even if the `callees` of a function are stale.
+use cranelift_entity::SecondaryMap;
+
+use crate::{module::FuncRef, InsnData, Module};
+
+#[derive(Debug, Default)]
+pub struct CallGraph {
+    /// The functions called by each function, sorted and without duplicates.
+    callees: SecondaryMap<FuncRef, Vec<FuncRef>>,
+    /// The functions calling each function, sorted and without duplicates.
+    callers: SecondaryMap<FuncRef, Vec<FuncRef>>,
+    /// Strongly connected components in reverse topological order, i.e., an SCC is placed
+    /// before the SCCs calling it. The members of each SCC are sorted.
+    sccs: Vec<Vec<FuncRef>>,
+    /// Maps a function to the index of its SCC in `sccs`.
+    scc_of: SecondaryMap<FuncRef, usize>,
+}
+
+impl CallGraph {
+    pub fn build(module: &Module) -> Self {
+        let mut graph = Self::default();
+
+        for func_ref in module.iter_functions() {
+            let func = &module.funcs[func_ref];
+            let mut callees: Vec<_> = func
+                .layout
+                .iter_block()
+                .flat_map(|block| func.layout.iter_insn(block))
+                .filter_map(|insn| match func.dfg.insn_data(insn) {
+                    InsnData::Call { func, .. } => Some(*func),
+                    _ => None,
+                })
+                .collect();
+            callees.sort_unstable();
+            callees.dedup();
+
+            // Callers are pushed in the order of `FuncRef`, so they are sorted.
+            for &callee in &callees {
+                graph.callers[callee].push(func_ref);
+            }
+            graph.callees[func_ref] = callees;
+        }
+
+        let mut finder = SccFinder::new(&graph);
+        for func_ref in module.iter_functions() {
+            if !finder.visited[func_ref] {
+                finder.visit(func_ref);
+            }
+        }
+        graph.sccs = finder.sccs;
+        for (idx, scc) in graph.sccs.iter().enumerate() {
+            for &func_ref in scc {
+                graph.scc_of[func_ref] = idx;
+            }
+        }
+
+        graph
+    }
+
+    /// Returns the functions called by `func_ref`.
+    pub fn callees(&self, func_ref: FuncRef) -> &[FuncRef] {
+        &self.callees[func_ref]
+    }
+
+    /// Returns the functions calling `func_ref`.
+    pub fn callers(&self, func_ref: FuncRef) -> &[FuncRef] {
+        &self.callers[func_ref]
+    }
+
+    /// Returns the strongly connected components in reverse topological order, i.e., an SCC is
+    /// placed before the SCCs calling it.
+    pub fn sccs(&self) -> &[Vec<FuncRef>] {
+        &self.sccs
+    }
+
+    /// Returns the strongly connected component containing `func_ref`, i.e., the functions that
+    /// are mutually recursive with `func_ref`.
+    pub fn scc_of(&self, func_ref: FuncRef) -> &[FuncRef] {
+        &self.sccs[self.scc_of[func_ref]]
+    }
+
+    /// Returns `true` if `func_ref` may call itself directly or indirectly.
+    pub fn is_recursive(&self, func_ref: FuncRef) -> bool {
+        self.scc_of(func_ref).len() > 1 || self.callees(func_ref).contains(&func_ref)
+    }
+
+    /// Returns `true` if `lhs` and `rhs` are in the same strongly connected component.
+    pub fn in_same_scc(&self, lhs: FuncRef, rhs: FuncRef) -> bool {
+        self.scc_of[lhs] == self.scc_of[rhs]
+    }
+
+    /// Returns an iterator over the functions in reverse topological order, i.e., callees are
+    /// visited before their callers except for recursive calls. This is the order for
+    /// bottom-up interprocedural passes.
+    pub fn reverse_topo_order(&self) -> impl Iterator<Item = FuncRef> + '_ {
+        self.sccs.iter().flatten().copied()
+    }
+}
+
+/// Finds strongly connected components by Tarjan's algorithm, which emits them in reverse
+/// topological order.
+struct SccFinder<'a> {
+    graph: &'a CallGraph,
+    visited: SecondaryMap<FuncRef, bool>,
+    low_link: SecondaryMap<FuncRef, u32>,
+    order_of: SecondaryMap<FuncRef, u32>,
+    on_stack: SecondaryMap<FuncRef, bool>,
+    stack: Vec<FuncRef>,
+    next_index: u32,
+    sccs: Vec<Vec<FuncRef>>,
+}
+
+impl<'a> SccFinder<'a> {
+    fn new(graph: &'a CallGraph) -> Self {
+        Self {
+            graph,
+            visited: SecondaryMap::default(),
+            low_link: SecondaryMap::default(),
+            order_of: SecondaryMap::default(),
+            on_stack: SecondaryMap::default(),
+            stack: Vec::new(),
+            next_index: 0,
+            sccs: Vec::new(),
+        }
+    }
+
+    fn visit(&mut self, func_ref: FuncRef) {
+        self.visited[func_ref] = true;
+        self.order_of[func_ref] = self.next_index;
+        self.low_link[func_ref] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(func_ref);
+        self.on_stack[func_ref] = true;
+
+        for &callee in self.graph.callees(func_ref) {
+            if !self.visited[callee] {
+                self.visit(callee);
+                self.low_link[func_ref] = self.low_link[func_ref].min(self.low_link[callee]);
+            } else if self.on_stack[callee] {
+                self.low_link[func_ref] = self.low_link[func_ref].min(self.order_of[callee]);
+            }
+        }
+
+        if self.low_link[func_ref] == self.order_of[func_ref] {
+            let mut scc = Vec::new();
+            loop {
+                let member = self.stack.pop().unwrap();
+                self.on_stack[member] = false;
+                scc.push(member);
+                if member == func_ref {
+                    break;
+                }
+            }
+            scc.sort_unstable();
+            self.sccs.push(scc);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        builder::{test_util::build_test_isa, ModuleBuilder},
+        func_cursor::InsnInserter,
+        module::ModuleCtx,
+        Linkage, Signature, Type,
+    };
+
+    #[test]
+    fn call_graph() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let mut declare =
+            |name| mb.declare_function(Signature::new(name, Linkage::Private, &[], Type::Void));
+        let main = declare("main");
+        let even = declare("even");
+        let odd = declare("odd");
+        let fact = declare("fact");
+        let leaf = declare("leaf");
+
+        // `main` -> `even` <-> `odd` -> `leaf`, `main` -> `fact` -> `fact`, `leaf`.
+        let edges = [
+            (main, vec![even, fact, even]),
+            (even, vec![odd]),
+            (odd, vec![even, leaf]),
+            (fact, vec![fact, leaf]),
+            (leaf, vec![]),
+        ];
+        for (func, callees) in edges {
+            let mut builder = mb.build_function::<InsnInserter>(func);
+            let b0 = builder.append_block();
+            builder.switch_to_block(b0);
+            for callee in callees {
+                builder.call(callee, &[]);
+            }
+            builder.ret(&[]);
+            builder.seal_all();
+            mb = builder.finish();
+        }
+        let module = mb.build();
+
+        let graph = CallGraph::build(&module);
+        assert_eq!(graph.callees(main), [even, fact]);
+        assert_eq!(graph.callers(leaf), [odd, fact]);
+        assert!(graph.callers(main).is_empty());
+
+        assert_eq!(graph.scc_of(odd), [even, odd]);
+        assert!(graph.in_same_scc(even, odd));
+        assert!(graph.is_recursive(even));
+        assert!(graph.is_recursive(fact));
+        assert!(!graph.is_recursive(main));
+        assert!(!graph.is_recursive(leaf));
+
+        // Each function appears after its callees except for the recursive calls.
+        let order: Vec<_> = graph.reverse_topo_order().collect();
+        assert_eq!(order.len(), 5);
+        let pos = |func| order.iter().position(|f| *f == func).unwrap();
+        for func in module.iter_functions() {
+            for &callee in graph.callees(func) {
+                assert!(graph.in_same_scc(func, callee) || pos(callee) < pos(func));
+            }
+        }
+        assert_eq!(order.last(), Some(&main));
+    }
+}
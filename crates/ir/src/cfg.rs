@@ -1,8 +1,8 @@
-use std::collections::BTreeSet;
+use std::{collections::BTreeSet, fmt::Write};
 
-use cranelift_entity::{packed_option::PackedOption, SecondaryMap};
+use cranelift_entity::{packed_option::PackedOption, EntityRef, SecondaryMap};
 
-use crate::{Block, Function, Insn};
+use crate::{ir_writer::FuncWriter, module::FuncRef, Block, Function, Insn, InsnData};
 
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct ControlFlowGraph {
@@ -44,14 +44,50 @@ impl ControlFlowGraph {
         self.blocks[block].succ_num()
     }
 
+    /// Returns the number of predecessors of the `block`.
+    /// A block whose in degree is greater than 1 is a merge block.
+    pub fn in_degree(&self, block: Block) -> usize {
+        self.pred_num_of(block)
+    }
+
+    /// Returns the number of successors of the `block`.
+    /// A block whose out degree is greater than 1 is a fork.
+    pub fn out_degree(&self, block: Block) -> usize {
+        self.succ_num_of(block)
+    }
+
+    /// Returns the entry block, which is the first block in the layout of the function the graph
+    /// is computed from.
+    ///
+    /// The entry block may have predecessors, i.e., back edges into it. The execution starts at
+    /// the entry without flowing from any of them, so analyses treat the entry as the root
+    /// regardless of its predecessors, e.g., the entry dominates all the reachable blocks.
+    /// Passes that rely on code in the entry running only once, or that may place phis in the
+    /// entry, must check [`Self::entry_pred`] first.
     pub fn entry(&self) -> Option<Block> {
         self.entry.expand()
     }
 
+    /// Returns a predecessor of the entry block, or `None` if the entry has no predecessors.
+    pub fn entry_pred(&self) -> Option<Block> {
+        let entry = self.entry()?;
+        self.preds_of(entry).next().copied()
+    }
+
+    /// Returns an iterator over the blocks reachable from the entry block in post order.
+    /// Unreachable blocks are excluded.
     pub fn post_order(&self) -> CfgPostOrder {
         CfgPostOrder::new(self)
     }
 
+    /// Returns the blocks reachable from the entry block in reverse post order, so each block
+    /// appears before its successors except for back edges. Unreachable blocks are excluded.
+    pub fn reverse_post_order(&self) -> Vec<Block> {
+        let mut rpo: Vec<_> = self.post_order().collect();
+        rpo.reverse();
+        rpo
+    }
+
     pub fn add_edge(&mut self, from: Block, to: Block) {
         self.blocks[to].push_pred(from);
         self.blocks[from].push_succ(to);
@@ -76,8 +112,59 @@ impl ControlFlowGraph {
         self.exits.clear();
     }
 
+    /// Recomputes the outgoing edges of `block` from its terminator, which is useful to follow
+    /// a rewrite of the terminator without recomputing the whole graph.
+    /// If `block` is no longer in the layout, all its edges are removed.
+    pub fn recompute_block(&mut self, func: &Function, block: Block) {
+        let succs: Vec<_> = self.succs_of(block).copied().collect();
+        for succ in succs {
+            self.remove_edge(block, succ);
+        }
+        self.exits.retain(|exit| *exit != block);
+
+        if !func.layout.is_block_inserted(block) {
+            let preds: Vec<_> = self.preds_of(block).copied().collect();
+            for pred in preds {
+                self.remove_edge(pred, block);
+            }
+            return;
+        }
+
+        if let Some(last_insn) = func.layout.last_insn_of(block) {
+            self.analyze_insn(func, last_insn);
+        }
+    }
+
+    /// Checks that the graph is identical to the one freshly computed from `func`, which is
+    /// useful to validate incremental updates.
+    ///
+    /// # Panics
+    /// Panics if the graph is inconsistent with `func`. The check is skipped in release builds.
+    pub fn debug_check_consistent(&self, func: &Function) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+
+        let mut expected = Self::new();
+        expected.compute(func);
+
+        assert_eq!(self.entry(), expected.entry(), "entry block mismatch");
+        let mut exits = self.exits.clone();
+        let mut expected_exits = expected.exits.clone();
+        exits.sort_unstable();
+        expected_exits.sort_unstable();
+        assert_eq!(exits, expected_exits, "exit blocks mismatch");
+
+        for block in func.layout.iter_block() {
+            assert_eq!(
+                self.blocks[block], expected.blocks[block],
+                "edges of {block} mismatch"
+            );
+        }
+    }
+
     fn analyze_insn(&mut self, func: &Function, insn: Insn) {
-        if func.dfg.is_return(insn) {
+        if func.dfg.is_exit(insn) {
             let exit = func.layout.insn_block(insn);
             self.exits.push(exit);
         }
@@ -89,6 +176,102 @@ impl ControlFlowGraph {
     }
 }
 
+/// Exports the CFG of `func` as JSON, so that tools other than graphviz can visualize it.
+///
+/// The output is an object with the following fields. The field names and the edge kinds are
+/// stable, so tooling can rely on them.
+/// - `function`: the name of the function.
+/// - `entry`: the id of the entry block, or `null` if the function has no blocks.
+/// - `blocks`: the blocks in the layout order. Each block is an object with
+///   - `id`: the block id, e.g., `"block0"`.
+///   - `insns`: the text of the insns in the same format as the IR writer.
+/// - `edges`: the edges in the layout order of their sources. Each edge is an object with
+///   - `from`, `to`: the ids of the source and the destination blocks.
+///   - `kind`: `"fallthrough"` for a `jump`, `"branch"` for a `br`, and `"table"` for a
+///     `br_table`.
+///
+/// Each pair of blocks has at most one edge even if the terminator refers to the destination
+/// more than once, which is the same as [`ControlFlowGraph`].
+pub fn export_json(func: &Function, cfg: &ControlFlowGraph) -> String {
+    // The func ref is only used to look up value names from a debug provider, so a dummy one is
+    // fine here.
+    let mut writer = FuncWriter::new(FuncRef::new(0), func, None);
+
+    let mut json = String::new();
+    write!(json, "{{\"function\":{}", JsonStr(func.sig.name())).unwrap();
+    match cfg.entry() {
+        Some(entry) => write!(json, ",\"entry\":{}", JsonStr(&entry.to_string())).unwrap(),
+        None => json.push_str(",\"entry\":null"),
+    }
+
+    json.push_str(",\"blocks\":[");
+    for (i, block) in func.layout.iter_block().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        write!(json, "{{\"id\":{},\"insns\":[", JsonStr(&block.to_string())).unwrap();
+        for (j, insn) in func.layout.iter_insn(block).enumerate() {
+            if j > 0 {
+                json.push(',');
+            }
+            let text = writer.insn_string(insn).unwrap();
+            write!(json, "{}", JsonStr(&text)).unwrap();
+        }
+        json.push_str("]}");
+    }
+
+    json.push_str("],\"edges\":[");
+    let mut is_first = true;
+    for block in func.layout.iter_block() {
+        let kind = match func
+            .layout
+            .last_insn_of(block)
+            .map(|insn| func.dfg.insn_data(insn))
+        {
+            Some(InsnData::Jump { .. }) => "fallthrough",
+            Some(InsnData::Branch { .. }) => "branch",
+            Some(InsnData::BrTable { .. }) => "table",
+            _ => continue,
+        };
+        for succ in cfg.succs_of(block) {
+            if !is_first {
+                json.push(',');
+            }
+            is_first = false;
+            write!(
+                json,
+                "{{\"from\":{},\"to\":{},\"kind\":\"{kind}\"}}",
+                JsonStr(&block.to_string()),
+                JsonStr(&succ.to_string())
+            )
+            .unwrap();
+        }
+    }
+    json.push_str("]}");
+
+    json
+}
+
+/// Displays a string as a JSON string literal.
+struct JsonStr<'a>(&'a str);
+
+impl std::fmt::Display for JsonStr<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_char('"')?;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\t' => f.write_str("\\t")?,
+                c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+        f.write_char('"')
+    }
+}
+
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
 struct BlockNode {
     preds: BTreeSet<Block>,
@@ -200,3 +383,226 @@ impl NodeState {
         self.0 = 2;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{builder::test_util::*, Type};
+
+    #[test]
+    fn diamond_degree() {
+        let mut builder = test_func_builder(&[], Type::Void);
+
+        let entry_block = builder.append_block();
+        let then_block = builder.append_block();
+        let else_block = builder.append_block();
+        let merge_block = builder.append_block();
+
+        builder.switch_to_block(entry_block);
+        let v0 = builder.make_imm_value(true);
+        builder.br(v0, then_block, else_block);
+
+        builder.switch_to_block(then_block);
+        builder.jump(merge_block);
+
+        builder.switch_to_block(else_block);
+        builder.jump(merge_block);
+
+        builder.switch_to_block(merge_block);
+        builder.ret(&[]);
+
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &module.funcs[func_ref];
+
+        let mut cfg = ControlFlowGraph::new();
+        cfg.compute(func);
+
+        assert_eq!(cfg.entry_pred(), None);
+        assert_eq!(cfg.in_degree(entry_block), 0);
+        assert_eq!(cfg.out_degree(entry_block), 2);
+        assert_eq!(cfg.in_degree(then_block), 1);
+        assert_eq!(cfg.out_degree(then_block), 1);
+        assert_eq!(cfg.in_degree(else_block), 1);
+        assert_eq!(cfg.out_degree(else_block), 1);
+        assert_eq!(cfg.in_degree(merge_block), 2);
+        assert_eq!(cfg.out_degree(merge_block), 0);
+    }
+
+    #[test]
+    fn recompute_block() {
+        let mut builder = test_func_builder(&[Type::I1], Type::Void);
+
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+
+        builder.switch_to_block(b0);
+        let arg = builder.args()[0];
+        builder.br(arg, b1, b2);
+
+        builder.switch_to_block(b1);
+        builder.jump(b2);
+
+        builder.switch_to_block(b2);
+        builder.ret(&[]);
+
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+
+        let mut cfg = ControlFlowGraph::new();
+        cfg.compute(func);
+
+        // Rewrite `br` in `b0` to `jump b2`.
+        let br = func.layout.last_insn_of(b0).unwrap();
+        func.dfg.remove_branch_dest(br, b1);
+        cfg.recompute_block(func, b0);
+        cfg.debug_check_consistent(func);
+        assert_eq!(cfg.succs_of(b0).copied().collect::<Vec<_>>(), vec![b2]);
+        assert_eq!(cfg.pred_num_of(b1), 0);
+
+        // Remove the unreachable `b1`.
+        func.remove_block(b1);
+        cfg.recompute_block(func, b1);
+        cfg.debug_check_consistent(func);
+        assert_eq!(cfg.preds_of(b2).copied().collect::<Vec<_>>(), vec![b0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "mismatch")]
+    #[cfg(debug_assertions)]
+    fn inconsistent_cfg() {
+        let mut builder = test_func_builder(&[], Type::Void);
+
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+
+        builder.switch_to_block(b0);
+        builder.jump(b1);
+
+        builder.switch_to_block(b1);
+        builder.ret(&[]);
+
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &module.funcs[func_ref];
+
+        let mut cfg = ControlFlowGraph::new();
+        cfg.compute(func);
+        cfg.remove_edge(b0, b1);
+        cfg.debug_check_consistent(func);
+    }
+
+    #[test]
+    fn traversal_order() {
+        let mut builder = test_func_builder(&[Type::I1], Type::Void);
+
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+        let b3 = builder.append_block();
+        let unreachable = builder.append_block();
+
+        builder.switch_to_block(b0);
+        let arg = builder.args()[0];
+        builder.br(arg, b1, b2);
+
+        builder.switch_to_block(b1);
+        builder.jump(b3);
+
+        builder.switch_to_block(b2);
+        builder.br(arg, b0, b3);
+
+        builder.switch_to_block(b3);
+        builder.ret(&[]);
+
+        builder.switch_to_block(unreachable);
+        builder.jump(b3);
+
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &module.funcs[func_ref];
+
+        let mut cfg = ControlFlowGraph::new();
+        cfg.compute(func);
+        assert_eq!(cfg.entry(), Some(b0));
+        // `b2` jumps back to the entry.
+        assert_eq!(cfg.entry_pred(), Some(b2));
+
+        let po: Vec<_> = cfg.post_order().collect();
+        let rpo = cfg.reverse_post_order();
+        assert_eq!(po.len(), 4);
+        assert!(!rpo.contains(&unreachable));
+        assert_eq!(rpo.iter().rev().copied().collect::<Vec<_>>(), po);
+
+        // Each block appears before its successors except for the back edge `b2 -> b0`.
+        let pos = |block| rpo.iter().position(|b| *b == block).unwrap();
+        assert_eq!(rpo[0], b0);
+        assert!(pos(b1) < pos(b3));
+        assert!(pos(b2) < pos(b3));
+    }
+
+    #[test]
+    fn json_export() {
+        let mut builder = test_func_builder(&[Type::I64], Type::Void);
+
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+        let b3 = builder.append_block();
+
+        let arg0 = builder.args()[0];
+        builder.switch_to_block(b0);
+        let v1 = builder.make_imm_value(1i64);
+        builder.br_table(arg0, Some(b2), &[(v1, b1)]);
+
+        builder.switch_to_block(b1);
+        let cond = builder.make_imm_value(true);
+        builder.br(cond, b2, b3);
+
+        builder.switch_to_block(b2);
+        builder.jump(b3);
+
+        builder.switch_to_block(b3);
+        builder.ret(&[]);
+
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &module.funcs[func_ref];
+
+        let mut cfg = ControlFlowGraph::new();
+        cfg.compute(func);
+
+        let expected = concat!(
+            r#"{"function":"test_func","entry":"block0","blocks":["#,
+            r#"{"id":"block0","insns":["br_table v0 block2 (1.i64 block1);"]},"#,
+            r#"{"id":"block1","insns":["br 1.i1 block2 block3;"]},"#,
+            r#"{"id":"block2","insns":["jump block3;"]},"#,
+            r#"{"id":"block3","insns":["return;"]}],"edges":["#,
+            r#"{"from":"block0","to":"block1","kind":"table"},"#,
+            r#"{"from":"block0","to":"block2","kind":"table"},"#,
+            r#"{"from":"block1","to":"block2","kind":"branch"},"#,
+            r#"{"from":"block1","to":"block3","kind":"branch"},"#,
+            r#"{"from":"block2","to":"block3","kind":"fallthrough"}]}"#,
+        );
+        assert_eq!(export_json(func, &cfg), expected);
+    }
+
+    #[test]
+    fn json_str_escape() {
+        let escaped = JsonStr("a\"b\\c\nd\u{1}").to_string();
+        assert_eq!(escaped, r#""a\"b\\c\nd\u0001""#);
+    }
+}
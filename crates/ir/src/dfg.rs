@@ -1,13 +1,16 @@
 //! This module contains Sonatine IR data flow graph.
-use std::collections::BTreeSet;
+use std::{collections::BTreeSet, fmt};
 
-use cranelift_entity::{entity_impl, packed_option::PackedOption, PrimaryMap, SecondaryMap};
-use rustc_hash::FxHashMap;
+use cranelift_entity::{entity_impl, PrimaryMap, SecondaryMap};
+use rustc_hash::{FxHashMap, FxHashSet};
 use smallvec::SmallVec;
 
-use crate::{global_variable::ConstantValue, module::ModuleCtx, GlobalVariable};
+use crate::{
+    global_variable::ConstantValue, isa::Endian, linker::SymbolMap, module::ModuleCtx,
+    GlobalVariable, Layout, SourceLoc, TypeLayout, I256, U256,
+};
 
-use super::{BranchInfo, Immediate, Insn, InsnData, Type, Value, ValueData};
+use super::{BranchInfo, DataLocationKind, Immediate, Insn, InsnData, Type, Value, ValueData};
 
 #[derive(Debug, Clone)]
 pub struct DataFlowGraph {
@@ -17,10 +20,15 @@ pub struct DataFlowGraph {
     #[doc(hidden)]
     pub values: PrimaryMap<Value, ValueData>,
     insns: PrimaryMap<Insn, InsnData>,
-    insn_results: SecondaryMap<Insn, PackedOption<Value>>,
+    insn_results: SecondaryMap<Insn, SmallVec<[Value; 1]>>,
     #[doc(hidden)]
     pub immediates: FxHashMap<Immediate, Value>,
+    undefs: FxHashMap<Type, Value>,
+    globals: FxHashMap<GlobalVariable, Value>,
     users: SecondaryMap<Value, BTreeSet<Insn>>,
+    /// Source locations of insns. This is allocated only when a location is set, so programs
+    /// without locations pay nothing.
+    source_locs: Option<SecondaryMap<Insn, Option<SourceLoc>>>,
 }
 
 impl DataFlowGraph {
@@ -32,10 +40,107 @@ impl DataFlowGraph {
             insns: PrimaryMap::default(),
             insn_results: SecondaryMap::default(),
             immediates: FxHashMap::default(),
+            undefs: FxHashMap::default(),
+            globals: FxHashMap::default(),
             users: SecondaryMap::default(),
+            source_locs: None,
         }
     }
 
+    /// Splits the graph into the parts that are serialized. The module context is not a part
+    /// of them, and is provided again in [`DataFlowGraph::from_parts`].
+    #[cfg(feature = "serde")]
+    pub(crate) fn to_parts(&self) -> DfgParts {
+        DfgParts {
+            blocks: self.blocks.clone(),
+            values: self.values.clone(),
+            insns: self.insns.clone(),
+            insn_results: self.insn_results.clone(),
+            immediates: self.immediates.clone(),
+            undefs: self.undefs.clone(),
+            globals: self.globals.clone(),
+            users: self.users.clone(),
+            source_locs: self.source_locs.clone(),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn from_parts(ctx: ModuleCtx, parts: DfgParts) -> Self {
+        let DfgParts {
+            blocks,
+            values,
+            insns,
+            insn_results,
+            immediates,
+            undefs,
+            globals,
+            users,
+            source_locs,
+        } = parts;
+
+        Self {
+            ctx,
+            blocks,
+            values,
+            insns,
+            insn_results,
+            immediates,
+            undefs,
+            globals,
+            users,
+            source_locs,
+        }
+    }
+
+    /// Rebinds the graph to `ctx`, and rewrites the types, the global variables and the callees
+    /// referred to by the graph with `map`. This is used to move a function into another module.
+    /// Calls to functions missing in `map` are left untouched, which is the case only for calls
+    /// detached from the layout after their callees are removed.
+    pub(crate) fn relocate(&mut self, ctx: ModuleCtx, map: &SymbolMap) {
+        self.ctx = ctx;
+
+        for value in self.values.values_mut() {
+            match value {
+                ValueData::Insn { ty, .. }
+                | ValueData::Arg { ty, .. }
+                | ValueData::Immediate { ty, .. }
+                | ValueData::Undef { ty } => *ty = map.ty(*ty),
+                ValueData::Global { gv, ty } => {
+                    *gv = map.gv(*gv);
+                    *ty = map.ty(*ty);
+                }
+            }
+        }
+
+        for insn_data in self.insns.values_mut() {
+            match insn_data {
+                InsnData::Cast { ty, .. } | InsnData::Alloca { ty } | InsnData::Phi { ty, .. } => {
+                    *ty = map.ty(*ty)
+                }
+                InsnData::Call { func, ret_tys, .. } => {
+                    if let Some(mapped) = map.func(*func) {
+                        *func = mapped;
+                    }
+                    for ret_ty in ret_tys {
+                        *ret_ty = map.ty(*ret_ty);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.undefs = self
+            .undefs
+            .drain()
+            .map(|(ty, value)| (map.ty(ty), value))
+            .collect();
+        self.globals = self
+            .globals
+            .drain()
+            .map(|(gv, value)| (map.gv(gv), value))
+            .collect();
+    }
+
     pub fn make_block(&mut self) -> Block {
         self.blocks.push(BlockData::new())
     }
@@ -66,11 +171,74 @@ impl DataFlowGraph {
         value
     }
 
-    pub fn make_global_value(&mut self, gv: GlobalVariable) -> Value {
-        let gv_ty = self.ctx.with_gv_store(|s| s.ty(gv));
+    /// Returns an immediate value of `ty` made from `bytes` in the byte order of the target ISA.
+    /// This allows to make immediates of types that don't map to Rust primitives, e.g., `i256`.
+    ///
+    /// `bytes` must be as long as the size of `ty` in memory, see [`crate::TypeLayout`]. The bits
+    /// above the width of `ty`, i.e., the padding and the high bits of the last byte, must be
+    /// either zero or the sign extension of the value.
+    pub fn make_imm_value_typed(
+        &mut self,
+        bytes: &[u8],
+        ty: Type,
+    ) -> Result<Value, InvalidImmediate> {
+        if !ty.is_integral() {
+            return Err(InvalidImmediate::NotIntegral(ty));
+        }
+        let expected = TypeLayout::of(ty, &self.ctx).size;
+        if bytes.len() != expected {
+            return Err(InvalidImmediate::SizeMismatch {
+                ty,
+                expected,
+                actual: bytes.len(),
+            });
+        }
+
+        let val = match self.ctx.endian() {
+            Endian::Big => I256::from_be_slice(bytes),
+            Endian::Little => I256::from_le_slice(bytes),
+        };
+        let imm = Immediate::from_i256(val, ty);
+
+        // `val` is sign extended from the bytes, so it's compared with the zero extension of the
+        // immediate only in the width of the bytes.
+        let mask = match bytes.len() {
+            32 => U256::MAX,
+            len => (U256::one() << (len * 8)) - U256::one(),
+        };
+        if imm.as_i256() != val && imm.as_zext_u256() != val.to_u256() & mask {
+            return Err(InvalidImmediate::OutOfRange(ty));
+        }
+
+        Ok(self.make_imm_value(imm))
+    }
+
+    /// Returns an undefined value of `ty`. See [`ValueData::Undef`] for the semantics.
+    pub fn make_undef(&mut self, ty: Type) -> Value {
+        if let Some(&value) = self.undefs.get(&ty) {
+            return value;
+        }
+
+        let value = self.make_value(ValueData::Undef { ty });
+        self.undefs.insert(ty, value);
+        value
+    }
+
+    /// Returns a value of the pointer to `gv`.
+    /// References to the same global variable are interned to the same value.
+    pub fn make_global_value(&mut self, gv: GlobalVariable) -> Result<Value, DanglingGlobal> {
+        if let Some(&value) = self.globals.get(&gv) {
+            return Ok(value);
+        }
+
+        let gv_ty = self
+            .ctx
+            .with_gv_store(|s| s.contains(gv).then(|| s.ty(gv)))
+            .ok_or(DanglingGlobal(gv))?;
         let ty = self.ctx.with_ty_store_mut(|s| s.make_ptr(gv_ty));
-        let value_data = ValueData::Global { gv, ty };
-        self.make_value(value_data)
+        let value = self.make_value(ValueData::Global { gv, ty });
+        self.globals.insert(gv, value);
+        Ok(value)
     }
 
     pub fn replace_insn(&mut self, insn: Insn, insn_data: InsnData) {
@@ -94,14 +262,57 @@ impl DataFlowGraph {
         self.users[alias].append(&mut users);
     }
 
+    /// Collapses structurally identical immediates, global values and undefined values into a
+    /// single value, and redirects their users to it.
+    ///
+    /// Values made by [`Self::make_value`] directly bypass interning, so this is useful after
+    /// building a graph without [`Self::make_imm_value`] and its friends. Insn results and
+    /// function arguments are left untouched, and collapsed values are left without users.
+    /// Values without users are skipped, so a second call collapses nothing.
+    ///
+    /// Returns the number of collapsed values.
+    pub fn dedup_values(&mut self) -> usize {
+        let mut collapsed = 0;
+        let values: Vec<_> = self.values.keys().collect();
+        for value in values {
+            let canonical = match self.values[value] {
+                ValueData::Immediate { imm, .. } => *self.immediates.entry(imm).or_insert(value),
+                ValueData::Global { gv, .. } => *self.globals.entry(gv).or_insert(value),
+                ValueData::Undef { ty } => *self.undefs.entry(ty).or_insert(value),
+                ValueData::Insn { .. } | ValueData::Arg { .. } => continue,
+            };
+
+            if canonical != value && !self.users[value].is_empty() {
+                self.change_to_alias(value, canonical);
+                collapsed += 1;
+            }
+        }
+
+        collapsed
+    }
+
     pub fn make_result(&mut self, insn: Insn) -> Option<ValueData> {
         let ty = self.insns[insn].result_type(self)?;
         Some(ValueData::Insn { insn, ty })
     }
 
+    /// Makes the results of `insn`, one for each of [`InsnData::result_types`].
+    pub fn make_results(&mut self, insn: Insn) -> SmallVec<[ValueData; 2]> {
+        self.insns[insn]
+            .result_types(self)
+            .into_iter()
+            .map(|ty| ValueData::Insn { insn, ty })
+            .collect()
+    }
+
     pub fn attach_result(&mut self, insn: Insn, value: Value) {
-        debug_assert!(self.insn_results[insn].is_none());
-        self.insn_results[insn] = value.into();
+        self.attach_results(insn, &[value]);
+    }
+
+    /// Attaches `values` as the results of `insn` in order.
+    pub fn attach_results(&mut self, insn: Insn, values: &[Value]) {
+        debug_assert!(self.insn_results[insn].is_empty());
+        self.insn_results[insn] = values.into();
     }
 
     pub fn make_arg_value(&mut self, ty: Type, idx: usize) -> ValueData {
@@ -112,6 +323,24 @@ impl DataFlowGraph {
         &self.insns[insn]
     }
 
+    /// Returns the source location of `insn` if it's set.
+    pub fn insn_source_loc(&self, insn: Insn) -> Option<&SourceLoc> {
+        self.source_locs.as_ref()?[insn].as_ref()
+    }
+
+    /// Sets the source location of `insn`, or clears it if `loc` is `None`.
+    pub fn set_insn_source_loc(&mut self, insn: Insn, loc: Option<SourceLoc>) {
+        match (&mut self.source_locs, loc) {
+            (Some(source_locs), loc) => source_locs[insn] = loc,
+            (None, Some(loc)) => {
+                let mut source_locs = SecondaryMap::new();
+                source_locs[insn] = Some(loc);
+                self.source_locs = Some(source_locs);
+            }
+            (None, None) => {}
+        }
+    }
+
     pub fn value_data(&self, value: Value) -> &ValueData {
         &self.values[value]
     }
@@ -124,6 +353,11 @@ impl DataFlowGraph {
         self.insns[insn].may_trap()
     }
 
+    /// Returns `true` if `insn` is pure. See [`InsnData::is_pure`] for the pure insns.
+    pub fn insn_is_pure(&self, insn: Insn) -> bool {
+        self.insns[insn].is_pure()
+    }
+
     pub fn attach_user(&mut self, insn: Insn) {
         let data = &self.insns[insn];
         for arg in data.args() {
@@ -163,7 +397,8 @@ impl DataFlowGraph {
             ValueData::Insn { ty, .. }
             | ValueData::Arg { ty, .. }
             | ValueData::Immediate { ty, .. }
-            | ValueData::Global { ty, .. } => *ty,
+            | ValueData::Global { ty, .. }
+            | ValueData::Undef { ty } => *ty,
         }
     }
 
@@ -241,8 +476,42 @@ impl DataFlowGraph {
         old_arg
     }
 
+    /// Swaps `i`-th and `j`-th arguments of the `insn`.
+    /// Users map is kept as is because both arguments remain operands of the `insn`.
+    ///
+    /// This is intended to be used for operand canonicalization, so the `insn` must be a
+    /// commutative binary insn.
+    pub fn swap_insn_args(&mut self, insn: Insn, i: usize, j: usize) {
+        debug_assert!(
+            matches!(&self.insns[insn], InsnData::Binary { code, .. } if code.is_commutative()),
+            "`swap_insn_args` must be used only for commutative insns"
+        );
+        self.insns[insn].args_mut().swap(i, j);
+    }
+
+    /// Orders the args of a commutative binary insn by their value ids, so that structurally
+    /// equal commutative insns have the same args, e.g., `add v1 v0` becomes `add v0 v1`.
+    /// Returns `true` if the args are swapped.
+    ///
+    /// This is a no-op for the other insns.
+    pub fn canonicalize_commutative(&mut self, insn: Insn) -> bool {
+        match self.insns[insn] {
+            InsnData::Binary { code, args } if code.is_commutative() && args[1] < args[0] => {
+                self.swap_insn_args(insn, 0, 1);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the result of `insn`, or the first one if it's a call with multiple results.
     pub fn insn_result(&self, insn: Insn) -> Option<Value> {
-        self.insn_results[insn].expand()
+        self.insn_results[insn].first().copied()
+    }
+
+    /// Returns all results of `insn`.
+    pub fn insn_results(&self, insn: Insn) -> &[Value] {
+        &self.insn_results[insn]
     }
 
     pub fn analyze_branch(&self, insn: Insn) -> BranchInfo {
@@ -319,15 +588,116 @@ impl DataFlowGraph {
         self.insns[insn].is_branch()
     }
 
+    pub fn is_halt(&self, insn: Insn) -> bool {
+        self.insns[insn].is_halt()
+    }
+
+    /// Returns `true` if `insn` leaves the function without a successor.
+    pub fn is_exit(&self, insn: Insn) -> bool {
+        self.insns[insn].is_exit()
+    }
+
+    /// Returns `true` if `insn` must be the last insn of a block.
+    pub fn is_terminator(&self, insn: Insn) -> bool {
+        self.insns[insn].is_terminator()
+    }
+
     /// Returns `true` if `value` is an immediate.
     pub fn is_imm(&self, value: Value) -> bool {
         self.value_imm(value).is_some()
     }
 
+    /// Returns `true` if `value` is an undefined value.
+    pub fn is_undef(&self, value: Value) -> bool {
+        matches!(self.value_data(value), ValueData::Undef { .. })
+    }
+
     /// Returns `true` if `value` is a function argument.
     pub fn is_arg(&self, value: Value) -> bool {
         matches!(self.value_data(value), ValueData::Arg { .. })
     }
+
+    /// Returns `true` if the address allocated by the alloca `insn` escapes, i.e., is used
+    /// other than as the address of a non-atomic memory load or store. The slot of a non-escaping
+    /// alloca is accessed only directly, so it can be promoted to SSA values.
+    ///
+    /// Derived pointers, e.g., the results of `gep`, are conservatively regarded as escaping.
+    ///
+    /// # Panics
+    /// Panics if `insn` is not an alloca.
+    pub fn alloca_escapes(&self, insn: Insn) -> bool {
+        assert!(
+            matches!(self.insns[insn], InsnData::Alloca { .. }),
+            "`{insn:?}` is not an alloca"
+        );
+        let Some(addr) = self.insn_result(insn) else {
+            return false;
+        };
+
+        self.users(addr).any(|&user| match &self.insns[user] {
+            InsnData::Load {
+                loc: DataLocationKind::Memory,
+                ordering,
+                ..
+            } => ordering.is_atomic(),
+            // Storing the address itself makes it reachable through the memory.
+            InsnData::Store {
+                args,
+                loc: DataLocationKind::Memory,
+                ordering,
+            } => args[1] == addr || ordering.is_atomic(),
+            _ => true,
+        })
+    }
+
+    /// Returns the first insn in `layout` that uses a value defined by itself or by a later insn
+    /// in the same block, together with the value.
+    ///
+    /// This is a cheap check of a common mistake in incremental construction, and doesn't verify
+    /// the dominance across blocks. Phi args are not checked since they flow from predecessors.
+    pub fn check_local_dominance(&self, layout: &Layout) -> Option<(Insn, Value)> {
+        let mut undefined = FxHashSet::default();
+        for block in layout.iter_block() {
+            undefined.clear();
+            undefined.extend(
+                layout
+                    .iter_insn(block)
+                    .flat_map(|insn| self.insn_results(insn).iter().copied()),
+            );
+
+            for insn in layout.iter_insn(block) {
+                if !self.is_phi(insn) {
+                    if let Some(&arg) = self
+                        .insn_args(insn)
+                        .iter()
+                        .find(|arg| undefined.contains(*arg))
+                    {
+                        return Some((insn, arg));
+                    }
+                }
+                for result in self.insn_results(insn) {
+                    undefined.remove(result);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// The serialized form of [`DataFlowGraph`] without the module context.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(crate) struct DfgParts {
+    blocks: PrimaryMap<Block, BlockData>,
+    values: PrimaryMap<Value, ValueData>,
+    insns: PrimaryMap<Insn, InsnData>,
+    insn_results: SecondaryMap<Insn, SmallVec<[Value; 1]>>,
+    immediates: FxHashMap<Immediate, Value>,
+    undefs: FxHashMap<Type, Value>,
+    globals: FxHashMap<GlobalVariable, Value>,
+    users: SecondaryMap<Value, BTreeSet<Insn>>,
+    source_locs: Option<SecondaryMap<Insn, Option<SourceLoc>>>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -338,6 +708,7 @@ pub enum ValueDef {
 
 /// An opaque reference to [`BlockData`]
 #[derive(Clone, PartialEq, Eq, Copy, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Block(pub u32);
 entity_impl!(Block, "block");
 
@@ -345,6 +716,7 @@ entity_impl!(Block, "block");
 /// A Block data doesn't hold any information for layout of a program. It is managed by
 /// [`super::layout::Layout`].
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BlockData {}
 
 impl BlockData {
@@ -352,3 +724,393 @@ impl BlockData {
         Self::default()
     }
 }
+
+/// An error returned when a [`GlobalVariable`] is not defined in the module of the graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DanglingGlobal(pub GlobalVariable);
+
+impl fmt::Display for DanglingGlobal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "global variable `{:?}` is not defined in the module",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for DanglingGlobal {}
+
+/// An error of [`DataFlowGraph::make_imm_value_typed`] and
+/// [`FunctionBuilder::zero`](crate::builder::FunctionBuilder::zero).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidImmediate {
+    /// Immediates of non-integral types can't be made.
+    NotIntegral(Type),
+    /// The number of the bytes doesn't match the size of the type.
+    SizeMismatch {
+        ty: Type,
+        expected: usize,
+        actual: usize,
+    },
+    /// The bits above the width of the type are neither zero nor the sign extension of the
+    /// value.
+    OutOfRange(Type),
+}
+
+impl fmt::Display for InvalidImmediate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotIntegral(ty) => write!(f, "immediate of `{ty:?}` is not integral"),
+            Self::SizeMismatch {
+                ty,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "immediate of `{ty:?}` must be {expected} bytes, but {actual} bytes are given"
+            ),
+            Self::OutOfRange(ty) => write!(
+                f,
+                "bits above the width of `{ty:?}` must be zero or the sign extension of the value"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InvalidImmediate {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::{test_util::*, ModuleBuilder},
+        func_cursor::InsnInserter,
+        GlobalVariableData, Linkage, Signature, Type, U256,
+    };
+
+    #[test]
+    fn insn_source_loc() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg = builder.args()[0];
+        let v0 = builder.add(arg, arg);
+        let v1 = builder.mul(v0, arg);
+        builder.ret(&[v1]);
+        builder.seal_all();
+
+        let dfg = &mut builder.func.dfg;
+        let [add, mul] = [dfg.value_insn(v0).unwrap(), dfg.value_insn(v1).unwrap()];
+        assert_eq!(dfg.insn_source_loc(add), None);
+        assert!(dfg.source_locs.is_none());
+
+        let loc = SourceLoc::new("main.fe", 3, 5);
+        dfg.set_insn_source_loc(add, Some(loc.clone()));
+        assert_eq!(dfg.insn_source_loc(add), Some(&loc));
+        assert_eq!(dfg.insn_source_loc(mul), None);
+
+        dfg.set_insn_source_loc(add, None);
+        assert_eq!(dfg.insn_source_loc(add), None);
+    }
+
+    #[test]
+    fn make_imm_value_typed() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        let dfg = &mut builder.func.dfg;
+
+        // The EVM is big endian.
+        let mut bytes = [0; 32];
+        bytes[0] = 0x80;
+        bytes[31] = 0x01;
+        let v = dfg.make_imm_value_typed(&bytes, Type::I256).unwrap();
+        let imm = dfg.value_imm(v).unwrap();
+        assert_eq!(imm.ty(), Type::I256);
+        assert_eq!(imm.as_zext_u256(), U256::from_big_endian(&bytes));
+
+        // Immediates are interned.
+        let v = dfg.make_imm_value_typed(&[0, 0, 1, 0], Type::I32).unwrap();
+        assert_eq!(v, dfg.make_imm_value(256i32));
+
+        // `i24` occupies 4 bytes.
+        let v = dfg
+            .make_imm_value_typed(&[0, 0xff, 0xff, 0xff], Type::int(24))
+            .unwrap();
+        assert_eq!(
+            dfg.value_imm(v),
+            Some(Immediate::from_i256((-1).into(), Type::int(24)))
+        );
+
+        assert_eq!(
+            dfg.make_imm_value_typed(&[0; 16], Type::I256),
+            Err(InvalidImmediate::SizeMismatch {
+                ty: Type::I256,
+                expected: 32,
+                actual: 16
+            })
+        );
+        // The padding and the high bits must be zero or the sign extension.
+        let v = dfg
+            .make_imm_value_typed(&[0xff, 0xff, 0xff, 0xff], Type::int(24))
+            .unwrap();
+        assert_eq!(
+            dfg.value_imm(v),
+            Some(Immediate::from_i256((-1).into(), Type::int(24)))
+        );
+        assert_eq!(
+            dfg.make_imm_value_typed(&[0x01, 0, 0, 0], Type::int(24)),
+            Err(InvalidImmediate::OutOfRange(Type::int(24)))
+        );
+        assert_eq!(
+            dfg.make_imm_value_typed(&[0x80, 0xff, 0xff, 0xff], Type::int(24)),
+            Err(InvalidImmediate::OutOfRange(Type::int(24)))
+        );
+        assert_eq!(
+            dfg.make_imm_value_typed(&[0x02], Type::I1),
+            Err(InvalidImmediate::OutOfRange(Type::I1))
+        );
+        let v = dfg.make_imm_value_typed(&[0x01], Type::I1).unwrap();
+        assert_eq!(v, dfg.make_imm_value(true));
+
+        let ptr = dfg.ctx.with_ty_store_mut(|s| s.make_ptr(Type::I8));
+        assert_eq!(
+            dfg.make_imm_value_typed(&[0; 32], ptr),
+            Err(InvalidImmediate::NotIntegral(ptr))
+        );
+    }
+
+    #[test]
+    fn check_local_dominance() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg = builder.args()[0];
+        builder.jump(b1);
+
+        builder.switch_to_block(b1);
+        let phi = builder.phi(Type::I32, &[(arg, b0)]);
+        let v1 = builder.add(phi, arg);
+        let v2 = builder.mul(v1, arg);
+        builder.append_phi_arg(phi, v2, b1);
+        builder.ret(&[v2]);
+        builder.seal_all();
+
+        // A phi may use a value defined later in the block.
+        let func = &mut builder.func;
+        assert_eq!(func.dfg.check_local_dominance(&func.layout), None);
+
+        // Move `mul` before `add`.
+        let add = func.dfg.value_insn(v1).unwrap();
+        let mul = func.dfg.value_insn(v2).unwrap();
+        func.layout.remove_insn(mul);
+        func.layout.insert_insn_before(mul, add);
+        assert_eq!(
+            func.dfg.check_local_dominance(&func.layout),
+            Some((mul, v1))
+        );
+    }
+
+    #[test]
+    fn alloca_escapes() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let ptr_ty = mb.ptr_type(Type::I32);
+        let callee = mb.declare_function(Signature::new(
+            "callee",
+            Linkage::External,
+            &[ptr_ty],
+            Type::Void,
+        ));
+        let func_ref = mb.declare_function(Signature::new("f", Linkage::Public, &[], Type::I32));
+
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let local = builder.alloca(Type::I32);
+        let stored = builder.alloca(Type::I32);
+        let passed = builder.alloca(Type::I32);
+        let slot = builder.alloca(ptr_ty);
+
+        let one = builder.make_imm_value(1i32);
+        builder.memory_store(local, one);
+        let v = builder.memory_load(local);
+        // The address of `stored` is stored to `slot`.
+        builder.memory_store(slot, stored);
+        builder.call(callee, &[passed]);
+        builder.ret(&[v]);
+        builder.seal_all();
+
+        let dfg = &builder.func.dfg;
+        let insn = |v| dfg.value_insn(v).unwrap();
+        assert!(!dfg.alloca_escapes(insn(local)));
+        assert!(dfg.alloca_escapes(insn(stored)));
+        assert!(dfg.alloca_escapes(insn(passed)));
+        assert!(!dfg.alloca_escapes(insn(slot)));
+    }
+
+    #[test]
+    fn insn_is_pure() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I64);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let arg = builder.args()[0];
+
+        let sum = builder.add(arg, arg);
+        let quot = builder.udiv(sum, arg);
+        let ext = builder.sext(quot, Type::I64);
+        let addr = builder.alloca(Type::I32);
+        builder.memory_store(addr, sum);
+        let loaded = builder.memory_load(addr);
+        builder.ret(&[ext]);
+        builder.seal_all();
+
+        let dfg = &builder.func.dfg;
+        let insn = |v| dfg.value_insn(v).unwrap();
+        assert!(dfg.insn_is_pure(insn(sum)));
+        assert!(!dfg.insn_is_pure(insn(quot)));
+        assert!(dfg.insn_is_pure(insn(ext)));
+        assert!(!dfg.insn_is_pure(insn(addr)));
+        assert!(!dfg.insn_is_pure(insn(loaded)));
+
+        let ret = builder.func.layout.last_insn_of(b0).unwrap();
+        assert!(!dfg.insn_is_pure(ret));
+    }
+
+    #[test]
+    fn dedup_values() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        // Make values bypassing interning as a frontend may do.
+        let imm = Immediate::I32(1);
+        let dfg = &mut builder.func.dfg;
+        let v1 = dfg.make_value(ValueData::Immediate { imm, ty: Type::I32 });
+        let v2 = dfg.make_value(ValueData::Immediate { imm, ty: Type::I32 });
+        let v3 = dfg.make_value(ValueData::Undef { ty: Type::I32 });
+        let v4 = dfg.make_value(ValueData::Undef { ty: Type::I32 });
+
+        let arg = builder.args()[0];
+        let v5 = builder.add(arg, v1);
+        let v6 = builder.add(v5, v2);
+        let v7 = builder.add(v6, v3);
+        let v8 = builder.add(v7, v4);
+        builder.ret(&[v8]);
+        builder.seal_all();
+
+        let dfg = &mut builder.func.dfg;
+        assert_eq!(dfg.dedup_values(), 2);
+        assert_eq!(dfg.dedup_values(), 0);
+
+        let insn = |v| dfg.value_insn(v).unwrap();
+        assert_eq!(dfg.insn_args(insn(v6)), &[v5, v1]);
+        assert_eq!(dfg.insn_args(insn(v8)), &[v7, v3]);
+        assert_eq!(dfg.users_num(v1), 2);
+        assert_eq!(dfg.users_num(v2), 0);
+        assert_eq!(dfg.users_num(v4), 0);
+
+        // Insn results are never collapsed.
+        assert_eq!(dfg.insn_args(insn(v7)), &[v6, v3]);
+        assert_eq!(dfg.make_imm_value(1i32), v1);
+    }
+
+    #[test]
+    fn intern_global_value() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        let gv = builder.module_builder.make_global(GlobalVariableData::new(
+            "foo".to_string(),
+            Type::I32,
+            Linkage::Private,
+            false,
+            None,
+        ));
+        let dfg = &mut builder.func.dfg;
+
+        let v0 = dfg.make_global_value(gv).unwrap();
+        let v1 = dfg.make_global_value(gv).unwrap();
+        assert_eq!(v0, v1);
+        assert_eq!(dfg.value_gv(v0), Some(gv));
+
+        let dangling = GlobalVariable(1);
+        assert_eq!(
+            dfg.make_global_value(dangling),
+            Err(DanglingGlobal(dangling))
+        );
+    }
+
+    #[test]
+    fn canonicalize_commutative() {
+        let mut builder = test_func_builder(&[Type::I32, Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg0 = builder.args()[0];
+        let arg1 = builder.args()[1];
+        let v2 = builder.mul(arg1, arg0);
+        let v3 = builder.sub(arg1, arg0);
+        let v4 = builder.add(arg0, v3);
+        let v5 = builder.add(v2, v4);
+        builder.ret(&[v5]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+        let ret = func.layout.last_insn_of(b0).unwrap();
+        let dfg = &mut func.dfg;
+        let [mul, sub, add] = [v2, v3, v4].map(|v| dfg.value_insn(v).unwrap());
+        let users = |dfg: &DataFlowGraph| {
+            [arg0, arg1, v2, v3, v4].map(|v| dfg.users(v).copied().collect::<Vec<_>>())
+        };
+        let users_before = users(dfg);
+
+        assert!(dfg.canonicalize_commutative(mul));
+        assert_eq!(dfg.insn_args(mul), &[arg0, arg1]);
+        assert!(!dfg.canonicalize_commutative(mul));
+
+        // Non-commutative insns and already ordered args are left as is.
+        assert!(!dfg.canonicalize_commutative(sub));
+        assert_eq!(dfg.insn_args(sub), &[arg1, arg0]);
+        assert!(!dfg.canonicalize_commutative(add));
+        assert!(!dfg.canonicalize_commutative(ret));
+
+        assert_eq!(users(dfg), users_before);
+    }
+
+    #[test]
+    fn swap_insn_args() {
+        let mut builder = test_func_builder(&[Type::I32, Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg0 = builder.args()[0];
+        let arg1 = builder.args()[1];
+        let v2 = builder.add(arg0, arg1);
+        builder.ret(&[v2]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+
+        let insn = func.dfg.value_insn(v2).unwrap();
+        func.dfg.swap_insn_args(insn, 0, 1);
+
+        assert_eq!(func.dfg.insn_args(insn), &[arg1, arg0]);
+        assert_eq!(func.dfg.users_num(arg0), 1);
+        assert_eq!(func.dfg.users_num(arg1), 1);
+        assert_eq!(func.dfg.users(arg0).next(), Some(&insn));
+        assert_eq!(func.dfg.users(arg1).next(), Some(&insn));
+
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i32, v1.i32) -> i32 {
+    block0:
+        v2.i32 = add v1 v0;
+        return v2;
+
+}
+"
+        );
+    }
+}
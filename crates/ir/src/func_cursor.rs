@@ -251,3 +251,113 @@ impl FuncCursor for InsnInserter {
         self.loc
     }
 }
+
+impl InsnInserter {
+    /// Makes an insn of `data`, and inserts it right before `anchor`.
+    /// The cursor is moved to the new insn, and the new insn is returned so that the caller can
+    /// attach its result.
+    ///
+    /// # Panics
+    /// Panics if `anchor` is not inserted in the layout.
+    pub fn insert_before(&mut self, func: &mut Function, anchor: Insn, data: InsnData) -> Insn {
+        assert!(
+            func.layout.is_insn_inserted(anchor),
+            "anchor `{anchor:?}` is not inserted in the layout"
+        );
+
+        let insn = func.dfg.make_insn(data);
+        func.layout.insert_insn_before(insn, anchor);
+        self.set_location(CursorLocation::At(insn));
+        insn
+    }
+
+    /// Makes an insn of `data`, and inserts it right after `anchor`.
+    /// The cursor is moved to the new insn, and the new insn is returned so that the caller can
+    /// attach its result.
+    ///
+    /// # Panics
+    /// Panics if `anchor` is not inserted in the layout, or `anchor` is a terminator, because
+    /// nothing may follow the terminator of a block.
+    pub fn insert_after(&mut self, func: &mut Function, anchor: Insn, data: InsnData) -> Insn {
+        assert!(
+            func.layout.is_insn_inserted(anchor),
+            "anchor `{anchor:?}` is not inserted in the layout"
+        );
+        assert!(
+            !func.dfg.is_terminator(anchor),
+            "can't insert an insn after the terminator `{anchor:?}`"
+        );
+
+        let insn = func.dfg.make_insn(data);
+        func.layout.insert_insn_after(insn, anchor);
+        self.set_location(CursorLocation::At(insn));
+        insn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{builder::test_util::*, insn::BinaryOp, Type};
+
+    fn build_func() -> (Function, Value) {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg = builder.args()[0];
+        let v1 = builder.add(arg, arg);
+        builder.ret(&[v1]);
+        builder.seal_all();
+
+        (builder.func, arg)
+    }
+
+    #[test]
+    fn insert_before_first_insn() {
+        let (mut func, arg) = build_func();
+        let block = func.layout.entry_block().unwrap();
+        let first = func.layout.first_insn_of(block).unwrap();
+
+        let mut inserter = InsnInserter::at_location(CursorLocation::NoWhere);
+        let insn =
+            inserter.insert_before(&mut func, first, InsnData::binary(BinaryOp::Mul, arg, arg));
+        let result = inserter.make_result(&mut func, insn).unwrap();
+        inserter.attach_result(&mut func, insn, result);
+
+        assert_eq!(inserter.loc(), CursorLocation::At(insn));
+        assert_eq!(func.layout.first_insn_of(block), Some(insn));
+        assert_eq!(func.layout.next_insn_of(insn), Some(first));
+        assert_eq!(func.layout.prev_insn_of(first), Some(insn));
+        assert_eq!(func.dfg.insn_result(insn), Some(result));
+        assert_eq!(func.dfg.users_num(arg), 2);
+    }
+
+    #[test]
+    fn insert_after() {
+        let (mut func, arg) = build_func();
+        let block = func.layout.entry_block().unwrap();
+        let first = func.layout.first_insn_of(block).unwrap();
+        let last = func.layout.last_insn_of(block).unwrap();
+
+        let mut inserter = InsnInserter::at_location(CursorLocation::NoWhere);
+        let insn =
+            inserter.insert_after(&mut func, first, InsnData::binary(BinaryOp::Mul, arg, arg));
+
+        assert_eq!(func.layout.next_insn_of(first), Some(insn));
+        assert_eq!(func.layout.next_insn_of(insn), Some(last));
+        assert_eq!(func.layout.last_insn_of(block), Some(last));
+    }
+
+    #[test]
+    #[should_panic(expected = "can't insert an insn after the terminator")]
+    fn insert_after_terminator() {
+        let (mut func, arg) = build_func();
+        let block = func.layout.entry_block().unwrap();
+        let last = func.layout.last_insn_of(block).unwrap();
+
+        let mut inserter = InsnInserter::at_location(CursorLocation::NoWhere);
+        inserter.insert_after(&mut func, last, InsnData::binary(BinaryOp::Mul, arg, arg));
+    }
+}
@@ -1,8 +1,14 @@
 use super::{module::FuncRef, DataFlowGraph, Layout, Type, Value};
-use crate::{module::ModuleCtx, types::DisplayType, Linkage};
+use crate::{
+    attributes::{AttrMap, AttrValue},
+    func_cursor::{CursorLocation, FuncCursor, InsnInserter},
+    isa::TargetIsa,
+    module::ModuleCtx,
+    Block, Insn, Linkage,
+};
 use rustc_hash::FxHashMap;
 use smallvec::SmallVec;
-use std::fmt::{self, Write};
+use std::fmt;
 
 #[derive(Debug, Clone)]
 pub struct Function {
@@ -14,6 +20,9 @@ pub struct Function {
 
     /// Stores signatures of all functions that are called by the function.
     pub callees: FxHashMap<FuncRef, Signature>,
+
+    /// Attributes attached by frontends and passes, e.g., [`crate::attributes::NOINLINE`].
+    pub attributes: AttrMap,
 }
 
 impl Function {
@@ -35,11 +44,144 @@ impl Function {
             dfg,
             layout: Layout::default(),
             callees: FxHashMap::default(),
+            attributes: AttrMap::default(),
+        }
+    }
+
+    /// Sets the attribute `name` of the function to `value`, and returns the previous value if
+    /// any.
+    pub fn set_attr(&mut self, name: impl Into<String>, value: AttrValue) -> Option<AttrValue> {
+        self.attributes.insert(name, value)
+    }
+
+    pub fn get_attr(&self, name: &str) -> Option<&AttrValue> {
+        self.attributes.get(name)
+    }
+
+    /// Returns the sum of the estimated costs of all insns in the function on `isa`.
+    ///
+    /// Each insn is counted once regardless of how many times it's executed at runtime, so this
+    /// is meant to compare the function before and after a transformation.
+    pub fn estimate_cost(&self, isa: &TargetIsa) -> u64 {
+        let cost_model = isa.cost_model();
+        self.layout
+            .iter_block()
+            .flat_map(|block| self.layout.iter_insn(block))
+            .map(|insn| cost_model.insn_cost(self.dfg.insn_data(insn)))
+            .sum()
+    }
+
+    /// Removes `block` and all its insns from the function, and removes the phi args flowing
+    /// from `block` in its successors.
+    ///
+    /// `block` must have no predecessors except for itself; use [`Self::force_remove_block`] to
+    /// also remove the branches to `block`.
+    pub fn remove_block(&mut self, block: Block) {
+        debug_assert!(
+            self.preds_of(block).is_empty(),
+            "{block} still has predecessors"
+        );
+        self.detach_block(block);
+    }
+
+    /// Similar to [`Self::remove_block`], but also removes the destinations pointing at
+    /// `block` from the branches of its predecessors.
+    ///
+    /// # Panics
+    /// Panics if a predecessor jumps to `block` with a `jump` insn, because the predecessor
+    /// would lose its terminator.
+    pub fn force_remove_block(&mut self, block: Block) {
+        for pred in self.preds_of(block) {
+            let last_insn = self.layout.last_insn_of(pred).unwrap();
+            self.dfg.remove_branch_dest(last_insn, block);
+        }
+        self.detach_block(block);
+    }
+
+    /// Moves `insn` right before `before` in the layout.
+    ///
+    /// In debug builds, this panics if the move places a use of a value before its definition
+    /// in the same block, i.e., if an argument of `insn` is defined at or after `before`, or if
+    /// the result of `insn` is used before `before`.
+    pub fn move_insn(&mut self, insn: Insn, before: Insn) {
+        #[cfg(debug_assertions)]
+        {
+            let block = self.layout.insn_block(before);
+            // The position of each insn in the block after the move.
+            let order: Vec<_> = self
+                .layout
+                .iter_insn(block)
+                .filter(|i| *i != insn)
+                .flat_map(|i| if i == before { vec![insn, i] } else { vec![i] })
+                .collect();
+            let pos = |i: Insn| order.iter().position(|o| *o == i);
+            let insn_pos = pos(insn).unwrap();
+
+            for arg in self.dfg.insn_args(insn) {
+                if let Some(def) = self.dfg.value_insn(*arg) {
+                    assert!(
+                        pos(def).is_none_or(|def_pos| def_pos < insn_pos),
+                        "moving `{insn:?}` places a use of `{arg:?}` before its definition"
+                    );
+                }
+            }
+            for &result in self.dfg.insn_results(insn) {
+                for user in self.dfg.users(result) {
+                    assert!(
+                        self.dfg.is_phi(*user)
+                            || pos(*user).is_none_or(|user_pos| insn_pos < user_pos),
+                        "moving `{insn:?}` places a use of `{result:?}` before its definition"
+                    );
+                }
+            }
+        }
+
+        self.layout.move_insn(insn, before);
+    }
+
+    /// Returns the predecessors of `block` other than `block` itself.
+    fn preds_of(&self, block: Block) -> Vec<Block> {
+        self.layout
+            .iter_block()
+            .filter(|pred| *pred != block && self.succs_of(*pred).contains(&block))
+            .collect()
+    }
+
+    fn succs_of(&self, block: Block) -> Vec<Block> {
+        let Some(last_insn) = self.layout.last_insn_of(block) else {
+            return vec![];
+        };
+
+        let mut succs: Vec<_> = self.dfg.analyze_branch(last_insn).iter_dests().collect();
+        succs.sort_unstable();
+        succs.dedup();
+        succs
+    }
+
+    fn detach_block(&mut self, block: Block) {
+        for succ in self.succs_of(block) {
+            if succ == block {
+                continue;
+            }
+
+            let phis: Vec<_> = self
+                .layout
+                .iter_insn(succ)
+                .take_while(|insn| self.dfg.is_phi(*insn))
+                .collect();
+            for phi in phis {
+                if self.dfg.phi_blocks(phi).contains(&block) {
+                    self.dfg.remove_phi_arg(phi, block);
+                }
+            }
         }
+
+        InsnInserter::at_location(CursorLocation::BlockTop(block)).remove_block(self);
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Signature {
     /// Name of the function.
     name: String,
@@ -48,18 +190,33 @@ pub struct Signature {
     linkage: Linkage,
 
     args: SmallVec<[Type; 8]>,
-    ret_ty: Type,
+
+    /// Types of the return values. This is empty if the function returns nothing.
+    ret_tys: SmallVec<[Type; 2]>,
 }
 
 impl Signature {
+    /// Makes a signature of a function returning at most one value.
+    /// `ret_ty` is [`Type::Void`] if the function returns nothing.
     pub fn new(name: &str, linkage: Linkage, args: &[Type], ret_ty: Type) -> Self {
+        let ret_tys: &[Type] = if ret_ty == Type::Void { &[] } else { &[ret_ty] };
+        Self::with_ret_tys(name, linkage, args, ret_tys)
+    }
+
+    /// Makes a signature of a function returning multiple values.
+    pub fn with_ret_tys(name: &str, linkage: Linkage, args: &[Type], ret_tys: &[Type]) -> Self {
+        debug_assert!(
+            !ret_tys.contains(&Type::Void),
+            "void can't be a type of return values"
+        );
         Self {
             name: name.to_string(),
             linkage,
             args: args.into(),
-            ret_ty,
+            ret_tys: ret_tys.into(),
         }
     }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -72,13 +229,28 @@ impl Signature {
         &self.args
     }
 
+    pub fn ret_tys(&self) -> &[Type] {
+        &self.ret_tys
+    }
+
+    /// Returns the type of the return value, or [`Type::Void`] if the function returns nothing.
+    ///
+    /// # Panics
+    /// Panics if the function returns multiple values; use [`Self::ret_tys`] instead.
     pub fn ret_ty(&self) -> Type {
-        self.ret_ty
+        match self.ret_tys.as_slice() {
+            [] => Type::Void,
+            [ty] => *ty,
+            _ => panic!("`%{}` returns multiple values", self.name),
+        }
     }
 
     #[doc(hidden)]
     pub fn set_ret_ty(&mut self, ty: Type) {
-        self.ret_ty = ty;
+        self.ret_tys.clear();
+        if ty != Type::Void {
+            self.ret_tys.push(ty);
+        }
     }
 }
 
@@ -96,22 +268,191 @@ impl<'a, 'b> DisplaySignature<'a, 'b> {
 impl<'a, 'b> fmt::Display for DisplaySignature<'a, 'b> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let Self { sig, dfg } = *self;
-        let Signature {
-            name,
-            linkage,
-            args,
-            ret_ty,
-        } = sig;
-
-        let mut args_ty = String::new();
-        for arg_ty in args {
-            let ty = DisplayType::new(*arg_ty, dfg);
-            write!(&mut args_ty, "{ty} ")?;
-        }
-        let args_ty = args_ty.trim();
+        let func_ty = dfg
+            .ctx
+            .with_ty_store(|s| s.display_func_type(&sig.args, &sig.ret_tys));
+        write!(f, "func {} %{}{func_ty}", sig.linkage, sig.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{builder::test_util::*, insn::DataLocationKind, Function, Type};
+
+    #[test]
+    fn move_insn() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg = builder.args()[0];
+        let v1 = builder.add(arg, arg);
+        let v2 = builder.mul(arg, arg);
+        let v3 = builder.sub(v1, v2);
+        builder.ret(&[v3]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+        let insn = |func: &Function, v| func.dfg.value_insn(v).unwrap();
+
+        // `mul` is independent of `add`, so it can be moved to the block top.
+        func.move_insn(insn(func, v2), insn(func, v1));
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        v2.i32 = mul v0 v0;
+        v1.i32 = add v0 v0;
+        v3.i32 = sub v1 v2;
+        return v3;
+
+}
+"
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "before its definition")]
+    fn move_insn_before_def() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg = builder.args()[0];
+        let v1 = builder.add(arg, arg);
+        let v2 = builder.mul(v1, arg);
+        builder.ret(&[v2]);
+        builder.seal_all();
+
+        let mut func = builder.func;
+        let add = func.dfg.value_insn(v1).unwrap();
+        let mul = func.dfg.value_insn(v2).unwrap();
+        func.move_insn(mul, add);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "before its definition")]
+    fn move_def_after_use() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg = builder.args()[0];
+        let v1 = builder.add(arg, arg);
+        let v2 = builder.mul(v1, arg);
+        builder.ret(&[v2]);
+        builder.seal_all();
+
+        let mut func = builder.func;
+        let add = func.dfg.value_insn(v1).unwrap();
+        let ret = func.layout.last_insn_of(b0).unwrap();
+        func.move_insn(add, ret);
+    }
+
+    #[test]
+    fn remove_block() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+
+        builder.switch_to_block(b0);
+        let arg = builder.args()[0];
+        builder.jump(b2);
+
+        builder.switch_to_block(b1);
+        let v1 = builder.add(arg, arg);
+        builder.jump(b2);
+
+        builder.switch_to_block(b2);
+        let v2 = builder.phi(Type::I32, &[(arg, b0), (v1, b1)]);
+        builder.ret(&[v2]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+
+        func.remove_block(b1);
+        assert!(!func.layout.is_block_inserted(b1));
+        assert_eq!(func.dfg.users_num(arg), 1);
+
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        jump block2;
+
+    block2:
+        v2.i32 = phi (v0 block0);
+        return v2;
+
+}
+"
+        );
+    }
+
+    #[test]
+    fn force_remove_block() {
+        let mut builder = test_func_builder(&[Type::I1], Type::Void);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+
+        builder.switch_to_block(b0);
+        let arg = builder.args()[0];
+        builder.br(arg, b1, b2);
+
+        builder.switch_to_block(b1);
+        builder.jump(b1);
+
+        builder.switch_to_block(b2);
+        builder.ret(&[]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+
+        func.force_remove_block(b1);
+        assert_eq!(func.dfg.users_num(arg), 0);
+
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i1) -> void {
+    block0:
+        jump block2;
+
+    block2:
+        return;
+
+}
+"
+        );
+    }
+
+    #[test]
+    fn estimate_cost() {
+        let mut builder = test_func_builder(&[Type::I256], Type::Void);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg = builder.args()[0];
+        let v1 = builder.add(arg, arg);
+        let v2 = builder.mul(v1, arg);
+        builder.store(DataLocationKind::Storage, arg, v2);
+        builder.ret(&[]);
+        builder.seal_all();
 
-        let ret_ty = DisplayType::new(*ret_ty, dfg);
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &module.funcs[func_ref];
 
-        write!(f, "func {linkage} %{name}({args_ty}) -> {ret_ty}")
+        // `ADD`, `MUL`, `SSTORE` and the jump to the return address.
+        assert_eq!(func.estimate_cost(&module.ctx.isa), 3 + 5 + 20000 + 8);
     }
 }
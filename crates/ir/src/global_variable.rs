@@ -3,16 +3,33 @@ use std::fmt;
 use cranelift_entity::PrimaryMap;
 use rustc_hash::FxHashMap;
 
-use crate::{types::DisplayType, DataFlowGraph, Immediate, Linkage, Type};
+use crate::{
+    types::{DisplayType, TypeStore},
+    DataFlowGraph, Immediate, Linkage, Type,
+};
 
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GlobalVariableStore {
     gv_data: PrimaryMap<GlobalVariable, GlobalVariableData>,
     symbols: FxHashMap<String, GlobalVariable>,
 }
 
 impl GlobalVariableStore {
-    pub fn make_gv(&mut self, gv_data: GlobalVariableData) -> GlobalVariable {
+    /// Makes a global variable.
+    ///
+    /// # Panics
+    /// Panics if the symbol is already used, or the initializer doesn't match the type of the
+    /// global variable.
+    pub fn make_gv(&mut self, gv_data: GlobalVariableData, ty_store: &TypeStore) -> GlobalVariable {
+        if let Some(data) = &gv_data.data {
+            assert!(
+                data.is_compatible_with(gv_data.ty, ty_store),
+                "initializer of global `{}` doesn't match its type",
+                gv_data.symbol
+            );
+        }
+
         match self.symbols.entry(gv_data.symbol.to_string()) {
             std::collections::hash_map::Entry::Occupied(_) => {
                 panic!("duplicate global symbol `{}`", gv_data.symbol);
@@ -25,10 +42,30 @@ impl GlobalVariableStore {
         }
     }
 
+    /// Replaces the data of `gv` with `gv_data` of the same symbol.
+    pub(crate) fn replace_gv_data(&mut self, gv: GlobalVariable, gv_data: GlobalVariableData) {
+        debug_assert_eq!(self.gv_data[gv].symbol, gv_data.symbol);
+        self.gv_data[gv] = gv_data;
+    }
+
+    /// Renames `gv` to the unused `symbol`.
+    pub(crate) fn rename_gv(&mut self, gv: GlobalVariable, symbol: String) {
+        let data = &mut self.gv_data[gv];
+        self.symbols.remove(&data.symbol);
+        let prev = self.symbols.insert(symbol.clone(), gv);
+        debug_assert!(prev.is_none(), "duplicate global symbol `{symbol}`");
+        data.symbol = symbol;
+    }
+
     pub fn gv_data(&self, gv: GlobalVariable) -> &GlobalVariableData {
         &self.gv_data[gv]
     }
 
+    /// Returns `true` if `gv` is made in the store.
+    pub fn contains(&self, gv: GlobalVariable) -> bool {
+        self.gv_data.is_valid(gv)
+    }
+
     pub fn gv_by_symbol(&self, symbol: &str) -> Option<GlobalVariable> {
         self.symbols.get(symbol).copied()
     }
@@ -48,10 +85,22 @@ impl GlobalVariableStore {
     pub fn all_gv_data(&self) -> impl Iterator<Item = &GlobalVariableData> {
         self.gv_data.values()
     }
+
+    /// Returns all global variables in a deterministic order, i.e., sorted by symbol name, then
+    /// by creation order.
+    /// This should be used when emitting or serializing global variables.
+    pub fn ordered_iter(&self) -> impl Iterator<Item = (GlobalVariable, &GlobalVariableData)> {
+        let mut gvs: Vec<_> = self.gv_data.iter().collect();
+        gvs.sort_by(|(lhs_gv, lhs), (rhs_gv, rhs)| {
+            lhs.symbol.cmp(&rhs.symbol).then(lhs_gv.cmp(rhs_gv))
+        });
+        gvs.into_iter()
+    }
 }
 
 /// An opaque reference to [`GlobalVariableData`].
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GlobalVariable(pub u32);
 cranelift_entity::entity_impl!(GlobalVariable);
 
@@ -77,6 +126,7 @@ impl<'a> fmt::Display for DisplayGlobalVariable<'a> {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GlobalVariableData {
     pub symbol: String,
     pub ty: Type,
@@ -141,6 +191,7 @@ impl<'a, 'b> fmt::Display for DisplayGlobalVariableData<'a, 'b> {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConstantValue {
     Immediate(Immediate),
     Array(Vec<ConstantValue>),
@@ -159,6 +210,33 @@ impl ConstantValue {
     pub fn make_struct(data: Vec<ConstantValue>) -> Self {
         Self::Struct(data)
     }
+
+    /// Returns `true` if the value can initialize a global variable of `ty`, i.e., immediates
+    /// have the same type, and aggregates have the same shape as `ty` recursively.
+    pub fn is_compatible_with(&self, ty: Type, ty_store: &TypeStore) -> bool {
+        match self {
+            Self::Immediate(imm) => imm.ty() == ty,
+            Self::Array(elems) => match ty_store.array_def(ty) {
+                Some((elem_ty, len)) => {
+                    elems.len() == len
+                        && elems
+                            .iter()
+                            .all(|elem| elem.is_compatible_with(elem_ty, ty_store))
+                }
+                None => false,
+            },
+            Self::Struct(fields) => match ty_store.struct_fields(ty) {
+                Some(field_tys) => {
+                    fields.len() == field_tys.len()
+                        && fields
+                            .iter()
+                            .zip(field_tys)
+                            .all(|(field, field_ty)| field.is_compatible_with(*field_ty, ty_store))
+                }
+                None => false,
+            },
+        }
+    }
 }
 
 impl fmt::Display for ConstantValue {
@@ -201,13 +279,16 @@ mod test {
 
         let cv = ConstantValue::make_imm(1618i32);
         let gv = ctx.with_gv_store_mut(|s| {
-            s.make_gv(GlobalVariableData::new(
-                String::from("foo"),
-                Type::I32,
-                Linkage::Public,
-                true,
-                Some(cv),
-            ))
+            s.make_gv(
+                GlobalVariableData::new(
+                    String::from("foo"),
+                    Type::I32,
+                    Linkage::Public,
+                    true,
+                    Some(cv),
+                ),
+                &TypeStore::default(),
+            )
         });
 
         let dfg = DataFlowGraph::new(ctx);
@@ -225,19 +306,119 @@ mod test {
         let cv2 = ConstantValue::make_imm(2i32);
         let const_arr = ConstantValue::make_array(vec![cv0, cv1, cv2]);
         let ty = ctx.with_ty_store_mut(|s| s.make_array(Type::I32, 3));
-        let gv = ctx.with_gv_store_mut(|s| {
-            s.make_gv(GlobalVariableData::new(
-                String::from("foo"),
-                ty,
-                Linkage::Private,
-                true,
-                Some(const_arr),
-            ))
+        let gv = ctx.with_ty_store(|ty_store| {
+            ctx.with_gv_store_mut(|s| {
+                s.make_gv(
+                    GlobalVariableData::new(
+                        String::from("foo"),
+                        ty,
+                        Linkage::Private,
+                        true,
+                        Some(const_arr),
+                    ),
+                    ty_store,
+                )
+            })
+        });
+
+        let dfg = DataFlowGraph::new(ctx);
+        let display_gv = DisplayGlobalVariable::new(gv, &dfg);
+
+        assert_eq!(display_gv.to_string(), "[i32; 3] const private [8, 4, 2]");
+    }
+
+    #[test]
+    fn display_gv_nested() {
+        let ctx = ModuleCtx::new(build_test_isa());
+
+        let ty = ctx.with_ty_store_mut(|s| {
+            let arr = s.make_array(Type::I8, 2);
+            s.make_struct("s", &[Type::I32, arr], false)
+        });
+        let data = ConstantValue::make_struct(vec![
+            ConstantValue::make_imm(1i32),
+            ConstantValue::make_array(vec![
+                ConstantValue::make_imm(2i8),
+                ConstantValue::make_imm(3i8),
+            ]),
+        ]);
+        let gv = ctx.with_ty_store(|ty_store| {
+            ctx.with_gv_store_mut(|s| {
+                s.make_gv(
+                    GlobalVariableData::constant(String::from("foo"), ty, Linkage::Private, data),
+                    ty_store,
+                )
+            })
         });
 
         let dfg = DataFlowGraph::new(ctx);
         let display_gv = DisplayGlobalVariable::new(gv, &dfg);
 
-        assert_eq!(display_gv.to_string(), "[i32;3] const private [8, 4, 2]");
+        assert_eq!(display_gv.to_string(), "%s const private {1, [2, 3]}");
+    }
+
+    #[test]
+    fn initializer_compatibility() {
+        let mut ty_store = TypeStore::default();
+        let arr = ty_store.make_array(Type::I8, 2);
+        let st = ty_store.make_struct("s", &[Type::I32, arr], false);
+
+        let imm = |v: i8| ConstantValue::make_imm(v);
+        let arr_data = ConstantValue::make_array(vec![imm(1), imm(2)]);
+        assert!(arr_data.is_compatible_with(arr, &ty_store));
+        assert!(!ConstantValue::make_array(vec![imm(1)]).is_compatible_with(arr, &ty_store));
+        assert!(!arr_data.is_compatible_with(st, &ty_store));
+
+        let st_data = ConstantValue::make_struct(vec![ConstantValue::make_imm(1i32), arr_data]);
+        assert!(st_data.is_compatible_with(st, &ty_store));
+        let st_data = ConstantValue::make_struct(vec![imm(1), imm(2)]);
+        assert!(!st_data.is_compatible_with(st, &ty_store));
+    }
+
+    #[test]
+    #[should_panic(expected = "initializer of global `foo` doesn't match its type")]
+    fn make_gv_with_mismatched_initializer() {
+        let mut store = GlobalVariableStore::default();
+        store.make_gv(
+            GlobalVariableData::constant(
+                String::from("foo"),
+                Type::I64,
+                Linkage::Private,
+                ConstantValue::make_imm(1i32),
+            ),
+            &TypeStore::default(),
+        );
+    }
+
+    #[test]
+    fn ordered_iter() {
+        let make_store = |symbols: &[&str]| {
+            let mut store = GlobalVariableStore::default();
+            for symbol in symbols {
+                store.make_gv(
+                    GlobalVariableData::new(
+                        symbol.to_string(),
+                        Type::I32,
+                        Linkage::Private,
+                        false,
+                        None,
+                    ),
+                    &TypeStore::default(),
+                );
+            }
+            store
+        };
+
+        let store0 = make_store(&["foo", "bar", "baz"]);
+        let store1 = make_store(&["baz", "foo", "bar"]);
+
+        let symbols = |store: &GlobalVariableStore| {
+            store
+                .ordered_iter()
+                .map(|(_, data)| data.symbol.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(symbols(&store0), vec!["bar", "baz", "foo"]);
+        assert_eq!(symbols(&store0), symbols(&store1));
     }
 }
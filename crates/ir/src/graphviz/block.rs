@@ -1,34 +1,54 @@
 use std::fmt::Write;
 
+use cranelift_entity::EntityRef;
 use dot2::label;
 
-use crate::{function::DisplaySignature, insn::DisplayInsn, Block, ControlFlowGraph, Function};
+use crate::{
+    function::DisplaySignature, ir_writer::FuncWriter, module::FuncRef, Block, ControlFlowGraph,
+    Function,
+};
 
-use super::function::DUMMY_BLOCK;
+use super::{function::DUMMY_BLOCK, GraphvizOptions};
 
 #[derive(Debug, Clone, Copy)]
 pub(super) struct BlockNode<'a> {
     pub(super) func: &'a Function,
     pub(super) cfg: &'a ControlFlowGraph,
     pub(super) block: Block,
+    pub(super) options: GraphvizOptions,
 }
 
 impl<'a> BlockNode<'a> {
-    pub(super) fn new(func: &'a Function, cfg: &'a ControlFlowGraph, block: Block) -> Self {
-        Self { func, cfg, block }
+    pub(super) fn new(
+        func: &'a Function,
+        cfg: &'a ControlFlowGraph,
+        block: Block,
+        options: GraphvizOptions,
+    ) -> Self {
+        Self {
+            func,
+            cfg,
+            block,
+            options,
+        }
     }
 
     pub(super) fn succs(self) -> Vec<Self> {
         self.cfg
             .succs_of(self.block)
-            .map(|block| BlockNode::new(self.func, self.cfg, *block))
+            .map(|block| BlockNode::new(self.func, self.cfg, *block, self.options))
             .collect()
     }
 }
 
 impl<'a> BlockNode<'a> {
     pub(super) fn label(self) -> label::Text<'static> {
-        let Self { block, func, .. } = self;
+        let Self {
+            block,
+            func,
+            cfg,
+            options,
+        } = self;
         let Function {
             sig, dfg, layout, ..
         } = func;
@@ -42,22 +62,25 @@ impl<'a> BlockNode<'a> {
         // Write block header.
         write!(
             &mut label,
-            r#"<tr><td bgcolor="gray" align="center" colspan="1">{}</td></tr>"#,
-            block
+            r#"<tr><td bgcolor="gray" align="center" colspan="1">{} (preds: {})</td></tr>"#,
+            block,
+            cfg.pred_num_of(block)
         )
         .unwrap();
 
         // Write block body.
-        write!(label, r#"<tr><td align="left" balign="left">"#).unwrap();
-        for insn in layout.iter_insn(self.block) {
-            let display_insn = DisplayInsn::new(insn, func);
-            let mut insn_string = String::new();
-            write!(&mut insn_string, "{}", display_insn).unwrap();
-
-            write!(label, "{}", dot2::escape_html(&insn_string)).unwrap();
-            write!(label, "<br/>").unwrap();
+        if options.show_insts {
+            // The func ref is only used to look up value names from a debug provider, so a
+            // dummy one is fine here.
+            let mut writer = FuncWriter::new(FuncRef::new(0), func, None);
+            write!(label, r#"<tr><td align="left" balign="left">"#).unwrap();
+            for insn in layout.iter_insn(self.block) {
+                let insn_string = writer.insn_string(insn).unwrap();
+                write!(label, "{}", dot2::escape_html(&insn_string)).unwrap();
+                write!(label, "<br/>").unwrap();
+            }
+            write!(label, r#"</td></tr>"#).unwrap();
         }
-        write!(label, r#"</td></tr>"#).unwrap();
 
         write!(label, "</table>").unwrap();
 
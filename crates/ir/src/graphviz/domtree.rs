@@ -0,0 +1,168 @@
+use std::iter;
+
+use dot2::{label::Text, GraphWalk, Id, Labeller, Style};
+
+use crate::{Block, ControlFlowGraph, Function};
+
+use super::{
+    block::BlockNode, function::DUMMY_BLOCK, DominanceFrontiers, DominatorTree, GraphvizOptions,
+};
+
+pub(super) struct DomTreeGraph<'a> {
+    func: &'a Function,
+    cfg: &'a ControlFlowGraph,
+    domtree: &'a dyn DominatorTree,
+    frontiers: Option<&'a dyn DominanceFrontiers>,
+    options: GraphvizOptions,
+}
+
+impl<'a> DomTreeGraph<'a> {
+    pub(super) fn new(
+        func: &'a Function,
+        cfg: &'a ControlFlowGraph,
+        domtree: &'a dyn DominatorTree,
+        frontiers: Option<&'a dyn DominanceFrontiers>,
+        options: GraphvizOptions,
+    ) -> Self {
+        Self {
+            func,
+            cfg,
+            domtree,
+            frontiers,
+            options,
+        }
+    }
+
+    /// Returns the blocks reachable from the entry block in layout order, followed by the dummy
+    /// block that labels the graph with the function signature.
+    fn blocks(&self) -> Vec<BlockNode<'a>> {
+        let Self {
+            func, cfg, options, ..
+        } = *self;
+        let entry = func.layout.entry_block();
+        func.layout
+            .iter_block()
+            .filter(|&block| Some(block) == entry || self.domtree.idom_of(block).is_some())
+            .chain(iter::once(DUMMY_BLOCK))
+            .map(|block| BlockNode::new(func, cfg, block, options))
+            .collect()
+    }
+}
+
+impl<'a> Labeller<'a> for DomTreeGraph<'a> {
+    type Node = BlockNode<'a>;
+    type Edge = DomEdge<'a>;
+    type Subgraph = ();
+
+    fn graph_id(&self) -> dot2::Result<Id<'a>> {
+        Id::new(format!("{}_domtree", self.func.sig.name()))
+    }
+
+    fn node_id(&self, n: &Self::Node) -> dot2::Result<Id<'a>> {
+        let block = n.block;
+        if block == DUMMY_BLOCK {
+            return dot2::Id::new("dummy_block");
+        }
+        dot2::Id::new(format!("{block}"))
+    }
+
+    fn node_shape(&self, _n: &Self::Node) -> Option<Text<'a>> {
+        Some(Text::LabelStr("none".into()))
+    }
+
+    fn edge_style(&'a self, e: &Self::Edge) -> Style {
+        match e.kind {
+            DomEdgeKind::Signature => Style::Invisible,
+            DomEdgeKind::Idom => Style::None,
+            DomEdgeKind::Frontier => Style::Dashed,
+        }
+    }
+
+    fn node_label(&'a self, n: &Self::Node) -> dot2::Result<Text<'a>> {
+        Ok(n.label())
+    }
+
+    fn edge_label(&self, e: &Self::Edge) -> Text<'a> {
+        if e.kind == DomEdgeKind::Frontier && self.options.show_edge_labels {
+            Text::LabelStr("df".into())
+        } else {
+            Text::LabelStr("".into())
+        }
+    }
+}
+
+impl<'a> GraphWalk<'a> for DomTreeGraph<'a> {
+    type Node = BlockNode<'a>;
+    type Edge = DomEdge<'a>;
+    type Subgraph = ();
+
+    fn nodes(&self) -> dot2::Nodes<'a, Self::Node> {
+        self.blocks().into()
+    }
+
+    fn edges(&'a self) -> dot2::Edges<'a, Self::Edge> {
+        let mut blocks = self.blocks();
+        let dummy_block = blocks.pop().unwrap();
+
+        let mut edges = vec![];
+        if let Some(&entry) = blocks.first() {
+            edges.push(DomEdge {
+                from: dummy_block,
+                to: entry,
+                kind: DomEdgeKind::Signature,
+            });
+        }
+
+        let node_of = |block: Block| blocks.iter().copied().find(|node| node.block == block);
+        for &node in &blocks {
+            if let Some(idom) = self.domtree.idom_of(node.block).and_then(node_of) {
+                edges.push(DomEdge {
+                    from: idom,
+                    to: node,
+                    kind: DomEdgeKind::Idom,
+                });
+            }
+        }
+
+        if let Some(frontiers) = self.frontiers {
+            for &node in &blocks {
+                for frontier in frontiers.frontiers_of(node.block) {
+                    if let Some(frontier) = node_of(frontier) {
+                        edges.push(DomEdge {
+                            from: node,
+                            to: frontier,
+                            kind: DomEdgeKind::Frontier,
+                        });
+                    }
+                }
+            }
+        }
+
+        edges.into()
+    }
+
+    fn source(&self, edge: &Self::Edge) -> Self::Node {
+        edge.from
+    }
+
+    fn target(&self, edge: &Self::Edge) -> Self::Node {
+        edge.to
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct DomEdge<'a> {
+    from: BlockNode<'a>,
+    to: BlockNode<'a>,
+    kind: DomEdgeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DomEdgeKind {
+    /// An invisible edge from the dummy block that carries the function signature.
+    Signature,
+    /// A tree edge from the immediate dominator to a block.
+    Idom,
+    /// An edge from a block to a block in its dominance frontier.
+    Frontier,
+}
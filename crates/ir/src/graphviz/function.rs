@@ -4,29 +4,30 @@ use dot2::{label::Text, GraphWalk, Id, Labeller, Style};
 
 use crate::{value::DisplayArgValue, Block, ControlFlowGraph, Function, InsnData};
 
-use super::block::BlockNode;
+use super::{block::BlockNode, GraphvizOptions};
 
 pub(super) const DUMMY_BLOCK: Block = Block(u32::MAX);
 
 pub(super) struct FunctionGraph<'a> {
     func: &'a Function,
     cfg: &'a ControlFlowGraph,
+    options: GraphvizOptions,
 }
 
 impl<'a> FunctionGraph<'a> {
-    pub fn new(func: &'a Function, cfg: &'a ControlFlowGraph) -> Self {
-        Self { func, cfg }
+    pub fn new(func: &'a Function, cfg: &'a ControlFlowGraph, options: GraphvizOptions) -> Self {
+        Self { func, cfg, options }
     }
 }
 
 impl<'a> FunctionGraph<'a> {
     pub(super) fn blocks(&self) -> Vec<BlockNode<'a>> {
-        let Self { func, cfg } = self;
+        let Self { func, cfg, options } = *self;
         // Dummy block is needed to label the graph with the function signature. Returns a vector
         // with the dummy block as a last element.
         cfg.post_order()
-            .map(|block| BlockNode::new(func, cfg, block))
-            .chain(iter::once(BlockNode::new(func, cfg, DUMMY_BLOCK)))
+            .map(|block| BlockNode::new(func, cfg, block, options))
+            .chain(iter::once(BlockNode::new(func, cfg, DUMMY_BLOCK, options)))
             .collect()
     }
 }
@@ -81,13 +82,13 @@ impl<'a> GraphWalk<'a> for FunctionGraph<'a> {
     }
 
     fn edges(&'a self) -> dot2::Edges<'a, Self::Edge> {
-        let Self { func, cfg } = self;
+        let Self { func, cfg, options } = *self;
         let mut blocks = self.blocks();
 
         let dummy_block = blocks.pop().unwrap();
         let mut edges = vec![BlockEdge {
             from: dummy_block,
-            to: BlockNode::new(func, cfg, Block(0u32)),
+            to: BlockNode::new(func, cfg, Block(0u32), options),
             func,
         }];
         for block in blocks {
@@ -121,21 +122,66 @@ pub(super) struct BlockEdge<'a> {
 }
 
 impl<'a> BlockEdge<'a> {
+    /// Returns the edge label that consists of the branch condition and the phi args flowing
+    /// through the edge.
     fn label(self) -> Text<'static> {
         let Self { from, to, func } = self;
+        if !from.options.show_edge_labels || from.block == DUMMY_BLOCK {
+            return Text::LabelStr("".into());
+        }
+
         let to = to.block;
         let from = from.block;
+        let mut labels = self.branch_labels();
         for insn in func.layout.iter_insn(to) {
             if let InsnData::Phi { values, blocks, .. } = func.dfg.insn_data(insn) {
                 for (i, block) in blocks.into_iter().enumerate() {
                     if *block == from {
                         let flow_arg = values[i];
                         let v = DisplayArgValue::new(flow_arg, &func.dfg);
-                        return Text::LabelStr(format!("{v}").into());
+                        labels.push(format!("{v}"));
+                    }
+                }
+            }
+        }
+        Text::LabelStr(labels.join(", ").into())
+    }
+
+    /// Returns `then`/`else` for a `br`, and the case values for a `br_table`.
+    fn branch_labels(self) -> Vec<String> {
+        let Self { from, to, func } = self;
+        let Some(last_insn) = func.layout.last_insn_of(from.block) else {
+            return vec![];
+        };
+
+        let mut labels = vec![];
+        match func.dfg.insn_data(last_insn) {
+            InsnData::Branch { dests, .. } => {
+                if dests[0] == to.block {
+                    labels.push("then".to_string());
+                }
+                if dests[1] == to.block {
+                    labels.push("else".to_string());
+                }
+            }
+            InsnData::BrTable {
+                args,
+                default,
+                table,
+            } => {
+                for (value, dest) in args[1..].iter().zip(table.iter()) {
+                    if *dest == to.block {
+                        let v = DisplayArgValue::new(*value, &func.dfg);
+                        labels.push(format!("{v}"));
                     }
                 }
+                if *default == Some(to.block) {
+                    labels.push("default".to_string());
+                }
             }
+            _ => {}
         }
-        Text::LabelStr("".into())
+
+        labels
     }
 }
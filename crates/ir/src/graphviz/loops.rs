@@ -0,0 +1,58 @@
+use std::io;
+
+use rustc_hash::FxHashMap;
+
+use crate::{Block, Function};
+
+use super::LoopNest;
+
+/// Writes a `subgraph cluster_` per loop in `func`. Clusters of inner loops are nested in the
+/// clusters of their parent loops, and blocks not in any loop are not written.
+///
+/// The blocks are only referenced by their ids, so the clusters must be written into a graph
+/// that already declares the block nodes.
+pub(super) fn write_loop_clusters<W: io::Write>(
+    func: &Function,
+    loops: &dyn LoopNest,
+    output: &mut W,
+) -> io::Result<()> {
+    // Blocks directly contained by each loop, and child loops of each loop, in layout order.
+    let mut blocks: FxHashMap<Block, Vec<Block>> = FxHashMap::default();
+    let mut children: FxHashMap<Option<Block>, Vec<Block>> = FxHashMap::default();
+    for block in func.layout.iter_block() {
+        let Some(header) = loops.innermost_loop_header(block) else {
+            continue;
+        };
+        blocks.entry(header).or_default().push(block);
+        if header == block {
+            children
+                .entry(loops.parent_loop_header(header))
+                .or_default()
+                .push(header);
+        }
+    }
+
+    for &header in children.get(&None).into_iter().flatten() {
+        write_cluster(header, &blocks, &children, 1, output)?;
+    }
+    Ok(())
+}
+
+fn write_cluster<W: io::Write>(
+    header: Block,
+    blocks: &FxHashMap<Block, Vec<Block>>,
+    children: &FxHashMap<Option<Block>, Vec<Block>>,
+    depth: usize,
+    output: &mut W,
+) -> io::Result<()> {
+    let indent = "    ".repeat(depth);
+    writeln!(output, "{indent}subgraph cluster_{header} {{")?;
+    writeln!(output, "{indent}    label=\"loop {header}\";")?;
+    for block in &blocks[&header] {
+        writeln!(output, "{indent}    {block};")?;
+    }
+    for &child in children.get(&Some(header)).into_iter().flatten() {
+        write_cluster(child, blocks, children, depth + 1, output)?;
+    }
+    writeln!(output, "{indent}}}")
+}
@@ -1,22 +1,117 @@
 use std::io;
 
-use crate::{ControlFlowGraph, Function};
+use crate::{Block, ControlFlowGraph, Function};
 
 mod block;
+mod domtree;
 mod function;
+mod loops;
 
+use domtree::DomTreeGraph;
 use function::FunctionGraph;
 
-pub fn render_to<W: io::Write>(func: &Function, output: &mut W) -> io::Result<()> {
+/// Options to control the verbosity of the graphviz output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphvizOptions {
+    /// If `true`, each block node lists its insns.
+    pub show_insts: bool,
+    /// If `true`, each edge is labeled with the branch condition and the phi args flowing
+    /// through the edge.
+    pub show_edge_labels: bool,
+}
+
+impl Default for GraphvizOptions {
+    fn default() -> Self {
+        Self {
+            show_insts: true,
+            show_edge_labels: true,
+        }
+    }
+}
+
+pub fn render_to<W: io::Write>(
+    func: &Function,
+    output: &mut W,
+    options: GraphvizOptions,
+) -> io::Result<()> {
     let mut cfg = ControlFlowGraph::new();
     cfg.compute(func);
-    let func_graph = FunctionGraph::new(func, &cfg);
+    let func_graph = FunctionGraph::new(func, &cfg, options);
     dot2::render(&func_graph, output).map_err(|err| match err {
         dot2::Error::Io(err) => err,
         _ => panic!("invalid graphviz id"),
     })
 }
 
+/// The loop nest rendered by [`render_with_loops`]. Loops are identified by their headers.
+pub trait LoopNest {
+    /// Returns the header of the innermost loop containing `block`, or `None` if `block` is not
+    /// in any loop.
+    fn innermost_loop_header(&self, block: Block) -> Option<Block>;
+
+    /// Returns the header of the loop immediately enclosing the loop headed by `header`, or
+    /// `None` if the loop is outermost.
+    fn parent_loop_header(&self, header: Block) -> Option<Block>;
+}
+
+/// Renders the CFG of `func` in the same way as [`render_to`], and groups the blocks of each
+/// loop into a `subgraph cluster_` named after the loop header.
+///
+/// Clusters of inner loops are nested in the clusters of their parent loops. Blocks not in any
+/// loop stay at the top level.
+pub fn render_with_loops<W: io::Write>(
+    func: &Function,
+    loops: &dyn LoopNest,
+    output: &mut W,
+    options: GraphvizOptions,
+) -> io::Result<()> {
+    let mut graph = vec![];
+    render_to(func, &mut graph, options)?;
+
+    // `dot2` doesn't support nested subgraphs, so the clusters are spliced in before the closing
+    // brace of the rendered graph.
+    let body_end = graph
+        .iter()
+        .rposition(|&b| b == b'}')
+        .expect("rendered graph must be closed");
+    output.write_all(&graph[..body_end])?;
+    loops::write_loop_clusters(func, loops, output)?;
+    output.write_all(&graph[body_end..])
+}
+
+/// The immediate dominator relation rendered by [`render_domtree_to`].
+pub trait DominatorTree {
+    /// Returns the immediate dominator of `block`, or `None` if `block` is the entry block or
+    /// unreachable.
+    fn idom_of(&self, block: Block) -> Option<Block>;
+}
+
+/// Dominance frontiers optionally overlaid by [`render_domtree_to`].
+pub trait DominanceFrontiers {
+    fn frontiers_of(&self, block: Block) -> Vec<Block>;
+}
+
+/// Renders the dominator tree of `func` as a separate dot graph.
+///
+/// Blocks are labeled in the same way as [`render_to`] so that both graphs are easy to compare.
+/// Tree edges go from the immediate dominator to each block. If `frontiers` is given, an edge
+/// from each block to the blocks in its dominance frontier is drawn as a dashed line.
+pub fn render_domtree_to<W: io::Write>(
+    func: &Function,
+    domtree: &dyn DominatorTree,
+    frontiers: Option<&dyn DominanceFrontiers>,
+    output: &mut W,
+    options: GraphvizOptions,
+) -> io::Result<()> {
+    let mut cfg = ControlFlowGraph::new();
+    cfg.compute(func);
+    let domtree_graph = DomTreeGraph::new(func, &cfg, domtree, frontiers, options);
+    dot2::render(&domtree_graph, output).map_err(|err| match err {
+        dot2::Error::Io(err) => err,
+        _ => panic!("invalid graphviz id"),
+    })
+}
+
 #[cfg(test)]
 mod test {
     use crate::{builder::test_util::test_func_builder, Type};
@@ -48,29 +143,77 @@ mod test {
         builder.switch_to_block(merge_block);
         let v3 = builder.phi(Type::I64, &[(v1, then_block), (v2, else_block)]);
         builder.add(v3, arg0);
-        builder.ret(None);
+        builder.ret(&[]);
 
         builder.seal_all();
         let module = builder.finish().build();
         let func_ref = module.iter_functions().next().unwrap();
 
         let mut text = vec![];
-        render_to(&module.funcs[func_ref], &mut text).unwrap();
+        render_to(
+            &module.funcs[func_ref],
+            &mut text,
+            GraphvizOptions::default(),
+        )
+        .unwrap();
         let text = String::from_utf8(text).unwrap();
 
         let expected =             "digraph test_func {
-    block3[label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\"><tr><td bgcolor=\"gray\" align=\"center\" colspan=\"1\">block3</td></tr><tr><td align=\"left\" balign=\"left\">v3.i64 = phi (1.i64 block1) (2.i64 block2);<br/>v4.i64 = add v3 v0;<br/>ret;<br/></td></tr></table>>][shape=\"none\"];
-    block2[label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\"><tr><td bgcolor=\"gray\" align=\"center\" colspan=\"1\">block2</td></tr><tr><td align=\"left\" balign=\"left\">jump block3;<br/></td></tr></table>>][shape=\"none\"];
-    block1[label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\"><tr><td bgcolor=\"gray\" align=\"center\" colspan=\"1\">block1</td></tr><tr><td align=\"left\" balign=\"left\">jump block3;<br/></td></tr></table>>][shape=\"none\"];
-    block0[label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\"><tr><td bgcolor=\"gray\" align=\"center\" colspan=\"1\">block0</td></tr><tr><td align=\"left\" balign=\"left\">branch v0 block1 block2;<br/></td></tr></table>>][shape=\"none\"];
-    dummy_block[label=\"func public %test_func(i64) -> ()\"][shape=\"none\"];
+    block3[label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\"><tr><td bgcolor=\"gray\" align=\"center\" colspan=\"1\">block3 (preds: 2)</td></tr><tr><td align=\"left\" balign=\"left\">v3.i64 = phi (1.i64 block1) (2.i64 block2);<br/>v4.i64 = add v3 v0;<br/>return;<br/></td></tr></table>>][shape=\"none\"];
+    block2[label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\"><tr><td bgcolor=\"gray\" align=\"center\" colspan=\"1\">block2 (preds: 1)</td></tr><tr><td align=\"left\" balign=\"left\">jump block3;<br/></td></tr></table>>][shape=\"none\"];
+    block1[label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\"><tr><td bgcolor=\"gray\" align=\"center\" colspan=\"1\">block1 (preds: 1)</td></tr><tr><td align=\"left\" balign=\"left\">jump block3;<br/></td></tr></table>>][shape=\"none\"];
+    block0[label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\"><tr><td bgcolor=\"gray\" align=\"center\" colspan=\"1\">block0 (preds: 0)</td></tr><tr><td align=\"left\" balign=\"left\">br v0 block1 block2;<br/></td></tr></table>>][shape=\"none\"];
+    dummy_block[label=\"func public %test_func(i64) -> void\"][shape=\"none\"];
     dummy_block -> block0[label=\"\"][style=\"invis\"];
     block2 -> block3[label=\"2.i64\"];
     block1 -> block3[label=\"1.i64\"];
-    block0 -> block1[label=\"\"];
-    block0 -> block2[label=\"\"];
+    block0 -> block1[label=\"then\"];
+    block0 -> block2[label=\"else\"];
 }
 ";
         assert_eq!(text, expected);
     }
+
+    #[test]
+    fn br_table_edge_labels() {
+        let mut builder = test_func_builder(&[Type::I64], Type::Void);
+
+        let entry_block = builder.append_block();
+        let case_block = builder.append_block();
+        let default_block = builder.append_block();
+
+        let arg0 = builder.args()[0];
+
+        builder.switch_to_block(entry_block);
+        let v1 = builder.make_imm_value(1i64);
+        let v2 = builder.make_imm_value(2i64);
+        builder.br_table(
+            arg0,
+            Some(default_block),
+            &[(v1, case_block), (v2, case_block)],
+        );
+
+        builder.switch_to_block(case_block);
+        builder.ret(&[]);
+
+        builder.switch_to_block(default_block);
+        builder.ret(&[]);
+
+        builder.seal_all();
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+
+        let options = GraphvizOptions {
+            show_insts: false,
+            show_edge_labels: true,
+        };
+        let mut text = vec![];
+        render_to(&module.funcs[func_ref], &mut text, options).unwrap();
+        let text = String::from_utf8(text).unwrap();
+
+        assert!(text.contains("block0 -> block1[label=\"1.i64, 2.i64\"];"));
+        assert!(text.contains("block0 -> block2[label=\"default\"];"));
+        assert!(text.contains("block1 (preds: 1)"));
+        assert!(!text.contains("return;"));
+    }
 }
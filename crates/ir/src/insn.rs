@@ -18,6 +18,7 @@ use super::{
 
 /// An opaque reference to [`InsnData`]
 #[derive(Debug, Clone, PartialEq, Eq, Copy, Hash, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Insn(pub u32);
 cranelift_entity::entity_impl!(Insn);
 
@@ -46,6 +47,7 @@ impl<'a> fmt::Display for DisplayInsn<'a> {
 
 /// An instruction data definition.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InsnData {
     /// Unary instructions.
     Unary { code: UnaryOp, args: [Value; 1] },
@@ -64,19 +66,22 @@ pub enum InsnData {
     Load {
         args: [Value; 1],
         loc: DataLocationKind,
+        ordering: MemoryOrdering,
     },
 
     /// Store a value to memory or storage.
     Store {
         args: [Value; 2],
         loc: DataLocationKind,
+        ordering: MemoryOrdering,
     },
 
     /// Call a function in the same contract.
+    /// The call has a result for each return type of the callee.
     Call {
         func: FuncRef,
         args: SmallVec<[Value; 8]>,
-        ret_ty: Type,
+        ret_tys: SmallVec<[Type; 2]>,
     },
 
     /// Unconditional jump instruction.
@@ -86,6 +91,10 @@ pub enum InsnData {
     Branch { args: [Value; 1], dests: [Block; 2] },
 
     /// Indirect jump instruction.
+    ///
+    /// Jumps to the block of the first case whose value equals the first arg, or to `default` if
+    /// no case matches. Without `default`, the behavior is undefined if no case matches, so the
+    /// frontend must only omit it when the cases are proven exhaustive.
     BrTable {
         args: SmallVec<[Value; 8]>,
         default: Option<Block>,
@@ -95,8 +104,15 @@ pub enum InsnData {
     /// Allocate a memory on the stack frame for the given type.
     Alloca { ty: Type },
 
-    /// Return.
-    Return { args: Option<Value> },
+    /// Return. The number of `args` must match the number of the return values of the function.
+    Return { args: SmallVec<[Value; 2]> },
+
+    /// Halt the execution of the whole contract, not only of the function. `args` are the
+    /// pointer to and the length of the output data, and are empty for [`HaltOp::Stop`].
+    Halt {
+        code: HaltOp,
+        args: SmallVec<[Value; 2]>,
+    },
 
     /// Get element pointer.
     Gep { args: SmallVec<[Value; 8]> },
@@ -111,11 +127,15 @@ pub enum InsnData {
 
 /// Indicates where the data is stored.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataLocationKind {
     /// Volatile memory.
     Memory,
     /// Non-volatile storage.
     Storage,
+    /// Storage that is discarded at the end of each transaction, e.g., the transient storage of
+    /// the EVM accessed by `TLOAD` and `TSTORE`.
+    TransientStorage,
 }
 
 impl DataLocationKind {
@@ -123,6 +143,7 @@ impl DataLocationKind {
         match self {
             Self::Memory => "@memory",
             Self::Storage => "@storage",
+            Self::TransientStorage => "@transient",
         }
     }
 }
@@ -134,6 +155,7 @@ impl FromStr for DataLocationKind {
         match s {
             "@memory" => Ok(Self::Memory),
             "@storage" => Ok(Self::Storage),
+            "@transient" => Ok(Self::TransientStorage),
             _ => Err(()),
         }
     }
@@ -145,6 +167,70 @@ impl fmt::Display for DataLocationKind {
     }
 }
 
+/// The ordering constraint of a load or a store with respect to the other accesses, following
+/// the atomic orderings of the C++ memory model.
+///
+/// An atomic access is never removed, merged with another access, or forwarded from a store, since
+/// another execution may observe or write the location concurrently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MemoryOrdering {
+    /// A non-atomic access, which may be freely reordered with the accesses to other locations.
+    #[default]
+    NotAtomic,
+    /// An atomic access that doesn't order the other accesses.
+    Relaxed,
+    /// An atomic load that isn't reordered with the later accesses.
+    Acquire,
+    /// An atomic store that isn't reordered with the earlier accesses.
+    Release,
+    /// An atomic access that is `Acquire` for a load and `Release` for a store, and is also in a
+    /// single total order with all the other `SeqCst` accesses.
+    SeqCst,
+}
+
+impl MemoryOrdering {
+    pub fn is_atomic(self) -> bool {
+        self != Self::NotAtomic
+    }
+
+    /// Returns `true` if the ordering is allowed for a load, i.e., it's not `Release`.
+    pub fn is_valid_for_load(self) -> bool {
+        self != Self::Release
+    }
+
+    /// Returns `true` if the ordering is allowed for a store, i.e., it's not `Acquire`.
+    pub fn is_valid_for_store(self) -> bool {
+        self != Self::Acquire
+    }
+
+    /// Returns the keyword of the ordering, or `None` for a non-atomic access, which is written
+    /// without a keyword.
+    pub(super) fn as_str(self) -> Option<&'static str> {
+        match self {
+            Self::NotAtomic => None,
+            Self::Relaxed => Some("relaxed"),
+            Self::Acquire => Some("acquire"),
+            Self::Release => Some("release"),
+            Self::SeqCst => Some("seq_cst"),
+        }
+    }
+}
+
+impl FromStr for MemoryOrdering {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "relaxed" => Ok(Self::Relaxed),
+            "acquire" => Ok(Self::Acquire),
+            "release" => Ok(Self::Release),
+            "seq_cst" => Ok(Self::SeqCst),
+            _ => Err(()),
+        }
+    }
+}
+
 impl InsnData {
     pub fn unary(code: UnaryOp, lhs: Value) -> Self {
         Self::Unary { code, args: [lhs] }
@@ -253,7 +339,7 @@ impl InsnData {
             | Self::Phi { values: args, .. }
             | Self::Gep { args } => args,
 
-            Self::Return { args } => args.as_ref().map(core::slice::from_ref).unwrap_or_default(),
+            Self::Return { args } | Self::Halt { args, .. } => args,
 
             _ => &[],
         }
@@ -273,7 +359,7 @@ impl InsnData {
             | Self::Phi { values: args, .. }
             | Self::Gep { args } => args,
 
-            Self::Return { args } => args.as_mut().map(core::slice::from_mut).unwrap_or_default(),
+            Self::Return { args } | Self::Halt { args, .. } => args,
 
             _ => &mut [],
         }
@@ -341,6 +427,21 @@ impl InsnData {
         )
     }
 
+    pub fn is_halt(&self) -> bool {
+        matches!(self, InsnData::Halt { .. })
+    }
+
+    /// Returns `true` if the insn leaves the function without a successor, i.e., returns or
+    /// halts.
+    pub fn is_exit(&self) -> bool {
+        self.is_return() || self.is_halt()
+    }
+
+    /// Returns `true` if the insn must be the last insn of a block.
+    pub fn is_terminator(&self) -> bool {
+        self.is_branch() || self.is_exit()
+    }
+
     pub fn has_side_effect(&self) -> bool {
         matches!(
             self,
@@ -348,35 +449,91 @@ impl InsnData {
                 | InsnData::Store { .. }
                 | InsnData::Call { .. }
                 | InsnData::Return { .. }
+                | InsnData::Halt { .. }
                 | InsnData::Alloca { .. }
         )
     }
 
+    /// Returns `true` if the insn may read from memory or storage.
+    /// A halt reads the output data from memory.
+    pub fn may_read_memory(&self) -> bool {
+        matches!(
+            self,
+            InsnData::Load { .. } | InsnData::Call { .. } | InsnData::Halt { .. }
+        )
+    }
+
+    /// Returns `true` if the insn may write to memory or storage.
+    pub fn may_write_memory(&self) -> bool {
+        matches!(self, InsnData::Store { .. } | InsnData::Call { .. })
+    }
+
+    /// Returns `true` if the insn may trap.
+    /// Division traps only if the divisor is zero; signed division of the minimum value by `-1`
+    /// wraps around instead of trapping.
     pub fn may_trap(&self) -> bool {
         match self {
             InsnData::Load { .. } | InsnData::Store { .. } | InsnData::Call { .. } => true,
-            InsnData::Binary { code, .. } => matches!(code, BinaryOp::Udiv | BinaryOp::Sdiv),
+            InsnData::Binary { code, .. } => {
+                matches!(
+                    code,
+                    BinaryOp::Udiv | BinaryOp::Sdiv | BinaryOp::Urem | BinaryOp::Srem
+                ) || code.is_checked()
+            }
             _ => false,
         }
     }
 
+    /// Returns `true` if the insn is pure, i.e., it has no side effect, can't trap, and doesn't
+    /// read memory or storage. The result of a pure insn depends only on its args, so the insn is
+    /// safe to speculate, duplicate, or eliminate when unused.
+    ///
+    /// The pure insns are unary ops, casts, `gep`, phis, and binary ops except for divisions,
+    /// remainders and checked arithmetic, which may trap. Loads are impure since the memory may be
+    /// written through an aliasing pointer, and branches are impure since they change the
+    /// control flow.
+    pub fn is_pure(&self) -> bool {
+        !(self.has_side_effect()
+            || self.may_trap()
+            || self.may_read_memory()
+            || self.is_terminator())
+    }
+
+    /// Returns the type of the result if the insn has exactly one result.
     pub fn result_type(&self, dfg: &DataFlowGraph) -> Option<Type> {
         match self {
             Self::Unary { args, .. } => Some(dfg.value_ty(args[0])),
             Self::Binary { code, args } => Some(code.result_type(dfg, args)),
             Self::Cast { ty, .. } => Some(*ty),
-            Self::Load { args, .. } => {
-                let ptr_ty = dfg.value_ty(args[0]);
-                debug_assert!(dfg.ctx.with_ty_store(|s| s.is_ptr(ptr_ty)));
-                dfg.ctx.with_ty_store(|s| s.deref(ptr_ty))
+            Self::Load { args, loc, .. } => {
+                let addr_ty = dfg.value_ty(args[0]);
+                if addr_ty.is_pointer(&dfg.ctx) {
+                    dfg.ctx.with_ty_store(|s| s.deref(addr_ty))
+                } else {
+                    // A storage slot can also be addressed by an integral key, in which case the
+                    // whole slot is loaded.
+                    debug_assert!(*loc != DataLocationKind::Memory && addr_ty.is_integral());
+                    Some(Type::I256)
+                }
             }
             Self::Gep { args } => Some(get_gep_result_type(dfg, args[0], &args[1..])),
-            Self::Call { ret_ty, .. } => Some(*ret_ty),
+            Self::Call { ret_tys, .. } => match ret_tys.as_slice() {
+                [ty] => Some(*ty),
+                _ => None,
+            },
             Self::Phi { ty, .. } => Some(*ty),
             Self::Alloca { ty } => Some(dfg.ctx.with_ty_store_mut(|s| s.make_ptr(*ty))),
             _ => None,
         }
     }
+
+    /// Returns the types of all results of the insn. Only a call may have more than one result.
+    pub fn result_types(&self, dfg: &DataFlowGraph) -> SmallVec<[Type; 2]> {
+        match self {
+            Self::Call { ret_tys, .. } => ret_tys.clone(),
+            _ => self.result_type(dfg).into_iter().collect(),
+        }
+    }
 }
 
 pub struct DisplayInsnData<'a> {
@@ -412,13 +569,28 @@ impl<'a> fmt::Display for DisplayInsnData<'a> {
                 display_arg_values(f, args, dfg)?;
                 ";".fmt(f)
             }
-            Load { args, loc } => {
+            Load {
+                args,
+                loc,
+                ordering,
+            } => {
                 write!(f, "{loc} load ")?;
+                if let Some(ordering) = ordering.as_str() {
+                    write!(f, "{ordering} ")?;
+                }
                 display_arg_values(f, args, dfg)?;
                 ";".fmt(f)
             }
-            Store { args, loc } => {
-                write!(f, "store {loc} ")?;
+            Store {
+                args,
+                loc,
+                ordering,
+            } => {
+                write!(f, "store ")?;
+                if let Some(ordering) = ordering.as_str() {
+                    write!(f, "{ordering} ")?;
+                }
+                write!(f, "{loc} ")?;
                 display_arg_values(f, args, dfg)?;
                 ";".fmt(f)
             }
@@ -460,7 +632,15 @@ impl<'a> fmt::Display for DisplayInsnData<'a> {
             }
             Return { args } => {
                 "ret".fmt(f)?;
-                if let Some(arg) = args {
+                for arg in args {
+                    let v = DisplayArgValue::new(*arg, dfg);
+                    write!(f, " {v}")?;
+                }
+                ";".fmt(f)
+            }
+            Halt { code, args } => {
+                code.as_str().fmt(f)?;
+                for arg in args {
                     let v = DisplayArgValue::new(*arg, dfg);
                     write!(f, " {v}")?;
                 }
@@ -485,9 +665,13 @@ impl<'a> fmt::Display for DisplayInsnData<'a> {
 
 /// Unary operations.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnaryOp {
     Not,
     Neg,
+    /// Copies the argument as is. This is used to materialize a value, e.g., an immediate, as a
+    /// distinct value.
+    Copy,
 }
 
 impl UnaryOp {
@@ -495,6 +679,7 @@ impl UnaryOp {
         match self {
             Self::Not => "not",
             Self::Neg => "neg",
+            Self::Copy => "copy",
         }
     }
 }
@@ -506,6 +691,7 @@ impl FromStr for UnaryOp {
         match s {
             "not" => Ok(Self::Not),
             "neg" => Ok(Self::Neg),
+            "copy" => Ok(Self::Copy),
             _ => Err(()),
         }
     }
@@ -519,12 +705,15 @@ impl fmt::Display for UnaryOp {
 
 /// Binary operations.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryOp {
     Add,
     Sub,
     Mul,
     Udiv,
     Sdiv,
+    Urem,
+    Srem,
     Lt,
     Gt,
     Slt,
@@ -538,13 +727,51 @@ pub enum BinaryOp {
     And,
     Or,
     Xor,
+    Shl,
+    Lshr,
+    Ashr,
+    /// Signed addition that traps on overflow.
+    AddChecked,
+    /// Signed subtraction that traps on overflow.
+    SubChecked,
+    /// Signed multiplication that traps on overflow.
+    MulChecked,
+    /// Unsigned addition that traps on overflow.
+    UaddChecked,
+    /// Unsigned subtraction that traps on overflow, i.e., if the result is negative.
+    UsubChecked,
+    /// Unsigned multiplication that traps on overflow.
+    UmulChecked,
 }
 
 impl BinaryOp {
     pub fn is_commutative(self) -> bool {
         matches!(
             self,
-            Self::Add | Self::Mul | Self::And | Self::Or | Self::Xor
+            Self::Add
+                | Self::Mul
+                | Self::Eq
+                | Self::Ne
+                | Self::And
+                | Self::Or
+                | Self::Xor
+                | Self::AddChecked
+                | Self::MulChecked
+                | Self::UaddChecked
+                | Self::UmulChecked
+        )
+    }
+
+    /// Returns `true` if the operation traps on overflow.
+    pub fn is_checked(self) -> bool {
+        matches!(
+            self,
+            Self::AddChecked
+                | Self::SubChecked
+                | Self::MulChecked
+                | Self::UaddChecked
+                | Self::UsubChecked
+                | Self::UmulChecked
         )
     }
 
@@ -555,6 +782,8 @@ impl BinaryOp {
             Self::Mul => "mul",
             Self::Udiv => "udiv",
             Self::Sdiv => "sdiv",
+            Self::Urem => "urem",
+            Self::Srem => "srem",
             Self::Lt => "lt",
             Self::Gt => "gt",
             Self::Slt => "slt",
@@ -568,6 +797,15 @@ impl BinaryOp {
             Self::And => "and",
             Self::Or => "or",
             Self::Xor => "xor",
+            Self::Shl => "shl",
+            Self::Lshr => "lshr",
+            Self::Ashr => "ashr",
+            Self::AddChecked => "add_checked",
+            Self::SubChecked => "sub_checked",
+            Self::MulChecked => "mul_checked",
+            Self::UaddChecked => "uadd_checked",
+            Self::UsubChecked => "usub_checked",
+            Self::UmulChecked => "umul_checked",
         }
     }
 
@@ -612,6 +850,8 @@ impl FromStr for BinaryOp {
             "mul" => Ok(Self::Mul),
             "udiv" => Ok(Self::Udiv),
             "sdiv" => Ok(Self::Sdiv),
+            "urem" => Ok(Self::Urem),
+            "srem" => Ok(Self::Srem),
             "lt" => Ok(Self::Lt),
             "gt" => Ok(Self::Gt),
             "slt" => Ok(Self::Slt),
@@ -625,12 +865,22 @@ impl FromStr for BinaryOp {
             "and" => Ok(Self::And),
             "or" => Ok(Self::Or),
             "xor" => Ok(Self::Xor),
+            "shl" => Ok(Self::Shl),
+            "lshr" => Ok(Self::Lshr),
+            "ashr" => Ok(Self::Ashr),
+            "add_checked" => Ok(Self::AddChecked),
+            "sub_checked" => Ok(Self::SubChecked),
+            "mul_checked" => Ok(Self::MulChecked),
+            "uadd_checked" => Ok(Self::UaddChecked),
+            "usub_checked" => Ok(Self::UsubChecked),
+            "umul_checked" => Ok(Self::UmulChecked),
             _ => Err(()),
         }
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CastOp {
     Sext,
     Zext,
@@ -669,6 +919,55 @@ impl FromStr for CastOp {
     }
 }
 
+/// Halting operations, which end the execution of the contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HaltOp {
+    /// Stops the execution successfully without output data.
+    Stop,
+    /// Stops the execution successfully with output data.
+    ReturnData,
+    /// Stops the execution and reverts the state changes, with output data.
+    Revert,
+}
+
+impl HaltOp {
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            Self::Stop => "stop",
+            Self::ReturnData => "return_data",
+            Self::Revert => "revert",
+        }
+    }
+
+    /// Returns the number of the args the op takes.
+    pub fn args_num(self) -> usize {
+        match self {
+            Self::Stop => 0,
+            Self::ReturnData | Self::Revert => 2,
+        }
+    }
+}
+
+impl fmt::Display for HaltOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HaltOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stop" => Ok(Self::Stop),
+            "return_data" => Ok(Self::ReturnData),
+            "revert" => Ok(Self::Revert),
+            _ => Err(()),
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub enum BranchInfo<'a> {
     NotBranch,
@@ -785,6 +1084,13 @@ fn get_gep_result_type(dfg: &DataFlowGraph, base: Value, indices: &[Value]) -> T
                 };
                 s.fields[index]
             }
+            CompoundTypeData::Union(variants) => {
+                let index = match dfg.value_data(index) {
+                    ValueData::Immediate { imm, .. } => imm.as_usize(),
+                    _ => unreachable!(),
+                };
+                variants[index]
+            }
         });
     }
 
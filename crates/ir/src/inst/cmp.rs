@@ -3,6 +3,7 @@ use macros::Inst;
 use crate::Value;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "bool")]
 pub struct Lt {
     #[inst(value)]
     lhs: Value,
@@ -11,6 +12,7 @@ pub struct Lt {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "bool")]
 pub struct Gt {
     #[inst(value)]
     lhs: Value,
@@ -19,6 +21,7 @@ pub struct Gt {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "bool")]
 pub struct Slt {
     #[inst(value)]
     lhs: Value,
@@ -27,6 +30,7 @@ pub struct Slt {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "bool")]
 pub struct Sgt {
     #[inst(value)]
     lhs: Value,
@@ -35,6 +39,7 @@ pub struct Sgt {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "bool")]
 pub struct Le {
     #[inst(value)]
     lhs: Value,
@@ -43,6 +48,7 @@ pub struct Le {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "bool")]
 pub struct Ge {
     #[inst(value)]
     lhs: Value,
@@ -51,6 +57,7 @@ pub struct Ge {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "bool")]
 pub struct Sle {
     #[inst(value)]
     lhs: Value,
@@ -59,6 +66,7 @@ pub struct Sle {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "bool")]
 pub struct Sge {
     #[inst(value)]
     lhs: Value,
@@ -67,6 +75,7 @@ pub struct Sge {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "bool")]
 pub struct Eq {
     #[inst(value)]
     lhs: Value,
@@ -75,6 +84,7 @@ pub struct Eq {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "bool")]
 pub struct Ne {
     #[inst(value)]
     lhs: Value,
@@ -83,6 +93,7 @@ pub struct Ne {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "bool")]
 pub struct IsZero {
     #[inst(value)]
     lhs: Value,
@@ -4,11 +4,13 @@ use smallvec::SmallVec;
 use crate::{module::FuncRef, Block, Type, Value};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Inst)]
+#[inst(terminator, result = "none")]
 pub struct Jump {
     dest: Block,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Inst)]
+#[inst(terminator, result = "none")]
 pub struct Br {
     #[inst(value)]
     cond: Value,
@@ -18,6 +20,7 @@ pub struct Br {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Inst)]
+#[inst(terminator, result = "none")]
 pub struct BrTable {
     #[inst(value)]
     scrutinee: Value,
@@ -44,7 +47,7 @@ pub struct Call {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, terminator, result = "none")]
 pub struct Return {
     #[inst(value)]
     arg: Option<Value>,
@@ -4,13 +4,14 @@ use smallvec::SmallVec;
 use crate::Value;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct Mload {
     #[inst(value)]
     addr: Value,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "none")]
 pub struct Mstore {
     #[inst(value)]
     value: Value,
@@ -19,6 +20,7 @@ pub struct Mstore {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct Gep {
     #[inst(value)]
     values: SmallVec<[Value; 8]>,
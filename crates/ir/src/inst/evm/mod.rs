@@ -4,7 +4,7 @@ pub mod inst_set;
 use crate::Value;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, terminator, result = "none")]
 pub struct EvmStop {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
@@ -44,6 +44,7 @@ pub struct EvmByte {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmKeccak256 {
     #[inst(value)]
     addr: Value,
@@ -52,30 +53,37 @@ pub struct EvmKeccak256 {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmAddress {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmBalance {
     #[inst(value)]
     contract_addr: Value,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmOrigin {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmCaller {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmCallValue {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmCallDataLoad {
+    #[inst(value)]
     data_offset: Value,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "none")]
 pub struct EvmCallDataCopy {
     #[inst(value)]
     dst_addr: Value,
@@ -86,10 +94,11 @@ pub struct EvmCallDataCopy {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmCodeSize {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "none")]
 pub struct EvmCodeCopy {
     #[inst(value)]
     dst_addr: Value,
@@ -100,16 +109,18 @@ pub struct EvmCodeCopy {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmGasPrice {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmExtCodeSize {
     #[inst(value)]
     ext_addr: Value,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "none")]
 pub struct EvmExtCodeCopy {
     #[inst(value)]
     ext_addr: Value,
@@ -122,10 +133,11 @@ pub struct EvmExtCodeCopy {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmReturnDataSize {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "none")]
 pub struct EvmReturnDataCopy {
     #[inst(value)]
     dst_addr: Value,
@@ -136,55 +148,67 @@ pub struct EvmReturnDataCopy {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmExtCodeHash {
     #[inst(value)]
     ext_addr: Value,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmBlockHash {
     #[inst(value)]
     block_num: Value,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmCoinBase {
     #[inst(value)]
     block_num: Value,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmTimestamp {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmNumber {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmPrevRandao {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmGasLimit {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmChainId {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmSelfBalance {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmBaseFee {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmBlobHash {
     #[inst(value)]
     idx: Value,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmBlobBaseFee {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "none")]
 pub struct EvmMstore8 {
     #[inst(value)]
     addr: Value,
@@ -193,14 +217,14 @@ pub struct EvmMstore8 {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "word")]
 pub struct EvmSload {
     #[inst(value)]
     key: Value,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "none")]
 pub struct EvmSstore {
     #[inst(value)]
     key: Value,
@@ -209,20 +233,22 @@ pub struct EvmSstore {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmMsize {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
+#[inst(result = "word")]
 pub struct EvmGas {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "word")]
 pub struct EvmTload {
     #[inst(value)]
     key: Value,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "none")]
 pub struct EvmTstore {
     #[inst(value)]
     key: Value,
@@ -231,7 +257,7 @@ pub struct EvmTstore {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "none")]
 pub struct EvmLog0 {
     #[inst(value)]
     addr: Value,
@@ -240,7 +266,7 @@ pub struct EvmLog0 {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "none")]
 pub struct EvmLog1 {
     #[inst(value)]
     addr: Value,
@@ -251,7 +277,7 @@ pub struct EvmLog1 {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "none")]
 pub struct EvmLog2 {
     #[inst(value)]
     addr: Value,
@@ -264,7 +290,7 @@ pub struct EvmLog2 {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "none")]
 pub struct EvmLog3 {
     #[inst(value)]
     addr: Value,
@@ -279,7 +305,7 @@ pub struct EvmLog3 {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "none")]
 pub struct EvmLog4 {
     #[inst(value)]
     addr: Value,
@@ -296,7 +322,7 @@ pub struct EvmLog4 {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "word")]
 pub struct EvmCreate {
     #[inst(value)]
     val: Value,
@@ -307,7 +333,7 @@ pub struct EvmCreate {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "word")]
 pub struct EvmCall {
     #[inst(value)]
     gas: Value,
@@ -326,7 +352,7 @@ pub struct EvmCall {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, terminator, result = "none")]
 pub struct EvmReturn {
     #[inst(value)]
     addr: Value,
@@ -335,7 +361,7 @@ pub struct EvmReturn {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "word")]
 pub struct EvmDelegateCall {
     #[inst(value)]
     gas: Value,
@@ -352,7 +378,7 @@ pub struct EvmDelegateCall {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "word")]
 pub struct EvmCreate2 {
     #[inst(value)]
     val: Value,
@@ -365,7 +391,7 @@ pub struct EvmCreate2 {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, result = "word")]
 pub struct EvmStaticCall {
     #[inst(value)]
     gas: Value,
@@ -382,7 +408,7 @@ pub struct EvmStaticCall {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, terminator, result = "none")]
 pub struct EvmRevert {
     #[inst(value)]
     addr: Value,
@@ -391,7 +417,7 @@ pub struct EvmRevert {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Inst)]
-#[inst(has_side_effect)]
+#[inst(has_side_effect, terminator, result = "none")]
 pub struct EvmSelfDestruct {
     #[inst(value)]
     addr: Value,
@@ -209,6 +209,32 @@ mod tests {
         let resolved = inst_set.resolve_inst_mut(insts[2].as_mut());
         assert!(matches!(resolved, TestInstKindMut::Not(_)));
     }
+
+    #[test]
+    fn all_insts() {
+        use crate::inst::{InstMeta, OperandCount, ResultKind};
+
+        let inst_set = TestInstSet::new();
+        let names: Vec<_> = inst_set.all_insts().iter().map(|meta| meta.name).collect();
+        assert_eq!(names, ["add", "sub", "not", "phi", "jump"]);
+
+        assert_eq!(
+            Add::META,
+            InstMeta {
+                name: "add",
+                operands: OperandCount::Fixed(2),
+                has_side_effect: false,
+                is_terminator: false,
+                result: ResultKind::SameAsOperands,
+            }
+        );
+        assert_eq!(Phi::META.operands, OperandCount::AtLeast(0));
+        assert_eq!(Phi::META.result, ResultKind::Explicit);
+        const { assert!(Jump::META.is_terminator) };
+        assert_eq!(Jump::META.result, ResultKind::None);
+        assert_eq!(cmp::Lt::META.result, ResultKind::Bool);
+        const { assert!(evm::EvmSstore::META.has_side_effect) };
+    }
 }
 
 pub(super) mod sealed {
@@ -20,6 +20,43 @@ pub trait Inst: inst_set::sealed::Registered + Any {
     fn as_text(&self) -> &'static str;
 }
 
+/// Metadata of an inst type, which is available without an inst object.
+/// Each inst type has it as `META`, and [`inst_set::InstSetBase::all_insts`] lists them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InstMeta {
+    /// The text form of the inst, e.g., `add`.
+    pub name: &'static str,
+    pub operands: OperandCount,
+    pub has_side_effect: bool,
+    /// `true` if the inst terminates a block.
+    pub is_terminator: bool,
+    pub result: ResultKind,
+}
+
+/// The number of the value operands of an inst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperandCount {
+    Fixed(usize),
+    /// The inst takes the given number of operands or more, e.g., `call` and `phi`.
+    AtLeast(usize),
+}
+
+/// Describes how the result type of an inst is determined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResultKind {
+    /// The inst has no result.
+    None,
+    /// The result has the same type as the operands, e.g., `add`.
+    SameAsOperands,
+    /// The result is a boolean, e.g., `lt`.
+    Bool,
+    /// The result is a word of the target, e.g., an address or a value read from the
+    /// environment.
+    Word,
+    /// The result type is given explicitly by the inst, e.g., `sext` and `call`.
+    Explicit,
+}
+
 /// This trait works as a "proof" that a specific ISA contains `I`,
 /// and then allows a construction and reflection of type `I` in that specific ISA context.
 pub trait HasInst<I: Inst> {
@@ -0,0 +1,107 @@
+//! This module contains [`inst_match!`](crate::inst_match), a macro to match on the insn data
+//! with the variants and the opcodes in scope.
+
+/// Matches the data of `insn` in `dfg` against the given arms.
+///
+/// The variants of [`InsnData`](crate::InsnData) and the opcodes, i.e., the variants of
+/// [`UnaryOp`](crate::insn::UnaryOp), [`BinaryOp`](crate::insn::BinaryOp),
+/// [`CastOp`](crate::insn::CastOp), [`HaltOp`](crate::insn::HaltOp) and
+/// [`DataLocationKind`](crate::DataLocationKind), are in scope of the arms, so they can be
+/// written without paths.
+///
+/// The insn data is matched by place, so `Copy` fields like `args: [a, b]` are bound by value.
+/// Fields that are not `Copy`, e.g., the args of `Call`, need to be bound with `ref`.
+///
+/// A misspelled variant or opcode would be a fresh binding in a plain `match`. Such bindings
+/// are rejected by denying non snake case bindings, so typos fail to compile.
+///
+/// # Usage
+/// ```rust
+/// use sonatina_ir::{inst_match, DataFlowGraph, Insn, Value};
+///
+/// fn fold_double(dfg: &DataFlowGraph, insn: Insn) -> Option<(Value, u32)> {
+///     inst_match!(dfg, insn, {
+///         Binary { code: Add, args: [lhs, rhs] } if lhs == rhs => Some((lhs, 1)),
+///         Binary { code: Shl, args: [lhs, _] } => Some((lhs, 2)),
+///         _ => None,
+///     })
+/// }
+/// ```
+///
+/// A misspelled opcode is rejected.
+/// ```rust, compile_fail
+/// use sonatina_ir::{inst_match, DataFlowGraph, Insn};
+///
+/// fn is_add(dfg: &DataFlowGraph, insn: Insn) -> bool {
+///     inst_match!(dfg, insn, {
+///         Binary { code: Addd, .. } => true,
+///         _ => false,
+///     })
+/// }
+/// ```
+#[macro_export]
+macro_rules! inst_match {
+    ($dfg:expr, $insn:expr, { $($arms:tt)* }) => {{
+        #[allow(unused_imports)]
+        use $crate::insn::{
+            BinaryOp::*, CastOp::*, DataLocationKind::*, HaltOp::*, InsnData::*, UnaryOp::*,
+        };
+
+        #[deny(non_snake_case)]
+        let result = match *$dfg.insn_data($insn) {
+            $($arms)*
+        };
+        result
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{builder::test_util::*, Insn, Type, Value};
+
+    /// Returns the operands of `insn` if it's a commutative binary insn.
+    fn commutative_args(dfg: &crate::DataFlowGraph, insn: Insn) -> Option<(Value, Value)> {
+        inst_match!(dfg, insn, {
+            Binary { code: Add | Mul | And | Or | Xor | Eq | Ne, args: [lhs, rhs] } => {
+                Some((lhs, rhs))
+            }
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn match_binary() {
+        let mut builder = test_func_builder(&[Type::I32, Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let [v0, v1] = [builder.args()[0], builder.args()[1]];
+        let add = builder.add(v0, v1);
+        let sub = builder.sub(add, v1);
+        let ext = builder.sext(sub, Type::I64);
+        let trunc = builder.trunc(ext, Type::I32);
+        builder.ret(&[trunc]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let dfg = &module.funcs[func_ref].dfg;
+        let insn = |value| dfg.value_insn(value).unwrap();
+
+        assert_eq!(commutative_args(dfg, insn(add)), Some((v0, v1)));
+        assert_eq!(commutative_args(dfg, insn(sub)), None);
+
+        let cast_ty = inst_match!(dfg, insn(ext), {
+            Cast { code: Sext | Zext, ty, .. } => Some(ty),
+            _ => None,
+        });
+        assert_eq!(cast_ty, Some(Type::I64));
+
+        let ret = module.funcs[func_ref].layout.last_insn_of(b0).unwrap();
+        let is_return = inst_match!(dfg, ret, {
+            Return { ref args } => args.as_slice() == [trunc],
+            _ => false,
+        });
+        assert!(is_return);
+    }
+}
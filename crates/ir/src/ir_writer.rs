@@ -1,8 +1,10 @@
 use std::io;
 
+use cranelift_entity::{EntityRef, SecondaryMap};
+
 use crate::{
     module::{FuncRef, ModuleCtx},
-    types::{CompoundType, CompoundTypeData, StructData},
+    types::StructData,
     DataLocationKind, GlobalVariableData, Module,
 };
 
@@ -18,6 +20,9 @@ impl DebugProvider for () {}
 pub struct ModuleWriter<'a> {
     module: &'a Module,
     debug: Option<&'a dyn DebugProvider>,
+    effects: bool,
+    normalized: bool,
+    source_locs: bool,
 }
 
 impl<'a> ModuleWriter<'a> {}
@@ -27,6 +32,9 @@ impl<'a> ModuleWriter<'a> {
         Self {
             module,
             debug: None,
+            effects: false,
+            normalized: false,
+            source_locs: false,
         }
     }
 
@@ -34,12 +42,36 @@ impl<'a> ModuleWriter<'a> {
         Self {
             module,
             debug: Some(debug),
+            effects: false,
+            normalized: false,
+            source_locs: false,
         }
     }
 
+    /// Enables or disables effect annotations of insns.
+    /// See [`FuncWriter::with_effects`].
+    pub fn with_effects(mut self, effects: bool) -> Self {
+        self.effects = effects;
+        self
+    }
+
+    /// Enables or disables normalized numbering of values and blocks.
+    /// See [`FuncWriter::with_normalized_numbering`].
+    pub fn with_normalized_numbering(mut self, normalized: bool) -> Self {
+        self.normalized = normalized;
+        self
+    }
+
+    /// Enables or disables source location annotations of insns.
+    /// See [`FuncWriter::with_source_locs`].
+    pub fn with_source_locs(mut self, source_locs: bool) -> Self {
+        self.source_locs = source_locs;
+        self
+    }
+
     pub fn write(&mut self, mut w: impl io::Write) -> io::Result<()> {
         // Write target.
-        writeln!(w, "target = {}", self.module.ctx.isa.triple())?;
+        writeln!(w, "target = \"{}\"", self.module.ctx.isa.triple())?;
 
         // Write struct types defined in the module.
         self.module.ctx.with_ty_store(|s| {
@@ -51,16 +83,31 @@ impl<'a> ModuleWriter<'a> {
 
         // Write module level global variables.
         self.module.ctx.with_gv_store(|s| {
-            for gv in s.all_gv_data() {
+            for (_, gv) in s.ordered_iter() {
                 gv.ir_write(&self.module.ctx, &mut w)?;
             }
 
             io::Result::Ok(())
         })?;
 
+        // Write declarations of the functions without body. They must precede the function
+        // definitions to be parsed back.
         for func_ref in self.module.funcs.keys() {
             let func = &self.module.funcs[func_ref];
-            let mut func_writer = FuncWriter::new(func_ref, func, self.debug);
+            if func.layout.entry_block().is_none() {
+                write_func_declaration(func, &self.module.ctx, &mut w)?;
+            }
+        }
+
+        for func_ref in self.module.funcs.keys() {
+            let func = &self.module.funcs[func_ref];
+            if func.layout.entry_block().is_none() {
+                continue;
+            }
+            let mut func_writer = FuncWriter::new(func_ref, func, self.debug)
+                .with_effects(self.effects)
+                .with_normalized_numbering(self.normalized)
+                .with_source_locs(self.source_locs);
             func_writer.write(&mut w)?;
             writeln!(w)?;
         }
@@ -80,6 +127,9 @@ pub struct FuncWriter<'a> {
     func: &'a Function,
     level: u8,
     debug: Option<&'a dyn DebugProvider>,
+    effects: bool,
+    source_locs: bool,
+    numbering: Option<Numbering>,
 }
 
 impl<'a> FuncWriter<'a> {
@@ -93,9 +143,36 @@ impl<'a> FuncWriter<'a> {
             func,
             level: 0,
             debug,
+            effects: false,
+            source_locs: false,
+            numbering: None,
         }
     }
 
+    /// Enables or disables effect annotations of insns.
+    /// If enabled, each insn is followed by a comment like `# effects: read-mem, trap`.
+    pub fn with_effects(mut self, effects: bool) -> Self {
+        self.effects = effects;
+        self
+    }
+
+    /// Enables or disables source location annotations of insns.
+    /// If enabled, each insn with a source location is followed by a comment like
+    /// `# @main.fe:3:5`.
+    pub fn with_source_locs(mut self, source_locs: bool) -> Self {
+        self.source_locs = source_locs;
+        self
+    }
+
+    /// Enables or disables normalized numbering of values and blocks.
+    /// If enabled, values and blocks are renumbered densely in layout order before printing, so
+    /// that equivalent functions print identically regardless of their internal ids. Names from
+    /// the debug provider are ignored in this mode to avoid clashing with the new numbers.
+    pub fn with_normalized_numbering(mut self, normalized: bool) -> Self {
+        self.numbering = normalized.then(|| Numbering::new(self.func));
+        self
+    }
+
     pub fn write(&mut self, mut w: impl io::Write) -> io::Result<()> {
         // TODO: extern declarations aren't printed correctly
 
@@ -110,7 +187,7 @@ impl<'a> FuncWriter<'a> {
             &mut w,
         )?;
         write!(w, ") -> ")?;
-        self.func.sig.ret_ty().ir_write(self.ctx(), &mut w)?;
+        write_ret_tys(self.func.sig.ret_tys(), self.ctx(), &mut w)?;
 
         writeln!(w, " {{")?;
         self.level += 1;
@@ -137,10 +214,37 @@ impl<'a> FuncWriter<'a> {
         unsafe { Ok(String::from_utf8_unchecked(s)) }
     }
 
+    /// Returns the textual form of `insn` without indentation, e.g., `v1.i32 = add v0 1.i32;`.
+    pub fn insn_string(&mut self, insn: Insn) -> io::Result<String> {
+        let level = std::mem::replace(&mut self.level, 0);
+        let mut s = Vec::new();
+        let res = insn.write(self, &mut s);
+        self.level = level;
+        res?;
+        unsafe { Ok(String::from_utf8_unchecked(s)) }
+    }
+
     pub fn value_name(&self, value: Value) -> Option<&str> {
+        if self.numbering.is_some() {
+            return None;
+        }
         self.debug.and_then(|d| d.value_name(self.func_ref, value))
     }
 
+    fn value_number(&mut self, value: Value) -> u32 {
+        match &mut self.numbering {
+            Some(numbering) => numbering.value(value),
+            None => value.0,
+        }
+    }
+
+    fn block_number(&mut self, block: Block) -> u32 {
+        match &mut self.numbering {
+            Some(numbering) => numbering.block(block),
+            None => block.0,
+        }
+    }
+
     fn write_block_with_insn(&mut self, block: Block, mut w: impl io::Write) -> io::Result<()> {
         self.indent(&mut w)?;
         block.write(self, &mut w)?;
@@ -199,6 +303,113 @@ impl<'a> FuncWriter<'a> {
     }
 }
 
+fn write_func_declaration(
+    func: &Function,
+    ctx: &ModuleCtx,
+    w: &mut impl io::Write,
+) -> io::Result<()> {
+    let sig = &func.sig;
+    write!(w, "declare {} %{}(", sig.linkage(), sig.name())?;
+    for (i, ty) in sig.args().iter().enumerate() {
+        if i != 0 {
+            write!(w, ", ")?;
+        }
+        ty.ir_write(ctx, &mut *w)?;
+    }
+    write!(w, ") -> ")?;
+    write_ret_tys(sig.ret_tys(), ctx, &mut *w)?;
+    writeln!(w, ";")
+}
+
+/// Writes `void` if there is no return value, the type if there is only one, and the
+/// parenthesized list of the types otherwise, e.g., `(i32, i64)`.
+fn write_ret_tys(ret_tys: &[Type], ctx: &ModuleCtx, w: &mut impl io::Write) -> io::Result<()> {
+    match ret_tys {
+        [] => Type::Void.ir_write(ctx, w),
+        [ty] => ty.ir_write(ctx, w),
+        tys => {
+            write!(w, "(")?;
+            for (i, ty) in tys.iter().enumerate() {
+                if i != 0 {
+                    write!(w, ", ")?;
+                }
+                ty.ir_write(ctx, &mut *w)?;
+            }
+            write!(w, ")")
+        }
+    }
+}
+
+/// Returns the textual form of `module`: the target, struct types, global variables,
+/// declarations of external functions and function definitions, in this order.
+/// Types are printed in declaration order, global variables in symbol order, and functions in
+/// declaration order, so the output can be parsed back to an equivalent module.
+pub fn dump_module(module: &Module) -> String {
+    ModuleWriter::new(module).dump_string().unwrap()
+}
+
+/// Returns the textual form of `func` with values and blocks renumbered densely in layout order.
+/// See [`FuncWriter::with_normalized_numbering`].
+pub fn dump_func_normalized(func: &Function) -> String {
+    // The func ref is only used to look up value names from a debug provider, so a dummy one is
+    // fine here.
+    let mut writer = FuncWriter::new(FuncRef::new(0), func, None).with_normalized_numbering(true);
+    writer.dump_string().unwrap()
+}
+
+/// Dense numbers of values and blocks assigned in layout order.
+/// Values and blocks that are referred to but not defined in the layout, e.g., a result of a
+/// removed insn, are numbered after them when they're first printed, so that the numbers never
+/// collide.
+struct Numbering {
+    values: SecondaryMap<Value, Option<u32>>,
+    blocks: SecondaryMap<Block, Option<u32>>,
+    next_value: u32,
+    next_block: u32,
+}
+
+impl Numbering {
+    fn new(func: &Function) -> Self {
+        let mut numbering = Self {
+            values: SecondaryMap::new(),
+            blocks: SecondaryMap::new(),
+            next_value: 0,
+            next_block: 0,
+        };
+
+        for &arg in &func.arg_values {
+            numbering.value(arg);
+        }
+
+        for block in func.layout.iter_block() {
+            numbering.block(block);
+            for insn in func.layout.iter_insn(block) {
+                for &result in func.dfg.insn_results(insn) {
+                    numbering.value(result);
+                }
+            }
+        }
+
+        numbering
+    }
+
+    /// Returns the number of `value`, numbering it if it isn't numbered yet.
+    fn value(&mut self, value: Value) -> u32 {
+        *self.values[value].get_or_insert_with(|| {
+            self.next_value += 1;
+            self.next_value - 1
+        })
+    }
+
+    /// Returns the number of `block`, numbering it if it isn't numbered yet.
+    fn block(&mut self, block: Block) -> u32 {
+        *self.blocks[block].get_or_insert_with(|| {
+            self.next_block += 1;
+            self.next_block - 1
+        })
+    }
+}
+
 trait IrWrite {
     fn write(&self, writer: &mut FuncWriter, w: &mut impl io::Write) -> io::Result<()>;
 }
@@ -214,10 +425,14 @@ impl IrWrite for Value {
             writer
                 .ctx()
                 .with_gv_store(|s| write!(w, "%{}", s.gv_data(gv).symbol))
+        } else if writer.func.dfg.is_undef(value) {
+            write!(w, "undef.")?;
+            let ty = writer.func.dfg.value_ty(value);
+            ty.ir_write(writer.ctx(), w)
         } else if let Some(name) = writer.value_name(value) {
             write!(w, "{name}")
         } else {
-            write!(w, "v{}", value.0)
+            write!(w, "v{}", writer.value_number(value))
         }
     }
 }
@@ -229,53 +444,23 @@ impl GlobalVariableData {
         self.ty.ir_write(ctx, w)?;
 
         if let Some(data) = &self.data {
-            write!(w, " = {};", data)
+            writeln!(w, " = {};", data)
         } else {
-            write!(w, ";")
+            writeln!(w, ";")
         }
     }
 }
 
 impl IrWrite for Block {
-    fn write(&self, _: &mut FuncWriter, w: &mut impl io::Write) -> io::Result<()> {
-        w.write_fmt(format_args!("block{}", self.0))
+    fn write(&self, writer: &mut FuncWriter, w: &mut impl io::Write) -> io::Result<()> {
+        w.write_fmt(format_args!("block{}", writer.block_number(*self)))
     }
 }
 
 impl Type {
     fn ir_write(&self, ctx: &ModuleCtx, w: &mut impl io::Write) -> io::Result<()> {
-        match self {
-            Self::I1 => write!(w, "i1"),
-            Self::I8 => write!(w, "i8"),
-            Self::I16 => write!(w, "i16"),
-            Self::I32 => write!(w, "i32"),
-            Self::I64 => write!(w, "i64"),
-            Self::I128 => write!(w, "i128"),
-            Self::I256 => write!(w, "i256"),
-            Self::Void => write!(w, "void"),
-            Self::Compound(compound) => compound.ir_write(ctx, w),
-        }
-    }
-}
-
-impl CompoundType {
-    fn ir_write(&self, ctx: &ModuleCtx, w: &mut impl io::Write) -> io::Result<()> {
-        let comp_data = ctx.with_ty_store(|s| s.resolve_compound(*self).clone());
-
-        match comp_data {
-            CompoundTypeData::Array { elem, len } => {
-                write!(w, "[")?;
-                elem.ir_write(ctx, &mut *w)?;
-                write!(w, "; {}]", len)
-            }
-            CompoundTypeData::Ptr(elem) => {
-                write!(w, "*")?;
-                elem.ir_write(ctx, w)
-            }
-            CompoundTypeData::Struct(def) => {
-                write!(w, "%{}", def.name)
-            }
-        }
+        let ty = ctx.with_ty_store(|s| s.display(*self));
+        write!(w, "{ty}")
     }
 }
 
@@ -308,11 +493,17 @@ impl IrWrite for Insn {
         use InsnData::*;
 
         writer.indent(&mut *w)?;
-        if let Some(insn_result) = writer.func.dfg.insn_result(*self) {
-            insn_result.write(writer, &mut *w)?;
-            w.write_all(b".")?;
-            let ty = writer.func.dfg.value_ty(insn_result);
-            ty.ir_write(writer.ctx(), &mut *w)?;
+        let results = writer.func.dfg.insn_results(*self);
+        if !results.is_empty() {
+            for (idx, &result) in results.iter().enumerate() {
+                if idx > 0 {
+                    w.write_all(b", ")?;
+                }
+                result.write(writer, &mut *w)?;
+                w.write_all(b".")?;
+                let ty = writer.func.dfg.value_ty(result);
+                ty.ir_write(writer.ctx(), &mut *w)?;
+            }
             w.write_all(b" = ")?;
         }
 
@@ -336,23 +527,41 @@ impl IrWrite for Insn {
                 writer.write_insn_args(args, &mut *w)?;
             }
 
-            Load { args, loc } => {
+            Load {
+                args,
+                loc,
+                ordering,
+            } => {
                 write!(w, "load")?;
                 writer.space(&mut *w)?;
+                if let Some(ordering) = ordering.as_str() {
+                    write!(w, "{ordering}")?;
+                    writer.space(&mut *w)?;
+                }
                 match loc {
                     DataLocationKind::Memory => write!(w, "@memory")?,
                     DataLocationKind::Storage => write!(w, "@storage")?,
+                    DataLocationKind::TransientStorage => write!(w, "@transient")?,
                 }
                 writer.space(&mut *w)?;
                 writer.write_insn_args(args, &mut *w)?;
             }
 
-            Store { args, loc } => {
+            Store {
+                args,
+                loc,
+                ordering,
+            } => {
                 write!(w, "store")?;
                 writer.space(&mut *w)?;
+                if let Some(ordering) = ordering.as_str() {
+                    write!(w, "{ordering}")?;
+                    writer.space(&mut *w)?;
+                }
                 match loc {
                     DataLocationKind::Memory => write!(w, "@memory")?,
                     DataLocationKind::Storage => write!(w, "@storage")?,
+                    DataLocationKind::TransientStorage => write!(w, "@transient")?,
                 }
                 writer.space(&mut *w)?;
                 writer.write_insn_args(args, &mut *w)?;
@@ -391,10 +600,8 @@ impl IrWrite for Insn {
                 writer.space(&mut *w)?;
                 if let Some(default) = default {
                     default.write(writer, &mut *w)?;
-                } else {
-                    write!(w, "undef")?;
+                    writer.space(&mut *w)?;
                 }
-                writer.space(&mut *w)?;
 
                 let mut table_args = vec![];
                 for (value, block) in args[1..].iter().zip(table.iter()) {
@@ -417,7 +624,15 @@ impl IrWrite for Insn {
 
             Return { args } => {
                 write!(w, "return")?;
-                if let Some(arg) = args {
+                for arg in args {
+                    writer.space(&mut *w)?;
+                    arg.write(writer, &mut *w)?;
+                }
+            }
+
+            Halt { code, args } => {
+                write!(w, "{code}")?;
+                for arg in args {
                     writer.space(&mut *w)?;
                     arg.write(writer, &mut *w)?;
                 }
@@ -447,6 +662,30 @@ impl IrWrite for Insn {
         }
 
         write!(w, ";")?;
+
+        if writer.effects {
+            let mut effects = vec![];
+            if insn_data.may_read_memory() {
+                effects.push("read-mem");
+            }
+            if insn_data.may_write_memory() {
+                effects.push("write-mem");
+            }
+            if insn_data.may_trap() {
+                effects.push("trap");
+            }
+
+            if !effects.is_empty() {
+                write!(w, " # effects: {}", effects.join(", "))?;
+            }
+        }
+
+        if writer.source_locs {
+            if let Some(loc) = writer.func.dfg.insn_source_loc(*self) {
+                write!(w, " # @{loc}")?;
+            }
+        }
+
         Ok(())
     }
 }
@@ -476,3 +715,303 @@ impl IrWrite for Vec<u8> {
         w.write_all(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{builder::test_util::*, MemoryOrdering, SourceLoc};
+
+    #[test]
+    fn transient_storage() {
+        let mut builder = test_func_builder(&[Type::I256], Type::Void);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg = builder.args()[0];
+        let v1 = builder.make_imm_value(1i32);
+        builder.tstore(arg, v1);
+        builder.ret(&[]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i256) -> void {
+    block0:
+        store @transient v0 1.i32;
+        return;
+
+}
+"
+        );
+    }
+
+    #[test]
+    fn ordered_access() {
+        let mut builder = test_func_builder(&[Type::I256], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg = builder.args()[0];
+        let v1 = builder.make_imm_value(1i32);
+        builder.store_ordered(DataLocationKind::Storage, arg, v1, MemoryOrdering::Release);
+        let v2 = builder.alloca(Type::I32);
+        let v3 = builder.load_ordered(DataLocationKind::Memory, v2, MemoryOrdering::SeqCst);
+        builder.ret(&[v3]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i256) -> i32 {
+    block0:
+        store release @storage v0 1.i32;
+        v2.*i32 = alloca i32;
+        v3.i32 = load seq_cst @memory v2;
+        return v3;
+
+}
+"
+        );
+    }
+
+    #[test]
+    fn undef_value() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg = builder.args()[0];
+        // The undef value takes `v1`.
+        let undef = builder.make_undef(Type::I32);
+        let v2 = builder.add(arg, undef);
+        builder.ret(&[v2]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        v2.i32 = add v0 undef.i32;
+        return v2;
+
+}
+"
+        );
+    }
+
+    #[test]
+    fn effect_annotations() {
+        let mut builder = test_func_builder(&[], Type::Void);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let v0 = builder.alloca(Type::I32);
+        let v1 = builder.load(DataLocationKind::Memory, v0);
+        let v2 = builder.add(v1, v1);
+        builder.store(DataLocationKind::Memory, v0, v2);
+        builder.ret(&[]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &module.funcs[func_ref];
+        let mut writer = FuncWriter::new(func_ref, func, None).with_effects(true);
+
+        assert_eq!(
+            writer.dump_string().unwrap(),
+            "func public %test_func() -> void {
+    block0:
+        v0.*i32 = alloca i32;
+        v1.i32 = load @memory v0; # effects: read-mem, trap
+        v2.i32 = add v1 v1;
+        store @memory v0 v2; # effects: write-mem, trap
+        return;
+
+}
+"
+        );
+    }
+
+    #[test]
+    fn source_loc_annotations() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg = builder.args()[0];
+        builder.set_source_loc(SourceLoc::new("main.fe", 3, 5));
+        let v1 = builder.add(arg, arg);
+        builder.clear_source_loc();
+        builder.ret(&[v1]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &module.funcs[func_ref];
+        let mut writer = FuncWriter::new(func_ref, func, None).with_source_locs(true);
+
+        assert_eq!(
+            writer.dump_string().unwrap(),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        v1.i32 = add v0 v0; # @main.fe:3:5
+        return v1;
+
+}
+"
+        );
+    }
+
+    fn build_add_chain(shift_ids: bool) -> (Module, FuncRef) {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        // An unused immediate consumes a value id, which shifts the ids of the later values.
+        if shift_ids {
+            builder.make_imm_value(42i32);
+        }
+
+        let arg = builder.args()[0];
+        let one = builder.make_imm_value(1i32);
+        let v1 = builder.add(arg, one);
+        builder.jump(b1);
+
+        builder.switch_to_block(b1);
+        let v2 = builder.mul(v1, arg);
+        builder.ret(&[v2]);
+        builder.seal_all();
+
+        let module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        (module, func_ref)
+    }
+
+    #[test]
+    fn normalized_numbering() {
+        let (module, func_ref) = build_add_chain(false);
+        let (shifted_module, shifted_func_ref) = build_add_chain(true);
+        assert_ne!(
+            dump_func(&module, func_ref),
+            dump_func(&shifted_module, shifted_func_ref)
+        );
+
+        let expected = "func public %test_func(v0.i32) -> i32 {
+    block0:
+        v1.i32 = add v0 1.i32;
+        jump block1;
+
+    block1:
+        v2.i32 = mul v1 v0;
+        return v2;
+
+}
+";
+        assert_eq!(dump_func_normalized(&module.funcs[func_ref]), expected);
+        assert_eq!(
+            dump_func_normalized(&shifted_module.funcs[shifted_func_ref]),
+            expected
+        );
+    }
+
+    #[test]
+    fn normalized_numbering_of_values_outside_layout() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg = builder.args()[0];
+        let v1 = builder.add(arg, arg);
+        let v2 = builder.mul(v1, arg);
+        builder.ret(&[v2]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+        let add = func.dfg.value_insn(v1).unwrap();
+        func.layout.remove_insn(add);
+
+        // `v1` isn't defined in the layout, so it's numbered after the values defined there.
+        assert_eq!(
+            dump_func_normalized(&module.funcs[func_ref]),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        v1.i32 = mul v2 v0;
+        return v1;
+
+}
+"
+        );
+    }
+
+    #[test]
+    fn module_level_items() {
+        use crate::{
+            builder::ModuleBuilder, func_cursor::InsnInserter, global_variable::ConstantValue,
+            Linkage, Signature,
+        };
+
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let point = mb.declare_struct_type("point", &[Type::I32, Type::I32], false);
+        mb.make_global(GlobalVariableData::constant(
+            "origin".to_string(),
+            point,
+            Linkage::Private,
+            ConstantValue::make_struct(vec![
+                ConstantValue::make_imm(0i32),
+                ConstantValue::make_imm(1i32),
+            ]),
+        ));
+        let counter = mb.make_global(GlobalVariableData::new(
+            "counter".to_string(),
+            Type::I32,
+            Linkage::Public,
+            false,
+            None,
+        ));
+
+        let sig = Signature::new("ext", Linkage::External, &[Type::I32], Type::I32);
+        let ext = mb.declare_function(sig);
+        let sig = Signature::new("main", Linkage::Public, &[], Type::I32);
+        let main = mb.declare_function(sig);
+
+        let mut builder = mb.build_function::<InsnInserter>(main);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        let ptr = builder.make_global_value(counter);
+        let x = builder.load(DataLocationKind::Memory, ptr);
+        let v = builder.call(ext, &[x]).unwrap();
+        builder.ret(&[v]);
+        builder.seal_all();
+        let module = builder.finish().build();
+
+        assert_eq!(
+            dump_module(&module),
+            "target = \"evm-ethereum-london\"
+type %point = {i32, i32};
+gv public %counter:i32;
+gv private const %origin:%point = {0, 1};
+declare external %ext(i32) -> i32;
+func public %main() -> i32 {
+    block0:
+        v1.i32 = load @memory %counter;
+        v2.i32 = call %ext v1;
+        return v2;
+
+}
+
+"
+        );
+    }
+}
@@ -1,6 +1,9 @@
-use crate::Type;
+use crate::{
+    insn::{BinaryOp, CastOp, DataLocationKind, UnaryOp},
+    InsnData, Type,
+};
 
-use super::{IsaSpecificTypeProvider, TargetIsa};
+use super::{CostModel, IsaSpecificTypeProvider, TargetIsa};
 
 use sonatina_triple::{Architecture, Chain, EvmVersion, TargetTriple, Version};
 
@@ -39,3 +42,112 @@ impl IsaSpecificTypeProvider for EvmEth {
         Type::I256
     }
 }
+
+/// Estimates the gas consumed by the EVM code that an insn is lowered to.
+///
+/// Storage accesses are estimated as cold accesses, and storage writes as writes to fresh
+/// slots, so the estimate is an upper bound of the actual cost in most cases. Transient storage
+/// accesses have a fixed cost.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EvmCostModel;
+
+impl EvmCostModel {
+    const VERY_LOW: u64 = 3;
+    const LOW: u64 = 5;
+    const MID: u64 = 8;
+    const HIGH: u64 = 10;
+    const COLD_SLOAD: u64 = 2100;
+    const SSTORE_SET: u64 = 20000;
+    const TRANSIENT_ACCESS: u64 = 100;
+}
+
+impl CostModel for EvmCostModel {
+    fn insn_cost(&self, insn: &InsnData) -> u64 {
+        match insn {
+            InsnData::Unary { code, .. } => match code {
+                UnaryOp::Not | UnaryOp::Copy => Self::VERY_LOW,
+                // `0 - x`.
+                UnaryOp::Neg => 2 * Self::VERY_LOW,
+            },
+
+            InsnData::Binary { code, .. } => match code {
+                BinaryOp::Add
+                | BinaryOp::Sub
+                | BinaryOp::Lt
+                | BinaryOp::Gt
+                | BinaryOp::Slt
+                | BinaryOp::Sgt
+                | BinaryOp::Eq
+                | BinaryOp::And
+                | BinaryOp::Or
+                | BinaryOp::Xor
+                | BinaryOp::Shl
+                | BinaryOp::Lshr
+                | BinaryOp::Ashr => Self::VERY_LOW,
+                // Negated comparisons need an additional `ISZERO`.
+                BinaryOp::Le | BinaryOp::Ge | BinaryOp::Sle | BinaryOp::Sge | BinaryOp::Ne => {
+                    2 * Self::VERY_LOW
+                }
+                BinaryOp::Mul
+                | BinaryOp::Udiv
+                | BinaryOp::Sdiv
+                | BinaryOp::Urem
+                | BinaryOp::Srem => Self::LOW,
+                // The operation itself, an overflow check and a conditional jump to the trap.
+                BinaryOp::AddChecked
+                | BinaryOp::SubChecked
+                | BinaryOp::UaddChecked
+                | BinaryOp::UsubChecked => 3 * Self::VERY_LOW + Self::HIGH,
+                BinaryOp::MulChecked | BinaryOp::UmulChecked => {
+                    2 * Self::LOW + 2 * Self::VERY_LOW + Self::HIGH
+                }
+            },
+
+            InsnData::Cast { code, .. } => match code {
+                CastOp::Sext => Self::LOW,
+                CastOp::Zext | CastOp::Trunc => Self::VERY_LOW,
+                CastOp::BitCast => 0,
+            },
+
+            InsnData::Load { loc, .. } => match loc {
+                DataLocationKind::Memory => Self::VERY_LOW,
+                DataLocationKind::Storage => Self::COLD_SLOAD,
+                DataLocationKind::TransientStorage => Self::TRANSIENT_ACCESS,
+            },
+
+            InsnData::Store { loc, .. } => match loc {
+                DataLocationKind::Memory => Self::VERY_LOW,
+                DataLocationKind::Storage => Self::SSTORE_SET,
+                DataLocationKind::TransientStorage => Self::TRANSIENT_ACCESS,
+            },
+
+            // Pushing the args and the return address, and jumping to the callee.
+            InsnData::Call { args, .. } => (args.len() as u64 + 1) * Self::VERY_LOW + Self::MID,
+
+            InsnData::Jump { .. } | InsnData::Return { .. } => Self::MID,
+
+            // Pushing the args. The cost of the memory expansion is not included.
+            InsnData::Halt { args, .. } => args.len() as u64 * Self::VERY_LOW,
+
+            InsnData::Branch { .. } => Self::HIGH,
+
+            // Each entry is lowered to a comparison and a conditional jump.
+            InsnData::BrTable { table, default, .. } => {
+                let cost = table.len() as u64 * (2 * Self::VERY_LOW + Self::HIGH);
+                if default.is_some() {
+                    cost + Self::MID
+                } else {
+                    cost
+                }
+            }
+
+            // Each index is scaled and added to the base pointer.
+            InsnData::Gep { args } => {
+                args.len().saturating_sub(1) as u64 * (Self::LOW + Self::VERY_LOW)
+            }
+
+            // Stack slots are assigned statically, and phis are resolved by copies in the preds.
+            InsnData::Alloca { .. } | InsnData::Phi { .. } => 0,
+        }
+    }
+}
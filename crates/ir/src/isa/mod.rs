@@ -1,9 +1,10 @@
 use dyn_clone::DynClone;
 use sonatina_triple::{Architecture, TargetTriple};
 
-use crate::Type;
+use crate::{InsnData, Type};
 
 pub mod evm_eth;
+pub mod registry;
 
 pub struct IsaBuilder {
     triple: TargetTriple,
@@ -35,6 +36,20 @@ impl TargetIsa {
         &self.triple
     }
 
+    /// Returns the cost model of the target, which estimates the execution cost of insns.
+    pub fn cost_model(&self) -> &'static dyn CostModel {
+        match self.triple.architecture {
+            Architecture::Evm => &evm_eth::EvmCostModel,
+        }
+    }
+
+    /// Returns the byte order of the target memory.
+    pub fn endian(&self) -> Endian {
+        match self.triple.architecture {
+            Architecture::Evm => Endian::Big,
+        }
+    }
+
     fn new(triple: TargetTriple, type_provider: Box<dyn IsaSpecificTypeProvider>) -> Self {
         Self {
             triple,
@@ -43,7 +58,14 @@ impl TargetIsa {
     }
 }
 
-pub trait IsaSpecificTypeProvider: std::fmt::Debug + DynClone {
+/// Byte order of multi-byte values in the target memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endian {
+    Big,
+    Little,
+}
+
+pub trait IsaSpecificTypeProvider: std::fmt::Debug + DynClone + Send + Sync {
     fn pointer_type(&self) -> Type;
     fn address_type(&self) -> Type;
     fn balance_type(&self) -> Type;
@@ -51,3 +73,11 @@ pub trait IsaSpecificTypeProvider: std::fmt::Debug + DynClone {
 }
 
 dyn_clone::clone_trait_object!(IsaSpecificTypeProvider);
+
+/// Estimates the execution cost of insns on a target, e.g., gas on EVM.
+///
+/// The cost is a static estimate of executing the insn once, so it's meant to compare
+/// alternatives in optimization heuristics rather than to predict the exact runtime cost.
+pub trait CostModel: std::fmt::Debug + Send + Sync {
+    fn insn_cost(&self, insn: &InsnData) -> u64;
+}
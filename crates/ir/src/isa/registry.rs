@@ -0,0 +1,197 @@
+//! This module contains the registry of ISAs, which finds the ISA of a target triple.
+//!
+//! The built-in ISAs are registered when the registry is first used. Tools can register their
+//! own ISAs with [`register_isa`], which take precedence over the ones registered before.
+use std::{
+    fmt,
+    sync::{OnceLock, RwLock},
+};
+
+use sonatina_triple::TargetTriple;
+
+use super::{evm_eth::EvmEth, TargetIsa};
+
+/// A function building the ISA of a target triple matched by the registered pattern.
+pub type IsaFactory = fn(TargetTriple) -> TargetIsa;
+
+/// A pattern of target triples in the `architecture-chain-version` form, e.g.,
+/// `evm-ethereum-*`. `*` matches any component.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TriplePattern {
+    architecture: Option<String>,
+    chain: Option<String>,
+    version: Option<String>,
+}
+
+impl TriplePattern {
+    pub fn parse(s: &str) -> Result<Self, InvalidTriplePattern> {
+        let component = |c: &str| match c {
+            "" => Err(InvalidTriplePattern(s.to_string())),
+            "*" => Ok(None),
+            c => Ok(Some(c.to_string())),
+        };
+
+        match s.split('-').collect::<Vec<_>>().as_slice() {
+            [architecture, chain, version] => Ok(Self {
+                architecture: component(architecture)?,
+                chain: component(chain)?,
+                version: component(version)?,
+            }),
+            _ => Err(InvalidTriplePattern(s.to_string())),
+        }
+    }
+
+    pub fn matches(&self, triple: &TargetTriple) -> bool {
+        let matches = |pat: &Option<String>, c: &dyn fmt::Display| match pat {
+            Some(pat) => *pat == c.to_string(),
+            None => true,
+        };
+
+        matches(&self.architecture, &triple.architecture)
+            && matches(&self.chain, &triple.chain)
+            && matches(&self.version, &triple.version)
+    }
+}
+
+impl fmt::Display for TriplePattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let component = |c: &Option<String>| c.clone().unwrap_or_else(|| "*".to_string());
+        write!(
+            f,
+            "{}-{}-{}",
+            component(&self.architecture),
+            component(&self.chain),
+            component(&self.version)
+        )
+    }
+}
+
+/// An error returned when a triple pattern is not in the `architecture-chain-version` form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidTriplePattern(pub String);
+
+impl fmt::Display for InvalidTriplePattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "the format of triple pattern must be `architecture-chain-version`, but got `{}`",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidTriplePattern {}
+
+/// Registers `factory` as the ISA of the triples matched by `pattern`.
+/// If multiple registered patterns match a triple, the one registered last is used.
+pub fn register_isa(pattern: TriplePattern, factory: IsaFactory) {
+    registry().write().unwrap().push((pattern, factory));
+}
+
+/// Returns the ISA of `triple`, or `None` if no registered pattern matches the triple.
+pub fn isa_for_triple(triple: &TargetTriple) -> Option<TargetIsa> {
+    let factory = registry()
+        .read()
+        .unwrap()
+        .iter()
+        .rev()
+        .find_map(|(pattern, factory)| pattern.matches(triple).then_some(*factory))?;
+    Some(factory(triple.clone()))
+}
+
+fn registry() -> &'static RwLock<Vec<(TriplePattern, IsaFactory)>> {
+    static REGISTRY: OnceLock<RwLock<Vec<(TriplePattern, IsaFactory)>>> = OnceLock::new();
+
+    REGISTRY.get_or_init(|| {
+        let builtins: [(_, IsaFactory); 1] = [("evm-ethereum-*", EvmEth::build_isa)];
+        let builtins = builtins
+            .into_iter()
+            .map(|(pattern, factory)| (TriplePattern::parse(pattern).unwrap(), factory))
+            .collect();
+        RwLock::new(builtins)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{isa::IsaSpecificTypeProvider, Type};
+
+    #[derive(Debug, Clone)]
+    struct NarrowPtr;
+
+    impl IsaSpecificTypeProvider for NarrowPtr {
+        fn pointer_type(&self) -> Type {
+            Type::I64
+        }
+
+        fn address_type(&self) -> Type {
+            Type::I64
+        }
+
+        fn balance_type(&self) -> Type {
+            Type::I64
+        }
+
+        fn gas_type(&self) -> Type {
+            Type::I64
+        }
+    }
+
+    #[test]
+    fn builtin_isa() {
+        let triple = TargetTriple::parse("evm-ethereum-london").unwrap();
+        let isa = isa_for_triple(&triple).unwrap();
+        assert_eq!(isa.triple(), &triple);
+        assert_eq!(isa.type_provider().pointer_type(), Type::I256);
+    }
+
+    /// Registers an ISA while the guard is alive, so the process-global registry seen by the
+    /// other tests is restored even if the test fails.
+    struct ScopedRegistration(TriplePattern);
+
+    impl ScopedRegistration {
+        fn new(pattern: &str, factory: IsaFactory) -> Self {
+            let pattern = TriplePattern::parse(pattern).unwrap();
+            register_isa(pattern.clone(), factory);
+            Self(pattern)
+        }
+    }
+
+    impl Drop for ScopedRegistration {
+        fn drop(&mut self) {
+            let mut registry = registry().write().unwrap();
+            if let Some(idx) = registry.iter().rposition(|(pattern, _)| *pattern == self.0) {
+                registry.remove(idx);
+            }
+        }
+    }
+
+    #[test]
+    fn registered_isa_takes_precedence() {
+        // No other test uses the frontier triple, and the registration is removed at the end.
+        let _registration = ScopedRegistration::new("evm-ethereum-frontier", |triple| {
+            TargetIsa::new(triple, Box::new(NarrowPtr))
+        });
+
+        let frontier = TargetTriple::parse("evm-ethereum-frontier").unwrap();
+        let isa = isa_for_triple(&frontier).unwrap();
+        assert_eq!(isa.type_provider().pointer_type(), Type::I64);
+
+        let homestead = TargetTriple::parse("evm-ethereum-homestead").unwrap();
+        let isa = isa_for_triple(&homestead).unwrap();
+        assert_eq!(isa.type_provider().pointer_type(), Type::I256);
+    }
+
+    #[test]
+    fn triple_pattern() {
+        let pattern = TriplePattern::parse("evm-*-london").unwrap();
+        assert_eq!(pattern.to_string(), "evm-*-london");
+        assert!(pattern.matches(&TargetTriple::parse("evm-ethereum-london").unwrap()));
+        assert!(!pattern.matches(&TargetTriple::parse("evm-ethereum-istanbul").unwrap()));
+
+        assert!(TriplePattern::parse("evm-ethereum").is_err());
+        assert!(TriplePattern::parse("evm--london").is_err());
+    }
+}
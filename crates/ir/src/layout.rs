@@ -4,6 +4,7 @@ use cranelift_entity::SecondaryMap;
 use super::{Block, Insn};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Layout {
     blocks: SecondaryMap<Block, BlockNode>,
     insns: SecondaryMap<Insn, InsnNode>,
@@ -289,6 +290,21 @@ impl Layout {
 
         self.insns[insn] = InsnNode::default();
     }
+
+    /// Moves `insn` right before `before`. `before` may be in another block.
+    ///
+    /// This doesn't check the order of definitions and uses; use [`crate::Function::move_insn`]
+    /// to verify that the move keeps each value defined before its uses.
+    pub fn move_insn(&mut self, insn: Insn, before: Insn) {
+        debug_assert!(self.is_insn_inserted(insn));
+        debug_assert!(self.is_insn_inserted(before));
+        if insn == before {
+            return;
+        }
+
+        self.remove_insn(insn);
+        self.insert_insn_before(insn, before);
+    }
 }
 
 struct BlockIter<'a> {
@@ -322,6 +338,7 @@ impl<'a> Iterator for InsnIter<'a> {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct BlockNode {
     prev: Option<Block>,
     next: Option<Block>,
@@ -330,6 +347,7 @@ struct BlockNode {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct InsnNode {
     /// An block in which the insn exists.
     block: Option<Block>,
@@ -552,4 +570,53 @@ mod tests {
         assert_eq!(layout.first_insn_of(b1), None);
         assert_eq!(layout.last_insn_of(b1), None);
     }
+
+    #[test]
+    fn test_move_insn() {
+        let mut layout = Layout::new();
+        let ctx = ModuleCtx::new(build_test_isa());
+        let mut dfg = DataFlowGraph::new(ctx);
+        let b1 = dfg.make_block();
+        layout.append_block(b1);
+
+        // insn1 -> insn2 -> insn3.
+        let i1 = dfg.make_dummy_insn();
+        let i2 = dfg.make_dummy_insn();
+        let i3 = dfg.make_dummy_insn();
+        layout.append_insn(i1, b1);
+        layout.append_insn(i2, b1);
+        layout.append_insn(i3, b1);
+
+        let insns = |layout: &Layout| layout.iter_insn(b1).collect::<Vec<_>>();
+
+        // Move the last insn to the block top: insn3 -> insn1 -> insn2.
+        layout.move_insn(i3, i1);
+        assert_eq!(insns(&layout), vec![i3, i1, i2]);
+        assert_eq!(layout.first_insn_of(b1), Some(i3));
+        assert_eq!(layout.last_insn_of(b1), Some(i2));
+        assert_eq!(layout.prev_insn_of(i3), None);
+        assert_eq!(layout.next_insn_of(i2), None);
+
+        // Move the first insn right before the last one: insn1 -> insn3 -> insn2.
+        layout.move_insn(i3, i2);
+        assert_eq!(insns(&layout), vec![i1, i3, i2]);
+        assert_eq!(layout.first_insn_of(b1), Some(i1));
+        assert_eq!(layout.prev_insn_of(i1), None);
+
+        // Moving an insn before itself or its successor is a no-op.
+        layout.move_insn(i3, i3);
+        layout.move_insn(i3, i2);
+        assert_eq!(insns(&layout), vec![i1, i3, i2]);
+
+        // Move an insn to another block.
+        let b2 = dfg.make_block();
+        layout.append_block(b2);
+        let i4 = dfg.make_dummy_insn();
+        layout.append_insn(i4, b2);
+        layout.move_insn(i2, i4);
+        assert_eq!(insns(&layout), vec![i1, i3]);
+        assert_eq!(layout.last_insn_of(b1), Some(i3));
+        assert_eq!(layout.insn_block(i2), b2);
+        assert_eq!(layout.iter_insn(b2).collect::<Vec<_>>(), vec![i2, i4]);
+    }
 }
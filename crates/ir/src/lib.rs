@@ -1,4 +1,6 @@
+pub mod attributes;
 pub mod builder;
+pub mod call_graph;
 pub mod cfg;
 pub mod dfg;
 pub mod func_cursor;
@@ -7,31 +9,43 @@ pub mod global_variable;
 pub mod graphviz;
 pub mod insn;
 pub mod inst;
+pub mod inst_match;
 pub mod ir_writer;
 pub mod isa;
 pub mod layout;
 pub mod linkage;
+pub mod linker;
 pub mod module;
+#[cfg(feature = "serde")]
+pub mod serialize;
+pub mod source_loc;
+pub mod type_layout;
 pub mod types;
 pub mod value;
+pub mod visitor;
 
 mod bigint;
 
-pub use bigint::{I256, U256};
+pub use attributes::{AttrMap, AttrValue};
+pub use bigint::{U256Ext, I256, U256};
 pub use builder::Variable;
+pub use call_graph::CallGraph;
 pub use cfg::ControlFlowGraph;
 pub use dfg::{Block, BlockData, DataFlowGraph};
 pub use function::{Function, Signature};
 pub use global_variable::{GlobalVariable, GlobalVariableData};
-pub use graphviz::render_to;
-pub use insn::{BranchInfo, DataLocationKind, Insn, InsnData};
+pub use graphviz::{render_domtree_to, render_to, render_with_loops, GraphvizOptions};
+pub use insn::{BranchInfo, DataLocationKind, Insn, InsnData, MemoryOrdering};
 pub use inst::{
     inst_set::{InstSetBase, InstSetExt},
     HasInst, Inst,
 };
 pub use layout::Layout;
 pub use linkage::Linkage;
+pub use linker::LinkError;
 pub use module::Module;
+pub use source_loc::SourceLoc;
+pub use type_layout::{FieldLayout, TypeLayout, TypeLayoutError};
 pub use types::Type;
 pub use value::{Immediate, Value, ValueData};
 
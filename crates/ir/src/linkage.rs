@@ -1,6 +1,7 @@
 use std::{fmt, str::FromStr};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Linkage of symbols.
 pub enum Linkage {
     /// The symbol is defined in the module, and can be used from the outside of the module.
@@ -12,6 +13,11 @@ pub enum Linkage {
 
     /// The symbol is defined outside of the module.
     External,
+
+    /// The symbol is defined in the module, and can be used from the outside of the module.
+    /// Unlike [`Linkage::Public`], the definition is replaced with a non-weak definition of the
+    /// same symbol when modules are linked.
+    Weak,
 }
 
 impl fmt::Display for Linkage {
@@ -20,6 +26,7 @@ impl fmt::Display for Linkage {
             Self::Public => write!(f, "public"),
             Self::Private => write!(f, "private"),
             Self::External => write!(f, "external"),
+            Self::Weak => write!(f, "weak"),
         }
     }
 }
@@ -32,6 +39,7 @@ impl FromStr for Linkage {
             "public" => Ok(Self::Public),
             "private" => Ok(Self::Private),
             "external" => Ok(Self::External),
+            "weak" => Ok(Self::Weak),
             _ => Err(()),
         }
     }
@@ -0,0 +1,573 @@
+//! This module contains the linker that merges a [`Module`] into another.
+use std::fmt;
+
+use cranelift_entity::EntityRef;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    module::FuncRef,
+    types::{CompoundType, CompoundTypeData, StructData},
+    Function, GlobalVariable, Linkage, Module, Signature, Type,
+};
+
+impl Module {
+    /// Links `other` into the module.
+    ///
+    /// Functions and global variables are resolved by their symbols:
+    /// * An `external` declaration is resolved to the definition of the same symbol in the other
+    ///   module if any.
+    /// * A `weak` definition is overridden by a `public` definition of the same symbol.
+    /// * Two `public` definitions of the same symbol conflict.
+    /// * A `private` symbol is local to its module, so it's renamed if the other module has a
+    ///   symbol of the same name. If both symbols are private, the one of `other` is renamed.
+    ///
+    /// Types of `other` are merged into the type store of the module, and `FuncRef`s and global
+    /// variables referred to by the functions of `other` are renumbered to the ones of the
+    /// module.
+    ///
+    /// On error, functions and global variables of the module are left unchanged.
+    pub fn link(&mut self, other: Module) -> Result<(), LinkError> {
+        let Module {
+            funcs: other_funcs,
+            ctx: other_ctx,
+        } = other;
+
+        // Clones of a context share both the type store and the global variable store, so there
+        // is nothing to merge except for functions.
+        let shares_stores = self.ctx.type_store_id() == other_ctx.type_store_id();
+
+        let mut map = SymbolMap::default();
+        if !shares_stores {
+            let compounds: Vec<_> = other_ctx.with_ty_store(|s| {
+                s.all_compounds()
+                    .map(|(cmpd, data)| (cmpd, data.clone()))
+                    .collect()
+            });
+            self.merge_types(compounds, &mut map)?;
+        }
+
+        // Resolve global variables.
+        let mut new_gvs = Vec::new();
+        let mut replaced_gvs = Vec::new();
+        let mut renamed_gvs = Vec::new();
+        if !shares_stores {
+            let other_gvs: Vec<_> = other_ctx.with_gv_store(|s| {
+                s.ordered_iter()
+                    .map(|(gv, data)| (gv, data.clone()))
+                    .collect()
+            });
+            let mut used_symbols: FxHashSet<_> = self
+                .ctx
+                .with_gv_store(|s| s.all_gv_data().map(|data| data.symbol.clone()).collect());
+            used_symbols.extend(other_gvs.iter().map(|(_, data)| data.symbol.clone()));
+
+            for (other_gv, mut gv_data) in other_gvs {
+                gv_data.ty = map.ty(gv_data.ty);
+                let Some(gv) = self.ctx.with_gv_store(|s| s.gv_by_symbol(&gv_data.symbol)) else {
+                    new_gvs.push((other_gv, gv_data));
+                    continue;
+                };
+
+                let (linkage, ty) = self.ctx.with_gv_store(|s| {
+                    let data = s.gv_data(gv);
+                    (data.linkage, data.ty)
+                });
+                match resolve(&gv_data.symbol, linkage, gv_data.linkage)? {
+                    Resolution::RenameSelf => {
+                        renamed_gvs.push((gv, fresh_name(&gv_data.symbol, &mut used_symbols)));
+                        new_gvs.push((other_gv, gv_data));
+                        continue;
+                    }
+                    Resolution::RenameOther => {
+                        gv_data.symbol = fresh_name(&gv_data.symbol, &mut used_symbols);
+                        new_gvs.push((other_gv, gv_data));
+                        continue;
+                    }
+                    _ if ty != gv_data.ty => {
+                        return Err(LinkError::TypeMismatch(gv_data.symbol));
+                    }
+                    Resolution::TakeOther => replaced_gvs.push((gv, gv_data)),
+                    Resolution::KeepSelf => {}
+                }
+                map.gvs.insert(other_gv, gv);
+            }
+        }
+
+        // Resolve functions.
+        let func_by_name: FxHashMap<_, _> = self
+            .funcs
+            .iter()
+            .map(|(func_ref, func)| (func.sig.name().to_string(), func_ref))
+            .collect();
+        let mut used_names: FxHashSet<_> = func_by_name.keys().cloned().collect();
+        used_names.extend(other_funcs.values().map(|func| func.sig.name().to_string()));
+
+        let mut new_funcs = Vec::new();
+        let mut replaced_funcs = Vec::new();
+        // The new signatures of the renamed private functions.
+        let mut renamed_sigs = FxHashMap::default();
+        for (other_ref, mut func) in other_funcs {
+            func.sig = map.sig(&func.sig);
+            let new_ref = FuncRef::new(self.funcs.len() + new_funcs.len());
+            let Some(&func_ref) = func_by_name.get(func.sig.name()) else {
+                map.funcs.insert(other_ref, new_ref);
+                new_funcs.push(func);
+                continue;
+            };
+
+            let sig = &self.funcs[func_ref].sig;
+            match resolve(sig.name(), sig.linkage(), func.sig.linkage())? {
+                Resolution::RenameSelf => {
+                    let name = fresh_name(sig.name(), &mut used_names);
+                    renamed_sigs.insert(func_ref, with_name(sig, &name));
+                    map.funcs.insert(other_ref, new_ref);
+                    new_funcs.push(func);
+                    continue;
+                }
+                Resolution::RenameOther => {
+                    let name = fresh_name(func.sig.name(), &mut used_names);
+                    func.sig = with_name(&func.sig, &name);
+                    renamed_sigs.insert(new_ref, func.sig.clone());
+                    map.funcs.insert(other_ref, new_ref);
+                    new_funcs.push(func);
+                    continue;
+                }
+                _ if sig.args() != func.sig.args() || sig.ret_tys() != func.sig.ret_tys() => {
+                    return Err(LinkError::TypeMismatch(func.sig.name().to_string()));
+                }
+                Resolution::TakeOther => replaced_funcs.push((func_ref, func)),
+                Resolution::KeepSelf => {}
+            }
+            map.funcs.insert(other_ref, func_ref);
+        }
+
+        // All symbols are resolved without conflicts, so it's safe to modify the module.
+        // The private symbols of the module are renamed first to make room for the new ones.
+        self.ctx.with_ty_store(|ty_store| {
+            self.ctx.with_gv_store_mut(|s| {
+                for (gv, symbol) in renamed_gvs {
+                    s.rename_gv(gv, symbol);
+                }
+                for (other_gv, gv_data) in new_gvs {
+                    map.gvs.insert(other_gv, s.make_gv(gv_data, ty_store));
+                }
+                for (gv, gv_data) in replaced_gvs {
+                    s.replace_gv_data(gv, gv_data);
+                }
+            })
+        });
+
+        for mut func in new_funcs {
+            map.relocate(&mut func, self);
+            self.funcs.push(func);
+        }
+        for (func_ref, mut func) in replaced_funcs {
+            map.relocate(&mut func, self);
+            self.funcs[func_ref] = func;
+        }
+
+        // Call sites keep copies of the callee signatures, which must follow the renaming.
+        for (func_ref, sig) in &renamed_sigs {
+            self.funcs[*func_ref].sig = sig.clone();
+        }
+        if !renamed_sigs.is_empty() {
+            for func in self.funcs.values_mut() {
+                for (callee, sig) in func.callees.iter_mut() {
+                    if let Some(renamed) = renamed_sigs.get(callee) {
+                        *sig = renamed.clone();
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Makes the types of `compounds` in the type store of the module, and records the
+    /// correspondence in `map`.
+    /// `compounds` must be sorted so that each type comes after its component types.
+    fn merge_types(
+        &self,
+        compounds: Vec<(CompoundType, CompoundTypeData)>,
+        map: &mut SymbolMap,
+    ) -> Result<(), LinkError> {
+        self.ctx.with_ty_store_mut(|s| {
+            for (other_cmpd, data) in compounds {
+                let ty = match data {
+                    CompoundTypeData::Array { elem, len } => s.make_array(map.ty(elem), len),
+                    CompoundTypeData::Ptr(ty) => s.make_ptr(map.ty(ty)),
+                    CompoundTypeData::Union(variants) => {
+                        let variants: Vec<_> = variants.into_iter().map(|ty| map.ty(ty)).collect();
+                        s.make_union(&variants)
+                    }
+                    CompoundTypeData::Struct(StructData {
+                        name,
+                        fields,
+                        packed,
+                    }) => {
+                        let fields: Vec<_> = fields.into_iter().map(|ty| map.ty(ty)).collect();
+                        match s.struct_type_by_name(&name) {
+                            Some(ty) => {
+                                let def = s.struct_def(ty).unwrap();
+                                if def.fields != fields || def.packed != packed {
+                                    return Err(LinkError::ConflictingStruct(name));
+                                }
+                                ty
+                            }
+                            None => s.make_struct(&name, &fields, packed),
+                        }
+                    }
+                };
+                map.types.insert(other_cmpd, ty);
+            }
+            Ok(())
+        })
+    }
+}
+
+/// An error returned from [`Module::link`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    /// The symbol is defined in both modules, and neither definition is weak.
+    DuplicateSymbol(String),
+
+    /// The symbol has different types in the modules.
+    TypeMismatch(String),
+
+    /// The struct type of the name is defined with different fields in the modules.
+    ConflictingStruct(String),
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DuplicateSymbol(symbol) => write!(f, "duplicate symbol `{symbol}`"),
+            Self::TypeMismatch(symbol) => write!(f, "type mismatch of symbol `{symbol}`"),
+            Self::ConflictingStruct(name) => {
+                write!(f, "conflicting definitions of struct `{name}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resolution {
+    KeepSelf,
+    TakeOther,
+    /// The private symbol of the module is renamed, and the symbol of the other is added.
+    RenameSelf,
+    /// The private symbol of the other module is renamed and added.
+    RenameOther,
+}
+
+/// Decides which of the symbols of the same name survives.
+fn resolve(symbol: &str, lhs: Linkage, rhs: Linkage) -> Result<Resolution, LinkError> {
+    match (lhs, rhs) {
+        (_, Linkage::Private) => Ok(Resolution::RenameOther),
+        (Linkage::Private, _) => Ok(Resolution::RenameSelf),
+        (Linkage::Public, Linkage::Public) => Err(LinkError::DuplicateSymbol(symbol.to_string())),
+        (_, Linkage::External) => Ok(Resolution::KeepSelf),
+        (Linkage::External, _) | (Linkage::Weak, Linkage::Public) => Ok(Resolution::TakeOther),
+        (_, Linkage::Weak) => Ok(Resolution::KeepSelf),
+    }
+}
+
+/// Returns a name made from `name` that is not in `used`, and adds it to `used`.
+fn fresh_name(name: &str, used: &mut FxHashSet<String>) -> String {
+    let fresh = (1..)
+        .map(|n| format!("{name}_{n}"))
+        .find(|candidate| !used.contains(candidate))
+        .unwrap();
+    used.insert(fresh.clone());
+    fresh
+}
+
+fn with_name(sig: &Signature, name: &str) -> Signature {
+    Signature::with_ret_tys(name, sig.linkage(), sig.args(), sig.ret_tys())
+}
+
+/// Maps the entities of the linked module to the ones of the module linked into.
+/// Entities missing in the map are mapped to themselves, which is the case when both modules
+/// share their stores.
+#[derive(Debug, Default)]
+pub(crate) struct SymbolMap {
+    types: FxHashMap<CompoundType, Type>,
+    gvs: FxHashMap<GlobalVariable, GlobalVariable>,
+    funcs: FxHashMap<FuncRef, FuncRef>,
+}
+
+impl SymbolMap {
+    /// Makes a map that renumbers only functions, e.g., after functions are removed from a
+    /// module.
+    pub(crate) fn with_funcs(funcs: FxHashMap<FuncRef, FuncRef>) -> Self {
+        Self {
+            funcs,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn ty(&self, ty: Type) -> Type {
+        match ty {
+            Type::Compound(cmpd) => self.types.get(&cmpd).copied().unwrap_or(ty),
+            _ => ty,
+        }
+    }
+
+    pub(crate) fn gv(&self, gv: GlobalVariable) -> GlobalVariable {
+        self.gvs.get(&gv).copied().unwrap_or(gv)
+    }
+
+    /// Returns the function `func_ref` is mapped to, or `None` if it's missing in the map, e.g.,
+    /// because it has been removed from the module.
+    pub(crate) fn func(&self, func_ref: FuncRef) -> Option<FuncRef> {
+        self.funcs.get(&func_ref).copied()
+    }
+
+    fn sig(&self, sig: &Signature) -> Signature {
+        let args: Vec<_> = sig.args().iter().map(|ty| self.ty(*ty)).collect();
+        let ret_tys: Vec<_> = sig.ret_tys().iter().map(|ty| self.ty(*ty)).collect();
+        Signature::with_ret_tys(sig.name(), sig.linkage(), &args, &ret_tys)
+    }
+
+    pub(crate) fn relocate(&self, func: &mut Function, module: &Module) {
+        func.callees = func
+            .callees
+            .drain()
+            .filter_map(|(func_ref, sig)| Some((self.func(func_ref)?, self.sig(&sig))))
+            .collect();
+        func.dfg.relocate(module.ctx.clone(), self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        builder::{test_util::build_test_isa, ModuleBuilder},
+        func_cursor::InsnInserter,
+        module::ModuleCtx,
+        InsnData,
+    };
+
+    /// Builds a module defining `name` that returns `value`, and optionally declaring `callee`
+    /// that is called before returning.
+    fn build_module(name: &str, linkage: Linkage, value: i32, callee: Option<&str>) -> Module {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let callee = callee.map(|callee| {
+            mb.declare_function(Signature::new(callee, Linkage::External, &[], Type::I32))
+        });
+        let func_ref = mb.declare_function(Signature::new(name, linkage, &[], Type::I32));
+
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        if let Some(callee) = callee {
+            builder.call(callee, &[]);
+        }
+        let value = builder.make_imm_value(value);
+        builder.ret(&[value]);
+        builder.seal_all();
+        builder.finish().build()
+    }
+
+    fn func_by_name<'a>(module: &'a Module, name: &str) -> (FuncRef, &'a Function) {
+        module
+            .funcs
+            .iter()
+            .find(|(_, func)| func.sig.name() == name)
+            .unwrap()
+    }
+
+    fn returned_imm(func: &Function) -> i32 {
+        let block = func.layout.entry_block().unwrap();
+        let ret = func.layout.last_insn_of(block).unwrap();
+        let value = func.dfg.insn_arg(ret, 0);
+        match func.dfg.value_imm(value) {
+            Some(crate::Immediate::I32(imm)) => imm,
+            imm => panic!("unexpected return value {imm:?}"),
+        }
+    }
+
+    #[test]
+    fn resolve_external_declaration() {
+        let mut module = build_module("main", Linkage::Public, 0, Some("foo"));
+        let other = build_module("foo", Linkage::Public, 1, None);
+        module.link(other).unwrap();
+
+        assert_eq!(module.funcs.len(), 2);
+        let (foo, foo_func) = func_by_name(&module, "foo");
+        assert_eq!(foo_func.sig.linkage(), Linkage::Public);
+        assert_eq!(returned_imm(foo_func), 1);
+
+        let (_, main_func) = func_by_name(&module, "main");
+        let entry = main_func.layout.entry_block().unwrap();
+        let call = main_func.layout.first_insn_of(entry).unwrap();
+        assert!(matches!(
+            main_func.dfg.insn_data(call),
+            InsnData::Call { func, .. } if *func == foo
+        ));
+        assert_eq!(
+            module.topo_order(),
+            vec![foo, func_by_name(&module, "main").0]
+        );
+    }
+
+    #[test]
+    fn renumber_callees_of_other() {
+        let mut module = build_module("foo", Linkage::Public, 1, None);
+        let other = build_module("main", Linkage::Public, 0, Some("foo"));
+        module.link(other).unwrap();
+
+        let (foo, _) = func_by_name(&module, "foo");
+        let (main, main_func) = func_by_name(&module, "main");
+        assert_eq!(module.funcs.len(), 2);
+        assert!(main_func.callees.contains_key(&foo));
+        assert_eq!(
+            main_func.dfg.ctx.type_store_id(),
+            module.ctx.type_store_id()
+        );
+        assert_eq!(module.topo_order(), vec![foo, main]);
+    }
+
+    #[test]
+    fn duplicate_symbol() {
+        let mut module = build_module("foo", Linkage::Public, 0, None);
+        let other = build_module("foo", Linkage::Public, 1, None);
+        assert_eq!(
+            module.link(other),
+            Err(LinkError::DuplicateSymbol("foo".to_string()))
+        );
+        assert_eq!(returned_imm(func_by_name(&module, "foo").1), 0);
+    }
+
+    #[test]
+    fn rename_private_symbol() {
+        let mut module = build_module("foo", Linkage::Public, 0, None);
+        let other = build_module("foo", Linkage::Private, 1, None);
+        assert_eq!(module.link(other), Ok(()));
+        assert_eq!(returned_imm(func_by_name(&module, "foo").1), 0);
+        assert_eq!(returned_imm(func_by_name(&module, "foo_1").1), 1);
+
+        let mut module = build_module("foo", Linkage::Private, 0, None);
+        let other = build_module("foo", Linkage::Public, 1, None);
+        assert_eq!(module.link(other), Ok(()));
+        assert_eq!(returned_imm(func_by_name(&module, "foo").1), 1);
+        assert_eq!(returned_imm(func_by_name(&module, "foo_1").1), 0);
+    }
+
+    #[test]
+    fn rename_private_callee() {
+        let mut module = build_module("foo", Linkage::Public, 0, None);
+
+        // Links a module where `main` calls the private `foo`.
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let foo = mb.declare_function(Signature::new("foo", Linkage::Private, &[], Type::I32));
+        let main = mb.declare_function(Signature::new("main", Linkage::Public, &[], Type::I32));
+        for (func_ref, callee) in [(foo, None), (main, Some(foo))] {
+            let mut builder = mb.build_function::<InsnInserter>(func_ref);
+            let b0 = builder.append_block();
+            builder.switch_to_block(b0);
+            let value = match callee {
+                Some(callee) => builder.call(callee, &[]).unwrap(),
+                None => builder.make_imm_value(1i32),
+            };
+            builder.ret(&[value]);
+            builder.seal_all();
+            mb = builder.finish();
+        }
+        let other = mb.build();
+        assert_eq!(module.link(other), Ok(()));
+
+        let (foo, _) = func_by_name(&module, "foo");
+        let (foo_1, _) = func_by_name(&module, "foo_1");
+        let (_, main) = func_by_name(&module, "main");
+        assert_ne!(foo, foo_1);
+        assert!(!main.callees.contains_key(&foo));
+        assert_eq!(main.callees[&foo_1].name(), "foo_1");
+    }
+
+    #[test]
+    fn override_weak_definition() {
+        let mut module = build_module("foo", Linkage::Weak, 0, None);
+        let other = build_module("foo", Linkage::Public, 1, None);
+        module.link(other).unwrap();
+        let (_, foo) = func_by_name(&module, "foo");
+        assert_eq!(foo.sig.linkage(), Linkage::Public);
+        assert_eq!(returned_imm(foo), 1);
+
+        // A weak definition never overrides a strong one.
+        let other = build_module("foo", Linkage::Weak, 2, None);
+        module.link(other).unwrap();
+        assert_eq!(returned_imm(func_by_name(&module, "foo").1), 1);
+    }
+
+    #[test]
+    fn merge_types_and_globals() {
+        let mut module = build_module("main", Linkage::Public, 0, None);
+        module
+            .ctx
+            .with_ty_store_mut(|s| s.make_struct("s", &[Type::I8], false));
+
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let (st, ptr) = mb.ctx.with_ty_store_mut(|s| {
+            let st = s.make_struct("s", &[Type::I8], false);
+            (st, s.make_ptr(st))
+        });
+        let gv = mb.make_global(GlobalVariableData::new(
+            "gv".to_string(),
+            st,
+            Linkage::Public,
+            false,
+            None,
+        ));
+        let func_ref =
+            mb.declare_function(Signature::new("foo", Linkage::Public, &[ptr], Type::Void));
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        builder.make_global_value(gv);
+        builder.alloca(st);
+        builder.ret(&[]);
+        builder.seal_all();
+        module.link(builder.finish().build()).unwrap();
+
+        let linked_st = module
+            .ctx
+            .with_ty_store(|s| s.struct_type_by_name("s"))
+            .unwrap();
+        let linked_ptr = module.ctx.with_ty_store_mut(|s| s.make_ptr(linked_st));
+        let linked_gv = module.ctx.with_gv_store(|s| s.gv_by_symbol("gv")).unwrap();
+        assert_eq!(module.ctx.with_gv_store(|s| s.ty(linked_gv)), linked_st);
+
+        let (_, foo) = func_by_name(&module, "foo");
+        assert_eq!(foo.sig.args(), &[linked_ptr]);
+        let entry = foo.layout.entry_block().unwrap();
+        let alloca = foo.layout.first_insn_of(entry).unwrap();
+        assert_eq!(foo.dfg.insn_data(alloca), &InsnData::alloca(linked_st));
+        assert!(foo
+            .dfg
+            .values
+            .values()
+            .any(|value| matches!(value, crate::ValueData::Global { gv, .. } if *gv == linked_gv)));
+    }
+
+    #[test]
+    fn conflicting_struct() {
+        let mut module = build_module("main", Linkage::Public, 0, None);
+        module
+            .ctx
+            .with_ty_store_mut(|s| s.make_struct("s", &[Type::I8], false));
+        let other = build_module("foo", Linkage::Public, 0, None);
+        other
+            .ctx
+            .with_ty_store_mut(|s| s.make_struct("s", &[Type::I16], false));
+
+        assert_eq!(
+            module.link(other),
+            Err(LinkError::ConflictingStruct("s".to_string()))
+        );
+    }
+}
@@ -1,13 +1,23 @@
 use std::{
+    cell::RefCell,
     fmt,
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock, Weak,
+    },
 };
 
 use cranelift_entity::{entity_impl, PrimaryMap};
+use rustc_hash::{FxHashMap, FxHashSet};
 
-use crate::Function;
+use crate::{linker::SymbolMap, CallGraph, Function, InsnData};
 
-use crate::{global_variable::GlobalVariableStore, isa::TargetIsa, types::TypeStore};
+use crate::{
+    global_variable::GlobalVariableStore,
+    isa::{Endian, TargetIsa},
+    types::{CompoundType, TypeStore},
+    TypeLayout,
+};
 
 use super::Linkage;
 
@@ -33,28 +43,152 @@ impl Module {
         self.funcs.keys()
     }
 
+    /// Calls `f` on each function in the module in the order of `FuncRef`, so the result of a
+    /// pass applied by this is deterministic.
+    pub fn for_each_function(&mut self, mut f: impl FnMut(FuncRef, &mut Function)) {
+        for (func_ref, func) in self.funcs.iter_mut() {
+            f(func_ref, func);
+        }
+    }
+
+    /// Similar to [`Self::for_each_function`], but stops at the first function for which `f`
+    /// fails, and returns the error. The functions after it are left untouched.
+    pub fn try_for_each_function<E>(
+        &mut self,
+        mut f: impl FnMut(FuncRef, &mut Function) -> Result<(), E>,
+    ) -> Result<(), E> {
+        for (func_ref, func) in self.funcs.iter_mut() {
+            f(func_ref, func)?;
+        }
+        Ok(())
+    }
+
+    /// Removes `func_ref` from the module, and returns the removed function.
+    /// See [`Self::remove_functions`].
+    pub fn remove_function(&mut self, func_ref: FuncRef) -> Function {
+        self.remove_functions(&[func_ref]).pop().unwrap()
+    }
+
+    /// Removes `func_refs` from the module, and returns the removed functions in the order of
+    /// `FuncRef`.
+    ///
+    /// The remaining functions are renumbered densely in their order, and the callees of them
+    /// are rewritten accordingly. So `FuncRef`s obtained before the removal are invalidated.
+    /// Calls detached from the layout, e.g., the ones replaced by the inliner, are not rewritten
+    /// if they refer to a removed function.
+    ///
+    /// # Panics
+    /// Panics if a removed function is still called from the layout of a remaining function.
+    pub fn remove_functions(&mut self, func_refs: &[FuncRef]) -> Vec<Function> {
+        let removed: FxHashSet<_> = func_refs.iter().copied().collect();
+        for (func_ref, func) in self.funcs.iter() {
+            if removed.contains(&func_ref) {
+                continue;
+            }
+            let callee = func
+                .layout
+                .iter_block()
+                .flat_map(|block| func.layout.iter_insn(block))
+                .find_map(|insn| match func.dfg.insn_data(insn) {
+                    InsnData::Call { func, .. } if removed.contains(func) => Some(*func),
+                    _ => None,
+                });
+            if let Some(callee) = callee {
+                panic!(
+                    "`%{}` is still called by `%{}`",
+                    self.funcs[callee].sig.name(),
+                    func.sig.name()
+                );
+            }
+        }
+
+        let mut funcs = PrimaryMap::with_capacity(self.funcs.len() - removed.len());
+        let mut removed_funcs = Vec::with_capacity(removed.len());
+        let mut renumbered = FxHashMap::default();
+        for (func_ref, func) in std::mem::take(&mut self.funcs) {
+            if removed.contains(&func_ref) {
+                removed_funcs.push(func);
+            } else {
+                renumbered.insert(func_ref, funcs.push(func));
+            }
+        }
+
+        let map = SymbolMap::with_funcs(renumbered);
+        for func in funcs.values_mut() {
+            map.relocate(func, self);
+        }
+        self.funcs = funcs;
+
+        removed_funcs
+    }
+
     /// Returns `true` if the function has external linkage.
     pub fn is_external(&self, func_ref: FuncRef) -> bool {
         self.funcs[func_ref].sig.linkage() == Linkage::External
     }
+
+    /// Returns all functions in the module in reverse call graph order, i.e., callees are
+    /// placed before their callers.
+    /// Functions in the same strongly connected component (mutually recursive functions) are
+    /// placed contiguously.
+    pub fn topo_order(&self) -> Vec<FuncRef> {
+        CallGraph::build(self).reverse_topo_order().collect()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ModuleCtx {
     pub isa: TargetIsa,
     type_store: Arc<RwLock<TypeStore>>,
+    type_store_id: u64,
     gv_store: Arc<RwLock<GlobalVariableStore>>,
 }
 
+thread_local! {
+    /// Layouts of compound types computed by [`TypeLayout::of`], keyed by the id of the type
+    /// store. Compound types are never modified once made, so the cached layouts never go stale,
+    /// and each thread looks them up without locking.
+    static LAYOUT_CACHE: RefCell<FxHashMap<u64, LayoutCache>> = RefCell::default();
+}
+
+struct LayoutCache {
+    /// The type store the layouts belong to. The cache is pruned once the store is dropped.
+    type_store: Weak<RwLock<TypeStore>>,
+    layouts: FxHashMap<CompoundType, TypeLayout>,
+}
+
 impl ModuleCtx {
     pub fn new(isa: TargetIsa) -> Self {
+        Self::with_stores(isa, TypeStore::default(), GlobalVariableStore::default())
+    }
+
+    /// Makes a context holding the given stores, e.g., the ones restored from a serialized
+    /// module.
+    pub(crate) fn with_stores(
+        isa: TargetIsa,
+        type_store: TypeStore,
+        gv_store: GlobalVariableStore,
+    ) -> Self {
+        static NEXT_TYPE_STORE_ID: AtomicU64 = AtomicU64::new(0);
+
         Self {
             isa,
-            type_store: Arc::new(RwLock::new(TypeStore::default())),
-            gv_store: Arc::new(RwLock::new(GlobalVariableStore::default())),
+            type_store: Arc::new(RwLock::new(type_store)),
+            type_store_id: NEXT_TYPE_STORE_ID.fetch_add(1, Ordering::Relaxed),
+            gv_store: Arc::new(RwLock::new(gv_store)),
         }
     }
 
+    /// Returns the id that uniquely identifies the type store of the context in the process.
+    /// Clones of a context share the same id.
+    pub fn type_store_id(&self) -> u64 {
+        self.type_store_id
+    }
+
+    pub fn endian(&self) -> Endian {
+        self.isa.endian()
+    }
+
     pub fn with_ty_store<F, R>(&self, f: F) -> R
     where
         F: FnOnce(&TypeStore) -> R,
@@ -69,6 +203,35 @@ impl ModuleCtx {
         f(&mut self.type_store.write().unwrap())
     }
 
+    pub(crate) fn cached_layout(&self, cmpd_ty: CompoundType) -> Option<TypeLayout> {
+        LAYOUT_CACHE.with(|cache| {
+            let cache = cache.borrow();
+            cache
+                .get(&self.type_store_id)?
+                .layouts
+                .get(&cmpd_ty)
+                .copied()
+        })
+    }
+
+    /// Caches `layout` in the cache of the current thread.
+    /// The layouts of dropped type stores are released here, so the cache doesn't grow with
+    /// contexts that are no longer alive.
+    pub(crate) fn cache_layout(&self, cmpd_ty: CompoundType, layout: TypeLayout) {
+        LAYOUT_CACHE.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            cache.retain(|_, cache| cache.type_store.strong_count() > 0);
+            cache
+                .entry(self.type_store_id)
+                .or_insert_with(|| LayoutCache {
+                    type_store: Arc::downgrade(&self.type_store),
+                    layouts: FxHashMap::default(),
+                })
+                .layouts
+                .insert(cmpd_ty, layout);
+        });
+    }
+
     pub fn with_gv_store<F, R>(&self, f: F) -> R
     where
         F: FnOnce(&GlobalVariableStore) -> R,
@@ -85,6 +248,7 @@ impl ModuleCtx {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FuncRef(u32);
 entity_impl!(FuncRef);
 
@@ -106,3 +270,139 @@ impl<'a> fmt::Display for DisplayCalleeFuncRef<'a> {
         write!(f, "{}", sig.name())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        builder::{test_util::build_test_isa, ModuleBuilder},
+        func_cursor::InsnInserter,
+        Signature, Type,
+    };
+
+    #[test]
+    fn topo_order_chain() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+
+        let main = mb.declare_function(Signature::new("main", Linkage::Public, &[], Type::Void));
+        let mid = mb.declare_function(Signature::new("mid", Linkage::Private, &[], Type::Void));
+        let leaf = mb.declare_function(Signature::new("leaf", Linkage::Private, &[], Type::Void));
+
+        let mut builder = mb.build_function::<InsnInserter>(main);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        builder.call(mid, &[]);
+        builder.ret(&[]);
+        builder.seal_all();
+        let mb = builder.finish();
+
+        let mut builder = mb.build_function::<InsnInserter>(mid);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        builder.call(leaf, &[]);
+        builder.ret(&[]);
+        builder.seal_all();
+        let mb = builder.finish();
+
+        let mut builder = mb.build_function::<InsnInserter>(leaf);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        builder.ret(&[]);
+        builder.seal_all();
+        let module = builder.finish().build();
+
+        assert_eq!(module.topo_order(), vec![leaf, mid, main]);
+    }
+
+    #[test]
+    fn for_each_function_order() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let funcs: Vec<_> = ["f0", "f1", "f2"]
+            .into_iter()
+            .map(|name| mb.declare_function(Signature::new(name, Linkage::Public, &[], Type::Void)))
+            .collect();
+        let mut module = mb.build();
+
+        let mut visited = vec![];
+        module.for_each_function(|func_ref, _| visited.push(func_ref));
+        assert_eq!(visited, funcs);
+
+        let mut visited = vec![];
+        let result = module.try_for_each_function(|func_ref, func| {
+            if func.sig.name() == "f1" {
+                return Err(func_ref);
+            }
+            visited.push(func_ref);
+            Ok(())
+        });
+        assert_eq!(result, Err(funcs[1]));
+        assert_eq!(visited, vec![funcs[0]]);
+    }
+
+    #[test]
+    fn remove_function_renumbers_callees() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+
+        let dead = mb.declare_function(Signature::new("dead", Linkage::Private, &[], Type::Void));
+        let leaf = mb.declare_function(Signature::new("leaf", Linkage::Private, &[], Type::Void));
+        let main = mb.declare_function(Signature::new("main", Linkage::Public, &[], Type::Void));
+
+        let mut builder = mb.build_function::<InsnInserter>(main);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        builder.call(leaf, &[]);
+        builder.ret(&[]);
+        builder.seal_all();
+        let mut module = builder.finish().build();
+
+        let removed = module.remove_function(dead);
+        assert_eq!(removed.sig.name(), "dead");
+        assert_eq!(module.funcs.len(), 2);
+
+        // `leaf` and `main` are shifted down by one.
+        let [leaf, main] = [dead, leaf];
+        assert_eq!(module.funcs[leaf].sig.name(), "leaf");
+        assert_eq!(module.funcs[main].sig.name(), "main");
+        assert!(module.funcs[main].callees.contains_key(&leaf));
+        assert_eq!(module.topo_order(), vec![leaf, main]);
+    }
+
+    #[test]
+    #[should_panic(expected = "still called")]
+    fn remove_called_function() {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+
+        let leaf = mb.declare_function(Signature::new("leaf", Linkage::Private, &[], Type::Void));
+        let main = mb.declare_function(Signature::new("main", Linkage::Public, &[], Type::Void));
+
+        let mut builder = mb.build_function::<InsnInserter>(main);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+        builder.call(leaf, &[]);
+        builder.ret(&[]);
+        builder.seal_all();
+        let mut module = builder.finish().build();
+
+        module.remove_function(leaf);
+    }
+
+    #[test]
+    fn layout_cache_releases_dropped_stores() {
+        let cached_stores = || LAYOUT_CACHE.with(|cache| cache.borrow().len());
+        let layout_of_array = |ctx: &ModuleCtx| {
+            let ty = ctx.with_ty_store_mut(|s| s.make_array(Type::I8, 4));
+            TypeLayout::of(ty, ctx)
+        };
+
+        let ctx = ModuleCtx::new(build_test_isa());
+        layout_of_array(&ctx);
+        assert_eq!(cached_stores(), 1);
+        drop(ctx);
+
+        // The layouts of the dropped context are released when another context caches a layout.
+        let ctx = ModuleCtx::new(build_test_isa());
+        layout_of_array(&ctx);
+        assert_eq!(cached_stores(), 1);
+    }
+}
@@ -0,0 +1,264 @@
+//! This module contains the binary serialization of [`Module`].
+//!
+//! A serialized module starts with a [`ModuleHeader`], followed by the type store, the global
+//! variable store and the functions of the module.
+use std::fmt;
+
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+use sonatina_triple::{InvalidTriple, TargetTriple};
+
+use crate::{
+    attributes::AttrMap,
+    dfg::DfgParts,
+    global_variable::GlobalVariableStore,
+    isa::registry::isa_for_triple,
+    module::{FuncRef, ModuleCtx},
+    types::TypeStore,
+    DataFlowGraph, Function, Layout, Module, Signature, Value,
+};
+
+/// The header of a serialized module.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleHeader {
+    /// The version of the serialization format.
+    pub version: u32,
+
+    /// The target triple of the module, which is used to reconstruct the ISA on load.
+    pub triple: String,
+}
+
+impl ModuleHeader {
+    /// The current version of the serialization format. This is bumped whenever the
+    /// serialized form of the IR changes.
+    pub const VERSION: u32 = 3;
+
+    pub fn new(triple: &TargetTriple) -> Self {
+        Self {
+            version: Self::VERSION,
+            triple: triple.to_string(),
+        }
+    }
+}
+
+/// An error returned from [`Module::to_bytes`] and [`Module::from_bytes`].
+#[derive(Debug)]
+pub enum SerializeError {
+    /// The module couldn't be encoded or decoded.
+    Bincode(bincode::Error),
+
+    /// The version of the serialized module is not supported.
+    UnsupportedVersion(u32),
+
+    /// The target triple in the header is invalid.
+    InvalidTriple(InvalidTriple),
+
+    /// No ISA is registered for the target triple in the header.
+    UnsupportedTarget(TargetTriple),
+}
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bincode(err) => write!(f, "{err}"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported format version {version}, expected {}",
+                ModuleHeader::VERSION
+            ),
+            Self::InvalidTriple(err) => write!(f, "{err}"),
+            Self::UnsupportedTarget(triple) => write!(f, "no ISA is registered for `{triple}`"),
+        }
+    }
+}
+
+impl std::error::Error for SerializeError {}
+
+impl From<bincode::Error> for SerializeError {
+    fn from(err: bincode::Error) -> Self {
+        Self::Bincode(err)
+    }
+}
+
+/// The serialized form of [`Function`].
+#[derive(Serialize, Deserialize)]
+struct FunctionParts {
+    sig: Signature,
+    arg_values: SmallVec<[Value; 8]>,
+    dfg: DfgParts,
+    layout: Layout,
+    callees: FxHashMap<FuncRef, Signature>,
+    attributes: AttrMap,
+}
+
+impl Module {
+    /// Serializes the module into a binary format that can be loaded by [`Module::from_bytes`].
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SerializeError> {
+        let mut bytes = Vec::new();
+        bincode::serialize_into(&mut bytes, &ModuleHeader::new(self.ctx.isa.triple()))?;
+        self.ctx
+            .with_ty_store(|s| bincode::serialize_into(&mut bytes, s))?;
+        self.ctx
+            .with_gv_store(|s| bincode::serialize_into(&mut bytes, s))?;
+
+        let funcs: Vec<_> = self
+            .funcs
+            .values()
+            .map(|func| FunctionParts {
+                sig: func.sig.clone(),
+                arg_values: func.arg_values.clone(),
+                dfg: func.dfg.to_parts(),
+                layout: func.layout.clone(),
+                callees: func.callees.clone(),
+                attributes: func.attributes.clone(),
+            })
+            .collect();
+        bincode::serialize_into(&mut bytes, &funcs)?;
+
+        Ok(bytes)
+    }
+
+    /// Deserializes a module serialized by [`Module::to_bytes`]. The ISA of the module is
+    /// looked up from the target triple in the header with [`isa_for_triple`].
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, SerializeError> {
+        let header: ModuleHeader = bincode::deserialize_from(&mut bytes)?;
+        if header.version != ModuleHeader::VERSION {
+            return Err(SerializeError::UnsupportedVersion(header.version));
+        }
+        let triple = TargetTriple::parse(&header.triple).map_err(SerializeError::InvalidTriple)?;
+        let isa = isa_for_triple(&triple).ok_or(SerializeError::UnsupportedTarget(triple))?;
+
+        let type_store: TypeStore = bincode::deserialize_from(&mut bytes)?;
+        let gv_store: GlobalVariableStore = bincode::deserialize_from(&mut bytes)?;
+        let ctx = ModuleCtx::with_stores(isa, type_store, gv_store);
+
+        let mut module = Module {
+            funcs: Default::default(),
+            ctx,
+        };
+        let funcs: Vec<FunctionParts> = bincode::deserialize_from(&mut bytes)?;
+        for parts in funcs {
+            module.funcs.push(Function {
+                sig: parts.sig,
+                arg_values: parts.arg_values,
+                dfg: DataFlowGraph::from_parts(module.ctx.clone(), parts.dfg),
+                layout: parts.layout,
+                callees: parts.callees,
+                attributes: parts.attributes,
+            });
+        }
+
+        Ok(module)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        attributes::NOINLINE,
+        builder::{test_util::build_test_isa, ModuleBuilder},
+        func_cursor::InsnInserter,
+        global_variable::ConstantValue,
+        insn::BinaryOp,
+        ir_writer::dump_module,
+        AttrValue, DataLocationKind, GlobalVariableData, Linkage, SourceLoc, Type,
+    };
+
+    fn build_module() -> Module {
+        let mut mb = ModuleBuilder::new(ModuleCtx::new(build_test_isa()));
+        let point = mb.declare_struct_type("point", &[Type::I32, Type::I64], false);
+        let ptr = mb.ptr_type(point);
+        let gv = mb.make_global(GlobalVariableData::constant(
+            "ZERO".to_string(),
+            Type::I32,
+            Linkage::Private,
+            ConstantValue::make_imm(0),
+        ));
+
+        let callee = mb.declare_function(Signature::new(
+            "callee",
+            Linkage::External,
+            &[ptr],
+            Type::I32,
+        ));
+        let func_ref = mb.declare_function(Signature::new(
+            "main",
+            Linkage::Public,
+            &[Type::I1, ptr],
+            Type::I32,
+        ));
+
+        let mut builder = mb.build_function::<InsnInserter>(func_ref);
+        let [cond, p] = [builder.args()[0], builder.args()[1]];
+        let b0 = builder.append_block();
+        let b1 = builder.append_block();
+        let b2 = builder.append_block();
+
+        builder.switch_to_block(b0);
+        let zero_ptr = builder.make_global_value(gv);
+        let zero = builder.load(DataLocationKind::Memory, zero_ptr);
+        builder.br(cond, b1, b2);
+
+        builder.switch_to_block(b1);
+        let called = builder.call(callee, &[p]).unwrap();
+        let imm = builder.make_imm_value(-1i32);
+        let sum = builder.binary_op(BinaryOp::Add, called, imm);
+        builder.jump(b2);
+
+        builder.switch_to_block(b2);
+        let phi = builder.phi(Type::I32, &[(zero, b0), (sum, b1)]);
+        builder.ret(&[phi]);
+
+        builder.seal_all();
+        let ret = builder.func.layout.last_insn_of(b2).unwrap();
+        builder
+            .func
+            .dfg
+            .set_insn_source_loc(ret, Some(SourceLoc::new("main.fe", 7, 1)));
+        builder.func.set_attr(NOINLINE, AttrValue::Flag);
+        builder.finish().build()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let module = build_module();
+        let bytes = module.to_bytes().unwrap();
+        let restored = Module::from_bytes(&bytes).unwrap();
+
+        assert_eq!(restored.ctx.isa.triple(), module.ctx.isa.triple());
+        assert_eq!(dump_module(&restored), dump_module(&module));
+
+        let (func, restored_func) = module
+            .funcs
+            .values()
+            .zip(restored.funcs.values())
+            .next_back()
+            .unwrap();
+        assert_eq!(restored_func.attributes, func.attributes);
+        let ret = func
+            .layout
+            .last_insn_of(func.layout.last_block().unwrap())
+            .unwrap();
+        assert_eq!(
+            restored_func.dfg.insn_source_loc(ret),
+            func.dfg.insn_source_loc(ret)
+        );
+        assert!(func.dfg.insn_source_loc(ret).is_some());
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let module = build_module();
+        let mut bytes = module.to_bytes().unwrap();
+        // The version is the first field of the header.
+        bytes[..4].copy_from_slice(&(ModuleHeader::VERSION + 1).to_le_bytes());
+
+        assert!(matches!(
+            Module::from_bytes(&bytes),
+            Err(SerializeError::UnsupportedVersion(version)) if version == ModuleHeader::VERSION + 1
+        ));
+    }
+}
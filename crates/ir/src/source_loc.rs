@@ -0,0 +1,29 @@
+//! This module contains source locations, which map insns back to the source program.
+use std::fmt;
+
+/// A position in a source file, which is displayed as `file:line:col`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceLoc {
+    pub file: String,
+    /// 1-based line number.
+    pub line: u32,
+    /// 1-based column number.
+    pub col: u32,
+}
+
+impl SourceLoc {
+    pub fn new(file: impl Into<String>, line: u32, col: u32) -> Self {
+        Self {
+            file: file.into(),
+            line,
+            col,
+        }
+    }
+}
+
+impl fmt::Display for SourceLoc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.col)
+    }
+}
@@ -0,0 +1,433 @@
+//! This module contains the memory layout of types, i.e., sizes, alignments and field offsets.
+use std::fmt;
+
+use crate::{module::ModuleCtx, types::CompoundTypeData, Type};
+
+/// The size and the alignment of a type in the target memory, both in bytes.
+///
+/// Fields of a non-packed struct are placed at their natural alignment, and the size of the
+/// struct is rounded up to a multiple of its alignment. Fields of a packed struct are placed
+/// contiguously, and the alignment of the struct is `1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TypeLayout {
+    pub size: usize,
+    pub align: usize,
+}
+
+impl TypeLayout {
+    pub fn of(ty: Type, ctx: &ModuleCtx) -> Self {
+        match ty {
+            Type::I1 | Type::I8 => Self::scalar(1),
+            Type::I16 => Self::scalar(2),
+            Type::I32 => Self::scalar(4),
+            Type::I64 => Self::scalar(8),
+            Type::I128 => Self::scalar(16),
+            Type::I256 => Self::scalar(32),
+            // Arbitrary width integers are stored in the smallest power-of-two bytes that holds
+            // them, e.g., `i24` in 4 bytes.
+            Type::Int(width) => {
+                Self::scalar((width.bits() as usize).div_ceil(8).next_power_of_two())
+            }
+            Type::Void => Self { size: 0, align: 1 },
+            Type::Compound(cmpd_ty) => {
+                if let Some(layout) = ctx.cached_layout(cmpd_ty) {
+                    return layout;
+                }
+
+                // Release the lock before computing layouts of the component types.
+                let cmpd_ty_data = ctx.with_ty_store(|s| s.resolve_compound(cmpd_ty).clone());
+                let layout = match cmpd_ty_data {
+                    CompoundTypeData::Array { elem, len } => {
+                        let elem = Self::of(elem, ctx);
+                        Self {
+                            size: elem.size * len,
+                            align: elem.align,
+                        }
+                    }
+                    CompoundTypeData::Ptr(_) => {
+                        Self::of(ctx.isa.type_provider().pointer_type(), ctx)
+                    }
+                    CompoundTypeData::Struct(data) => {
+                        let (size, align) = Self::struct_fields(&data.fields, data.packed, ctx)
+                            .fold((0, 1), |(_, align), (offset, field)| {
+                                (offset + field.size, align.max(field.align))
+                            });
+                        if data.packed {
+                            Self { size, align: 1 }
+                        } else {
+                            Self {
+                                size: size.next_multiple_of(align),
+                                align,
+                            }
+                        }
+                    }
+                    CompoundTypeData::Union(variants) => {
+                        let (size, align) = variants.iter().fold((0, 1), |(size, align), ty| {
+                            let variant = Self::of(*ty, ctx);
+                            (size.max(variant.size), align.max(variant.align))
+                        });
+                        Self {
+                            size: size.next_multiple_of(align),
+                            align,
+                        }
+                    }
+                };
+                ctx.cache_layout(cmpd_ty, layout);
+                layout
+            }
+        }
+    }
+
+    /// Returns the byte offset of the `field_idx`-th field from the start of the struct `ty`.
+    pub fn field_offset(
+        ty: Type,
+        field_idx: usize,
+        ctx: &ModuleCtx,
+    ) -> Result<usize, TypeLayoutError> {
+        let (fields, packed) = ctx
+            .with_ty_store(|s| {
+                s.struct_def(ty)
+                    .map(|data| (data.fields.clone(), data.packed))
+            })
+            .ok_or(TypeLayoutError::NotStruct(ty))?;
+
+        let offset = Self::struct_fields(&fields, packed, ctx)
+            .nth(field_idx)
+            .map(|(offset, _)| offset)
+            .ok_or(TypeLayoutError::FieldOutOfBounds {
+                field_idx,
+                num_fields: fields.len(),
+            });
+        offset
+    }
+
+    /// Returns the layouts of the fields of the struct `ty` in the order of the fields.
+    ///
+    /// The padding after each field is the gap to the next field, and the padding after the
+    /// last field is the tail padding to the size of the struct. Fields of a packed struct have
+    /// no padding.
+    pub fn struct_layout(ty: Type, ctx: &ModuleCtx) -> Result<Vec<FieldLayout>, TypeLayoutError> {
+        let (fields, packed) = ctx
+            .with_ty_store(|s| {
+                s.struct_def(ty)
+                    .map(|data| (data.fields.clone(), data.packed))
+            })
+            .ok_or(TypeLayoutError::NotStruct(ty))?;
+
+        let struct_size = Self::of(ty, ctx).size;
+        let mut layouts: Vec<_> = Self::struct_fields(&fields, packed, ctx)
+            .map(|(offset, field)| FieldLayout {
+                offset,
+                size: field.size,
+                padding_after: 0,
+            })
+            .collect();
+
+        let mut next_offset = struct_size;
+        for layout in layouts.iter_mut().rev() {
+            layout.padding_after = next_offset - (layout.offset + layout.size);
+            next_offset = layout.offset;
+        }
+        Ok(layouts)
+    }
+
+    fn scalar(size: usize) -> Self {
+        Self { size, align: size }
+    }
+
+    /// Returns an iterator over the offsets and the layouts of the struct fields.
+    fn struct_fields<'a>(
+        fields: &'a [Type],
+        packed: bool,
+        ctx: &'a ModuleCtx,
+    ) -> impl Iterator<Item = (usize, Self)> + 'a {
+        let mut next_offset: usize = 0;
+        fields.iter().map(move |ty| {
+            let layout = Self::of(*ty, ctx);
+            let offset = if packed {
+                next_offset
+            } else {
+                next_offset.next_multiple_of(layout.align)
+            };
+            next_offset = offset + layout.size;
+            (offset, layout)
+        })
+    }
+}
+
+/// The placement of a struct field, all in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FieldLayout {
+    /// The offset from the start of the struct.
+    pub offset: usize,
+    pub size: usize,
+    /// The padding between the end of the field and the start of the next field, or the end of
+    /// the struct for the last field.
+    pub padding_after: usize,
+}
+
+/// An error returned from [`TypeLayout`] queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeLayoutError {
+    /// The type is not a struct type.
+    NotStruct(Type),
+
+    /// The field index is out of bounds of the struct fields.
+    FieldOutOfBounds { field_idx: usize, num_fields: usize },
+}
+
+impl fmt::Display for TypeLayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotStruct(ty) => write!(f, "`{ty:?}` is not a struct type"),
+            Self::FieldOutOfBounds {
+                field_idx,
+                num_fields,
+            } => write!(
+                f,
+                "field index {field_idx} is out of bounds of a struct with {num_fields} fields"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TypeLayoutError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::builder::test_util::build_test_isa;
+
+    #[test]
+    fn struct_field_offsets() {
+        let ctx = ModuleCtx::new(build_test_isa());
+        let fields = [Type::I8, Type::I64, Type::I8];
+        let unpacked = ctx.with_ty_store_mut(|s| s.make_struct("unpacked", &fields, false));
+        let packed = ctx.with_ty_store_mut(|s| s.make_struct("packed", &fields, true));
+
+        let offsets = |ty| {
+            (0..fields.len())
+                .map(|idx| TypeLayout::field_offset(ty, idx, &ctx).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        ctx.with_ty_store(|s| assert_eq!(s.struct_fields(packed), Some(fields.as_slice())));
+
+        assert_eq!(offsets(unpacked), vec![0, 8, 16]);
+        assert_eq!(
+            TypeLayout::of(unpacked, &ctx),
+            TypeLayout { size: 24, align: 8 }
+        );
+
+        assert_eq!(offsets(packed), vec![0, 1, 9]);
+        assert_eq!(
+            TypeLayout::of(packed, &ctx),
+            TypeLayout { size: 10, align: 1 }
+        );
+    }
+
+    #[test]
+    fn struct_layout() {
+        let ctx = ModuleCtx::new(build_test_isa());
+        let fields = [Type::I8, Type::I64, Type::I8];
+        let unpacked = ctx.with_ty_store_mut(|s| s.make_struct("unpacked", &fields, false));
+        let packed = ctx.with_ty_store_mut(|s| s.make_struct("packed", &fields, true));
+
+        let field = |offset, size, padding_after| FieldLayout {
+            offset,
+            size,
+            padding_after,
+        };
+        assert_eq!(
+            TypeLayout::struct_layout(unpacked, &ctx),
+            Ok(vec![field(0, 1, 7), field(8, 8, 0), field(16, 1, 7)])
+        );
+        assert_eq!(
+            TypeLayout::struct_layout(packed, &ctx),
+            Ok(vec![field(0, 1, 0), field(1, 8, 0), field(9, 1, 0)])
+        );
+        assert_eq!(
+            TypeLayout::struct_layout(Type::I64, &ctx),
+            Err(TypeLayoutError::NotStruct(Type::I64))
+        );
+    }
+
+    #[test]
+    fn arbitrary_width_int() {
+        let ctx = ModuleCtx::new(build_test_isa());
+        let layout = |bits| TypeLayout::of(Type::int(bits), &ctx);
+
+        assert_eq!(layout(24), TypeLayout { size: 4, align: 4 });
+        assert_eq!(layout(7), TypeLayout { size: 1, align: 1 });
+        assert_eq!(
+            layout(72),
+            TypeLayout {
+                size: 16,
+                align: 16
+            }
+        );
+
+        let st = ctx.with_ty_store_mut(|s| s.make_struct("s", &[Type::I8, Type::int(24)], false));
+        assert_eq!(TypeLayout::field_offset(st, 1, &ctx), Ok(4));
+    }
+
+    #[test]
+    fn nested_struct_field_offsets() {
+        let ctx = ModuleCtx::new(build_test_isa());
+        let (inner, outer) = ctx.with_ty_store_mut(|s| {
+            let inner = s.make_struct("inner", &[Type::I16, Type::I8], false);
+            let arr = s.make_array(inner, 3);
+            let outer = s.make_struct("outer", &[Type::I8, arr, Type::I32], false);
+            (inner, outer)
+        });
+
+        assert_eq!(
+            TypeLayout::of(inner, &ctx),
+            TypeLayout { size: 4, align: 2 }
+        );
+        assert_eq!(TypeLayout::field_offset(outer, 1, &ctx), Ok(2));
+        assert_eq!(TypeLayout::field_offset(outer, 2, &ctx), Ok(16));
+        assert_eq!(
+            TypeLayout::of(outer, &ctx),
+            TypeLayout { size: 20, align: 4 }
+        );
+    }
+
+    #[test]
+    fn field_offset_errors() {
+        let ctx = ModuleCtx::new(build_test_isa());
+        let st = ctx.with_ty_store_mut(|s| s.make_struct("s", &[Type::I32], false));
+
+        assert_eq!(
+            TypeLayout::field_offset(Type::I32, 0, &ctx),
+            Err(TypeLayoutError::NotStruct(Type::I32))
+        );
+        assert_eq!(
+            TypeLayout::field_offset(st, 1, &ctx),
+            Err(TypeLayoutError::FieldOutOfBounds {
+                field_idx: 1,
+                num_fields: 1
+            })
+        );
+    }
+
+    fn make_types(ctx: &ModuleCtx) -> Vec<Type> {
+        ctx.with_ty_store_mut(|s| {
+            let arr = s.make_array(Type::I8, 8);
+            let ptr = s.make_ptr(arr);
+            let st = s.make_struct("s", &[Type::I32, arr, ptr], false);
+            let packed = s.make_struct("p", &[Type::I8, Type::I64], true);
+            let union = s.make_union(&[Type::I32, arr, st]);
+            vec![arr, ptr, st, packed, union]
+        })
+    }
+
+    #[test]
+    fn layout_cache_survives_type_store_mutation() {
+        let ctx = ModuleCtx::new(build_test_isa());
+        let tys = make_types(&ctx);
+        let layouts: Vec<_> = tys.iter().map(|ty| TypeLayout::of(*ty, &ctx)).collect();
+
+        // Making new types never changes the existing ones, so the cached layouts are kept.
+        ctx.with_ty_store_mut(|s| {
+            s.make_array(Type::I64, 2);
+            s.make_struct("s2", &[Type::I8], true);
+        });
+        for (ty, layout) in tys.iter().zip(&layouts) {
+            let Type::Compound(cmpd_ty) = ty else {
+                unreachable!()
+            };
+            assert_eq!(ctx.cached_layout(*cmpd_ty), Some(*layout));
+        }
+    }
+
+    #[test]
+    fn layout_cache_is_per_context() {
+        // The same compound type refers to different types in different modules.
+        let ctx1 = ModuleCtx::new(build_test_isa());
+        let ctx2 = ModuleCtx::new(build_test_isa());
+        let ty1 = ctx1.with_ty_store_mut(|s| s.make_array(Type::I8, 4));
+        let ty2 = ctx2.with_ty_store_mut(|s| s.make_array(Type::I32, 4));
+        assert_eq!(ty1, ty2);
+
+        assert_eq!(TypeLayout::of(ty1, &ctx1).size, 4);
+        assert_eq!(TypeLayout::of(ty2, &ctx2).size, 16);
+    }
+
+    #[test]
+    fn concurrent_layout_queries() {
+        let ctx = ModuleCtx::new(build_test_isa());
+        let tys = make_types(&ctx);
+        let expected: Vec<_> = {
+            // Compute the layouts from a fresh context without the cache warmed up.
+            let ctx = ModuleCtx::new(build_test_isa());
+            make_types(&ctx)
+                .iter()
+                .map(|ty| TypeLayout::of(*ty, &ctx))
+                .collect()
+        };
+
+        // Queries race with each other and with type store mutations.
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let (ctx, tys, expected) = (&ctx, &tys, &expected);
+                scope.spawn(move || {
+                    for j in 0..1000 {
+                        if i == 0 && j % 100 == 0 {
+                            ctx.with_ty_store_mut(|s| s.make_array(Type::I8, j));
+                        }
+                        for (ty, expected) in tys.iter().zip(expected) {
+                            assert_eq!(TypeLayout::of(*ty, ctx), *expected);
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with `cargo test -- --ignored`"]
+    fn bench_cached_layout_queries() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = 200;
+
+        // Runs `ROUNDS` rounds of layout queries on each thread, and returns the elapsed time.
+        // `fresh_ctx` makes every round start from a cold cache.
+        let run = |fresh_ctx: bool| {
+            let ctx = ModuleCtx::new(build_test_isa());
+            let tys = make_types(&ctx);
+            let start = std::time::Instant::now();
+            let layouts: Vec<Vec<_>> = std::thread::scope(|scope| {
+                let handles: Vec<_> = (0..THREADS)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            let mut layouts = Vec::new();
+                            for _ in 0..ROUNDS {
+                                let ctx = if fresh_ctx {
+                                    let ctx = ModuleCtx::new(build_test_isa());
+                                    make_types(&ctx);
+                                    ctx
+                                } else {
+                                    ctx.clone()
+                                };
+                                layouts = tys.iter().map(|ty| TypeLayout::of(*ty, &ctx)).collect();
+                            }
+                            layouts
+                        })
+                    })
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            });
+            (start.elapsed(), layouts)
+        };
+
+        let (uncached_time, uncached) = run(true);
+        let (cached_time, cached) = run(false);
+        assert_eq!(cached, uncached);
+        assert!(
+            cached_time < uncached_time,
+            "cached queries took {cached_time:?}, uncached ones took {uncached_time:?}"
+        );
+    }
+}
@@ -5,9 +5,10 @@ use cranelift_entity::PrimaryMap;
 use indexmap::IndexMap;
 use rustc_hash::FxHashMap;
 
-use crate::DataFlowGraph;
+use crate::{module::ModuleCtx, DataFlowGraph};
 
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TypeStore {
     compounds: PrimaryMap<CompoundType, CompoundTypeData>,
     rev_types: FxHashMap<CompoundTypeData, CompoundType>,
@@ -40,6 +41,14 @@ impl TypeStore {
         Type::Compound(compound)
     }
 
+    /// Makes an untagged union type of the given variants.
+    /// All variants share the same storage at offset zero.
+    pub fn make_union(&mut self, variants: &[Type]) -> Type {
+        debug_assert!(!variants.is_empty(), "union must have at least one variant");
+        let ty = self.make_compound(CompoundTypeData::Union(variants.to_vec()));
+        Type::Compound(ty)
+    }
+
     /// Returns `[StructDef]` if the given type is a struct type.
     pub fn struct_def(&self, ty: Type) -> Option<&StructData> {
         match ty {
@@ -51,6 +60,11 @@ impl TypeStore {
         }
     }
 
+    /// Returns the field types if the given type is a struct type.
+    pub fn struct_fields(&self, ty: Type) -> Option<&[Type]> {
+        self.struct_def(ty).map(|def| def.fields.as_slice())
+    }
+
     pub fn array_def(&self, ty: Type) -> Option<(Type, usize)> {
         match ty {
             Type::Compound(compound) => match self.compounds[compound] {
@@ -61,6 +75,17 @@ impl TypeStore {
         }
     }
 
+    /// Returns the variants if the given type is a union type.
+    pub fn union_def(&self, ty: Type) -> Option<&[Type]> {
+        match ty {
+            Type::Compound(compound) => match self.compounds[compound] {
+                CompoundTypeData::Union(ref variants) => Some(variants),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     pub fn struct_type_by_name(&self, name: &str) -> Option<Type> {
         self.struct_types.get(name).map(|ty| Type::Compound(*ty))
     }
@@ -105,6 +130,85 @@ impl TypeStore {
         }
     }
 
+    pub fn is_union(&self, ty: Type) -> bool {
+        match ty {
+            Type::Compound(compound) => self.compounds[compound].is_union(),
+            _ => false,
+        }
+    }
+
+    /// Returns the canonical textual form of `ty`, e.g., `*i32`, `[i32; 8]` or `%point`.
+    /// Struct types are printed by name, so a struct type referring to itself through a pointer
+    /// is printed without recursion.
+    pub fn display(&self, ty: Type) -> String {
+        let mut s = String::new();
+        self.write_ty(ty, &mut s).unwrap();
+        s
+    }
+
+    /// Returns the canonical textual form of a function type, e.g., `(i32, i64) -> i1`.
+    /// The return types are printed as `void` if empty, and parenthesized if there are several.
+    pub fn display_func_type(&self, args: &[Type], ret_tys: &[Type]) -> String {
+        let mut s = String::new();
+        self.write_ty_list(args, ('(', ')'), &mut s).unwrap();
+        s.push_str(" -> ");
+        match ret_tys {
+            [] => s.push_str("void"),
+            [ty] => self.write_ty(*ty, &mut s).unwrap(),
+            tys => self.write_ty_list(tys, ('(', ')'), &mut s).unwrap(),
+        }
+        s
+    }
+
+    fn write_ty(&self, ty: Type, w: &mut impl fmt::Write) -> fmt::Result {
+        let compound = match ty {
+            Type::I1 => return write!(w, "i1"),
+            Type::I8 => return write!(w, "i8"),
+            Type::I16 => return write!(w, "i16"),
+            Type::I32 => return write!(w, "i32"),
+            Type::I64 => return write!(w, "i64"),
+            Type::I128 => return write!(w, "i128"),
+            Type::I256 => return write!(w, "i256"),
+            Type::Int(width) => return write!(w, "i{}", width.bits()),
+            Type::Void => return write!(w, "void"),
+            Type::Compound(compound) => compound,
+        };
+
+        match &self.compounds[compound] {
+            CompoundTypeData::Array { elem, len } => {
+                write!(w, "[")?;
+                self.write_ty(*elem, w)?;
+                write!(w, "; {len}]")
+            }
+            CompoundTypeData::Ptr(elem) => {
+                write!(w, "*")?;
+                self.write_ty(*elem, w)
+            }
+            CompoundTypeData::Struct(def) => write!(w, "%{}", def.name),
+            CompoundTypeData::Union(variants) => {
+                write!(w, "union")?;
+                self.write_ty_list(variants, ('{', '}'), w)
+            }
+        }
+    }
+
+    /// Writes `tys` as a comma separated list enclosed in `delims`.
+    fn write_ty_list(
+        &self,
+        tys: &[Type],
+        delims: (char, char),
+        w: &mut impl fmt::Write,
+    ) -> fmt::Result {
+        w.write_char(delims.0)?;
+        for (i, ty) in tys.iter().enumerate() {
+            if i != 0 {
+                write!(w, ", ")?;
+            }
+            self.write_ty(*ty, w)?;
+        }
+        w.write_char(delims.1)
+    }
+
     pub fn make_compound(&mut self, data: CompoundTypeData) -> CompoundType {
         if let Some(compound) = self.rev_types.get(&data) {
             *compound
@@ -118,10 +222,17 @@ impl TypeStore {
     pub fn resolve_compound(&self, compound: CompoundType) -> &CompoundTypeData {
         &self.compounds[compound]
     }
+
+    /// Returns all compound types in creation order, i.e., each type comes after its component
+    /// types.
+    pub(crate) fn all_compounds(&self) -> impl Iterator<Item = (CompoundType, &CompoundTypeData)> {
+        self.compounds.iter()
+    }
 }
 
 /// Sonatina IR types definition.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Type {
     I1,
     I8,
@@ -130,46 +241,53 @@ pub enum Type {
     I64,
     I128,
     I256,
+    /// An integer type of a width other than the above, e.g., `i24`. This can only be made by
+    /// [`Type::int`], so that each width has a unique representation.
+    Int(IntWidth),
     Compound(CompoundType),
     #[default]
     Void,
 }
 
-/// An opaque reference to [`CompoundTypeData`].
-#[derive(Debug, Clone, PartialEq, Eq, Copy, Hash, PartialOrd, Ord)]
-pub struct CompoundType(u32);
-cranelift_entity::entity_impl!(CompoundType);
+/// The width of [`Type::Int`], which is never the width of a fixed integer type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "u16", into = "u16")
+)]
+pub struct IntWidth(u16);
+
+impl IntWidth {
+    pub fn bits(self) -> u16 {
+        self.0
+    }
+}
 
-struct DisplayCompoundType<'a> {
-    cmpd_ty: CompoundType,
-    dfg: &'a DataFlowGraph,
+impl TryFrom<u16> for IntWidth {
+    type Error = &'static str;
+
+    fn try_from(bits: u16) -> Result<Self, Self::Error> {
+        match Type::try_int(bits) {
+            Some(Type::Int(width)) => Ok(width),
+            Some(_) => Err("the width of a fixed integer type"),
+            None => Err("unsupported integer width"),
+        }
+    }
 }
 
-impl<'a> fmt::Display for DisplayCompoundType<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use CompoundTypeData::*;
-        let dfg = self.dfg;
-        dfg.ctx
-            .with_ty_store(|s| match s.resolve_compound(self.cmpd_ty) {
-                Array { elem: ty, len } => {
-                    let ty = DisplayType::new(*ty, dfg);
-                    write!(f, "[{ty};{len}]")
-                }
-                Ptr(ty) => {
-                    let ty = DisplayType::new(*ty, dfg);
-                    write!(f, "*{ty}")
-                }
-                Struct(StructData { name, packed, .. }) => {
-                    if *packed {
-                        write!(f, "<{{{name}}}>")
-                    } else {
-                        write!(f, "{{{name}}}")
-                    }
-                }
-            })
+impl From<IntWidth> for u16 {
+    fn from(width: IntWidth) -> Self {
+        width.0
     }
 }
 
+/// An opaque reference to [`CompoundTypeData`].
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompoundType(u32);
+cranelift_entity::entity_impl!(CompoundType);
+
 pub struct DisplayType<'a> {
     ty: Type,
     dfg: &'a DataFlowGraph,
@@ -183,32 +301,21 @@ impl<'a> DisplayType<'a> {
 
 impl<'a> fmt::Display for DisplayType<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use Type::*;
-        match self.ty {
-            I1 => write!(f, "i1"),
-            I8 => write!(f, "i8"),
-            I16 => write!(f, "i16"),
-            I32 => write!(f, "i32"),
-            I64 => write!(f, "i64"),
-            I128 => write!(f, "i128"),
-            I256 => write!(f, "i256"),
-            Compound(cmpd_ty) => {
-                let dfg = self.dfg;
-                write!(f, "{}", DisplayCompoundType { cmpd_ty, dfg })
-            }
-            Void => write!(f, "()"),
-        }
+        self.dfg.ctx.with_ty_store(|s| s.write_ty(self.ty, f))
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CompoundTypeData {
     Array { elem: Type, len: usize },
     Ptr(Type),
     Struct(StructData),
+    Union(Vec<Type>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StructData {
     pub name: String,
     pub fields: Vec<Type>,
@@ -223,14 +330,100 @@ impl CompoundTypeData {
     pub fn is_ptr(&self) -> bool {
         matches!(self, Self::Ptr(_))
     }
+
+    pub fn is_union(&self) -> bool {
+        matches!(self, Self::Union(_))
+    }
 }
 
 impl Type {
+    /// Returns the integer type of `bits` width. The widths of the fixed integer types are mapped
+    /// to them, e.g., `Type::int(32)` is [`Type::I32`].
+    ///
+    /// # Panics
+    /// Panics if `bits` is zero or greater than `256`.
+    pub fn int(bits: u16) -> Self {
+        Self::try_int(bits)
+            .unwrap_or_else(|| panic!("integer type of {bits} bits is not supported"))
+    }
+
+    /// Similar to [`Self::int`], but returns `None` if `bits` is zero or greater than `256`.
+    pub fn try_int(bits: u16) -> Option<Self> {
+        Some(match bits {
+            1 => Self::I1,
+            8 => Self::I8,
+            16 => Self::I16,
+            32 => Self::I32,
+            64 => Self::I64,
+            128 => Self::I128,
+            256 => Self::I256,
+            2..=255 => Self::Int(IntWidth(bits)),
+            _ => return None,
+        })
+    }
+
+    /// Returns the bit width if the type is integral.
+    pub fn bits(&self) -> Option<u16> {
+        match self {
+            Self::I1 => Some(1),
+            Self::I8 => Some(8),
+            Self::I16 => Some(16),
+            Self::I32 => Some(32),
+            Self::I64 => Some(64),
+            Self::I128 => Some(128),
+            Self::I256 => Some(256),
+            Self::Int(width) => Some(width.bits()),
+            Self::Compound(_) | Self::Void => None,
+        }
+    }
+
     pub fn is_integral(&self) -> bool {
-        matches!(
-            self,
-            Self::I1 | Self::I8 | Self::I16 | Self::I32 | Self::I64 | Self::I128 | Self::I256
-        )
+        self.bits().is_some()
+    }
+
+    /// Returns `true` if the type is an integer type. This is the same as
+    /// [`Self::is_integral`].
+    pub fn is_integer(&self) -> bool {
+        self.is_integral()
+    }
+
+    /// Returns `true` if the type is a pointer type.
+    ///
+    /// This locks the type store of `ctx`, so it must not be called inside
+    /// [`ModuleCtx::with_ty_store`]; use [`TypeStore::is_ptr`] there.
+    pub fn is_pointer(&self, ctx: &ModuleCtx) -> bool {
+        ctx.with_ty_store(|s| s.is_ptr(*self))
+    }
+
+    /// Returns `true` if the type is an array, a struct or a union type.
+    pub fn is_aggregate(&self, ctx: &ModuleCtx) -> bool {
+        match self {
+            Self::Compound(compound) => {
+                ctx.with_ty_store(|s| !s.resolve_compound(*compound).is_ptr())
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if signed and unsigned insns can't behave differently on values of the
+    /// type. Integers are sign agnostic, and the insn decides the signedness, e.g., `sext` and
+    /// `zext`, so this is `true` only for non-integral types.
+    pub fn is_signed_context_irrelevant(&self) -> bool {
+        !self.is_integral()
+    }
+
+    /// Returns the bit width of the type. Pointers have the width of the pointer type of the
+    /// target, and `void` and aggregate types have no width.
+    pub fn bit_width(&self, ctx: &ModuleCtx) -> Option<u32> {
+        if let Some(bits) = self.bits() {
+            return Some(bits.into());
+        }
+
+        if self.is_pointer(ctx) {
+            ctx.isa.type_provider().pointer_type().bits().map(u32::from)
+        } else {
+            None
+        }
     }
 
     pub fn to_string(&self, dfg: &DataFlowGraph) -> String {
@@ -240,30 +433,111 @@ impl Type {
 
 impl cmp::PartialOrd for Type {
     fn partial_cmp(&self, rhs: &Self) -> Option<cmp::Ordering> {
-        use Type::*;
-
         if self == rhs {
             return Some(cmp::Ordering::Equal);
         }
 
-        if !self.is_integral() || !rhs.is_integral() {
-            return None;
-        }
+        // Only integral types are ordered by their widths.
+        Some(self.bits()?.cmp(&rhs.bits()?))
+    }
+}
 
-        match (self, rhs) {
-            (I1, _) => Some(cmp::Ordering::Less),
-            (I8, I1) => Some(cmp::Ordering::Greater),
-            (I8, _) => Some(cmp::Ordering::Less),
-            (I16, I1 | I8) => Some(cmp::Ordering::Greater),
-            (I16, _) => Some(cmp::Ordering::Less),
-            (I32, I1 | I8 | I16) => Some(cmp::Ordering::Greater),
-            (I32, _) => Some(cmp::Ordering::Less),
-            (I64, I128 | I256) => Some(cmp::Ordering::Less),
-            (I64, _) => Some(cmp::Ordering::Greater),
-            (I128, I256) => Some(cmp::Ordering::Less),
-            (I128, _) => Some(cmp::Ordering::Greater),
-            (I256, _) => Some(cmp::Ordering::Greater),
-            (_, _) => unreachable!(),
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let mut s = TypeStore::default();
+        let ptr = s.make_ptr(Type::I32);
+        let arr = s.make_array(Type::I32, 8);
+        let point = s.make_struct("point", &[Type::I32, ptr], false);
+        let packed = s.make_struct("packed", &[Type::I8, arr], true);
+        let point_ptr = s.make_ptr(point);
+        let nested = s.make_array(point_ptr, 2);
+        let nested = s.make_ptr(nested);
+        let union = s.make_union(&[Type::I64, packed]);
+
+        assert_eq!(s.display(Type::I1), "i1");
+        assert_eq!(s.display(Type::Void), "void");
+        assert_eq!(s.display(ptr), "*i32");
+        assert_eq!(s.display(arr), "[i32; 8]");
+        assert_eq!(s.display(point), "%point");
+        assert_eq!(s.display(packed), "%packed");
+        assert_eq!(s.display(nested), "*[*%point; 2]");
+        assert_eq!(s.display(union), "union{i64, %packed}");
+    }
+
+    #[test]
+    fn display_func_type() {
+        let mut s = TypeStore::default();
+        let ptr = s.make_ptr(Type::I8);
+
+        assert_eq!(
+            s.display_func_type(&[Type::I32, Type::I64], &[Type::I1]),
+            "(i32, i64) -> i1"
+        );
+        assert_eq!(s.display_func_type(&[ptr], &[]), "(*i8) -> void");
+        assert_eq!(
+            s.display_func_type(&[], &[Type::I32, ptr]),
+            "() -> (i32, *i8)"
+        );
+    }
+
+    #[test]
+    fn int() {
+        assert_eq!(Type::int(32), Type::I32);
+        assert_eq!(Type::int(24), Type::Int(IntWidth(24)));
+        assert_eq!(
+            IntWidth::try_from(32),
+            Err("the width of a fixed integer type")
+        );
+        assert_eq!(Type::try_int(0), None);
+        assert_eq!(Type::try_int(257), None);
+        assert_eq!(Type::int(24).bits(), Some(24));
+        assert!(Type::int(24).is_integral());
+        assert!(Type::I16 < Type::int(24) && Type::int(24) < Type::I32);
+
+        let s = TypeStore::default();
+        assert_eq!(s.display(Type::int(24)), "i24");
+    }
+
+    #[test]
+    fn predicates() {
+        use crate::builder::test_util::build_test_isa;
+
+        let ctx = ModuleCtx::new(build_test_isa());
+        let (ptr, arr, point, union) = ctx.with_ty_store_mut(|s| {
+            let ptr = s.make_ptr(Type::I8);
+            let arr = s.make_array(Type::I32, 4);
+            let point = s.make_struct("point", &[Type::I32, ptr], false);
+            let union = s.make_union(&[Type::I64, arr]);
+            (ptr, arr, point, union)
+        });
+
+        assert!(Type::I1.is_integer() && Type::int(24).is_integer());
+        assert!(!ptr.is_integer() && !Type::Void.is_integer());
+
+        assert!(ptr.is_pointer(&ctx));
+        assert!(!Type::I256.is_pointer(&ctx) && !arr.is_pointer(&ctx));
+
+        assert!(arr.is_aggregate(&ctx) && point.is_aggregate(&ctx) && union.is_aggregate(&ctx));
+        assert!(!ptr.is_aggregate(&ctx) && !Type::I32.is_aggregate(&ctx));
+
+        assert!(!Type::I8.is_signed_context_irrelevant());
+        assert!(ptr.is_signed_context_irrelevant() && Type::Void.is_signed_context_irrelevant());
+
+        assert_eq!(Type::I1.bit_width(&ctx), Some(1));
+        assert_eq!(Type::int(24).bit_width(&ctx), Some(24));
+        // The pointer type of the EVM is `i256`.
+        assert_eq!(ptr.bit_width(&ctx), Some(256));
+        assert_eq!(point.bit_width(&ctx), None);
+        assert_eq!(Type::Void.bit_width(&ctx), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn int_too_wide() {
+        Type::int(257);
     }
 }
@@ -2,12 +2,17 @@
 
 use std::{fmt, ops};
 
-use crate::{types::DisplayType, DataFlowGraph, GlobalVariable};
+use crate::{
+    insn::{BinaryOp, CastOp},
+    types::DisplayType,
+    DataFlowGraph, GlobalVariable,
+};
 
 use super::{Insn, Type, I256, U256};
 
 /// An opaque reference to [`ValueData`].
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Value(pub u32);
 cranelift_entity::entity_impl!(Value);
 
@@ -25,12 +30,19 @@ impl<'a> DisplayResultValue<'a> {
 impl<'a> fmt::Display for DisplayResultValue<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let Self { insn, dfg } = *self;
-        if let Some(value) = dfg.insn_result(insn) {
-            let ty = dfg.insn_result_ty(insn).unwrap();
-            let ty = DisplayType::new(ty, dfg);
-            return write!(f, "v{}.{ty} = ", value.0);
+        let results = dfg.insn_results(insn);
+        if results.is_empty() {
+            return Ok(());
         }
-        Ok(())
+
+        for (idx, &value) in results.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            let ty = DisplayType::new(dfg.value_ty(value), dfg);
+            write!(f, "v{}.{ty}", value.0)?;
+        }
+        write!(f, " = ")
     }
 }
 
@@ -53,6 +65,10 @@ impl<'a> fmt::Display for DisplayArgValue<'a> {
                 let ty = DisplayType::new(ty, dfg);
                 write!(f, "{imm}.{ty}")
             }
+            ValueData::Undef { ty } => {
+                let ty = DisplayType::new(ty, dfg);
+                write!(f, "undef.{ty}")
+            }
             _ => write!(f, "v{}", arg.0),
         }
     }
@@ -74,6 +90,7 @@ pub fn display_arg_values(
 
 /// An value data definition.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ValueData {
     /// The value is defined by an instruction.
     Insn { insn: Insn, ty: Type },
@@ -86,9 +103,17 @@ pub enum ValueData {
 
     /// The value is global value.
     Global { gv: GlobalVariable, ty: Type },
+
+    /// The value is undefined.
+    ///
+    /// Each use of an undefined value may observe an arbitrary value of `ty`, and different
+    /// uses may observe different values. Using it never traps by itself, so passes may replace
+    /// each use with any value of `ty`, e.g., fold `and v0 undef.i32` to `0.i32`.
+    Undef { ty: Type },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Immediate {
     I1(bool),
     I8(i8),
@@ -97,6 +122,11 @@ pub enum Immediate {
     I64(i64),
     I128(i128),
     I256(I256),
+    /// An immediate of [`Type::Int`]. `value` is sign extended from `bits` bits.
+    Int {
+        bits: u16,
+        value: I256,
+    },
 }
 
 impl Immediate {
@@ -109,17 +139,168 @@ impl Immediate {
             Self::I64(..) => Type::I64,
             Self::I128(..) => Type::I128,
             Self::I256(..) => Type::I256,
+            Self::Int { bits, .. } => Type::int(*bits),
         }
     }
 
+    /// Evaluates the binary operation `code` on `self` and `rhs` with the same semantics as the
+    /// interpreter. The result wraps around at the bit width of the operands, and comparisons
+    /// result in an `i1` immediate.
+    ///
+    /// Returns `None` if the types of the operands differ, or if the operation traps, i.e., a
+    /// division or remainder by zero, or an overflow of checked arithmetic.
+    ///
+    /// This is the entry point for constant evaluation. The methods for each operation, e.g.,
+    /// [`Self::udiv`], and the operator impls, e.g., `+`, return the result directly, and panic
+    /// if the types of the operands differ or if the divisor is zero.
+    pub fn binary_op(self, code: BinaryOp, rhs: Self) -> Option<Self> {
+        if self.ty() != rhs.ty() {
+            return None;
+        }
+        if matches!(
+            code,
+            BinaryOp::Udiv | BinaryOp::Sdiv | BinaryOp::Urem | BinaryOp::Srem
+        ) && rhs.is_zero()
+        {
+            return None;
+        }
+
+        Some(match code {
+            BinaryOp::Add => self + rhs,
+            BinaryOp::Sub => self - rhs,
+            BinaryOp::Mul => self * rhs,
+            BinaryOp::Udiv => self.udiv(rhs),
+            BinaryOp::Sdiv => self.sdiv(rhs),
+            BinaryOp::Urem => self.urem(rhs),
+            BinaryOp::Srem => self.srem(rhs),
+            BinaryOp::Lt => self.lt(rhs),
+            BinaryOp::Gt => self.gt(rhs),
+            BinaryOp::Slt => self.slt(rhs),
+            BinaryOp::Sgt => self.sgt(rhs),
+            BinaryOp::Le => self.le(rhs),
+            BinaryOp::Ge => self.ge(rhs),
+            BinaryOp::Sle => self.sle(rhs),
+            BinaryOp::Sge => self.sge(rhs),
+            BinaryOp::Eq => self.imm_eq(rhs),
+            BinaryOp::Ne => self.imm_ne(rhs),
+            BinaryOp::And => self & rhs,
+            BinaryOp::Or => self | rhs,
+            BinaryOp::Xor => self ^ rhs,
+            BinaryOp::Shl => self << rhs,
+            BinaryOp::Lshr => self.lshr(rhs),
+            BinaryOp::Ashr => self.ashr(rhs),
+            BinaryOp::AddChecked => self.checked_add(rhs)?,
+            BinaryOp::SubChecked => self.checked_sub(rhs)?,
+            BinaryOp::MulChecked => self.checked_mul(rhs)?,
+            BinaryOp::UaddChecked => self.checked_uadd(rhs)?,
+            BinaryOp::UsubChecked => self.checked_usub(rhs)?,
+            BinaryOp::UmulChecked => self.checked_umul(rhs)?,
+        })
+    }
+
+    /// Unsigned division that truncates toward zero.
     pub fn udiv(self, rhs: Self) -> Self {
-        self.apply_binop(rhs, |lhs, rhs| (lhs.to_u256() / rhs.to_u256()).into())
+        assert_eq!(self.ty(), rhs.ty());
+
+        // Operands must be zero extended so that the division is performed at the bit width of
+        // the operands.
+        let res = self.as_zext_u256() / rhs.as_zext_u256();
+        Self::from_i256(res.into(), self.ty())
     }
 
+    /// Signed division that truncates toward zero, e.g., `sdiv(-7, 2) == -3`.
+    /// The minimum value divided by `-1` wraps around to the minimum value.
     pub fn sdiv(self, rhs: Self) -> Self {
         self.apply_binop(rhs, |lhs, rhs| lhs.overflowing_div(rhs).0)
     }
 
+    /// Unsigned remainder.
+    pub fn urem(self, rhs: Self) -> Self {
+        assert_eq!(self.ty(), rhs.ty());
+
+        let res = self.as_zext_u256() % rhs.as_zext_u256();
+        Self::from_i256(res.into(), self.ty())
+    }
+
+    /// Signed remainder whose sign follows the dividend, e.g., `srem(-7, 2) == -1`.
+    /// The minimum value modulo `-1` is `0`.
+    pub fn srem(self, rhs: Self) -> Self {
+        self.apply_binop(rhs, |lhs, rhs| lhs.overflowing_rem(rhs).0)
+    }
+
+    /// Logical right shift that fills the vacated bits with zero. `rhs` is treated as unsigned,
+    /// and the result is zero if `rhs` is greater than or equal to the bit width of the
+    /// operands.
+    pub fn lshr(self, rhs: Self) -> Self {
+        assert_eq!(self.ty(), rhs.ty());
+
+        match self.shift_amount(rhs) {
+            Some(amount) => {
+                let res = self.as_zext_u256() >> amount;
+                Self::from_i256(res.into(), self.ty())
+            }
+            None => Self::zero(self.ty()),
+        }
+    }
+
+    /// Arithmetic right shift that fills the vacated bits with the sign bit. `rhs` is treated as
+    /// unsigned, and if it's greater than or equal to the bit width of the operands, all bits of
+    /// the result are the sign bit, i.e., the result is `-1` for negative values and `0`
+    /// otherwise.
+    pub fn ashr(self, rhs: Self) -> Self {
+        assert_eq!(self.ty(), rhs.ty());
+
+        let is_negative = self.is_negative();
+        match self.shift_amount(rhs) {
+            Some(amount) => {
+                // The value is sign extended to 256 bits, so filling the upper bits of the
+                // shifted `U256` is enough.
+                let mut res = self.as_i256().to_u256() >> amount;
+                if is_negative {
+                    res |= !(U256::MAX >> amount);
+                }
+                Self::from_i256(res.into(), self.ty())
+            }
+            None if is_negative => Self::all_one(self.ty()),
+            None => Self::zero(self.ty()),
+        }
+    }
+
+    /// Signed addition that returns `None` if the result overflows the bit width of the
+    /// operands.
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.apply_checked_binop(rhs, I256::checked_add)
+    }
+
+    /// Signed subtraction that returns `None` if the result overflows the bit width of the
+    /// operands.
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.apply_checked_binop(rhs, I256::checked_sub)
+    }
+
+    /// Signed multiplication that returns `None` if the result overflows the bit width of the
+    /// operands.
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        self.apply_checked_binop(rhs, I256::checked_mul)
+    }
+
+    /// Unsigned addition that returns `None` if the result overflows the bit width of the
+    /// operands.
+    pub fn checked_uadd(self, rhs: Self) -> Option<Self> {
+        self.apply_checked_unsigned_binop(rhs, U256::checked_add)
+    }
+
+    /// Unsigned subtraction that returns `None` if the result is negative.
+    pub fn checked_usub(self, rhs: Self) -> Option<Self> {
+        self.apply_checked_unsigned_binop(rhs, U256::checked_sub)
+    }
+
+    /// Unsigned multiplication that returns `None` if the result overflows the bit width of the
+    /// operands.
+    pub fn checked_umul(self, rhs: Self) -> Option<Self> {
+        self.apply_checked_unsigned_binop(rhs, U256::checked_mul)
+    }
+
     pub fn lt(self, rhs: Self) -> Self {
         self.apply_binop_raw(rhs, |lhs, rhs| (lhs.to_u256() < rhs.to_u256()).into())
     }
@@ -152,40 +333,65 @@ impl Immediate {
         self.apply_binop_raw(rhs, |lhs, rhs| (lhs >= rhs).into())
     }
 
+    /// Sign extends the immediate to `ty`.
+    ///
+    /// # Panics
+    /// Panics if `ty` is not an integral type wider than the type of the immediate.
     pub fn sext(self, ty: Type) -> Self {
-        debug_assert!(self.ty() < ty);
+        self.assert_cast_width(ty, |from, to| from < to);
         Self::from_i256(self.as_i256(), ty)
     }
 
+    /// Zero extends the immediate to `ty`.
+    ///
+    /// # Panics
+    /// Panics if `ty` is not an integral type wider than the type of the immediate.
     pub fn zext(self, ty: Type) -> Self {
-        debug_assert!(self.ty() < ty);
-        let i256: I256 = match self {
-            Self::I1(val) => (val as u8).into(),
-            Self::I8(val) => (val as u8).into(),
-            Self::I16(val) => (val as u16).into(),
-            Self::I32(val) => (val as u32).into(),
-            Self::I64(val) => (val as u64).into(),
-            Self::I128(val) => (val as u128).into(),
-            Self::I256(_) => unreachable!(),
-        };
-
-        Self::from_i256(i256, ty)
+        self.assert_cast_width(ty, |from, to| from < to);
+        Self::from_i256(self.as_zext_u256().into(), ty)
     }
 
+    /// Truncates the immediate to `ty`.
+    ///
+    /// # Panics
+    /// Panics if `ty` is not an integral type narrower than the type of the immediate.
     pub fn trunc(self, ty: Type) -> Self {
-        debug_assert!(self.ty() > ty);
-
+        self.assert_cast_width(ty, |from, to| from > to);
         Self::from_i256(self.as_i256(), ty)
     }
 
+    /// Casts the immediate to `ty` by `code` with the same semantics as the interpreter.
+    ///
+    /// Returns `None` for `bitcast`, or if the widths are invalid for the cast, i.e., `ty` must be
+    /// an integral type wider than the immediate for `sext` and `zext`, and narrower for `trunc`.
+    pub fn cast(self, code: CastOp, ty: Type) -> Option<Self> {
+        let from = self.ty().bits()?;
+        let to = ty.bits()?;
+        match code {
+            CastOp::Sext if from < to => Some(self.sext(ty)),
+            CastOp::Zext if from < to => Some(self.zext(ty)),
+            CastOp::Trunc if from > to => Some(self.trunc(ty)),
+            _ => None,
+        }
+    }
+
+    fn assert_cast_width(self, ty: Type, is_valid: impl FnOnce(u16, u16) -> bool) {
+        let from = self.ty().bits().unwrap();
+        let to = ty.bits();
+        assert!(
+            to.is_some_and(|to| is_valid(from, to)),
+            "invalid cast from `i{from}` to `{ty:?}`"
+        );
+    }
+
     pub fn imm_eq(self, rhs: Self) -> Self {
-        debug_assert_eq!(self.ty(), rhs.ty());
+        assert_eq!(self.ty(), rhs.ty());
 
         (self == rhs).into()
     }
 
     pub fn imm_ne(self, rhs: Self) -> Self {
-        debug_assert_eq!(self.ty(), rhs.ty());
+        assert_eq!(self.ty(), rhs.ty());
 
         (self != rhs).into()
     }
@@ -241,9 +447,28 @@ impl Immediate {
             Self::I64(val) => val.into(),
             Self::I128(val) => val.into(),
             Self::I256(val) => val,
+            Self::Int { value, .. } => value,
         }
     }
 
+    /// Returns the value as `U256` by zero extending it.
+    pub fn as_zext_u256(self) -> U256 {
+        let i256: I256 = match self {
+            Self::I1(val) => (val as u8).into(),
+            Self::I8(val) => (val as u8).into(),
+            Self::I16(val) => (val as u16).into(),
+            Self::I32(val) => (val as u32).into(),
+            Self::I64(val) => (val as u64).into(),
+            Self::I128(val) => (val as u128).into(),
+            Self::I256(val) => val,
+            Self::Int { bits, value } => {
+                let mask = (U256::one() << bits) - U256::one();
+                return value.to_u256() & mask;
+            }
+        };
+        i256.to_u256()
+    }
+
     pub fn as_usize(self) -> usize {
         debug_assert!(!self.is_negative());
         self.as_i256().to_u256().as_usize()
@@ -258,25 +483,72 @@ impl Immediate {
             Type::I64 => Self::I64(val.trunc_to_i64()),
             Type::I128 => Self::I128(val.trunc_to_i128()),
             Type::I256 => Self::I256(val),
+            Type::Int(width) => Self::Int {
+                bits: width.bits(),
+                value: val.trunc_to_bits(width.bits() as usize),
+            },
             _ => unreachable!(),
         }
     }
 
+    /// Returns the bit width of the immediate.
+    fn bits(self) -> usize {
+        match self {
+            Self::I1(..) => 1,
+            Self::I8(..) => 8,
+            Self::I16(..) => 16,
+            Self::I32(..) => 32,
+            Self::I64(..) => 64,
+            Self::I128(..) => 128,
+            Self::I256(..) => 256,
+            Self::Int { bits, .. } => bits as usize,
+        }
+    }
+
+    /// Returns `amount` as a shift amount for `self`, or `None` if all bits are shifted out.
+    fn shift_amount(self, amount: Self) -> Option<usize> {
+        let amount = amount.as_zext_u256();
+        (amount < U256::from(self.bits())).then(|| amount.as_usize())
+    }
+
     fn apply_binop<F>(self, rhs: Self, f: F) -> Self
     where
         F: FnOnce(I256, I256) -> I256,
     {
-        debug_assert_eq!(self.ty(), rhs.ty());
+        assert_eq!(self.ty(), rhs.ty());
 
         let res = self.apply_binop_raw(rhs, f);
         Self::from_i256(res, self.ty())
     }
 
+    fn apply_checked_binop<F>(self, rhs: Self, f: F) -> Option<Self>
+    where
+        F: FnOnce(I256, I256) -> Option<I256>,
+    {
+        let res = self.apply_binop_raw(rhs, f)?;
+        let imm = Self::from_i256(res, self.ty());
+        // The result overflows if it can't be represented in the bit width of the operands.
+        (imm.as_i256() == res).then_some(imm)
+    }
+
+    /// Similar to [`Self::apply_checked_binop`], but the operands are zero extended so that `f`
+    /// works on unsigned values.
+    fn apply_checked_unsigned_binop<F>(self, rhs: Self, f: F) -> Option<Self>
+    where
+        F: FnOnce(U256, U256) -> Option<U256>,
+    {
+        assert_eq!(self.ty(), rhs.ty());
+
+        let res = f(self.as_zext_u256(), rhs.as_zext_u256())?;
+        let imm = Self::from_i256(res.into(), self.ty());
+        (imm.as_zext_u256() == res).then_some(imm)
+    }
+
     fn apply_binop_raw<F, R>(self, rhs: Self, f: F) -> R
     where
         F: FnOnce(I256, I256) -> R,
     {
-        debug_assert_eq!(self.ty(), rhs.ty());
+        assert_eq!(self.ty(), rhs.ty());
 
         let lhs = self.as_i256();
         let rhs = rhs.as_i256();
@@ -347,6 +619,24 @@ impl ops::BitOr for Immediate {
     }
 }
 
+impl ops::Shl for Immediate {
+    type Output = Self;
+
+    /// Logical left shift. `rhs` is treated as unsigned, and the result is zero if `rhs` is
+    /// greater than or equal to the bit width of the operands.
+    fn shl(self, rhs: Self) -> Self {
+        assert_eq!(self.ty(), rhs.ty());
+
+        match self.shift_amount(rhs) {
+            Some(amount) => {
+                let res = self.as_zext_u256() << amount;
+                Self::from_i256(res.into(), self.ty())
+            }
+            None => Self::zero(self.ty()),
+        }
+    }
+}
+
 impl ops::Not for Immediate {
     type Output = Self;
 
@@ -379,6 +669,7 @@ impl fmt::Display for Immediate {
             Self::I64(v) => write!(f, "{}", v),
             Self::I128(v) => write!(f, "{}", v),
             Self::I256(v) => write!(f, "{}", v),
+            Self::Int { value, .. } => write!(f, "{}", value),
         }
     }
 }
@@ -411,3 +702,124 @@ impl From<U256> for Immediate {
         Self::I256(imm.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_op_wraps_at_width() {
+        let op = |code, lhs: i8, rhs: i8| Immediate::I8(lhs).binary_op(code, Immediate::I8(rhs));
+
+        assert_eq!(op(BinaryOp::Add, i8::MAX, 1), Some(Immediate::I8(i8::MIN)));
+        assert_eq!(op(BinaryOp::Sub, i8::MIN, 1), Some(Immediate::I8(i8::MAX)));
+        assert_eq!(op(BinaryOp::Mul, 64, 4), Some(Immediate::I8(0)));
+        // `-1` is `255` as an unsigned value.
+        assert_eq!(op(BinaryOp::Udiv, -1, 2), Some(Immediate::I8(127)));
+        assert_eq!(op(BinaryOp::Sdiv, -7, 2), Some(Immediate::I8(-3)));
+        assert_eq!(
+            op(BinaryOp::Sdiv, i8::MIN, -1),
+            Some(Immediate::I8(i8::MIN))
+        );
+
+        let int = |value: i32| Immediate::from_i256(value.into(), Type::int(24));
+        assert_eq!(
+            int(0x7f_ffff).binary_op(BinaryOp::Add, int(1)),
+            Some(int(-0x80_0000))
+        );
+    }
+
+    #[test]
+    fn binary_op_comparisons() {
+        let op = |code, lhs: i8, rhs: i8| Immediate::I8(lhs).binary_op(code, Immediate::I8(rhs));
+
+        assert_eq!(op(BinaryOp::Slt, -1, 0), Some(Immediate::I1(true)));
+        assert_eq!(op(BinaryOp::Lt, -1, 0), Some(Immediate::I1(false)));
+        assert_eq!(op(BinaryOp::Ge, -1, 0), Some(Immediate::I1(true)));
+        assert_eq!(op(BinaryOp::Eq, 3, 3), Some(Immediate::I1(true)));
+        assert_eq!(op(BinaryOp::Ne, 3, 3), Some(Immediate::I1(false)));
+    }
+
+    #[test]
+    fn sext_sign_boundary() {
+        // `0xff` in `i8`.
+        assert_eq!(
+            Immediate::I8(-1).sext(Type::I32),
+            Immediate::I32(0xffff_ffff_u32 as i32)
+        );
+        assert_eq!(Immediate::I8(i8::MAX).sext(Type::I32), Immediate::I32(0x7f));
+        assert_eq!(
+            Immediate::I8(i8::MIN).sext(Type::I16),
+            Immediate::I16(-0x80)
+        );
+        assert_eq!(Immediate::I1(true).sext(Type::I8), Immediate::I8(-1));
+
+        let int = |value: i32| Immediate::from_i256(value.into(), Type::int(24));
+        assert_eq!(int(-0x80_0000).sext(Type::I32), Immediate::I32(-0x80_0000));
+        assert_eq!(
+            Immediate::I16(-1).sext(Type::int(24)),
+            Immediate::from_i256((-1).into(), Type::int(24))
+        );
+    }
+
+    #[test]
+    fn zext_sign_boundary() {
+        assert_eq!(Immediate::I8(-1).zext(Type::I32), Immediate::I32(0xff));
+        assert_eq!(Immediate::I8(i8::MIN).zext(Type::I16), Immediate::I16(0x80));
+        assert_eq!(Immediate::I1(true).zext(Type::I8), Immediate::I8(1));
+        assert_eq!(
+            Immediate::from_i256((-1).into(), Type::int(24)).zext(Type::I32),
+            Immediate::I32(0xff_ffff)
+        );
+    }
+
+    #[test]
+    fn trunc_sign_boundary() {
+        assert_eq!(Immediate::I32(0xff).trunc(Type::I8), Immediate::I8(-1));
+        assert_eq!(Immediate::I32(0x180).trunc(Type::I8), Immediate::I8(-0x80));
+        assert_eq!(Immediate::I16(0x7f).trunc(Type::I8), Immediate::I8(0x7f));
+        assert_eq!(Immediate::I8(2).trunc(Type::I1), Immediate::I1(false));
+        assert_eq!(
+            Immediate::I32(0x80_0000).trunc(Type::int(24)),
+            Immediate::from_i256((-0x80_0000).into(), Type::int(24))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn sext_to_narrower_type() {
+        Immediate::I32(1).sext(Type::I8);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zext_to_same_type() {
+        Immediate::I32(1).zext(Type::I32);
+    }
+
+    #[test]
+    #[should_panic]
+    fn trunc_to_wider_type() {
+        Immediate::I8(1).trunc(Type::I32);
+    }
+
+    #[test]
+    #[should_panic]
+    fn add_different_types() {
+        let _ = Immediate::I8(1) + Immediate::I16(1);
+    }
+
+    #[test]
+    fn binary_op_failures() {
+        let op = |code, lhs: i8, rhs: i8| Immediate::I8(lhs).binary_op(code, Immediate::I8(rhs));
+
+        assert_eq!(
+            Immediate::I8(1).binary_op(BinaryOp::Add, Immediate::I16(1)),
+            None
+        );
+        assert_eq!(op(BinaryOp::Udiv, 1, 0), None);
+        assert_eq!(op(BinaryOp::Srem, 1, 0), None);
+        assert_eq!(op(BinaryOp::AddChecked, i8::MAX, 1), None);
+        assert_eq!(op(BinaryOp::AddChecked, 1, 1), Some(Immediate::I8(2)));
+    }
+}
@@ -0,0 +1,194 @@
+//! This module contains a visitor that rewrites insns of a function in place.
+//!
+//! Peephole optimizations only need to decide what to do with each insn by implementing
+//! [`InsnRewriter`]; [`rewrite_func`] applies the decisions while keeping the layout and the
+//! user sets of the [`DataFlowGraph`] consistent.
+use crate::{
+    func_cursor::{CursorLocation, FuncCursor, InsnInserter},
+    DataFlowGraph, Function, Insn, InsnData,
+};
+
+/// An action that [`rewrite_func`] applies to the visited insn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RewriteAction {
+    /// Leaves the insn as is.
+    Keep,
+
+    /// Replaces the insn with the insn data. The result of the insn, if any, is kept, so the new
+    /// insn must yield a value of the same type.
+    Replace(InsnData),
+
+    /// Removes the insn. The result of the insn must have no users, i.e., the rewriter must
+    /// redirect them to another value in advance, e.g., with
+    /// [`DataFlowGraph::change_to_alias`].
+    Remove,
+}
+
+pub trait InsnRewriter {
+    /// Inspects `insn`, and returns the action to be applied to it.
+    ///
+    /// The rewriter may modify `dfg` by itself, except that it must not modify `insn`.
+    fn rewrite(&mut self, dfg: &mut DataFlowGraph, insn: Insn) -> RewriteAction;
+}
+
+/// Applies `rewriter` to all insns of `func` in layout order.
+/// Each insn is visited once; replaced insns are not revisited.
+///
+/// Returns `true` if any insn is replaced or removed.
+pub fn rewrite_func(func: &mut Function, rewriter: &mut impl InsnRewriter) -> bool {
+    let mut changed = false;
+    let blocks: Vec<_> = func.layout.iter_block().collect();
+
+    for block in blocks {
+        let mut next_insn = func.layout.first_insn_of(block);
+        while let Some(insn) = next_insn {
+            next_insn = func.layout.next_insn_of(insn);
+
+            match rewriter.rewrite(&mut func.dfg, insn) {
+                RewriteAction::Keep => {}
+                RewriteAction::Replace(insn_data) => {
+                    debug_assert_eq!(
+                        insn_data.result_type(&func.dfg),
+                        func.dfg.insn_result_ty(insn),
+                        "replacing insn must yield a value of the same type"
+                    );
+                    func.dfg.replace_insn(insn, insn_data);
+                    changed = true;
+                }
+                RewriteAction::Remove => {
+                    debug_assert!(
+                        func.dfg
+                            .insn_results(insn)
+                            .iter()
+                            .all(|&result| func.dfg.users_num(result) == 0),
+                        "result of removed insn still has users"
+                    );
+                    InsnInserter::at_location(CursorLocation::At(insn)).remove_insn(func);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{builder::test_util::*, insn::BinaryOp, Type, I256};
+
+    /// Rewrites `x + 0` and `0 + x` to `x`.
+    struct AddZero;
+
+    impl InsnRewriter for AddZero {
+        fn rewrite(&mut self, dfg: &mut DataFlowGraph, insn: Insn) -> RewriteAction {
+            let InsnData::Binary {
+                code: BinaryOp::Add,
+                args: [lhs, rhs],
+            } = *dfg.insn_data(insn)
+            else {
+                return RewriteAction::Keep;
+            };
+
+            let is_zero = |value| dfg.value_imm(value).is_some_and(|imm| imm.is_zero());
+            let x = if is_zero(rhs) {
+                lhs
+            } else if is_zero(lhs) {
+                rhs
+            } else {
+                return RewriteAction::Keep;
+            };
+
+            let result = dfg.insn_result(insn).unwrap();
+            dfg.change_to_alias(result, x);
+            RewriteAction::Remove
+        }
+    }
+
+    /// Rewrites `x * 2` to `x + x`.
+    struct MulTwo;
+
+    impl InsnRewriter for MulTwo {
+        fn rewrite(&mut self, dfg: &mut DataFlowGraph, insn: Insn) -> RewriteAction {
+            let is_two = |value| {
+                dfg.value_imm(value)
+                    .is_some_and(|imm| imm.as_i256() == I256::from(2))
+            };
+            crate::inst_match!(dfg, insn, {
+                Binary { code: Mul, args: [lhs, rhs] } if is_two(rhs) => {
+                    RewriteAction::Replace(InsnData::binary(BinaryOp::Add, lhs, lhs))
+                }
+                _ => RewriteAction::Keep,
+            })
+        }
+    }
+
+    #[test]
+    fn peephole_add_zero() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg = builder.args()[0];
+        let zero = builder.make_imm_value(0i32);
+        let v1 = builder.add(arg, zero);
+        let v2 = builder.add(zero, v1);
+        let v3 = builder.mul(v2, v2);
+        builder.ret(&[v3]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+
+        assert!(rewrite_func(func, &mut AddZero));
+        assert_eq!(func.dfg.users_num(arg), 1);
+        assert_eq!(func.dfg.users_num(zero), 0);
+        assert!(!rewrite_func(func, &mut AddZero));
+
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        v4.i32 = mul v0 v0;
+        return v4;
+
+}
+"
+        );
+    }
+
+    #[test]
+    fn replace_insn() {
+        let mut builder = test_func_builder(&[Type::I32], Type::I32);
+        let b0 = builder.append_block();
+        builder.switch_to_block(b0);
+
+        let arg = builder.args()[0];
+        let two = builder.make_imm_value(2i32);
+        let v1 = builder.mul(arg, two);
+        builder.ret(&[v1]);
+        builder.seal_all();
+
+        let mut module = builder.finish().build();
+        let func_ref = module.iter_functions().next().unwrap();
+        let func = &mut module.funcs[func_ref];
+
+        assert!(rewrite_func(func, &mut MulTwo));
+        assert_eq!(func.dfg.users_num(two), 0);
+        assert_eq!(func.dfg.users_num(arg), 1);
+
+        assert_eq!(
+            dump_func(&module, func_ref),
+            "func public %test_func(v0.i32) -> i32 {
+    block0:
+        v2.i32 = add v0 v0;
+        return v2;
+
+}
+"
+        );
+    }
+}
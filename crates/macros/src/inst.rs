@@ -17,10 +17,17 @@ pub fn derive_inst(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
 
 struct InstStruct {
     struct_name: syn::Ident,
-    has_side_effect: bool,
+    attrs: InstAttrs,
     fields: Vec<InstField>,
 }
 
+#[derive(Default)]
+struct InstAttrs {
+    has_side_effect: bool,
+    is_terminator: bool,
+    result: Option<syn::Ident>,
+}
+
 struct InstField {
     ident: syn::Ident,
     ty: syn::Type,
@@ -29,7 +36,7 @@ struct InstField {
 
 impl InstStruct {
     fn new(item_struct: syn::ItemStruct) -> syn::Result<Self> {
-        let has_side_effect = Self::check_side_effect_attr(&item_struct)?;
+        let attrs = Self::parse_inst_attrs(&item_struct)?;
 
         let struct_ident = item_struct.ident;
 
@@ -44,7 +51,7 @@ impl InstStruct {
 
         Ok(Self {
             struct_name: struct_ident,
-            has_side_effect,
+            attrs,
             fields,
         })
     }
@@ -53,6 +60,7 @@ impl InstStruct {
         let ctor = self.make_ctor();
         let accessors = self.make_accessors();
         let cast_fn = self.make_cast_fn();
+        let meta = self.make_meta()?;
 
         let struct_name = &self.struct_name;
         let impl_inst = self.impl_inst();
@@ -63,27 +71,39 @@ impl InstStruct {
                 #accessors
 
                 #cast_fn
+
+                #meta
             }
 
             #impl_inst
         })
     }
 
-    fn check_side_effect_attr(item_struct: &syn::ItemStruct) -> syn::Result<bool> {
-        let mut has_side_effect = false;
+    fn parse_inst_attrs(item_struct: &syn::ItemStruct) -> syn::Result<InstAttrs> {
+        let mut attrs = InstAttrs::default();
 
         for attr in &item_struct.attrs {
             if attr.path().is_ident("inst") {
-                let meta = attr.parse_args::<syn::Meta>()?;
-                if let syn::Meta::Path(path) = meta {
-                    if path.is_ident("has_side_effect") {
-                        has_side_effect = true;
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("has_side_effect") {
+                        attrs.has_side_effect = true;
+                        Ok(())
+                    } else if meta.path.is_ident("terminator") {
+                        attrs.is_terminator = true;
+                        Ok(())
+                    } else if meta.path.is_ident("result") {
+                        let kind = meta.value()?.parse::<syn::LitStr>()?;
+                        attrs.result = Some(syn::Ident::new(&kind.value(), kind.span()));
+                        Ok(())
+                    } else {
+                        Err(meta
+                            .error("only `has_side_effect`, `terminator` and `result` are allowed"))
                     }
-                }
+                })?;
             }
         }
 
-        Ok(has_side_effect)
+        Ok(attrs)
     }
 
     fn parse_fields(fields: &syn::Fields) -> syn::Result<Vec<InstField>> {
@@ -192,9 +212,60 @@ impl InstStruct {
         }
     }
 
+    fn make_meta(&self) -> syn::Result<proc_macro2::TokenStream> {
+        let name = convert_to_snake(&self.struct_name.to_string());
+        let has_side_effect = self.attrs.has_side_effect;
+        let is_terminator = self.attrs.is_terminator;
+
+        // A value field of type `Value` is exactly one operand, and the other value fields,
+        // e.g., `Option<Value>` or `SmallVec<[Value; N]>`, make the number of operands variable.
+        let value_fields = self.fields.iter().filter(|f| f.value);
+        let num_fixed = value_fields.clone().filter(|f| is_value_ty(&f.ty)).count();
+        let operands = if value_fields.clone().all(|f| is_value_ty(&f.ty)) {
+            quote! { crate::inst::OperandCount::Fixed(#num_fixed) }
+        } else {
+            quote! { crate::inst::OperandCount::AtLeast(#num_fixed) }
+        };
+
+        // The result type is given by the type field if the inst has it, e.g., casts.
+        let result = match &self.attrs.result {
+            Some(kind) => match kind.to_string().as_str() {
+                "none" => quote! { None },
+                "same" => quote! { SameAsOperands },
+                "bool" => quote! { Bool },
+                "word" => quote! { Word },
+                "explicit" => quote! { Explicit },
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        kind,
+                        "`result` must be one of `none`, `same`, `bool`, `word` or `explicit`",
+                    ))
+                }
+            },
+            None if self
+                .fields
+                .iter()
+                .any(|f| f.ident == "ty" || f.ident == "ret_ty") =>
+            {
+                quote! { Explicit }
+            }
+            None => quote! { SameAsOperands },
+        };
+
+        Ok(quote! {
+            pub const META: crate::inst::InstMeta = crate::inst::InstMeta {
+                name: #name,
+                operands: #operands,
+                has_side_effect: #has_side_effect,
+                is_terminator: #is_terminator,
+                result: crate::inst::ResultKind::#result,
+            };
+        })
+    }
+
     fn impl_inst(&self) -> proc_macro2::TokenStream {
         let struct_name = &self.struct_name;
-        let has_side_effect = self.has_side_effect;
+        let has_side_effect = self.attrs.has_side_effect;
         let visit_fields: Vec<_> = self
             .fields
             .iter()
@@ -224,3 +295,7 @@ impl InstStruct {
         }
     }
 }
+
+fn is_value_ty(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Path(p) if p.qself.is_none() && p.path.is_ident("Value"))
+}
@@ -236,9 +236,15 @@ impl InstSet {
             }
         });
 
+        let insts = &self.insts;
         let ident = &self.ident;
         quote! {
             impl crate::InstSetBase for #ident {
+                fn all_insts(&self) -> &[crate::inst::InstMeta] {
+                    const ALL_INSTS: &[crate::inst::InstMeta] = &[#(#insts::META),*];
+                    ALL_INSTS
+                }
+
                 #(#methods)*
             }
         }
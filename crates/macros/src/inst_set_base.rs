@@ -38,6 +38,9 @@ impl TraitDefinition {
         quote! {
             #(#attrs)*
             pub trait InstSetBase {
+                /// Returns the metadata of all instructions in the set.
+                fn all_insts(&self) -> &[crate::inst::InstMeta];
+
                 #(#methods)*
             }
         }
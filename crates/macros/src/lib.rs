@@ -22,6 +22,10 @@ mod inst_set_base;
 ///
 /// # Arguments
 /// - `has_side_effect`: Marks the instruction as having a side effect.
+/// - `terminator`: Marks the instruction as a block terminator.
+/// - `result = "..."`: Specifies the type of the result, one of `none`, `same`, `bool`, `word`
+///   or `explicit`. If omitted, the result is `explicit` if the instruction has a `ty` or
+///   `ret_ty` field, and `same` otherwise. See `sonatina-ir::inst::ResultKind`.
 /// - `value`: Marks the field that contains value,
 ///            the specified field must implements `sonatina-ir::inst::ValueVisitable` trait.
 ///
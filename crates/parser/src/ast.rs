@@ -6,8 +6,8 @@ use crate::{
 use either::Either;
 use hex::FromHex;
 pub use ir::{
-    insn::{BinaryOp, CastOp, UnaryOp},
-    DataLocationKind, Immediate, Linkage,
+    insn::{BinaryOp, CastOp, HaltOp, UnaryOp},
+    DataLocationKind, Immediate, Linkage, MemoryOrdering,
 };
 use ir::{I256, U256};
 use pest::Parser as _;
@@ -119,7 +119,7 @@ pub struct FuncDeclaration {
     pub linkage: Linkage,
     pub name: FunctionName,
     pub params: Vec<Type>,
-    pub ret_type: Option<Type>,
+    pub ret_types: Vec<Type>,
 }
 
 impl FromSyntax<Error> for FuncDeclaration {
@@ -132,7 +132,9 @@ impl FromSyntax<Error> for FuncDeclaration {
             linkage,
             name: node.single(Rule::function_identifier),
             params: node.descend_into(Rule::function_param_type_list, |n| n.multi(Rule::type_name)),
-            ret_type: node.descend_into_opt(Rule::function_ret_type, |n| n.single(Rule::type_name)),
+            ret_types: node
+                .descend_into_opt(Rule::function_ret_type, |n| n.multi(Rule::type_name))
+                .unwrap_or_default(),
         }
     }
 }
@@ -193,7 +195,7 @@ pub struct FuncSignature {
     pub linkage: Linkage,
     pub name: FunctionName,
     pub params: Vec<ValueDeclaration>,
-    pub ret_type: Option<Type>,
+    pub ret_types: Vec<Type>,
 }
 
 impl FromSyntax<Error> for FuncSignature {
@@ -206,7 +208,9 @@ impl FromSyntax<Error> for FuncSignature {
             linkage,
             name: node.single(Rule::function_identifier),
             params: node.descend_into(Rule::function_params, |n| n.multi(Rule::value_declaration)),
-            ret_type: node.descend_into_opt(Rule::function_ret_type, |n| n.single(Rule::type_name)),
+            ret_types: node
+                .descend_into_opt(Rule::function_ret_type, |n| n.multi(Rule::type_name))
+                .unwrap_or_default(),
         }
     }
 }
@@ -274,12 +278,29 @@ impl FromSyntax<Error> for Stmt {
                 node.single(Rule::value_declaration),
                 node.single(Rule::expr),
             ),
+            Rule::multi_define_stmt => StmtKind::MultiDefine(
+                node.multi(Rule::value_declaration),
+                node.descend_into(Rule::call_expr, |node| {
+                    Call(
+                        node.single(Rule::function_identifier),
+                        node.multi(Rule::value),
+                    )
+                }),
+            ),
             Rule::store_stmt => StmtKind::Store(
                 node.parse_str(Rule::location),
+                node.parse_str_opt(Rule::store_ordering).unwrap_or_default(),
                 node.single(Rule::value),
                 node.single(Rule::value),
             ),
-            Rule::return_stmt => StmtKind::Return(node.single_opt(Rule::value)),
+            Rule::call_stmt => StmtKind::Call(Call(
+                node.single(Rule::function_identifier),
+                node.multi(Rule::value),
+            )),
+            Rule::return_stmt => StmtKind::Return(node.multi(Rule::value)),
+            Rule::halt_stmt => {
+                StmtKind::Halt(node.parse_str(Rule::halt_op), node.multi(Rule::value))
+            }
             Rule::jump_stmt => StmtKind::Jump(node.single(Rule::block_ident)),
             Rule::br_stmt => StmtKind::Branch(
                 node.single(Rule::value),
@@ -300,8 +321,11 @@ impl FromSyntax<Error> for Stmt {
 #[derive(Debug)]
 pub enum StmtKind {
     Define(ValueDeclaration, Expr),
-    Store(DataLocationKind, Value, Value),
-    Return(Option<Value>),
+    /// Defines the results of a call returning multiple values.
+    MultiDefine(Vec<ValueDeclaration>, Call),
+    Store(DataLocationKind, MemoryOrdering, Value, Value),
+    Return(Vec<Value>),
+    Halt(HaltOp, Vec<Value>),
     Jump(BlockId),
     Branch(Value, BlockId, BlockId),
     BranchTable(Value, Option<BlockId>, Vec<(Value, BlockId)>),
@@ -326,6 +350,7 @@ pub enum TypeKind {
     Int(IntType),
     Ptr(Box<Type>),
     Array(Box<Type>, usize),
+    Union(Vec<Type>),
     Struct(SmolStr),
     Void,
     Error,
@@ -335,7 +360,13 @@ impl FromSyntax<Error> for Type {
     fn from_syntax(node: &mut Node<Error>) -> Self {
         node.descend();
         let kind = match node.rule {
-            Rule::primitive_type => TypeKind::Int(IntType::from_str(node.txt).unwrap()),
+            Rule::primitive_type => match IntType::from_str(node.txt) {
+                Ok(ty) => TypeKind::Int(ty),
+                Err(_) => {
+                    node.error(Error::NumberOutOfBounds(node.span));
+                    TypeKind::Error
+                }
+            },
             Rule::ptr_type => TypeKind::Ptr(Box::new(node.single(Rule::type_name))),
             Rule::array_type => {
                 let Ok(size) = usize::from_str(node.get(Rule::array_size).as_str()) else {
@@ -347,6 +378,7 @@ impl FromSyntax<Error> for Type {
                 };
                 TypeKind::Array(Box::new(node.single(Rule::type_name)), size)
             }
+            Rule::union_type => TypeKind::Union(node.multi(Rule::type_name)),
             Rule::void_type => TypeKind::Void,
             Rule::struct_identifier => TypeKind::Struct(node.parse_str(Rule::struct_name)),
             _ => unreachable!(),
@@ -367,6 +399,8 @@ pub enum IntType {
     I64,
     I128,
     I256,
+    /// An integer type of a width other than the above, e.g., `i24`.
+    Int(u16),
 }
 
 impl From<IntType> for ir::Type {
@@ -379,6 +413,7 @@ impl From<IntType> for ir::Type {
             IntType::I64 => ir::Type::I64,
             IntType::I128 => ir::Type::I128,
             IntType::I256 => ir::Type::I256,
+            IntType::Int(bits) => ir::Type::int(bits),
         }
     }
 }
@@ -388,7 +423,7 @@ pub enum Expr {
     Binary(BinaryOp, Value, Value),
     Unary(UnaryOp, Value),
     Cast(CastOp, Value),
-    Load(DataLocationKind, Value),
+    Load(DataLocationKind, MemoryOrdering, Value),
     Alloca(Type),
     Call(Call),
     Gep(Vec<Value>),
@@ -413,7 +448,11 @@ impl FromSyntax<Error> for Expr {
             Rule::cast_expr => Expr::Cast(node.parse_str(Rule::cast_op), node.single(Rule::value)),
 
             Rule::gep_expr => Expr::Gep(node.multi(Rule::value)),
-            Rule::load_expr => Expr::Load(node.parse_str(Rule::location), node.single(Rule::value)),
+            Rule::load_expr => Expr::Load(
+                node.parse_str(Rule::location),
+                node.parse_str_opt(Rule::load_ordering).unwrap_or_default(),
+                node.single(Rule::value),
+            ),
             Rule::phi_expr => Expr::Phi(node.multi(Rule::phi_value)),
             _ => unreachable!(),
         }
@@ -459,6 +498,7 @@ pub struct Value {
 pub enum ValueKind {
     Immediate(Immediate),
     Named(ValueName),
+    Undef(Type),
     Error,
 }
 
@@ -493,8 +533,15 @@ impl FromSyntax<Error> for Value {
         node.descend();
         let kind = match node.rule {
             Rule::value_name => ValueKind::Named(ValueName::from_syntax(node)),
+            Rule::undef_value => ValueKind::Undef(node.single(Rule::type_name)),
             Rule::imm_number => {
-                let ty: IntType = node.parse_str(Rule::primitive_type);
+                let Ok(ty) = IntType::from_str(node.get(Rule::primitive_type).as_str()) else {
+                    node.error(Error::NumberOutOfBounds(node.span));
+                    return Value {
+                        kind: ValueKind::Error,
+                        span: node.span,
+                    };
+                };
                 node.descend();
                 let mut txt = node.txt;
                 match node.rule {
@@ -526,6 +573,20 @@ impl FromSyntax<Error> for Value {
                                 Some(Immediate::I256(i256))
                             })
                         }
+
+                        IntType::Int(bits) => {
+                            let s = txt.strip_prefix('-');
+                            let is_negative = s.is_some();
+                            txt = s.unwrap_or(txt);
+
+                            imm_or_err(node, || {
+                                let mut i256 = U256::from_dec_str(txt).ok()?.into();
+                                if is_negative {
+                                    i256 = I256::zero().overflowing_sub(i256).0;
+                                }
+                                int_imm(i256, bits)
+                            })
+                        }
                     },
 
                     Rule::hex => match ty {
@@ -554,6 +615,10 @@ impl FromSyntax<Error> for Value {
                                 ValueKind::Error
                             }
                         }
+                        IntType::Int(bits) => imm_or_err(node, || {
+                            let bytes = hex_bytes::<32>(txt)?;
+                            int_imm(U256::from_big_endian(&bytes).into(), bits)
+                        }),
                     },
                     _ => unreachable!(),
                 }
@@ -571,19 +636,28 @@ impl FromStr for IntType {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "i1" => Ok(Self::I1),
-            "i8" => Ok(Self::I8),
-            "i16" => Ok(Self::I16),
-            "i32" => Ok(Self::I32),
-            "i64" => Ok(Self::I64),
-            "i128" => Ok(Self::I128),
-            "i256" => Ok(Self::I256),
-            _ => Err(()),
+        let bits = s.strip_prefix('i').ok_or(())?.parse().map_err(|_| ())?;
+        match ir::Type::try_int(bits).ok_or(())? {
+            ir::Type::I1 => Ok(Self::I1),
+            ir::Type::I8 => Ok(Self::I8),
+            ir::Type::I16 => Ok(Self::I16),
+            ir::Type::I32 => Ok(Self::I32),
+            ir::Type::I64 => Ok(Self::I64),
+            ir::Type::I128 => Ok(Self::I128),
+            ir::Type::I256 => Ok(Self::I256),
+            _ => Ok(Self::Int(bits)),
         }
     }
 }
 
+/// Makes an immediate of `bits` width from `value`, or returns `None` if `value` doesn't fit in
+/// the width either as a signed or an unsigned integer.
+fn int_imm(value: I256, bits: u16) -> Option<Immediate> {
+    let fits_signed = value.trunc_to_bits(bits as usize) == value;
+    let fits_unsigned = (value.to_u256() >> bits as usize).is_zero();
+    (fits_signed || fits_unsigned).then(|| Immediate::from_i256(value, ir::Type::int(bits)))
+}
+
 fn imm_or_err<F>(node: &mut Node<Error>, f: F) -> ValueKind
 where
     F: Fn() -> Option<Immediate>,
@@ -1,6 +1,9 @@
 use std::io;
 
-use crate::{syntax::Rule, Span};
+use crate::{
+    syntax::{LineCol, Rule},
+    Span,
+};
 use annotate_snippets::{Level, Renderer, Snippet};
 use smol_str::SmolStr;
 use sonatina_triple::InvalidTriple;
@@ -18,6 +21,13 @@ pub enum Error {
         inferred: SmolStr,
         span: Span,
     },
+    /// A call declaring a different number of results than the callee returns.
+    ResultCountMismatch {
+        func: SmolStr,
+        returned: usize,
+        declared: usize,
+        span: Span,
+    },
 }
 
 #[derive(Debug)]
@@ -41,9 +51,15 @@ impl Error {
                 pest::error::InputLocation::Span((s, e)) => Span(s as u32, e as u32),
             },
             Error::TypeMismatch { span, .. } => *span,
+            Error::ResultCountMismatch { span, .. } => *span,
         }
     }
 
+    /// Returns the line and column where the error starts in `content`.
+    pub fn line_col(&self, content: &str) -> LineCol {
+        self.span().line_cols(content).0
+    }
+
     pub fn print(
         &self,
         mut w: impl io::Write,
@@ -69,6 +85,14 @@ impl Error {
             } => format!(
                 "type mismatch: value declared as `{specified}`, but inferred type is `{inferred}`",
             ),
+            Error::ResultCountMismatch {
+                func,
+                returned,
+                declared,
+                ..
+            } => format!(
+                "result count mismatch: `%{func}` returns {returned} values, but {declared} declared",
+            ),
         };
         let snippet = Level::Error.title("parse error").snippet(
             Snippet::source(content)
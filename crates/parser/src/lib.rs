@@ -19,7 +19,7 @@ pub mod ast;
 mod error;
 pub mod syntax;
 pub use error::{Error, UndefinedKind};
-pub use syntax::Span;
+pub use syntax::{LineCol, Span};
 
 type Bimap<K, V> = bimap::BiHashMap<K, V, BuildHasherDefault<FxHasher>>;
 
@@ -42,7 +42,7 @@ pub fn parse_module(input: &str) -> Result<ParsedModule, Vec<Error>> {
             .iter()
             .map(|t| ctx.type_(&mut builder, t))
             .collect::<Vec<_>>();
-        builder.declare_struct_type(&st.name.0, &fields, false);
+        builder.declare_struct_type(&st.name.0, &fields, st.packed);
     }
 
     for func in ast.declared_functions {
@@ -51,13 +51,9 @@ pub fn parse_module(input: &str) -> Result<ParsedModule, Vec<Error>> {
             .iter()
             .map(|t| ctx.type_(&mut builder, t))
             .collect::<Vec<_>>();
-        let ret_ty = func
-            .ret_type
-            .as_ref()
-            .map(|t| ctx.type_(&mut builder, t))
-            .unwrap_or(ir::Type::Void);
+        let ret_tys = ctx.ret_types(&mut builder, &func.ret_types);
 
-        let sig = Signature::new(&func.name.0, func.linkage, &params, ret_ty);
+        let sig = Signature::with_ret_tys(&func.name.0, func.linkage, &params, &ret_tys);
         builder.declare_function(sig);
     }
 
@@ -69,12 +65,8 @@ pub fn parse_module(input: &str) -> Result<ParsedModule, Vec<Error>> {
             .map(|decl| ctx.type_(&mut builder, &decl.1))
             .collect::<Vec<_>>();
 
-        let ret_ty = sig
-            .ret_type
-            .as_ref()
-            .map(|t| ctx.type_(&mut builder, t))
-            .unwrap_or(ir::Type::Void);
-        let sig = Signature::new(&sig.name.0, sig.linkage, &args, ret_ty);
+        let ret_tys = ctx.ret_types(&mut builder, &sig.ret_types);
+        let sig = Signature::with_ret_tys(&sig.name.0, sig.linkage, &args, &ret_tys);
 
         builder.declare_function(sig);
     }
@@ -139,7 +131,12 @@ impl BuildCtx {
         }
 
         for stmt in func.blocks.iter().flat_map(|b| b.stmts.iter()) {
-            if let StmtKind::Define(ValueDeclaration(name, ty), _) = &stmt.kind {
+            let decls = match &stmt.kind {
+                StmtKind::Define(decl, _) => std::slice::from_ref(decl),
+                StmtKind::MultiDefine(decls, _) => decls.as_slice(),
+                _ => &[],
+            };
+            for ValueDeclaration(name, ty) in decls {
                 let ty = self.type_(&mut fb.module_builder, ty);
                 self.declare_value(&mut fb.func, name, ty);
             }
@@ -189,29 +186,22 @@ impl BuildCtx {
                                     ty,
                                 }
                             }
-                            ast::Expr::Load(location, addr) => {
+                            ast::Expr::Load(location, ordering, addr) => {
                                 let addr = self.value(&mut fb, addr);
                                 InsnData::Load {
                                     args: [addr],
                                     loc: *location,
+                                    ordering: *ordering,
                                 }
                             }
                             ast::Expr::Alloca(ty) => {
                                 let ty = self.type_(&mut fb.module_builder, ty);
                                 InsnData::Alloca { ty }
                             }
-                            ast::Expr::Call(ast::Call(name, args)) => {
-                                let func = self.func_ref(&mut fb.module_builder, name);
-
-                                let args: smallvec::SmallVec<[ir::Value; 8]> =
-                                    args.iter().map(|val| self.value(&mut fb, val)).collect();
-
-                                let sig = fb.module_builder.get_sig(func).clone();
-                                let ret_ty = sig.ret_ty();
-                                fb.func.callees.insert(func, sig);
-
-                                InsnData::Call { func, args, ret_ty }
-                            }
+                            ast::Expr::Call(call) => match self.call_insn(&mut fb, call, 1) {
+                                Some(insn_data) => insn_data,
+                                None => continue,
+                            },
                             ast::Expr::Gep(vals) => {
                                 let args: SmallVec<[ir::Value; 8]> =
                                     vals.iter().map(|val| self.value(&mut fb, val)).collect();
@@ -244,18 +234,56 @@ impl BuildCtx {
                         fb.cursor.attach_result(&mut fb.func, insn, value);
                         fb.cursor.set_location(CursorLocation::At(insn));
                     }
-                    ast::StmtKind::Store(loc, addr, val) => {
-                        let addr = self.value(&mut fb, addr);
-                        let val = self.value(&mut fb, val);
+                    ast::StmtKind::MultiDefine(decls, call) => {
+                        let err_count = self.errors.len();
+                        let Some(insn_data) = self.call_insn(&mut fb, call, decls.len()) else {
+                            continue;
+                        };
+
+                        let mut values = SmallVec::<[ir::Value; 2]>::new();
+                        let ret_tys = insn_data.result_types(&fb.func.dfg);
+                        for (ValueDeclaration(name, type_), inferred_ty) in
+                            decls.iter().zip(ret_tys)
+                        {
+                            let ty = self.type_(&mut fb.module_builder, type_);
+                            if self.errors.len() == err_count && ty != inferred_ty {
+                                self.errors.push(Error::TypeMismatch {
+                                    specified: ty.to_string(&fb.func.dfg).into(),
+                                    inferred: inferred_ty.to_string(&fb.func.dfg).into(),
+                                    span: type_.span,
+                                });
+                            }
+                            values.push(*self.func_value_names.get_by_right(&name.string).unwrap());
+                        }
 
-                        match loc {
-                            ir::DataLocationKind::Memory => fb.memory_store(addr, val),
-                            ir::DataLocationKind::Storage => fb.storage_store(addr, val),
+                        let insn = fb.cursor.insert_insn_data(&mut fb.func, insn_data);
+                        for &value in &values {
+                            let ty = fb.func.dfg.value_ty(value);
+                            fb.func.dfg.values[value] = ir::ValueData::Insn { insn, ty };
                         }
+                        fb.func.dfg.attach_results(insn, &values);
+                        fb.cursor.set_location(CursorLocation::At(insn));
                     }
-                    ast::StmtKind::Return(val) => {
-                        let val = val.as_ref().map(|v| self.value(&mut fb, v));
-                        fb.ret(val);
+                    ast::StmtKind::Store(loc, ordering, addr, val) => {
+                        let addr = self.value(&mut fb, addr);
+                        let val = self.value(&mut fb, val);
+                        fb.store_ordered(*loc, addr, val, *ordering);
+                    }
+                    ast::StmtKind::Return(vals) => {
+                        let args = vals.iter().map(|val| self.value(&mut fb, val)).collect();
+                        // Insert the insn directly instead of using `FunctionBuilder::ret`, which
+                        // panics if the return values don't match the signature.
+                        let insn = fb
+                            .cursor
+                            .insert_insn_data(&mut fb.func, InsnData::Return { args });
+                        fb.cursor.set_location(CursorLocation::At(insn));
+                    }
+                    ast::StmtKind::Halt(code, vals) => {
+                        let args = vals.iter().map(|val| self.value(&mut fb, val)).collect();
+                        let insn = fb
+                            .cursor
+                            .insert_insn_data(&mut fb.func, InsnData::Halt { code: *code, args });
+                        fb.cursor.set_location(CursorLocation::At(insn));
                     }
                     ast::StmtKind::Jump(block_id) => {
                         let block_id = self.block(block_id);
@@ -287,7 +315,8 @@ impl BuildCtx {
                             .iter()
                             .map(|val| self.value(&mut fb, val))
                             .collect::<Vec<_>>();
-                        fb.call(func_ref, &args).unwrap();
+                        // The results are discarded, so the callee may return any number of values.
+                        fb.call_multi(func_ref, &args);
                     }
                 }
             }
@@ -299,6 +328,38 @@ impl BuildCtx {
         fb.finish()
     }
 
+    /// Makes a call insn of `call`. Reports an error and returns `None` if the callee doesn't
+    /// return `results` values.
+    fn call_insn(
+        &mut self,
+        fb: &mut FunctionBuilder<InsnInserter>,
+        call: &ast::Call,
+        results: usize,
+    ) -> Option<InsnData> {
+        let ast::Call(name, args) = call;
+        let func = self.func_ref(&mut fb.module_builder, name);
+        let args = args.iter().map(|val| self.value(fb, val)).collect();
+
+        let sig = fb.module_builder.get_sig(func).clone();
+        if sig.ret_tys().len() != results {
+            self.errors.push(Error::ResultCountMismatch {
+                func: name.inner.0.clone(),
+                returned: sig.ret_tys().len(),
+                declared: results,
+                span: name.span,
+            });
+            return None;
+        }
+
+        let ret_tys = sig.ret_tys().into();
+        fb.func.callees.insert(func, sig);
+        Some(InsnData::Call {
+            func,
+            args,
+            ret_tys,
+        })
+    }
+
     fn func_ref(&mut self, mb: &mut ModuleBuilder, name: &Spanned<ast::FunctionName>) -> FuncRef {
         mb.get_func_ref(&name.inner.0).unwrap_or_else(|| {
             self.errors.push(Error::Undefined(
@@ -346,6 +407,10 @@ impl BuildCtx {
     fn value(&mut self, fb: &mut FunctionBuilder<InsnInserter>, val: &ast::Value) -> ir::Value {
         match &val.kind {
             ast::ValueKind::Immediate(imm) => fb.make_imm_value(*imm),
+            ast::ValueKind::Undef(ty) => {
+                let ty = self.type_(&mut fb.module_builder, ty);
+                fb.make_undef(ty)
+            }
             ast::ValueKind::Named(name) => self
                 .func_value_names
                 .get_by_right(&name.string)
@@ -372,6 +437,10 @@ impl BuildCtx {
                 let elem = self.type_(mb, t);
                 mb.declare_array_type(elem, *n)
             }
+            ast::TypeKind::Union(variants) => {
+                let variants: Vec<_> = variants.iter().map(|t| self.type_(mb, t)).collect();
+                mb.declare_union_type(&variants)
+            }
             ast::TypeKind::Void => ir::Type::Void,
             ast::TypeKind::Struct(name) => mb.get_struct_type(name).unwrap_or_else(|| {
                 self.errors
@@ -381,4 +450,12 @@ impl BuildCtx {
             ast::TypeKind::Error => unreachable!(),
         }
     }
+
+    /// Returns the types of the return values; `void` means no return value.
+    fn ret_types(&mut self, mb: &mut ModuleBuilder, tys: &[ast::Type]) -> Vec<ir::Type> {
+        tys.iter()
+            .map(|t| self.type_(mb, t))
+            .filter(|ty| *ty != ir::Type::Void)
+            .collect()
+    }
 }
@@ -1,7 +1,7 @@
 use derive_more::Debug as Dbg;
 use either::Either;
 use pest::iterators::Pair;
-use std::{ops::Range, str::FromStr};
+use std::{fmt, ops::Range, str::FromStr};
 
 #[derive(pest_derive::Parser)]
 #[grammar = "sonatina.pest"]
@@ -18,6 +18,41 @@ impl Span {
     pub fn as_range(&self) -> Range<usize> {
         self.0 as usize..self.1 as usize
     }
+
+    /// Returns the line and column of the start and the end of the span in `content`.
+    pub fn line_cols(&self, content: &str) -> (LineCol, LineCol) {
+        (
+            LineCol::from_offset(content, self.0 as usize),
+            LineCol::from_offset(content, self.1 as usize),
+        )
+    }
+}
+
+/// A 1-based line and column of a position in the source text.
+/// The column is counted in chars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineCol {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl LineCol {
+    pub fn from_offset(content: &str, offset: usize) -> Self {
+        let offset = offset.min(content.len());
+        let before = &content[..offset];
+        let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl fmt::Display for LineCol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
 }
 
 #[derive(Dbg, Clone)]
@@ -1,7 +1,7 @@
 use std::path::Path;
 
 use dir_test::{dir_test, Fixture};
-use sonatina_parser::parse_module;
+use sonatina_parser::{parse_module, LineCol};
 mod common;
 
 #[dir_test(
@@ -24,3 +24,13 @@ fn test_errors(fixture: Fixture<&str>) {
     let s = String::from_utf8(v).unwrap();
     snap_test!(s, fixture.path());
 }
+
+#[test]
+fn error_line_col() {
+    let src = "target = \"evm-ethereum-london\"\n\nfunc %f() {\n    v0.i8 = add 1.i8 2.i8;\n}\n";
+    let Err(errs) = parse_module(src) else {
+        panic!("expected parse_module to fail with errors");
+    };
+    assert_eq!(errs[0].line_col(src), LineCol { line: 4, column: 5 });
+    assert_eq!(errs[0].line_col(src).to_string(), "4:5");
+}
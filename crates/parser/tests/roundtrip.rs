@@ -0,0 +1,416 @@
+//! Round-trip property test between the IR writer and the parser.
+//!
+//! Random type-correct and SSA-valid functions are printed with the IR writer, parsed back and
+//! printed again; both texts must be identical. Each block uses only the function args, its own
+//! phis and the values defined earlier in the block, and each phi arg is taken from the values
+//! available at the end of the corresponding predecessor, so any CFG is valid.
+use ir::{
+    builder::{test_util::test_func_builder, FunctionBuilder},
+    func_cursor::FuncCursor,
+    insn::BinaryOp,
+    ir_writer::{dump_module, ModuleWriter},
+    Block, Module, Type, Value,
+};
+use proptest::prelude::*;
+use sonatina_parser::parse_module;
+
+const TYPES: [Type; 4] = [Type::I8, Type::I16, Type::I32, Type::I64];
+
+const ARITH_OPS: [BinaryOp; 19] = [
+    BinaryOp::Add,
+    BinaryOp::Sub,
+    BinaryOp::Mul,
+    BinaryOp::Udiv,
+    BinaryOp::Sdiv,
+    BinaryOp::Urem,
+    BinaryOp::Srem,
+    BinaryOp::And,
+    BinaryOp::Or,
+    BinaryOp::Xor,
+    BinaryOp::Shl,
+    BinaryOp::Lshr,
+    BinaryOp::Ashr,
+    BinaryOp::AddChecked,
+    BinaryOp::SubChecked,
+    BinaryOp::MulChecked,
+    BinaryOp::UaddChecked,
+    BinaryOp::UsubChecked,
+    BinaryOp::UmulChecked,
+];
+
+const CMP_OPS: [BinaryOp; 10] = [
+    BinaryOp::Lt,
+    BinaryOp::Gt,
+    BinaryOp::Slt,
+    BinaryOp::Sgt,
+    BinaryOp::Le,
+    BinaryOp::Ge,
+    BinaryOp::Sle,
+    BinaryOp::Sge,
+    BinaryOp::Eq,
+    BinaryOp::Ne,
+];
+
+#[derive(Debug, Clone)]
+struct FuncSpec {
+    ty: usize,
+    arg_num: usize,
+    blocks: Vec<BlockSpec>,
+}
+
+#[derive(Debug, Clone)]
+struct BlockSpec {
+    phi_num: usize,
+    /// Indices into the values available at the end of each predecessor, used cyclically.
+    phi_args: Vec<usize>,
+    ops: Vec<(usize, Operand, Operand)>,
+    term: TermSpec,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Value(usize),
+    Imm(i64),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TermSpec {
+    Return(Operand),
+    Jump(usize),
+    Branch {
+        cmp: usize,
+        lhs: Operand,
+        rhs: Operand,
+        then: usize,
+        else_: usize,
+    },
+}
+
+fn operand() -> impl Strategy<Value = Operand> {
+    prop_oneof![
+        3 => any::<usize>().prop_map(Operand::Value),
+        1 => any::<i64>().prop_map(Operand::Imm),
+    ]
+}
+
+fn term() -> impl Strategy<Value = TermSpec> {
+    prop_oneof![
+        operand().prop_map(TermSpec::Return),
+        any::<usize>().prop_map(TermSpec::Jump),
+        (
+            0..CMP_OPS.len(),
+            operand(),
+            operand(),
+            any::<usize>(),
+            any::<usize>()
+        )
+            .prop_map(|(cmp, lhs, rhs, then, else_)| TermSpec::Branch {
+                cmp,
+                lhs,
+                rhs,
+                then,
+                else_,
+            }),
+    ]
+}
+
+fn block() -> impl Strategy<Value = BlockSpec> {
+    (
+        0..3usize,
+        prop::collection::vec(any::<usize>(), 0..4),
+        prop::collection::vec((0..ARITH_OPS.len(), operand(), operand()), 0..6),
+        term(),
+    )
+        .prop_map(|(phi_num, phi_args, ops, term)| BlockSpec {
+            phi_num,
+            phi_args,
+            ops,
+            term,
+        })
+}
+
+fn func() -> impl Strategy<Value = FuncSpec> {
+    (
+        0..TYPES.len(),
+        0..3usize,
+        prop::collection::vec(block(), 1..6),
+    )
+        .prop_map(|(ty, arg_num, blocks)| FuncSpec {
+            ty,
+            arg_num,
+            blocks,
+        })
+}
+
+impl FuncSpec {
+    /// Returns the successors of the `idx`-th block.
+    /// The entry block is never a successor so that it has no phis.
+    fn succs(&self, idx: usize) -> Vec<usize> {
+        if self.blocks.len() == 1 {
+            return vec![];
+        }
+        let target = |t: usize| 1 + t % (self.blocks.len() - 1);
+        match self.blocks[idx].term {
+            TermSpec::Return(_) => vec![],
+            TermSpec::Jump(dest) => vec![target(dest)],
+            TermSpec::Branch { then, else_, .. } => {
+                let (then, else_) = (target(then), target(else_));
+                if then == else_ {
+                    vec![then]
+                } else {
+                    vec![then, else_]
+                }
+            }
+        }
+    }
+
+    fn build(&self) -> Module {
+        let ty = TYPES[self.ty];
+        let mut builder = test_func_builder(&vec![ty; self.arg_num], ty);
+        let blocks: Vec<Block> = self.blocks.iter().map(|_| builder.append_block()).collect();
+        let args = builder.args().to_vec();
+
+        let mut preds = vec![vec![]; self.blocks.len()];
+        for idx in 0..self.blocks.len() {
+            for succ in self.succs(idx) {
+                preds[succ].push(idx);
+            }
+        }
+
+        let mut phis = vec![vec![]; self.blocks.len()];
+        let mut pools = vec![vec![]; self.blocks.len()];
+        for (idx, spec) in self.blocks.iter().enumerate() {
+            builder.switch_to_block(blocks[idx]);
+            let mut pool = args.clone();
+
+            if !preds[idx].is_empty() {
+                for _ in 0..spec.phi_num {
+                    let phi = builder.phi(ty, &[]);
+                    phis[idx].push(phi);
+                    pool.push(phi);
+                }
+            }
+
+            for &(op, lhs, rhs) in &spec.ops {
+                let lhs = resolve_operand(&mut builder, ty, &pool, lhs);
+                let rhs = resolve_operand(&mut builder, ty, &pool, rhs);
+                let value = builder.binary_op(ARITH_OPS[op], lhs, rhs);
+                pool.push(value);
+            }
+
+            let succs = self.succs(idx);
+            match spec.term {
+                TermSpec::Return(ret) => {
+                    let ret = resolve_operand(&mut builder, ty, &pool, ret);
+                    builder.ret(&[ret]);
+                }
+                TermSpec::Branch { cmp, lhs, rhs, .. } if succs.len() == 2 => {
+                    let lhs = resolve_operand(&mut builder, ty, &pool, lhs);
+                    let rhs = resolve_operand(&mut builder, ty, &pool, rhs);
+                    let cond = builder.binary_op(CMP_OPS[cmp], lhs, rhs);
+                    builder.br(cond, blocks[succs[0]], blocks[succs[1]]);
+                }
+                _ => match succs.first() {
+                    Some(&dest) => builder.jump(blocks[dest]),
+                    None => {
+                        let ret = resolve_operand(&mut builder, ty, &pool, Operand::Value(0));
+                        builder.ret(&[ret]);
+                    }
+                },
+            }
+
+            pools[idx] = pool;
+        }
+
+        for (idx, spec) in self.blocks.iter().enumerate() {
+            for (i, &phi) in phis[idx].iter().enumerate() {
+                for (j, &pred) in preds[idx].iter().enumerate() {
+                    let pick = if spec.phi_args.is_empty() {
+                        0
+                    } else {
+                        spec.phi_args[(i + j) % spec.phi_args.len()]
+                    };
+                    let pool = &pools[pred];
+                    let value = if pool.is_empty() {
+                        make_imm(&mut builder, ty, pick as i64)
+                    } else {
+                        pool[pick % pool.len()]
+                    };
+                    builder.append_phi_arg(phi, value, blocks[pred]);
+                }
+            }
+        }
+
+        builder.seal_all();
+        builder.finish().build()
+    }
+}
+
+fn resolve_operand<C>(
+    builder: &mut FunctionBuilder<C>,
+    ty: Type,
+    pool: &[Value],
+    operand: Operand,
+) -> Value
+where
+    C: FuncCursor,
+{
+    match operand {
+        Operand::Value(i) if !pool.is_empty() => pool[i % pool.len()],
+        Operand::Value(i) => make_imm(builder, ty, i as i64),
+        Operand::Imm(imm) => make_imm(builder, ty, imm),
+    }
+}
+
+fn make_imm<C>(builder: &mut FunctionBuilder<C>, ty: Type, imm: i64) -> Value
+where
+    C: FuncCursor,
+{
+    match ty {
+        Type::I8 => builder.make_imm_value(imm as i8),
+        Type::I16 => builder.make_imm_value(imm as i16),
+        Type::I32 => builder.make_imm_value(imm as i32),
+        Type::I64 => builder.make_imm_value(imm),
+        _ => unreachable!(),
+    }
+}
+
+fn reprint(src: &str) -> String {
+    let parsed = match parse_module(src) {
+        Ok(parsed) => parsed,
+        Err(errs) => {
+            let msgs: Vec<_> = errs
+                .iter()
+                .map(|err| err.print_to_string("<input>", src, false))
+                .collect();
+            panic!(
+                "failed to parse printed module:\n{src}\n{}",
+                msgs.join("\n")
+            );
+        }
+    };
+    ModuleWriter::with_debug_provider(&parsed.module, &parsed.debug)
+        .dump_string()
+        .unwrap()
+}
+
+proptest! {
+    #[test]
+    fn writer_parser_roundtrip(spec in func()) {
+        let printed = dump_module(&spec.build());
+        prop_assert_eq!(reprint(&printed), printed);
+    }
+}
+
+#[test]
+fn undef_roundtrip() {
+    let src = "target = \"evm-ethereum-london\"
+
+func public %f(v0.i32) -> i32 {
+    block0:
+        v1.i32 = and v0 undef.i32;
+        v2.i32 = or v1 undef.i32;
+        return v2;
+}
+";
+    let printed = reprint(src);
+    assert!(printed.contains("and v0 undef.i32"), "{printed}");
+    assert_eq!(reprint(&printed), printed);
+}
+
+#[test]
+fn multi_return_roundtrip() {
+    let src = "target = \"evm-ethereum-london\"
+
+declare external %pair(i32) -> (i32, i64);
+
+func public %f(v0.i32) -> (i32, i64) {
+    block0:
+        return v0 1.i64;
+}
+";
+    let printed = reprint(src);
+    assert!(printed.contains("-> (i32, i64) {"), "{printed}");
+    assert!(printed.contains("return v0 1.i64;"), "{printed}");
+    assert_eq!(reprint(&printed), printed);
+}
+
+#[test]
+fn multi_result_call_roundtrip() {
+    let src = "target = \"evm-ethereum-london\"
+
+declare external %pair(i32) -> (i32, i64);
+
+func public %f(v0.i32) -> i64 {
+    block0:
+        v1.i32, v2.i64 = call %pair v0;
+        v3.i64 = sext v1;
+        v4.i64 = add v2 v3;
+        return v4;
+}
+";
+    let printed = reprint(src);
+    assert!(
+        printed.contains("v1.i32, v2.i64 = call %pair v0;"),
+        "{printed}"
+    );
+    assert_eq!(reprint(&printed), printed);
+}
+
+#[test]
+fn int_width_roundtrip() {
+    let src = "target = \"evm-ethereum-london\"
+
+func public %f(v0.i24) -> i24 {
+    block0:
+        v1.i24 = add v0 -1.i24;
+        v2.i12 = trunc v1;
+        v3.i24 = sext v2;
+        v4.i32 = zext v3;
+        v5.i24 = trunc v4;
+        return v5;
+}
+";
+    let printed = reprint(src);
+    assert!(printed.contains("add v0 -1.i24"), "{printed}");
+    assert!(printed.contains("v4.i32 = zext v3;"), "{printed}");
+    assert_eq!(reprint(&printed), printed);
+}
+
+#[test]
+fn ordered_access_roundtrip() {
+    let src = "target = \"evm-ethereum-cancun\"
+
+func public %f(v0.*i32, v1.i256) -> i32 {
+    block0:
+        store release @memory v0 1.i32;
+        v2.i32 = load acquire @memory v0;
+        store seq_cst @transient v1 v2;
+        v3.i32 = load relaxed @memory v0;
+        return v3;
+}
+";
+    let printed = reprint(src);
+    assert!(
+        printed.contains("v2.i32 = load acquire @memory v0;"),
+        "{printed}"
+    );
+    assert!(
+        printed.contains("store seq_cst @transient v1 v2;"),
+        "{printed}"
+    );
+    assert_eq!(reprint(&printed), printed);
+}
+
+#[test]
+fn invalid_ordering() {
+    let src = "target = \"evm-ethereum-london\"
+
+func public %f(v0.*i32) -> i32 {
+    block0:
+        v1.i32 = load release @memory v0;
+        return v1;
+}
+";
+    assert!(parse_module(src).is_err());
+}
@@ -53,6 +53,59 @@ fn test_module_ir(fixture: Fixture<&str>) {
     snap_test!(w.dump_string().unwrap(), fixture.path(), Some("ir"));
 }
 
+#[dir_test(
+    dir: "$CARGO_MANIFEST_DIR/test_files/syntax/module",
+    glob: "*.sntn"
+)]
+fn test_module_roundtrip(fixture: Fixture<&str>) {
+    let printed = print_module(fixture.content());
+    assert_eq!(print_module(&printed), printed);
+}
+
+#[test]
+fn roundtrip_writer_syntax() {
+    let src = r#"target = "evm-ethereum-london"
+
+declare external %sink(i32) -> void;
+
+type %pair = <{ i8, i32 }>;
+
+func public %f(v0.i32, v1.*%pair, v2.*union{i32, [i8; 4]}) -> i32 {
+    block0:
+        call %sink v0;
+        v3.i32 = sub v0 -1.i32;
+        br_table v0 (1.i32 block1) (2.i32 block2);
+    block1:
+        jump block2;
+    block2:
+        v4.i32 = phi (v0 block0) (v3 block1);
+        return v4;
+}
+"#;
+
+    let printed = print_module(src);
+    assert!(printed.contains("declare external %sink(i32) -> void;"));
+    assert!(printed.contains("type %pair = <{i8, i32}>;"));
+    assert!(printed.contains("call %sink v0;"));
+    assert!(printed.contains("br_table v0 (1.i32 block1) (2.i32 block2);"));
+    assert_eq!(print_module(&printed), printed);
+}
+
+fn print_module(src: &str) -> String {
+    let parsed = match parse_module(src) {
+        Ok(parsed) => parsed,
+        Err(errs) => {
+            for err in errs {
+                eprintln!("{}", err.print_to_string("<input>", src, false));
+            }
+            panic!("failed to parse module:\n{src}");
+        }
+    };
+    ModuleWriter::with_debug_provider(&parsed.module, &parsed.debug)
+        .dump_string()
+        .unwrap()
+}
+
 fn test_rule(rule: Rule, fixture: Fixture<&str>) {
     match Parser::parse(rule, fixture.content()) {
         Ok(r) => {
@@ -112,6 +112,7 @@ impl Version {
                     "constantinople" => EvmVersion::Constantinople,
                     "istanbul" => EvmVersion::Istanbul,
                     "london" => EvmVersion::London,
+                    "cancun" => EvmVersion::Cancun,
                     _ => return Err(InvalidTriple::VersionNotSupported),
                 };
                 Ok(Self::EvmVersion(evm_version))
@@ -128,7 +129,8 @@ impl Display for Version {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The hard forks of EVM, ordered by their activation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum EvmVersion {
     Frontier,
     Homestead,
@@ -136,6 +138,7 @@ pub enum EvmVersion {
     Constantinople,
     Istanbul,
     London,
+    Cancun,
 }
 #[derive(Debug, Clone, Error)]
 pub enum InvalidTriple {
@@ -164,6 +167,7 @@ impl Display for EvmVersion {
             Self::Constantinople => write!(f, "constantinople"),
             Self::Istanbul => write!(f, "istanbul"),
             Self::London => write!(f, "london"),
+            Self::Cancun => write!(f, "cancun"),
         }
     }
 }
@@ -181,4 +185,12 @@ mod tests {
         assert_eq!(triple.chain, Chain::Ethereum);
         assert_eq!(triple.version, Version::EvmVersion(EvmVersion::Istanbul));
     }
+
+    #[test]
+    fn evm_version_order() {
+        let triple = TargetTriple::parse("evm-ethereum-cancun").unwrap();
+        assert_eq!(triple.version, Version::EvmVersion(EvmVersion::Cancun));
+        assert_eq!(triple.to_string(), "evm-ethereum-cancun");
+        assert!(EvmVersion::London < EvmVersion::Cancun);
+    }
 }